@@ -2,11 +2,13 @@
 //!
 //! This crate provides:
 //! - Timestamped HMAC message signing and validation
+//! - MAC address parsing and normalization
 //! - OS-specific service installation helpers
 
 extern crate alloc;
 extern crate core;
 
+mod mac;
 mod map_to_str;
 pub mod protocol;
 mod service_install;
@@ -15,6 +17,7 @@ mod validation;
 
 use std::{net::UdpSocket, path};
 
+pub use mac::*;
 pub use map_to_str::*;
 pub use protocol::*;
 pub use service_install::*;
@@ -28,6 +31,12 @@ pub use validation::*;
 /// must be updated as they hard‑code the constant when invoking the agent.
 pub const DEFAULT_COORDINATOR_BROADCAST_PORT: u16 = 5757;
 
+/// Default TCP port of the coordinator's HTTP server.
+///
+/// Used as the default of `[server].port` and of the coordinator's `install` and `demo-service`
+/// subcommands.
+pub const DEFAULT_COORDINATOR_PORT: u16 = 8080;
+
 /// Default TCP port on which an agent listens for control commands.
 ///
 /// This is used both as the default for CLI parsing inside `host_agent` and in
@@ -35,6 +44,11 @@ pub const DEFAULT_COORDINATOR_BROADCAST_PORT: u16 = 5757;
 /// if this value changes.
 pub const DEFAULT_AGENT_TCP_PORT: u16 = 9090;
 
+/// mDNS service type under which agents started with `--mdns-announce` announce themselves.
+///
+/// The instance name is the agent's hostname, which the coordinator matches against `[hosts]`.
+pub const MDNS_SERVICE_TYPE: &str = "_shuthost._tcp.local.";
+
 /// Expands to the current git-describe version string.
 ///
 /// Callers must depend on `git-version` directly; the path `::git_version::git_version` is
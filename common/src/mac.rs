@@ -0,0 +1,162 @@
+//! MAC address parsing and normalization.
+//!
+//! Config files and tools differ in how they write MAC addresses (e.g. Windows and many DHCP
+//! servers separate octets with dashes), so they are normalized to lowercase, colon-separated
+//! octets wherever they are read.
+
+use core::{error, fmt};
+
+/// Number of octets in a MAC address.
+pub const MAC_ADDRESS_LENGTH: usize = 6;
+
+/// Reasons a MAC address string is rejected by [`parse_mac`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacParseError {
+    /// The address doesn't consist of [`MAC_ADDRESS_LENGTH`] octets.
+    OctetCount { mac: String, found: usize },
+    /// An octet isn't two hex digits.
+    InvalidOctet { mac: String, octet: String },
+}
+
+impl fmt::Display for MacParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::OctetCount { ref mac, found } => write!(
+                f,
+                "invalid MAC address '{mac}': expected {MAC_ADDRESS_LENGTH} octets, found {found}"
+            ),
+            Self::InvalidOctet { ref mac, ref octet } => write!(
+                f,
+                "invalid MAC address '{mac}': '{octet}' is not a two-digit hex octet"
+            ),
+        }
+    }
+}
+
+impl error::Error for MacParseError {}
+
+/// Parses a MAC address of six two-digit hex octets separated by `:` or `-`.
+///
+/// # Errors
+///
+/// Returns an error if the address doesn't consist of exactly six hex octets with a single kind
+/// of separator.
+pub fn parse_mac(mac: &str) -> Result<[u8; MAC_ADDRESS_LENGTH], MacParseError> {
+    let separator = if mac.contains('-') { '-' } else { ':' };
+    let parts: Vec<&str> = mac.split(separator).collect();
+    if parts.len() != MAC_ADDRESS_LENGTH {
+        return Err(MacParseError::OctetCount {
+            mac: mac.to_string(),
+            found: parts.len(),
+        });
+    }
+
+    let mut mac_bytes = [0u8; MAC_ADDRESS_LENGTH];
+    for (mac_byte, part) in mac_bytes.iter_mut().zip(parts) {
+        let invalid = || MacParseError::InvalidOctet {
+            mac: mac.to_string(),
+            octet: part.to_string(),
+        };
+        // `from_str_radix` alone would also accept single digits and a leading `+`.
+        if part.len() != 2 || !part.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        *mac_byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    Ok(mac_bytes)
+}
+
+/// Formats MAC address octets as lowercase, colon-separated hex.
+#[must_use]
+pub fn format_mac(mac: [u8; MAC_ADDRESS_LENGTH]) -> String {
+    mac.iter()
+        .map(|octet| format!("{octet:02x}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Normalizes a MAC address accepted by [`parse_mac`] to the form of [`format_mac`].
+///
+/// # Errors
+///
+/// Returns an error if [`parse_mac`] rejects the address.
+pub fn normalize_mac(mac: &str) -> Result<String, MacParseError> {
+    parse_mac(mac).map(format_mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mac_valid() {
+        let bytes = parse_mac("01:23:45:67:89:ab").expect("Should parse valid MAC");
+        assert_eq!(bytes, [0x01, 0x23, 0x45, 0x67, 0x89, 0xab]);
+        assert_eq!(parse_mac("01:23:45:67:89:AB"), Ok(bytes));
+    }
+
+    #[test]
+    fn parse_mac_dash_separated() {
+        assert_eq!(
+            parse_mac("00-11-22-33-44-55"),
+            Ok([0x00, 0x11, 0x22, 0x33, 0x44, 0x55])
+        );
+        assert!(matches!(
+            parse_mac("00-11-22:33:44:55"),
+            Err(MacParseError::OctetCount { found: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn parse_mac_too_short() {
+        let err = parse_mac("00:11:22:33:44").unwrap_err();
+        assert!(matches!(err, MacParseError::OctetCount { found: 5, .. }));
+        assert!(err.to_string().contains("expected 6 octets, found 5"));
+    }
+
+    #[test]
+    fn parse_mac_too_long() {
+        assert!(matches!(
+            parse_mac("00:11:22:33:44:55:66"),
+            Err(MacParseError::OctetCount { found: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn parse_mac_non_hex() {
+        let err = parse_mac("01:23:45:67:89:zz").unwrap_err();
+        assert_eq!(
+            err,
+            MacParseError::InvalidOctet {
+                mac: "01:23:45:67:89:zz".to_string(),
+                octet: "zz".to_string(),
+            }
+        );
+        for mac in [
+            "1:23:45:67:89:ab",
+            "+1:23:45:67:89:ab",
+            "01:23:45:67:89:abc",
+        ] {
+            assert!(
+                matches!(parse_mac(mac), Err(MacParseError::InvalidOctet { .. })),
+                "{mac}"
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_mac_lowercases_and_uses_colons() {
+        assert_eq!(
+            normalize_mac("AA-BB-CC-0D-EE-FF"),
+            Ok("aa:bb:cc:0d:ee:ff".to_string())
+        );
+        assert_eq!(
+            normalize_mac("aa:bb:cc:0d:ee:ff"),
+            Ok("aa:bb:cc:0d:ee:ff".to_string())
+        );
+        assert!(matches!(
+            normalize_mac("aa:bb:cc"),
+            Err(MacParseError::OctetCount { found: 3, .. })
+        ));
+    }
+}
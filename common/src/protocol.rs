@@ -68,6 +68,8 @@ define_enum_with_str! {
         SelfExtractingPwsh => "self-extracting-pwsh",
         /// Launchd init system (macOS).
         Launchd => "launchd",
+        /// Windows Service Control Manager.
+        WindowsService => "windows-service",
     }
 }
 
@@ -11,7 +11,7 @@ use std::{
     process::{Command, Stdio},
 };
 
-use crate::{ResultMapErrExt, is_superuser, run_init_command};
+use crate::{ResultMapErrExt, is_superuser, remove_file_if_exists, run_init_command};
 
 /// Returns the launchd service file path for the given service name.
 pub fn get_service_path(name: &str) -> String {
@@ -129,3 +129,36 @@ pub fn start_and_enable_self_as_service(name: &str) -> Result<(), String> {
 
     Ok(())
 }
+
+/// Unloads the launchd daemon, then removes its plist and the installed binary.
+///
+/// # Arguments
+///
+/// * `name` - Base name of the service and binary.
+///
+/// # Errors
+///
+/// Returns `Err` if not root or files cannot be removed.
+pub fn uninstall_self_as_service(name: &str) -> Result<(), String> {
+    if !is_superuser() {
+        return Err("You must run this command as root or with sudo.".to_string());
+    }
+
+    let label = format!("com.github_9smtm6.{name}");
+    let plist_path = PathBuf::from(get_service_path(name));
+
+    match Command::new("launchctl")
+        .arg("bootout")
+        .arg("system")
+        .arg(&plist_path)
+        .stderr(Stdio::null())
+        .status()
+    {
+        Ok(status) if status.success() => println!("Unloaded service {label}."),
+        Ok(_) => println!("Service {label} was not loaded or could not be unloaded, continuing."),
+        Err(e) => return Err(format!("Failed to execute launchctl bootout: {e}")),
+    }
+
+    remove_file_if_exists(&plist_path, "launchd plist")?;
+    remove_file_if_exists(&PathBuf::from("/usr/local/bin/").join(name), "binary")
+}
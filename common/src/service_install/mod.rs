@@ -6,8 +6,10 @@ pub mod macos;
 pub mod openrc;
 #[cfg(target_os = "linux")]
 pub mod systemd;
+#[cfg(windows)]
+pub mod windows;
 
-use std::path;
+use std::{fs, io, path};
 
 /// Returns `true` if the current process is running as superuser (root).
 #[cfg(unix)]
@@ -20,8 +22,39 @@ pub fn is_superuser() -> bool {
     nix::unistd::geteuid().as_raw() == 0
 }
 
+/// Returns `true` if the current process is running elevated, i.e. as administrator.
+#[cfg(windows)]
+#[expect(
+    clippy::absolute_paths,
+    reason = "we don't want to add a bunch of imports behind cfg attributes"
+)]
+#[must_use]
+pub fn is_superuser() -> bool {
+    // SAFETY: `IsUserAnAdmin` takes no arguments and only queries the process token.
+    unsafe { windows_sys::Win32::UI::Shell::IsUserAnAdmin() != 0 }
+}
+
 /// Returns `true` if the system uses `OpenRC` (checks `/run/openrc` or `/etc/init.d`).
 #[must_use]
 pub fn is_openrc() -> bool {
     path::Path::new("/run/openrc").exists() || path::Path::new("/etc/init.d").exists()
 }
+
+/// Removes the file at `path`, printing `what` was removed. A missing file is not an error.
+///
+/// # Errors
+///
+/// Returns `Err` if the file exists but cannot be removed.
+pub fn remove_file_if_exists(path: &path::Path, what: &str) -> Result<(), String> {
+    match fs::remove_file(path) {
+        Ok(()) => {
+            println!("Removed {what} {path:?}");
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            println!("No {what} found at {path:?}, skipping.");
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to remove {what} {path:?}: {e}")),
+    }
+}
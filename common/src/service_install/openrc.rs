@@ -11,7 +11,10 @@ use std::{
     process::{Command, Stdio},
 };
 
-use crate::{ResultMapErrExt as _, is_superuser, run_init_command};
+use crate::{ResultMapErrExt as _, is_superuser, remove_file_if_exists, run_init_command};
+
+/// Interpreter of `OpenRC` init scripts, referenced by their shebang.
+const OPENRC_RUN: &str = "/sbin/openrc-run";
 
 /// Returns the `OpenRC` service file path for the given service name.
 #[must_use]
@@ -28,11 +31,17 @@ pub fn get_service_path(name: &str) -> String {
 ///
 /// # Errors
 ///
-/// Returns `Err` if not running as superuser or if filesystem operations fail.
+/// Returns `Err` if not running as superuser, if `OpenRC` is not installed or if filesystem
+/// operations fail.
 pub fn install_self_as_service(name: &str, init_script_content: &str) -> Result<(), String> {
     if !is_superuser() {
         return Err("You must run this command as root or with sudo.".to_string());
     }
+    if !Path::new(OPENRC_RUN).exists() {
+        return Err(format!(
+            "{OPENRC_RUN} not found, the init script could not be run. Is OpenRC installed?"
+        ));
+    }
 
     let binary_path = env::current_exe().map_err_to_string_simple()?;
     let target_bin = Path::new("/usr/local/sbin/").join(name);
@@ -67,9 +76,20 @@ pub fn install_self_as_service(name: &str, init_script_content: &str) -> Result<
     fs::set_permissions(&target_bin, fs::Permissions::from_mode(0o755))
         .map_err_to_string_simple()?;
 
-    let mut script_file = File::create(&init_script_path).map_err_to_string_simple()?;
+    write_init_script(&init_script_path, init_script_content)?;
+    println!("Created OpenRC init script at {init_script_path:?}");
+
+    Ok(())
+}
+
+/// Writes the init script to `path`, replacing an existing one.
+///
+/// The script is executable by root only, as it may contain secrets like the agent's shared
+/// secret.
+fn write_init_script(path: &Path, content: &str) -> Result<(), String> {
+    let mut script_file = File::create(path).map_err_to_string_simple()?;
     script_file
-        .write_all(init_script_content.as_bytes())
+        .write_all(content.as_bytes())
         .map_err_to_string_simple()?;
 
     let mut perms = script_file
@@ -77,12 +97,7 @@ pub fn install_self_as_service(name: &str, init_script_content: &str) -> Result<
         .map_err_to_string_simple()?
         .permissions();
     perms.set_mode(0o750);
-    fs::set_permissions(&init_script_path, perms).map_err_to_string_simple()?;
-    println!("Created OpenRC init script at {init_script_path:?}");
-
-    drop(script_file);
-
-    Ok(())
+    fs::set_permissions(path, perms).map_err_to_string_simple()
 }
 
 /// Adds the service to the default runlevel and starts it.
@@ -111,3 +126,72 @@ pub fn start_and_enable_self_as_service(name: &str) -> Result<(), String> {
     println!("Service {name} started and added to default runlevel.");
     Ok(())
 }
+
+/// Stops the service and removes it from the default runlevel, then removes the init script
+/// and the installed binary.
+///
+/// # Arguments
+///
+/// * `name` - Base name of the service and binary.
+///
+/// # Errors
+///
+/// Returns `Err` if not root or files cannot be removed.
+pub fn uninstall_self_as_service(name: &str) -> Result<(), String> {
+    if !is_superuser() {
+        return Err("You must run this command as root or with sudo.".to_string());
+    }
+
+    let commands: [(&str, &[&str]); 2] = [
+        ("rc-service", &[name, "stop"]),
+        ("rc-update", &["del", name, "default"]),
+    ];
+    for (program, args) in commands {
+        let description = format!("{program} {}", args.join(" "));
+        match Command::new(program)
+            .args(args)
+            .stderr(Stdio::null())
+            .status()
+        {
+            Ok(status) if status.success() => println!("Ran {description}."),
+            Ok(_) => println!("{description} failed, continuing."),
+            Err(e) => return Err(format!("Failed to execute {program}: {e}")),
+        }
+    }
+
+    remove_file_if_exists(&PathBuf::from(get_service_path(name)), "OpenRC init script")?;
+    remove_file_if_exists(&Path::new("/usr/local/sbin/").join(name), "binary")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn service_path_is_in_init_d() {
+        assert_eq!(
+            get_service_path("shuthost_agent"),
+            "/etc/init.d/shuthost_agent"
+        );
+    }
+
+    #[test]
+    fn init_script_replaces_existing_one_and_is_not_world_readable() {
+        let dir = env::temp_dir().join(format!("shuthost_openrc_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("shuthost_agent");
+        fs::write(&path, "old script with more content").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let result = write_init_script(&path, "#!/sbin/openrc-run\n");
+        let content = fs::read_to_string(&path);
+        let mode = fs::metadata(&path).map(|m| m.permissions().mode() & 0o777);
+        drop(fs::remove_dir_all(&dir));
+
+        result.unwrap();
+        assert_eq!(content.unwrap(), "#!/sbin/openrc-run\n");
+        assert_eq!(mode.unwrap(), 0o750);
+    }
+}
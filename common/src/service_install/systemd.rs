@@ -11,7 +11,7 @@ use std::{
     process::{Command, Stdio},
 };
 
-use crate::{ResultMapErrExt as _, is_superuser, run_init_command};
+use crate::{ResultMapErrExt as _, is_superuser, remove_file_if_exists, run_init_command};
 
 /// Returns the systemd service file path for the given service name.
 #[must_use]
@@ -116,3 +116,42 @@ pub fn start_and_enable_self_as_service(name: &str) -> Result<(), String> {
     println!("Service {service_name} started and enabled.");
     Ok(())
 }
+
+/// Stops and disables the service unit, then removes the unit file and the installed binary.
+///
+/// # Arguments
+///
+/// * `name` - Base name of the service and binary.
+///
+/// # Errors
+///
+/// Returns `Err` if not root, files cannot be removed, or systemd cannot be reloaded.
+pub fn uninstall_self_as_service(name: &str) -> Result<(), String> {
+    if !is_superuser() {
+        return Err("You must run this command as root or with sudo.".to_string());
+    }
+
+    let service_name = format!("{name}.service");
+    for action in ["stop", "disable"] {
+        match Command::new("systemctl")
+            .arg(action)
+            .arg(&service_name)
+            .stderr(Stdio::null())
+            .status()
+        {
+            Ok(status) if status.success() => println!("Ran systemctl {action} {service_name}."),
+            Ok(_) => println!("systemctl {action} {service_name} failed, continuing."),
+            Err(e) => return Err(format!("Failed to execute systemctl {action}: {e}")),
+        }
+    }
+
+    remove_file_if_exists(
+        &PathBuf::from(get_service_path(name)),
+        "systemd service file",
+    )?;
+    run_init_command!(
+        Command::new("systemctl").arg("daemon-reload"),
+        "reload systemd daemon",
+    );
+    remove_file_if_exists(&PathBuf::from("/usr/local/sbin/").join(name), "binary")
+}
@@ -0,0 +1,225 @@
+//! Windows Service Control Manager installer.
+//!
+//! Provides functions to register the current binary as an automatically starting service via
+//! `sc.exe`, start it and remove it again.
+
+use core::time::Duration;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::{ResultMapErrExt as _, is_superuser, run_init_command};
+
+/// How long to wait for a stopping service to exit before replacing its binary.
+const STOP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Returns the path the binary of the service `name` is installed to.
+#[must_use]
+pub fn get_binary_path(name: &str) -> PathBuf {
+    let program_files = env::var_os("ProgramFiles").unwrap_or_else(|| "C:\\Program Files".into());
+    Path::new(&program_files)
+        .join("shuthost")
+        .join(format!("{name}.exe"))
+}
+
+/// Returns the registry key of the service `name`, as accepted by `reg.exe`.
+#[must_use]
+pub fn get_registry_key(name: &str) -> String {
+    format!("HKLM\\SYSTEM\\CurrentControlSet\\Services\\{name}")
+}
+
+/// Installs the current binary as an automatically starting service, replacing an existing
+/// service of the same name.
+///
+/// # Arguments
+///
+/// * `name` - Name to assign to the service and executable.
+/// * `description` - Description shown in the services console.
+/// * `arguments` - Command line arguments the service manager starts the binary with.
+/// * `environment` - Environment variables of the service process, e.g. secrets that
+///   shouldn't be passed on the command line.
+///
+/// # Errors
+///
+/// Returns `Err` if not running elevated, or if `sc.exe`, `reg.exe` or filesystem operations fail.
+pub fn install_self_as_service(
+    name: &str,
+    description: &str,
+    arguments: &str,
+    environment: &[(&str, &str)],
+) -> Result<(), String> {
+    if !is_superuser() {
+        return Err(
+            "You must run this command from an elevated (administrator) prompt.".to_string(),
+        );
+    }
+
+    let binary_path = env::current_exe().map_err_to_string_simple()?;
+    let target_bin = get_binary_path(name);
+    if let Some(parent) = target_bin.parent() {
+        fs::create_dir_all(parent).map_err_to_string_simple()?;
+    }
+
+    let exists = service_exists(name);
+    if exists {
+        stop_service(name)?;
+    }
+
+    fs::copy(&binary_path, &target_bin).map_err_to_string_simple()?;
+    println!("Installed binary to {target_bin:?}");
+
+    let bin_path = format!("\"{}\" {arguments}", target_bin.display());
+    // `sc.exe` expects each option name, including its `=`, and its value as separate arguments.
+    let options = [
+        "binPath=",
+        bin_path.as_str(),
+        "start=",
+        "auto",
+        "DisplayName=",
+        name,
+    ];
+    if exists {
+        run_init_command!(
+            Command::new("sc.exe").arg("config").arg(name).args(options),
+            "update service",
+        );
+    } else {
+        run_init_command!(
+            Command::new("sc.exe").arg("create").arg(name).args(options),
+            "create service",
+        );
+    }
+    run_init_command!(
+        Command::new("sc.exe")
+            .arg("description")
+            .arg(name)
+            .arg(description),
+        "set service description",
+    );
+
+    let environment = environment
+        .iter()
+        .map(|&(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        // `reg.exe` separates the strings of a `REG_MULTI_SZ` value with a literal `\0`.
+        .join("\\0");
+    let registry_key = get_registry_key(name);
+    run_init_command!(
+        Command::new("reg.exe").args([
+            "add",
+            registry_key.as_str(),
+            "/v",
+            "Environment",
+            "/t",
+            "REG_MULTI_SZ",
+            "/d",
+            environment.as_str(),
+            "/f",
+        ]),
+        "set service environment",
+    );
+
+    println!("Registered service {name}.");
+    Ok(())
+}
+
+/// Starts the service. It was registered to start automatically at boot by
+/// [`install_self_as_service`].
+///
+/// # Arguments
+///
+/// * `name` - Name of the service to start.
+///
+/// # Errors
+///
+/// Returns `Err` if `sc.exe start` fails.
+pub fn start_and_enable_self_as_service(name: &str) -> Result<(), String> {
+    run_init_command!(
+        Command::new("sc.exe").arg("start").arg(name),
+        "start service",
+    );
+
+    println!("Service {name} started and set to start automatically.");
+    Ok(())
+}
+
+/// Returns the output of `sc.exe qc`, i.e. the service configuration including its command
+/// line, followed by the output of `reg.exe query` for its environment.
+///
+/// # Errors
+///
+/// Returns `Err` if the service doesn't exist or either command fails.
+pub fn query_service_config(name: &str) -> Result<String, String> {
+    let registry_key = get_registry_key(name);
+    let queries: [(&str, &[&str]); 2] = [
+        ("sc.exe", &["qc", name]),
+        (
+            "reg.exe",
+            &["query", registry_key.as_str(), "/v", "Environment"],
+        ),
+    ];
+
+    let mut config = String::new();
+    for (program, args) in queries {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err_to_string(&format!("Failed to execute {program}"))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to query service {name}: {}",
+                String::from_utf8_lossy(&output.stdout).trim()
+            ));
+        }
+        config.push_str(&String::from_utf8_lossy(&output.stdout));
+    }
+    Ok(config)
+}
+
+/// Returns `true` if a service named `name` is registered.
+#[must_use]
+pub fn service_exists(name: &str) -> bool {
+    Command::new("sc.exe")
+        .arg("query")
+        .arg(name)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Stops the service and waits until it exited, so its binary can be replaced.
+fn stop_service(name: &str) -> Result<(), String> {
+    // Fails if the service isn't running, which is fine.
+    drop(
+        Command::new("sc.exe")
+            .arg("stop")
+            .arg(name)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status(),
+    );
+
+    let poll_interval = Duration::from_millis(500);
+    let mut waited = Duration::ZERO;
+    while waited < STOP_TIMEOUT {
+        let output = Command::new("sc.exe")
+            .arg("query")
+            .arg(name)
+            .output()
+            .map_err_to_string("Failed to execute sc.exe query")?;
+        if String::from_utf8_lossy(&output.stdout).contains("STOPPED") {
+            println!("Stopped service {name}.");
+            return Ok(());
+        }
+        thread::sleep(poll_interval);
+        waited += poll_interval;
+    }
+    Err(format!(
+        "Service {name} did not stop within {} seconds",
+        STOP_TIMEOUT.as_secs()
+    ))
+}
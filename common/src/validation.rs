@@ -1,11 +1,22 @@
 //! HMAC validation utilities for verifying signed messages.
 //!
 //! This module provides functions for validating HMAC signatures and
-//! parsing signed messages with timestamp verification.
+//! parsing signed messages with timestamp verification, as well as
+//! validating the coordinator config snippets printed by the host agent
+//! (with the `config-snippet` feature).
 
+#[cfg(feature = "config-snippet")]
+use core::{net::IpAddr, slice};
+use std::collections::HashSet;
+
+#[cfg(feature = "config-snippet")]
+use toml::{Table, Value};
+
+#[cfg(feature = "config-snippet")]
+use crate::mac::parse_mac;
 use crate::signing::{sign_hmac, unix_time_seconds};
 
-/// Allowed time window (in seconds) for which a signed message timestamp is considered valid.
+/// Default time window (in seconds) for which a signed message timestamp is considered valid.
 pub const ALLOWED_WINDOW: u64 = 30; // Seconds
 
 /// Result of validating an HMAC-signed message.
@@ -19,6 +30,8 @@ pub enum HmacValidationResult {
     InvalidHmac,
     /// The message format was malformed.
     MalformedMessage,
+    /// The message is valid, but was already accepted before.
+    Replayed,
 }
 
 use secrecy::SecretString;
@@ -35,8 +48,19 @@ use secrecy::SecretString;
 /// A `HmacValidationResult` indicating if the message is valid or why it failed.
 #[must_use]
 pub fn validate_hmac_message(data: &str, secret: &SecretString) -> HmacValidationResult {
+    validate_hmac_message_with_tolerance(data, secret, ALLOWED_WINDOW)
+}
+
+/// Validates a signed message like [`validate_hmac_message`], but accepts a clock skew of up to
+/// `tolerance_secs` seconds instead of [`ALLOWED_WINDOW`].
+#[must_use]
+pub fn validate_hmac_message_with_tolerance(
+    data: &str,
+    secret: &SecretString,
+    tolerance_secs: u64,
+) -> HmacValidationResult {
     if let Some((timestamp, message, received_signature)) = parse_hmac_message(data) {
-        if !is_timestamp_in_valid_range(timestamp) {
+        if !is_timestamp_within_tolerance(timestamp, tolerance_secs) {
             return HmacValidationResult::InvalidTimestamp;
         }
         if !verify_hmac(
@@ -51,6 +75,76 @@ pub fn validate_hmac_message(data: &str, secret: &SecretString) -> HmacValidatio
     HmacValidationResult::MalformedMessage
 }
 
+/// Signed messages accepted within the tolerance window, for rejecting replays of them.
+///
+/// Entries are the whole signed message, so the same command signed by two different secrets
+/// in the same second doesn't count as a replay. Once its timestamp left the tolerance window
+/// a replay is rejected by the timestamp check anyway, so [`NonceCache::remove_expired`] should
+/// be called periodically to drop such entries.
+///
+/// The tolerance is passed to every check rather than fixed at construction, so it can change at
+/// runtime, e.g. on a config reload.
+#[derive(Debug, Default)]
+pub struct NonceCache {
+    /// Largest tolerance messages were accepted with. Entries are kept for this long, so a
+    /// message forgotten under a small tolerance can't be replayed after it was raised again.
+    retention_secs: u64,
+    seen: HashSet<String>,
+}
+
+impl NonceCache {
+    /// Number of remembered messages.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Whether no messages are remembered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Drops the messages whose timestamp is outside the largest tolerance window they were
+    /// checked with.
+    pub fn remove_expired(&mut self) {
+        self.remove_expired_at(unix_time_seconds());
+    }
+
+    fn remove_expired_at(&mut self, now: u64) {
+        let retention_secs = self.retention_secs;
+        self.seen.retain(|data| {
+            parse_hmac_message(data)
+                .is_some_and(|(timestamp, ..)| timestamp.saturating_add(retention_secs) >= now)
+        });
+    }
+}
+
+/// Validates a signed message like [`validate_hmac_message_with_tolerance`], and additionally
+/// rejects messages that were already accepted.
+///
+/// Note that this also rejects a client legitimately sending the same command twice within a
+/// second, as both messages are identical.
+#[must_use]
+pub fn validate_hmac_message_no_replay(
+    data: &str,
+    secret: &SecretString,
+    tolerance_secs: u64,
+    cache: &mut NonceCache,
+) -> HmacValidationResult {
+    match validate_hmac_message_with_tolerance(data, secret, tolerance_secs) {
+        HmacValidationResult::Valid(message) => {
+            cache.retention_secs = cache.retention_secs.max(tolerance_secs);
+            if cache.seen.insert(data.to_string()) {
+                HmacValidationResult::Valid(message)
+            } else {
+                HmacValidationResult::Replayed
+            }
+        }
+        other => other,
+    }
+}
+
 /// Verifies an HMAC signature against a message.
 #[must_use]
 pub fn verify_hmac(message: &str, received_signature: &str, secret: &SecretString) -> bool {
@@ -60,7 +154,13 @@ pub fn verify_hmac(message: &str, received_signature: &str, secret: &SecretStrin
 /// Checks if a timestamp is within the allowed time window.
 #[must_use]
 pub fn is_timestamp_in_valid_range(timestamp: u64) -> bool {
-    unix_time_seconds().abs_diff(timestamp) <= ALLOWED_WINDOW
+    is_timestamp_within_tolerance(timestamp, ALLOWED_WINDOW)
+}
+
+/// Checks if a timestamp is at most `tolerance_secs` seconds away from the current time.
+#[must_use]
+pub fn is_timestamp_within_tolerance(timestamp: u64, tolerance_secs: u64) -> bool {
+    unix_time_seconds().abs_diff(timestamp) <= tolerance_secs
 }
 
 /// Parses an HMAC message into its components.
@@ -74,6 +174,68 @@ pub fn parse_hmac_message(data: &str) -> Option<(u64, String, String)> {
     Some((timestamp, message.to_string(), signature.to_string()))
 }
 
+/// Placeholder the host agent prints for an IP or MAC address it failed to detect.
+pub const UNRECOGNIZED_ADDRESS: &str = "unrecognized";
+
+/// Validates a host entry for the coordinator's `[hosts]` section, as printed by the host agent.
+///
+/// The snippet may be a `[hosts."name"]` table or an inline `"name" = { ... }` entry, and must
+/// contain exactly one host with a valid `ip`, `mac` (a single address or a list) and `port`.
+/// Addresses the agent failed to detect may be [`UNRECOGNIZED_ADDRESS`], to be filled in by hand.
+///
+/// # Errors
+///
+/// Returns a description of the first problem found.
+#[cfg(feature = "config-snippet")]
+pub fn validate_config_snippet(snippet: &str) -> Result<(), String> {
+    let config: Table = format!("[hosts]\n{snippet}")
+        .parse()
+        .map_err(|e| format!("Invalid TOML: {e}"))?;
+    let mut hosts = config
+        .get("hosts")
+        .and_then(Value::as_table)
+        .into_iter()
+        .flatten();
+    let (Some((name, host)), None) = (hosts.next(), hosts.next()) else {
+        return Err("Expected exactly one host entry".to_string());
+    };
+    let field = |key: &str| {
+        host.get(key)
+            .ok_or_else(|| format!("Host \"{name}\" is missing `{key}`"))
+    };
+
+    let ip = field("ip")?;
+    if !ip
+        .as_str()
+        .is_some_and(|ip| ip == UNRECOGNIZED_ADDRESS || ip.parse::<IpAddr>().is_ok())
+    {
+        return Err(format!("Host \"{name}\" has an invalid ip {ip}"));
+    }
+
+    let macs = field("mac")?;
+    let macs = match *macs {
+        Value::String(_) => slice::from_ref(macs),
+        Value::Array(ref list) if !list.is_empty() => list.as_slice(),
+        _ => return Err(format!("Host \"{name}\" has an invalid mac {macs}")),
+    };
+    if let Some(invalid) = macs.iter().find(|value| {
+        !value
+            .as_str()
+            .is_some_and(|mac| mac == UNRECOGNIZED_ADDRESS || parse_mac(mac).is_ok())
+    }) {
+        return Err(format!("Host \"{name}\" has an invalid mac {invalid}"));
+    }
+
+    let port = field("port")?;
+    if port
+        .as_integer()
+        .is_none_or(|port| u16::try_from(port).is_err())
+    {
+        return Err(format!("Host \"{name}\" has an invalid port {port}"));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,10 +251,170 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn tolerance_widens_timestamp_window() {
+        let secret = SecretString::from("mysecret");
+        let message = format!("{}|hello", unix_time_seconds() - 2 * ALLOWED_WINDOW);
+        let signed = format!("{message}|{}", sign_hmac(&message, &secret));
+        assert_eq!(
+            validate_hmac_message(&signed, &secret),
+            HmacValidationResult::InvalidTimestamp
+        );
+        assert_eq!(
+            validate_hmac_message_with_tolerance(&signed, &secret, 3 * ALLOWED_WINDOW),
+            HmacValidationResult::Valid("hello".to_string())
+        );
+    }
+
+    #[test]
+    fn replayed_message_is_rejected() {
+        let secret = SecretString::from("mysecret");
+        let mut cache = NonceCache::default();
+        let signed = crate::create_signed_message("hello", &secret);
+        assert_eq!(
+            validate_hmac_message_no_replay(&signed, &secret, ALLOWED_WINDOW, &mut cache),
+            HmacValidationResult::Valid("hello".to_string())
+        );
+        assert_eq!(
+            validate_hmac_message_no_replay(&signed, &secret, ALLOWED_WINDOW, &mut cache),
+            HmacValidationResult::Replayed
+        );
+
+        // The same command signed with another secret is a different message.
+        let other_secret = SecretString::from("othersecret");
+        let other = crate::create_signed_message("hello", &other_secret);
+        assert_eq!(
+            validate_hmac_message_no_replay(&other, &other_secret, ALLOWED_WINDOW, &mut cache),
+            HmacValidationResult::Valid("hello".to_string())
+        );
+
+        // Invalid messages are not remembered.
+        let mut tampered = signed;
+        tampered.push('0');
+        assert_eq!(
+            validate_hmac_message_no_replay(&tampered, &secret, ALLOWED_WINDOW, &mut cache),
+            HmacValidationResult::InvalidHmac
+        );
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn nonce_cache_expires_messages_outside_window() {
+        let secret = SecretString::from("mysecret");
+        let mut cache = NonceCache::default();
+        let signed = crate::create_signed_message("hello", &secret);
+        assert_eq!(
+            validate_hmac_message_no_replay(&signed, &secret, ALLOWED_WINDOW, &mut cache),
+            HmacValidationResult::Valid("hello".to_string())
+        );
+
+        cache.remove_expired_at(unix_time_seconds());
+        assert_eq!(cache.len(), 1);
+        cache.remove_expired_at(unix_time_seconds() + ALLOWED_WINDOW + 1);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn nonce_cache_follows_tolerance_changes() {
+        let secret = SecretString::from("mysecret");
+        let mut cache = NonceCache::default();
+        let timestamp = unix_time_seconds() - 2 * ALLOWED_WINDOW;
+        let message = format!("{timestamp}|hello");
+        let signed = format!("{message}|{}", sign_hmac(&message, &secret));
+
+        assert_eq!(
+            validate_hmac_message_no_replay(&signed, &secret, ALLOWED_WINDOW, &mut cache),
+            HmacValidationResult::InvalidTimestamp
+        );
+        // Raised after the cache was created, e.g. by a config reload.
+        assert_eq!(
+            validate_hmac_message_no_replay(&signed, &secret, 3 * ALLOWED_WINDOW, &mut cache),
+            HmacValidationResult::Valid("hello".to_string())
+        );
+
+        // Lowering the tolerance again doesn't forget the message early.
+        assert_eq!(
+            validate_hmac_message_no_replay(
+                &crate::create_signed_message("other", &secret),
+                &secret,
+                ALLOWED_WINDOW,
+                &mut cache
+            ),
+            HmacValidationResult::Valid("other".to_string())
+        );
+        cache.remove_expired_at(unix_time_seconds());
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            validate_hmac_message_no_replay(&signed, &secret, 3 * ALLOWED_WINDOW, &mut cache),
+            HmacValidationResult::Replayed
+        );
+    }
+
     #[test]
     fn parse_hmac_message_works() {
         let data = "123|msg|sig";
         let parsed = parse_hmac_message(data);
         assert_eq!(parsed, Some((123, "msg".to_string(), "sig".to_string())));
     }
+
+    #[cfg(feature = "config-snippet")]
+    #[test]
+    fn valid_config_snippets_pass() {
+        let table = r#"[hosts."nas"]
+ip = "192.168.1.10"
+mac = "aa:bb:cc:dd:ee:ff"
+port = 9090
+shared_secret = "s3cret"
+enforce_state = false
+"#;
+        assert_eq!(validate_config_snippet(table), Ok(()));
+
+        let inline = r#""nas" = { ip = "fe80::1", mac = ["aa-bb-cc-dd-ee-ff", "11:22:33:44:55:66"], port = 9090, shared_secret = "s3cret" }"#;
+        assert_eq!(validate_config_snippet(inline), Ok(()));
+
+        let undetected = r#""nas" = { ip = "unrecognized", mac = "unrecognized", port = 9090 }"#;
+        assert_eq!(validate_config_snippet(undetected), Ok(()));
+    }
+
+    #[cfg(feature = "config-snippet")]
+    #[test]
+    fn invalid_config_snippets_fail() {
+        let snippet = |ip: &str, mac: &str, port: &str| {
+            format!(r#""nas" = {{ ip = {ip}, mac = {mac}, port = {port}, shared_secret = "s" }}"#)
+        };
+        let valid = snippet(r#""10.0.0.1""#, r#""aa:bb:cc:dd:ee:ff""#, "9090");
+        assert_eq!(validate_config_snippet(&valid), Ok(()));
+
+        for invalid in [
+            snippet(r#""10.0.0.256""#, r#""aa:bb:cc:dd:ee:ff""#, "9090"),
+            snippet(r#""10.0.0.1""#, r#""aa:bb:cc:dd:ee""#, "9090"),
+            snippet(
+                r#""10.0.0.1""#,
+                r#"["aa:bb:cc:dd:ee:ff", "zz:bb:cc:dd:ee:ff"]"#,
+                "9090",
+            ),
+            snippet(r#""10.0.0.1""#, "[]", "9090"),
+            snippet(r#""10.0.0.1""#, r#""aa:bb:cc:dd:ee:ff""#, "70000"),
+            snippet(r#""10.0.0.1""#, r#""aa:bb:cc:dd:ee:ff""#, r#""9090""#),
+        ] {
+            assert!(
+                validate_config_snippet(&invalid).is_err(),
+                "{invalid} should be rejected"
+            );
+        }
+
+        // Unescaped quotes break the TOML syntax.
+        assert!(
+            validate_config_snippet(
+                r#""nas" = { ip = "10.0.0.1", mac = "aa:bb:cc:dd:ee:ff", port = 9090, shared_secret = "s3cr"et" }"#
+            )
+            .unwrap_err()
+            .starts_with("Invalid TOML")
+        );
+        assert!(
+            validate_config_snippet(r#""nas" = { mac = "aa:bb:cc:dd:ee:ff", port = 9090 }"#)
+                .is_err()
+        );
+        assert!(validate_config_snippet("").is_err());
+    }
 }
@@ -0,0 +1,77 @@
+//! Measures insert and lookup of the M2M [`HmacCache`] while it is full.
+//!
+//! Run with `cargo bench -p shuthost_coordinator --bench hmac_cache`. Timing is done with `std`
+//! only, so the numbers are indicative, not statistically rigorous.
+
+use core::{hint::black_box, time::Duration};
+use std::time::Instant;
+
+use secrecy::SecretString;
+use shuthost_common::{ALLOWED_WINDOW, create_signed_message};
+use shuthost_coordinator::http::m2m::HmacCache;
+
+/// Number of cached entries, the default of `[server].hmac_cache_size`.
+const CAPACITY: usize = 1024;
+/// Number of requests timed per measurement.
+const REQUESTS: usize = 100_000;
+const CLIENT_ID: &str = "bench";
+
+fn main() {
+    let secret = SecretString::from("bench-secret");
+    let sign = |i: usize| create_signed_message(&format!("take{i}"), &secret);
+    let resident: Vec<_> = (0..CAPACITY).map(sign).collect();
+    let fresh: Vec<_> = (CAPACITY..CAPACITY + REQUESTS).map(sign).collect();
+
+    // Every request misses and evicts the oldest entry.
+    let insert_cache = filled_cache(&resident, &secret);
+    report(
+        "insert at capacity",
+        time(&fresh, |request| {
+            insert_cache.validate(CLIENT_ID, request, &secret, ALLOWED_WINDOW)
+        }),
+    );
+
+    // Entries expire after a few seconds, so the cache is refilled right before timing hits.
+    let lookup_cache = filled_cache(&resident, &secret);
+    report(
+        "lookup at capacity",
+        time(resident.iter().cycle().take(REQUESTS), |request| {
+            lookup_cache.validate(CLIENT_ID, request, &secret, ALLOWED_WINDOW)
+        }),
+    );
+
+    // Baseline: the HMAC check the cache saves on a hit.
+    let uncached = HmacCache::new(0);
+    report(
+        "uncached",
+        time(resident.iter().cycle().take(REQUESTS), |request| {
+            uncached.validate(CLIENT_ID, request, &secret, ALLOWED_WINDOW)
+        }),
+    );
+}
+
+fn filled_cache(requests: &[String], secret: &SecretString) -> HmacCache {
+    let cache = HmacCache::new(CAPACITY);
+    for request in requests {
+        black_box(cache.validate(CLIENT_ID, request, secret, ALLOWED_WINDOW));
+    }
+    cache
+}
+
+/// Runs `op` on each request and returns the average duration per request.
+fn time<'request, R>(
+    requests: impl IntoIterator<Item = &'request String>,
+    mut op: impl FnMut(&str) -> R,
+) -> Duration {
+    let mut count: u32 = 0;
+    let start = Instant::now();
+    for request in requests {
+        black_box(op(black_box(request)));
+        count += 1;
+    }
+    start.elapsed() / count.max(1)
+}
+
+fn report(name: &str, per_request: Duration) {
+    println!("{name:<20} {per_request:>10.2?} per request");
+}
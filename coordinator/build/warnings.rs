@@ -123,6 +123,22 @@ pub fn emit() -> Result<(), eyre::Report> {
         ));
     }
 
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    if let Some(workspace_root) = manifest_dir.parent() {
+        let unbuilt_agents: Vec<&str> = ALL_AGENTS
+            .iter()
+            .filter(|a| a.included && !workspace_root.join(a.rel_path).exists())
+            .map(|a| a.name)
+            .collect();
+
+        if !unbuilt_agents.is_empty() {
+            build_warnings.push(format!(
+                "The following agents are enabled via features, but their binaries have not been built: {}. Build them first (see the Justfile), or disable the respective features.",
+                unbuilt_agents.join(", ")
+            ));
+        }
+    }
+
     check_stale_agents(&mut build_warnings);
 
     for warning in &build_warnings {
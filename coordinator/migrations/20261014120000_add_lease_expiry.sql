@@ -0,0 +1,9 @@
+-- Expiry of leases taken with a TTL. NULL means the lease is held until released.
+ALTER TABLE web_interface_leases ADD COLUMN expires_at DATETIME;
+ALTER TABLE client_leases ADD COLUMN expires_at DATETIME;
+
+DROP VIEW leases;
+CREATE VIEW leases AS
+SELECT hostname, 'web_interface' AS lease_source_type, NULL AS lease_source_value, created_at, expires_at FROM web_interface_leases
+UNION ALL
+SELECT hostname, 'client' AS lease_source_type, client_id AS lease_source_value, created_at, expires_at FROM client_leases;
@@ -0,0 +1,16 @@
+-- Leases taken by the schedules in [server.schedules], keyed by schedule name.
+CREATE TABLE schedule_leases (
+    hostname TEXT NOT NULL,
+    schedule_name TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME,
+    UNIQUE(hostname, schedule_name)
+);
+
+DROP VIEW leases;
+CREATE VIEW leases AS
+SELECT hostname, 'web_interface' AS lease_source_type, NULL AS lease_source_value, created_at, expires_at FROM web_interface_leases
+UNION ALL
+SELECT hostname, 'client' AS lease_source_type, client_id AS lease_source_value, created_at, expires_at FROM client_leases
+UNION ALL
+SELECT hostname, 'schedule' AS lease_source_type, schedule_name AS lease_source_value, created_at, expires_at FROM schedule_leases;
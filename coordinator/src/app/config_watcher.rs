@@ -1,23 +1,35 @@
 //! Configuration file watching and reloading utilities.
 //!
 //! This module provides functions for monitoring configuration files
-//! for changes and automatically reloading them.
+//! for changes and automatically reloading them. The TLS certificate and key are watched the
+//! same way, so renewed certificates are picked up without a restart.
 
 use alloc::sync::Arc;
+use core::{iter, time::Duration};
 use std::{
+    ffi::OsStr,
     fs,
     path::{Path, PathBuf},
 };
 
+use axum_server::tls_rustls::RustlsConfig as AxumRustlsConfig;
 use eyre::{Result, WrapErr as _};
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
-use tokio::sync::mpsc::unbounded_channel;
-use tracing::{error, info, warn};
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, unbounded_channel},
+    time::{Instant, timeout_at},
+};
+use tracing::{debug, error, info, warn};
 
-use super::state::{ConfigRx, ConfigTx};
+use super::state::{ConfigError, ConfigRx, ConfigTx, WsTx};
 use crate::{
-    app::state::emit_warning_on_unsaved_sync_state,
+    app::{
+        db::{self, DbPool},
+        state::emit_warning_on_unsaved_sync_state,
+    },
     config::{self, ControllerConfig},
+    http::tls::TlsFiles,
+    websocket::WsMessage,
 };
 
 /// Handles the logic for reloading the configuration file and updating the application state.
@@ -31,13 +43,21 @@ use crate::{
 /// * `path` - The path to the configuration file.
 /// * `tx` - The sender part of a watch channel for broadcasting configuration updates.
 /// * `rx` - The receiver part of a watch channel for reading the current configuration state.
-async fn process_config_change(path: &Path, tx: &ConfigTx, rx: &ConfigRx) -> Result<()> {
+/// * `db_pool` - Database to remove the leases of removed hosts from, if persistence is enabled.
+async fn process_config_change(
+    path: &Path,
+    tx: &ConfigTx,
+    rx: &ConfigRx,
+    db_pool: Option<&DbPool>,
+) -> Result<()> {
     info!("Config file modified. Reloading...");
     let prev = rx.borrow().clone();
     let new_config = config::load(path)
         .await
         .wrap_err(format!("Failed to reload config at: {}", path.display()))?;
     let effective = ControllerConfig {
+        include: new_config.include.clone(),
+        included_files: new_config.included_files.clone(),
         hosts: new_config.hosts.clone(),
         clients: new_config.clients.clone(),
         notifications: new_config.notifications.clone(),
@@ -45,6 +65,7 @@ async fn process_config_change(path: &Path, tx: &ConfigTx, rx: &ConfigRx) -> Res
     };
     // Determine what changed
     let uneffective_change = effective != new_config;
+    let includes_changed = new_config.included_files != prev.included_files;
     let hosts_changed = new_config.hosts != prev.hosts;
     let clients_changed = new_config.clients != prev.clients;
     let notifications_changed = new_config.notifications != prev.notifications;
@@ -55,13 +76,23 @@ async fn process_config_change(path: &Path, tx: &ConfigTx, rx: &ConfigRx) -> Res
         );
     }
 
-    if hosts_changed || clients_changed || notifications_changed {
+    if includes_changed || hosts_changed || clients_changed || notifications_changed {
         emit_warning_on_unsaved_sync_state(&effective);
 
         // Only apply hosts/clients updates; keep prior server config
-        tx.send(Arc::new(effective))
+        let effective = Arc::new(effective);
+        tx.send(effective.clone())
             .wrap_err("Failed to send updated config through watch channel")?;
         info!("Applied hosts/clients/notifications changes from config file.");
+
+        // The new config is already applied, so this doesn't fail the reload.
+        if hosts_changed
+            && let Some(pool) = db_pool
+            && let Err(e) =
+                db::remove_orphaned_leases(pool, |host| effective.hosts.contains_key(host)).await
+        {
+            warn!(?e, "Failed to remove the persisted leases of removed hosts");
+        }
     } else if uneffective_change {
         // Only unsupported changes were made; nothing to apply
         info!("No applicable (hosts/clients) changes detected; ignoring unsupported updates.");
@@ -71,73 +102,266 @@ async fn process_config_change(path: &Path, tx: &ConfigTx, rx: &ConfigRx) -> Res
     Ok(())
 }
 
-/// Watches a config file for modifications and updates the provided channel on changes.
+/// Records the outcome of a reload in `config_error`, and tells the web UI when it changed.
 ///
-/// # Arguments
+/// Repeating the same error (e.g. saving the broken file again) is not broadcast again.
+async fn publish_reload_result(result: &Result<()>, ws_tx: &WsTx, config_error: &ConfigError) {
+    let error = result.as_ref().err().map(|e| format!("{e:#}"));
+    let mut current = config_error.write().await;
+    if *current == error {
+        return;
+    }
+    current.clone_from(&error);
+    drop(current);
+
+    let msg = match error {
+        Some(message) => WsMessage::ConfigError { message },
+        None => WsMessage::ConfigErrorResolved,
+    };
+    if ws_tx.send(msg).is_err() {
+        debug!("No Websocket Subscribers");
+    }
+}
+
+/// Time without further matching events after which a burst of file events is considered complete.
 ///
-/// * `path` - Path to the config file to watch.
-/// * `tx` - Watch channel sender to broadcast new config instances.
+/// Editors and tools often produce several events per save (truncate + write, or write to a temp
+/// file + rename), so reloading on the first event risks reading a partially written file.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Returns whether `event` may have changed the contents of the config file at `path`.
 ///
-/// # Panics
+/// Atomic saves replace the file via rename, which shows up as `Create`/`Remove` (or rename
+/// `Modify`) events on the directory rather than as a plain content modification.
+fn is_config_event(event: &Event, path: &Path, config_filename: &OsStr) -> bool {
+    if !matches!(
+        event.kind,
+        EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
+    ) {
+        return false;
+    }
+    // Check if any of the event paths match our config file
+    // We check both exact path match and filename match (for atomic writes)
+    event.paths.iter().any(|event_path| {
+        // Try exact match first
+        if event_path == path {
+            return true;
+        }
+        // Try canonicalized comparison (handles path format differences)
+        if let (Ok(canonical_event), Ok(canonical_config)) =
+            (fs::canonicalize(event_path), fs::canonicalize(path))
+            && canonical_event == canonical_config
+        {
+            return true;
+        }
+        // Fallback to filename match (handles atomic writes where temp files are involved)
+        event_path
+            .file_name()
+            .is_some_and(|event_filename| event_filename == config_filename)
+    })
+}
+
+/// Watches the non-recursive contents of `dirs` and forwards all events to the returned channel.
 ///
-/// Panics if the file watcher cannot be created or if the config file doesnt have a parent directory.
-pub(super) async fn watch_config_file(path: PathBuf, tx: ConfigTx) {
-    let (raw_tx, mut raw_rx) = unbounded_channel::<Event>();
+/// The watcher stops when the returned [`RecommendedWatcher`] is dropped.
+fn watch_dirs<'dir>(
+    dirs: impl IntoIterator<Item = &'dir Path>,
+) -> notify::Result<(RecommendedWatcher, UnboundedReceiver<Event>)> {
+    let (raw_tx, raw_rx) = unbounded_channel::<Event>();
 
     let mut watcher = RecommendedWatcher::new(
         move |res| {
             if let Ok(event) = res
                 && raw_tx.send(event).is_err()
             {
-                error!("Failed to send event to config watcher channel");
+                error!("Failed to send event to file watcher channel");
             }
         },
         notify::Config::default(),
-    )
-    .expect("Failed to create file watcher");
+    )?;
+    for dir in dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    }
+    Ok((watcher, raw_rx))
+}
 
-    let dir = path
-        .parent()
-        .expect("Config file must have a parent directory");
-    watcher
-        .watch(dir, RecursiveMode::NonRecursive)
-        .expect("Failed to watch config directory");
+/// Waits until the burst of events belonging to a save is over.
+///
+/// Only events matching `is_relevant` extend the window, so unrelated files in the directory
+/// (e.g. the database) can't postpone the reload indefinitely.
+///
+/// Returns `false` if the event channel was closed.
+async fn debounce(
+    raw_rx: &mut UnboundedReceiver<Event>,
+    is_relevant: impl Fn(&Event) -> bool,
+) -> bool {
+    let mut deadline = Instant::now() + DEBOUNCE;
+    loop {
+        match timeout_at(deadline, raw_rx.recv()).await {
+            Ok(Some(next)) => {
+                if is_relevant(&next) {
+                    deadline = Instant::now() + DEBOUNCE;
+                }
+            }
+            Ok(None) => return false,
+            Err(_elapsed) => return true,
+        }
+    }
+}
 
+/// Watches a config file and the files it includes for modifications and updates the provided
+/// channel on changes.
+///
+/// The parent directories are watched rather than the files themselves, so the watch stays valid
+/// when a file is replaced by a rename. Bursts of events are debounced by [`DEBOUNCE`]. When a
+/// reload changes the set of included files, the watch is set up again for the new set.
+///
+/// A file that fails to load (e.g. while it is being edited) leaves the last valid config in
+/// place. The error is stored in `config_error` and broadcast to the web UI until a later reload
+/// succeeds.
+///
+/// # Arguments
+///
+/// * `path` - Path to the config file to watch.
+/// * `tx` - Watch channel sender to broadcast new config instances.
+/// * `ws_tx` - Broadcast sender for notifying the web UI about reload errors.
+/// * `config_error` - Error of the last reload, shared with new WebSocket connections.
+/// * `db_pool` - Database to remove the leases of removed hosts from, if persistence is enabled.
+///
+/// # Panics
+///
+/// Panics if the file watcher cannot be created or if the config file doesnt have a parent directory.
+pub(super) async fn watch_config_file(
+    path: PathBuf,
+    tx: ConfigTx,
+    ws_tx: WsTx,
+    config_error: ConfigError,
+    db_pool: Option<DbPool>,
+) {
     // Receiver used to read the current effective config for change comparisons
     let rx = tx.subscribe();
 
-    // Get the filename to match against, as a fallback for path comparison issues
-    let config_filename = path.file_name().expect("Config file must have a filename");
+    loop {
+        let included_files = rx.borrow().included_files.clone();
+        let files: Vec<&Path> = iter::once(path.as_path())
+            .chain(included_files.iter().map(PathBuf::as_path))
+            .collect();
+        let mut dirs: Vec<&Path> = files
+            .iter()
+            .map(|file| {
+                file.parent()
+                    .expect("Config file must have a parent directory")
+            })
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        let (_watcher, mut raw_rx) = watch_dirs(dirs).expect("Failed to watch config directory");
 
-    while let Some(event) = raw_rx.recv().await {
-        if matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) {
-            // Check if any of the event paths match our config file
-            // We check both exact path match and filename match (for atomic writes)
-            let matches_config = event.paths.iter().any(|event_path| {
-                // Try exact match first
-                if event_path == &path {
-                    return true;
-                }
-                // Try canonicalized comparison (handles path format differences)
-                if let (Ok(canonical_event), Ok(canonical_config)) =
-                    (fs::canonicalize(event_path), fs::canonicalize(&path))
-                    && canonical_event == canonical_config
-                {
-                    return true;
-                }
-                // Fallback to filename match (handles atomic writes where temp files are involved)
-                if let Some(event_filename) = event_path.file_name()
-                    && event_filename == config_filename
-                {
-                    return true;
-                }
-                false
-            });
+        // The filenames are matched as a fallback for path comparison issues
+        let watched: Vec<(&Path, &OsStr)> = files
+            .iter()
+            .map(|&file| {
+                (
+                    file,
+                    file.file_name().expect("Config file must have a filename"),
+                )
+            })
+            .collect();
+        let is_relevant = |event: &Event| {
+            watched
+                .iter()
+                .any(|&(file, filename)| is_config_event(event, file, filename))
+        };
+
+        loop {
+            let Some(event) = raw_rx.recv().await else {
+                return;
+            };
+            if !is_relevant(&event) {
+                continue;
+            }
+
+            if !debounce(&mut raw_rx, is_relevant).await {
+                return;
+            }
+
+            if !path.exists() {
+                // Removed without (yet) being replaced; the recreation triggers another event.
+                debug!("Config file was removed, waiting for it to reappear");
+                continue;
+            }
 
-            if matches_config && let Err(e) = process_config_change(&path, &tx, &rx).await {
-                error!(?e, "Failed to process config change");
+            let result = process_config_change(&path, &tx, &rx, db_pool.as_ref()).await;
+            if let Err(ref e) = result {
+                error!(
+                    ?e,
+                    "Failed to process config change, keeping the last valid config"
+                );
+            }
+            publish_reload_result(&result, &ws_tx, &config_error).await;
+            if rx.borrow().included_files != included_files {
+                info!("Included config files changed, updating the file watch");
                 break;
             }
         }
     }
 }
+
+/// Watches the TLS certificate files and reloads `rustls_config` when any of them changes.
+///
+/// Renewals usually replace the certificate and key in quick succession, which the debounce turns
+/// into a single reload. If the new files can't be loaded, the previous certificate stays in use.
+///
+/// # Arguments
+///
+/// * `tls_files` - Resolved PEM certificate and key, or PKCS#12 archive, to watch and load.
+/// * `rustls_config` - Handle of the running server's TLS configuration.
+pub(super) async fn watch_tls_files(tls_files: TlsFiles, rustls_config: AxumRustlsConfig) {
+    let paths = tls_files.paths();
+    let mut dirs: Vec<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+    dirs.dedup();
+    let (_watcher, mut raw_rx) = match watch_dirs(dirs) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!(
+                ?e,
+                "Failed to watch TLS files, certificate changes require a restart"
+            );
+            return;
+        }
+    };
+
+    let files: Vec<_> = paths
+        .iter()
+        .map(|&path| (path, path.file_name().unwrap_or_default()))
+        .collect();
+    let is_relevant = |event: &Event| {
+        files
+            .iter()
+            .any(|&(path, filename)| is_config_event(event, path, filename))
+    };
+
+    while let Some(event) = raw_rx.recv().await {
+        if !is_relevant(&event) {
+            continue;
+        }
+
+        if !debounce(&mut raw_rx, is_relevant).await {
+            return;
+        }
+
+        if !paths.iter().all(|path| path.exists()) {
+            debug!("TLS certificate or key was removed, waiting for it to reappear");
+            continue;
+        }
+
+        info!("TLS certificate or key modified. Reloading...");
+        match tls_files.reload(&rustls_config).await {
+            Ok(()) => info!(?paths, "Reloaded TLS certificate"),
+            Err(e) => error!(
+                ?e,
+                "Failed to reload TLS certificate, keeping the previous one"
+            ),
+        }
+    }
+}
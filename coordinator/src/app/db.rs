@@ -2,6 +2,7 @@
 //!
 //! This module handles `SQLite` database operations for persisting leases and other state.
 
+use core::{net::IpAddr, str::FromStr as _, time::Duration};
 use std::{collections::HashMap, path::Path};
 
 #[cfg(unix)]
@@ -11,8 +12,13 @@ use chrono::{DateTime, Utc};
 use eyre::Context as _;
 use serde::{Deserialize, Serialize};
 use shuthost_common::protocol::{InitSystem, OsType};
-use sqlx::{Sqlite, SqlitePool, migrate::MigrateDatabase as _};
-use tracing::warn;
+use sqlx::{
+    Sqlite, SqlitePool,
+    migrate::{MigrateDatabase as _, MigrateError},
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+};
+use tokio::time::Instant;
+use tracing::{info, warn};
 
 use crate::app::{LeaseMap, LeaseSource};
 
@@ -29,6 +35,7 @@ struct LeaseRecord {
     hostname: String,
     lease_source_type: String,
     lease_source_value: Option<String>,
+    expires_at: Option<chrono::NaiveDateTime>,
 }
 
 /// Represents a key-value record from the database.
@@ -67,7 +74,7 @@ struct HostIpOverrideRecord {
 /// Updated when an agent startup broadcast arrives with a new address.
 #[derive(Debug, Clone)]
 pub(crate) struct HostOverride {
-    pub ip: String,
+    pub ip: IpAddr,
     pub port: u16,
 }
 
@@ -120,11 +127,22 @@ fn check_file_permissions(path: &Path, expected_mode: u32) {
     }
 }
 
+/// How long a connection waits for a lock held by another one before failing with `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Creates or opens the `SQLite` database and runs migrations.
 ///
+/// The database uses WAL journal mode with `synchronous=NORMAL`, so readers don't block the
+/// writer, and connections wait up to [`BUSY_TIMEOUT`] for locks held by each other.
+///
+/// Migrations from `coordinator/migrations` are tracked by sqlx in `_sqlx_migrations`; each pending
+/// one is applied in its own transaction. A database that was already migrated by a newer
+/// coordinator is refused instead of being used with an unknown schema.
+///
 /// # Arguments
 ///
 /// * `db_path` - Path to the `SQLite` database file.
+/// * `pool_size` - Maximum number of connections in the pool.
 ///
 /// # Returns
 ///
@@ -133,7 +151,7 @@ fn check_file_permissions(path: &Path, expected_mode: u32) {
 /// # Errors
 ///
 /// Returns an error if the database cannot be created or migrated.
-pub(crate) async fn init(db_path: &Path) -> eyre::Result<DbPool> {
+pub(crate) async fn init(db_path: &Path, pool_size: u32) -> eyre::Result<DbPool> {
     let db_url = format!("sqlite:{}", db_path.display());
 
     // Create database if it doesn't exist
@@ -141,16 +159,30 @@ pub(crate) async fn init(db_path: &Path) -> eyre::Result<DbPool> {
         Sqlite::create_database(&db_url).await?;
     }
 
-    let pool = DbPool::connect(&db_url).await?;
+    let options = SqliteConnectOptions::from_str(&db_url)?
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(BUSY_TIMEOUT);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(pool_size)
+        .connect_with(options)
+        .await?;
 
     // Run migrations
-    sqlx::migrate!("./migrations")
-        .run(&pool)
-        .await
-        .wrap_err(format!(
-            "Failed to run database migrations on: {}",
+    match sqlx::migrate!("./migrations").run(&pool).await {
+        Ok(()) => {}
+        Err(MigrateError::VersionMissing(version)) => eyre::bail!(
+            "Database {} contains migration {version}, which this coordinator does not know. \
+             It was likely used by a newer version; upgrade the coordinator or restore a backup.",
             db_path.display()
-        ))?;
+        ),
+        Err(e) => {
+            return Err(e).wrap_err(format!(
+                "Failed to run database migrations on: {}",
+                db_path.display()
+            ));
+        }
+    }
 
     #[cfg(unix)]
     {
@@ -168,11 +200,21 @@ pub(crate) async fn init(db_path: &Path) -> eyre::Result<DbPool> {
     Ok(pool)
 }
 
+/// Checks that the database still answers queries.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub(crate) async fn ping(pool: &DbPool) -> eyre::Result<()> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
 /// Loads all host IP overrides from the database.
 ///
 /// # Errors
 ///
-/// Returns an error if the database query fails.
+/// Returns an error if the database query fails or a stored IP address is invalid.
 #[tracing::instrument(skip(pool), err)]
 pub(crate) async fn load_host_ip_overrides(
     pool: &DbPool,
@@ -184,18 +226,22 @@ pub(crate) async fn load_host_ip_overrides(
     .fetch_all(pool)
     .await?;
 
-    Ok(records
+    records
         .into_iter()
         .map(|r| {
-            (
+            let ip = r.ip.parse().wrap_err(format!(
+                "Invalid IP override '{}' stored for host '{}'",
+                r.ip, r.hostname
+            ))?;
+            Ok((
                 r.hostname,
                 HostOverride {
-                    ip: r.ip,
+                    ip,
                     port: u16::try_from(r.port).expect("Invalid port value in database"),
                 },
-            )
+            ))
         })
-        .collect())
+        .collect()
 }
 
 /// Inserts or replaces a host IP override in the database.
@@ -207,9 +253,10 @@ pub(crate) async fn load_host_ip_overrides(
 pub(crate) async fn upsert_host_ip_override(
     pool: &DbPool,
     hostname: &str,
-    ip: &str,
+    ip: IpAddr,
     port: u16,
 ) -> eyre::Result<()> {
+    let ip = ip.to_string();
     let port = i64::from(port);
     sqlx::query!(
         "INSERT OR REPLACE INTO host_ip_overrides (hostname, ip, port) VALUES (?, ?, ?)",
@@ -232,6 +279,9 @@ pub(crate) async fn delete_host_ip_override(pool: &DbPool, hostname: &str) -> ey
 
 /// Loads all leases from the database into the in-memory map.
 ///
+/// Persisted expiries are converted back into deadlines relative to now; leases that expired
+/// while the coordinator was down are released on the first expiry check.
+///
 /// # Arguments
 ///
 /// * `pool` - Database connection pool.
@@ -248,7 +298,7 @@ pub(crate) async fn load_leases(pool: &DbPool, leases: &mut LeaseMap) -> eyre::R
     // Load all lease records
     let lease_records = sqlx::query_as!(
         LeaseRecord,
-        "SELECT hostname, lease_source_type, lease_source_value FROM leases"
+        "SELECT hostname, lease_source_type, lease_source_value, expires_at FROM leases"
     )
     .fetch_all(pool)
     .await?;
@@ -261,6 +311,9 @@ pub(crate) async fn load_leases(pool: &DbPool, leases: &mut LeaseMap) -> eyre::R
         let lease_source = match lease_source_type.as_str() {
             "web_interface" => LeaseSource::WebInterface,
             "client" => LeaseSource::Client(lease_source_value.unwrap_or_default()),
+            "schedule" => LeaseSource::Schedule {
+                name: lease_source_value.unwrap_or_default(),
+            },
             _ => {
                 warn!(
                     "Skipping invalid lease record with type: {}",
@@ -270,7 +323,15 @@ pub(crate) async fn load_leases(pool: &DbPool, leases: &mut LeaseMap) -> eyre::R
             }
         };
 
-        leases.entry(hostname).or_default().insert(lease_source);
+        let deadline = row.expires_at.and_then(|expires_at| {
+            let remaining =
+                DateTime::<Utc>::from_naive_utc_and_offset(expires_at, Utc) - Utc::now();
+            Instant::now().checked_add(remaining.to_std().unwrap_or_default())
+        });
+        leases
+            .entry(hostname)
+            .or_default()
+            .insert_with_expiry(lease_source, deadline);
     }
 
     Ok(())
@@ -283,6 +344,8 @@ pub(crate) async fn load_leases(pool: &DbPool, leases: &mut LeaseMap) -> eyre::R
 /// * `pool` - Database connection pool.
 /// * `hostname` - The hostname for the lease.
 /// * `lease_source` - The lease source to persist.
+/// * `expires_at` - When the lease expires, `None` if it is held until released.
+///   Replaces the expiry of an already persisted lease.
 ///
 /// # Errors
 ///
@@ -292,21 +355,37 @@ pub(crate) async fn add_lease(
     pool: &DbPool,
     hostname: &str,
     lease_source: &LeaseSource,
+    expires_at: Option<DateTime<Utc>>,
 ) -> sqlx::Result<()> {
     match *lease_source {
         LeaseSource::WebInterface => {
             sqlx::query!(
-                "INSERT OR IGNORE INTO web_interface_leases (hostname) VALUES (?)",
-                hostname
+                "INSERT INTO web_interface_leases (hostname, expires_at) VALUES (?, ?) \
+                 ON CONFLICT(hostname) DO UPDATE SET expires_at = excluded.expires_at",
+                hostname,
+                expires_at
             )
             .execute(pool)
             .await?;
         }
         LeaseSource::Client(ref client_id) => {
             sqlx::query!(
-                "INSERT OR IGNORE INTO client_leases (hostname, client_id) VALUES (?, ?)",
+                "INSERT INTO client_leases (hostname, client_id, expires_at) VALUES (?, ?, ?) \
+                 ON CONFLICT(hostname, client_id) DO UPDATE SET expires_at = excluded.expires_at",
                 hostname,
-                client_id
+                client_id,
+                expires_at
+            )
+            .execute(pool)
+            .await?;
+        }
+        LeaseSource::Schedule { ref name } => {
+            sqlx::query!(
+                "INSERT INTO schedule_leases (hostname, schedule_name, expires_at) VALUES (?, ?, ?) \
+                 ON CONFLICT(hostname, schedule_name) DO UPDATE SET expires_at = excluded.expires_at",
+                hostname,
+                name,
+                expires_at
             )
             .execute(pool)
             .await?;
@@ -350,6 +429,15 @@ pub(crate) async fn remove_lease(
             .execute(pool)
             .await?;
         }
+        LeaseSource::Schedule { ref name } => {
+            sqlx::query!(
+                "DELETE FROM schedule_leases WHERE hostname = ? AND schedule_name = ?",
+                hostname,
+                name
+            )
+            .execute(pool)
+            .await?;
+        }
     }
     Ok(())
 }
@@ -365,7 +453,7 @@ pub(crate) async fn remove_lease(
 ///
 /// Returns an error if the database operation fails.
 #[tracing::instrument(err)]
-pub(crate) async fn remove_client_leases(pool: &DbPool, client_id: &str) -> eyre::Result<()> {
+pub(crate) async fn remove_client_leases(pool: &DbPool, client_id: &str) -> sqlx::Result<()> {
     sqlx::query!("DELETE FROM client_leases WHERE client_id = ?", client_id)
         .execute(pool)
         .await?;
@@ -373,6 +461,75 @@ pub(crate) async fn remove_client_leases(pool: &DbPool, client_id: &str) -> eyre
     Ok(())
 }
 
+/// Removes the leases of all hosts, clients and schedules from the database.
+///
+/// # Arguments
+///
+/// * `pool` - Database connection pool.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+#[tracing::instrument(err)]
+pub(crate) async fn remove_all_leases(pool: &DbPool) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM web_interface_leases")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM client_leases")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM schedule_leases")
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Removes the persisted leases of hosts that are no longer configured.
+///
+/// Removing a host from the config leaves its leases in the database, where they would be
+/// loaded back on the next start, referencing a host that doesn't exist.
+///
+/// # Arguments
+///
+/// * `pool` - Database connection pool.
+/// * `is_configured` - Whether a host of that name is in the current config.
+///
+/// # Errors
+///
+/// Returns an error if a database operation fails.
+#[tracing::instrument(skip_all, err)]
+pub(crate) async fn remove_orphaned_leases(
+    pool: &DbPool,
+    is_configured: impl Fn(&str) -> bool,
+) -> eyre::Result<()> {
+    let hostnames: Vec<String> = sqlx::query_scalar("SELECT DISTINCT hostname FROM leases")
+        .fetch_all(pool)
+        .await?;
+
+    for hostname in hostnames.iter().filter(|h| !is_configured(h)) {
+        let mut tx = pool.begin().await?;
+        let mut removed = 0;
+        for statement in [
+            "DELETE FROM web_interface_leases WHERE hostname = ?",
+            "DELETE FROM client_leases WHERE hostname = ?",
+            "DELETE FROM schedule_leases WHERE hostname = ?",
+        ] {
+            removed += sqlx::query(statement)
+                .bind(hostname)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+        tx.commit().await?;
+        info!("Removed {removed} persisted lease(s) of unconfigured host '{hostname}'");
+    }
+
+    Ok(())
+}
+
 /// Stores a key-value pair in the database.
 ///
 /// # Arguments
@@ -979,13 +1136,19 @@ pub(crate) async fn unsubscribe_host_online_for(
 
 #[cfg(test)]
 mod tests {
+    use std::{env, fs, process};
+
+    use chrono::TimeDelta;
+    use futures::future;
+
     use super::*;
+    use crate::{app::LeaseSources, config::DEFAULT_DB_POOL_SIZE};
     use sqlx::Row as _;
     use std::collections::HashMap;
     use std::collections::HashSet;
 
     async fn setup_test_db() -> eyre::Result<DbPool> {
-        init(Path::new(":memory:")).await
+        init(Path::new(":memory:"), DEFAULT_DB_POOL_SIZE).await
     }
 
     #[tokio::test]
@@ -1006,6 +1169,73 @@ mod tests {
         assert!(names.contains("client_stats"));
     }
 
+    #[tokio::test]
+    async fn init_refuses_database_from_newer_version() {
+        let path = env::temp_dir().join(format!("shuthost_db_newer_{}.db", process::id()));
+        drop(fs::remove_file(&path));
+
+        let pool = init(&path, DEFAULT_DB_POOL_SIZE).await.unwrap();
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations \
+             (version, description, success, checksum, execution_time) \
+             VALUES (99991231000000, 'from the future', TRUE, x'00', 0)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+
+        let err = init(&path, DEFAULT_DB_POOL_SIZE)
+            .await
+            .unwrap_err()
+            .to_string();
+        for extension in ["db", "db-wal", "db-shm"] {
+            drop(fs::remove_file(path.with_extension(extension)));
+        }
+        assert!(err.contains("99991231000000"), "{err}");
+        assert!(err.contains("newer version"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn concurrent_writes_succeed_in_wal_mode() {
+        let path = env::temp_dir().join(format!("shuthost_db_wal_{}.db", process::id()));
+        drop(fs::remove_file(&path));
+
+        let pool = init(&path, DEFAULT_DB_POOL_SIZE).await.unwrap();
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let results = future::join_all((0..32).map(|i| {
+            let pool = pool.clone();
+            async move {
+                let client_id = format!("client{i}");
+                let used = update_client_last_used(&pool, &client_id, Utc::now()).await;
+                let leased = add_lease(
+                    &pool,
+                    &format!("host{}", i % 4),
+                    &LeaseSource::Client(client_id),
+                    None,
+                )
+                .await;
+                (used, leased)
+            }
+        }))
+        .await;
+        let stats = get_all_client_stats(&pool).await.unwrap();
+        pool.close().await;
+        for extension in ["db", "db-wal", "db-shm"] {
+            drop(fs::remove_file(path.with_extension(extension)));
+        }
+
+        assert_eq!(journal_mode, "wal");
+        for (used, leased) in results {
+            used.unwrap();
+            leased.unwrap();
+        }
+        assert_eq!(stats.len(), 32);
+    }
+
     #[tokio::test]
     async fn add_and_load_leases() {
         let pool = setup_test_db().await.unwrap();
@@ -1016,17 +1246,27 @@ mod tests {
         assert!(leases.is_empty());
 
         // Add web interface lease
-        add_lease(&pool, "host1", &LeaseSource::WebInterface)
+        add_lease(&pool, "host1", &LeaseSource::WebInterface, None)
             .await
             .unwrap();
 
         // Add client lease
-        add_lease(&pool, "host1", &LeaseSource::Client("client1".to_string()))
-            .await
-            .unwrap();
-        add_lease(&pool, "host2", &LeaseSource::Client("client1".to_string()))
-            .await
-            .unwrap();
+        add_lease(
+            &pool,
+            "host1",
+            &LeaseSource::Client("client1".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+        add_lease(
+            &pool,
+            "host2",
+            &LeaseSource::Client("client1".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
 
         // Load and verify
         load_leases(&pool, &mut leases).await.unwrap();
@@ -1038,17 +1278,61 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn remove_lease_works() {
+    async fn remove_orphaned_leases_keeps_configured_hosts() {
         let pool = setup_test_db().await.unwrap();
         let mut leases: LeaseMap = HashMap::new();
 
-        // Add leases
-        add_lease(&pool, "host1", &LeaseSource::WebInterface)
+        add_lease(&pool, "host1", &LeaseSource::WebInterface, None)
+            .await
+            .unwrap();
+        add_lease(&pool, "removed", &LeaseSource::WebInterface, None)
+            .await
+            .unwrap();
+        add_lease(
+            &pool,
+            "removed",
+            &LeaseSource::Client("client1".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+        add_lease(
+            &pool,
+            "removed",
+            &LeaseSource::Schedule {
+                name: "nightly".to_string(),
+            },
+            None,
+        )
+        .await
+        .unwrap();
+
+        remove_orphaned_leases(&pool, |host| host == "host1")
             .await
             .unwrap();
-        add_lease(&pool, "host1", &LeaseSource::Client("client1".to_string()))
+
+        load_leases(&pool, &mut leases).await.unwrap();
+        assert_eq!(leases.len(), 1);
+        assert!(leases["host1"].contains(&LeaseSource::WebInterface));
+    }
+
+    #[tokio::test]
+    async fn remove_lease_works() {
+        let pool = setup_test_db().await.unwrap();
+        let mut leases: LeaseMap = HashMap::new();
+
+        // Add leases
+        add_lease(&pool, "host1", &LeaseSource::WebInterface, None)
             .await
             .unwrap();
+        add_lease(
+            &pool,
+            "host1",
+            &LeaseSource::Client("client1".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
 
         // Remove web interface lease
         remove_lease(&pool, "host1", &LeaseSource::WebInterface)
@@ -1063,21 +1347,56 @@ mod tests {
         assert!(!leases["host1"].contains(&LeaseSource::WebInterface));
     }
 
+    #[tokio::test]
+    async fn schedule_leases_round_trip() {
+        let pool = setup_test_db().await.unwrap();
+        let nightly = LeaseSource::Schedule {
+            name: "nightly".to_string(),
+        };
+        let weekly = LeaseSource::Schedule {
+            name: "weekly".to_string(),
+        };
+        add_lease(&pool, "host1", &nightly, None).await.unwrap();
+        add_lease(&pool, "host1", &weekly, None).await.unwrap();
+        // Taking it again replaces rather than duplicates the row.
+        add_lease(&pool, "host1", &nightly, None).await.unwrap();
+        remove_lease(&pool, "host1", &weekly).await.unwrap();
+
+        let mut leases: LeaseMap = HashMap::new();
+        load_leases(&pool, &mut leases).await.unwrap();
+        assert_eq!(leases["host1"].iter().collect::<Vec<_>>(), [&nightly]);
+    }
+
     #[tokio::test]
     async fn remove_client_leases_works() {
         let pool = setup_test_db().await.unwrap();
         let mut leases: LeaseMap = HashMap::new();
 
         // Add client leases
-        add_lease(&pool, "host1", &LeaseSource::Client("client1".to_string()))
-            .await
-            .unwrap();
-        add_lease(&pool, "host2", &LeaseSource::Client("client1".to_string()))
-            .await
-            .unwrap();
-        add_lease(&pool, "host3", &LeaseSource::Client("client2".to_string()))
-            .await
-            .unwrap();
+        add_lease(
+            &pool,
+            "host1",
+            &LeaseSource::Client("client1".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+        add_lease(
+            &pool,
+            "host2",
+            &LeaseSource::Client("client1".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+        add_lease(
+            &pool,
+            "host3",
+            &LeaseSource::Client("client2".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
 
         // Remove all for client1
         remove_client_leases(&pool, "client1").await.unwrap();
@@ -1095,10 +1414,10 @@ mod tests {
         let mut leases: LeaseMap = HashMap::new();
 
         // Add same lease twice
-        add_lease(&pool, "host1", &LeaseSource::WebInterface)
+        add_lease(&pool, "host1", &LeaseSource::WebInterface, None)
             .await
             .unwrap();
-        add_lease(&pool, "host1", &LeaseSource::WebInterface)
+        add_lease(&pool, "host1", &LeaseSource::WebInterface, None)
             .await
             .unwrap();
 
@@ -1110,6 +1429,117 @@ mod tests {
         assert!(leases["host1"].contains(&LeaseSource::WebInterface));
     }
 
+    #[tokio::test]
+    async fn remove_all_leases_clears_both_tables() {
+        let pool = setup_test_db().await.unwrap();
+        add_lease(&pool, "host1", &LeaseSource::WebInterface, None)
+            .await
+            .unwrap();
+        add_lease(
+            &pool,
+            "host2",
+            &LeaseSource::Client("client1".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+
+        remove_all_leases(&pool).await.unwrap();
+
+        let mut leases: LeaseMap = HashMap::new();
+        load_leases(&pool, &mut leases).await.unwrap();
+        assert!(leases.values().all(LeaseSources::is_empty));
+    }
+
+    #[tokio::test]
+    async fn lease_expiry_roundtrip() {
+        let pool = setup_test_db().await.unwrap();
+        let mut leases: LeaseMap = HashMap::new();
+        let client = LeaseSource::Client("client1".to_string());
+
+        add_lease(
+            &pool,
+            "host1",
+            &client,
+            Some(Utc::now() - TimeDelta::seconds(5)),
+        )
+        .await
+        .unwrap();
+        add_lease(
+            &pool,
+            "host1",
+            &LeaseSource::WebInterface,
+            Some(Utc::now() + TimeDelta::hours(1)),
+        )
+        .await
+        .unwrap();
+        add_lease(
+            &pool,
+            "host2",
+            &client,
+            Some(Utc::now() + TimeDelta::hours(1)),
+        )
+        .await
+        .unwrap();
+        // Re-taking without a TTL clears the expiry.
+        add_lease(&pool, "host2", &client, None).await.unwrap();
+
+        load_leases(&pool, &mut leases).await.unwrap();
+
+        let now = Instant::now();
+        let host1 = leases.get_mut("host1").unwrap();
+        assert_eq!(host1.remove_expired(now), vec![client]);
+        assert!(host1.contains(&LeaseSource::WebInterface));
+        let host2 = leases.get_mut("host2").unwrap();
+        assert!(
+            host2
+                .remove_expired(now + Duration::from_hours(2))
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn host_ip_overrides_roundtrip() {
+        let pool = setup_test_db().await.unwrap();
+        assert!(load_host_ip_overrides(&pool).await.unwrap().is_empty());
+
+        upsert_host_ip_override(&pool, "host1", "192.168.1.20".parse().unwrap(), 5757)
+            .await
+            .unwrap();
+        upsert_host_ip_override(&pool, "host1", "192.168.1.21".parse().unwrap(), 5758)
+            .await
+            .unwrap();
+        upsert_host_ip_override(&pool, "host2", "fd00::2".parse().unwrap(), 5757)
+            .await
+            .unwrap();
+
+        let overrides = load_host_ip_overrides(&pool).await.unwrap();
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides["host1"].ip, IpAddr::from([192, 168, 1, 21]));
+        assert_eq!(overrides["host1"].port, 5758);
+        assert_eq!(overrides["host2"].ip, "fd00::2".parse::<IpAddr>().unwrap());
+
+        delete_host_ip_override(&pool, "host1").await.unwrap();
+        let overrides = load_host_ip_overrides(&pool).await.unwrap();
+        assert!(!overrides.contains_key("host1"));
+        assert!(overrides.contains_key("host2"));
+    }
+
+    #[tokio::test]
+    async fn load_host_ip_overrides_rejects_invalid_ip() {
+        let pool = setup_test_db().await.unwrap();
+        sqlx::query("INSERT INTO host_ip_overrides (hostname, ip, port) VALUES (?, ?, ?)")
+            .bind("host1")
+            .bind("not-an-ip")
+            .bind(5757)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let err = load_host_ip_overrides(&pool).await.unwrap_err();
+        assert!(err.to_string().contains("not-an-ip"), "{err}");
+    }
+
     #[tokio::test]
     async fn store_and_get_kv() {
         let pool = setup_test_db().await.unwrap();
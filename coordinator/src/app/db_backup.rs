@@ -0,0 +1,283 @@
+//! Copying the `SQLite` database of a coordinator config to and from a backup file.
+//!
+//! Backups use `VACUUM INTO`, which reads a consistent snapshot of the live database, so the
+//! coordinator can keep running and writing while one is taken. Restoring replaces the database
+//! file and can only be done while the coordinator is stopped.
+
+use core::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use std::path::{Path, PathBuf};
+
+use eyre::WrapErr as _;
+use sqlx::{AssertSqlSafe, Connection as _, SqliteConnection, sqlite::SqliteConnectOptions};
+use tokio::{fs, net::TcpStream, time::timeout};
+
+use crate::config::{ControllerConfig, DbConfig, load, resolve_config_relative_paths};
+
+/// Time a running coordinator gets to accept the connection made before restoring.
+const RUNNING_CHECK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long to wait for the live database to be unlocked by the running coordinator.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Copies the database configured in `config` to `output`, while the coordinator may be running.
+///
+/// Prints the number of rows of each table in the backup.
+///
+/// # Errors
+///
+/// Returns `Err` if the config has no enabled database, `output` already exists, or the backup
+/// fails.
+pub(crate) async fn backup(config: &str, output: &Path) -> eyre::Result<()> {
+    let (_, db_path) = load_db_path(config).await?;
+    if !fs::try_exists(&db_path).await? {
+        eyre::bail!("Database {} does not exist", db_path.display());
+    }
+    if fs::try_exists(output).await? {
+        eyre::bail!("Backup file {} already exists", output.display());
+    }
+
+    let counts = backup_database(&db_path, output)
+        .await
+        .wrap_err(format!("Failed to back up database {}", db_path.display()))?;
+    println!(
+        "Backed up database {} to {}",
+        db_path.display(),
+        output.display()
+    );
+    print_row_counts(&counts);
+    Ok(())
+}
+
+/// Replaces the database configured in `config` with the backup at `input`.
+///
+/// Refuses to run while something accepts connections on the configured port, as a running
+/// coordinator would keep using the old database.
+///
+/// # Errors
+///
+/// Returns `Err` if the config has no enabled database, the coordinator seems to be running,
+/// `input` is not a `SQLite` database, or copying it fails.
+pub(crate) async fn restore(config: &str, input: &Path) -> eyre::Result<()> {
+    let (parsed, db_path) = load_db_path(config).await?;
+    let addr = local_addr(&parsed)?;
+    if coordinator_running(addr).await {
+        eyre::bail!(
+            "The coordinator seems to be running, as {addr} accepts connections. Stop it before restoring."
+        );
+    }
+
+    let counts = restore_database(input, &db_path)
+        .await
+        .wrap_err(format!("Failed to restore database {}", db_path.display()))?;
+    println!(
+        "Restored database {} from {}",
+        db_path.display(),
+        input.display()
+    );
+    print_row_counts(&counts);
+    Ok(())
+}
+
+/// Loads the config at `config` and returns it with the resolved path of its database.
+async fn load_db_path(config: &str) -> eyre::Result<(ControllerConfig, PathBuf)> {
+    let config_path = Path::new(config);
+    let parsed = load(config_path)
+        .await
+        .wrap_err(format!("Failed to load config {config}"))?;
+    let db_path = match parsed.db {
+        Some(DbConfig {
+            enable: true,
+            ref path,
+            ..
+        }) => resolve_config_relative_paths(config_path, path),
+        _ => eyre::bail!("{config} has no enabled [db] section"),
+    };
+    Ok((parsed, db_path))
+}
+
+/// The address a coordinator started with `config` would be reachable at locally.
+fn local_addr(config: &ControllerConfig) -> eyre::Result<SocketAddr> {
+    let bind: IpAddr = config
+        .server
+        .bind
+        .parse()
+        .wrap_err(format!("Invalid bind address {}", config.server.bind))?;
+    let ip = match bind {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip @ (IpAddr::V4(_) | IpAddr::V6(_)) => ip,
+    };
+    Ok(SocketAddr::new(ip, config.server.port))
+}
+
+async fn coordinator_running(addr: SocketAddr) -> bool {
+    matches!(
+        timeout(RUNNING_CHECK_TIMEOUT, TcpStream::connect(addr)).await,
+        Ok(Ok(_))
+    )
+}
+
+async fn backup_database(db_path: &Path, output: &Path) -> eyre::Result<Vec<(String, i64)>> {
+    let options = SqliteConnectOptions::new()
+        .filename(db_path)
+        .busy_timeout(BUSY_TIMEOUT);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+    sqlx::query("VACUUM INTO ?")
+        .bind(output.to_string_lossy())
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+    restrict_permissions(output).await?;
+
+    row_counts(output).await
+}
+
+async fn restore_database(input: &Path, db_path: &Path) -> eyre::Result<Vec<(String, i64)>> {
+    // Also proves the backup is a readable database before anything is replaced.
+    let counts = row_counts(input)
+        .await
+        .wrap_err(format!("{} is not a usable database", input.display()))?;
+
+    // A leftover write-ahead log of the old database would be applied on top of the backup.
+    for suffix in ["-wal", "-shm"] {
+        let path = sidecar_path(db_path, suffix);
+        if fs::try_exists(&path).await? {
+            fs::remove_file(&path)
+                .await
+                .wrap_err(format!("Failed to remove {}", path.display()))?;
+        }
+    }
+    fs::copy(input, db_path).await?;
+    restrict_permissions(db_path).await?;
+
+    Ok(counts)
+}
+
+/// Path of a file `SQLite` keeps next to the database, named by appending `suffix` to the full
+/// file name, e.g. `shuthost.sqlite-wal`.
+fn sidecar_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Returns the number of rows of each table in the database at `path`, sorted by table name.
+async fn row_counts(path: &Path) -> eyre::Result<Vec<(String, i64)>> {
+    let options = SqliteConnectOptions::new().filename(path);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )
+    .fetch_all(&mut conn)
+    .await?;
+
+    let mut counts = Vec::with_capacity(tables.len());
+    for table in tables {
+        // Table names come from the schema itself and are quoted as identifiers.
+        let count: i64 = sqlx::query_scalar(AssertSqlSafe(format!(
+            r#"SELECT COUNT(*) FROM "{}""#,
+            table.replace('"', "\"\"")
+        )))
+        .fetch_one(&mut conn)
+        .await?;
+        counts.push((table, count));
+    }
+    conn.close().await?;
+    Ok(counts)
+}
+
+fn print_row_counts(counts: &[(String, i64)]) {
+    for &(ref table, count) in counts {
+        println!("  {table}: {count} row(s)");
+    }
+}
+
+/// Keeps the copy as private as the database the coordinator creates.
+async fn restrict_permissions(path: &Path) -> eyre::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::{fs::Permissions, os::unix::fs::PermissionsExt as _};
+
+        fs::set_permissions(path, Permissions::from_mode(0o600))
+            .await
+            .wrap_err(format!(
+                "Failed to restrict permissions of {}",
+                path.display()
+            ))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::app::db;
+
+    #[tokio::test]
+    async fn backup_and_restore_round_trip() {
+        let dir = env::temp_dir().join(format!("shuthost_db_backup_{}", process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let db_path = dir.join("shuthost.db");
+        let backup_path = dir.join("backup.db");
+
+        let pool = db::init(&db_path, 1).await.unwrap();
+        db::store_kv(&pool, "kept", "1").await.unwrap();
+        let counts = backup_database(&db_path, &backup_path).await.unwrap();
+        assert!(counts.contains(&("kv_store".to_string(), 1)), "{counts:?}");
+
+        db::store_kv(&pool, "dropped", "2").await.unwrap();
+        pool.close().await;
+        restore_database(&backup_path, &db_path).await.unwrap();
+
+        let pool = db::init(&db_path, 1).await.unwrap();
+        assert_eq!(
+            db::get_kv(&pool, "kept").await.unwrap().as_deref(),
+            Some("1")
+        );
+        assert_eq!(db::get_kv(&pool, "dropped").await.unwrap(), None);
+        pool.close().await;
+        drop(fs::remove_dir_all(&dir).await);
+    }
+
+    #[test]
+    fn sidecar_path_appends_to_the_file_name() {
+        assert_eq!(
+            sidecar_path(Path::new("/var/lib/shuthost.db"), "-wal"),
+            Path::new("/var/lib/shuthost.db-wal")
+        );
+        assert_eq!(
+            sidecar_path(Path::new("data/shuthost.sqlite"), "-shm"),
+            Path::new("data/shuthost.sqlite-shm")
+        );
+        assert_eq!(
+            sidecar_path(Path::new("shuthost"), "-wal"),
+            Path::new("shuthost-wal")
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_rejects_non_database() {
+        let dir = env::temp_dir().join(format!("shuthost_db_restore_{}", process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let input = dir.join("not_a.db");
+        fs::write(&input, "definitely not sqlite").await.unwrap();
+        let db_path = dir.join("shuthost.db");
+
+        let result = restore_database(&input, &db_path).await;
+        let exists = fs::try_exists(&db_path).await.unwrap();
+        drop(fs::remove_dir_all(&dir).await);
+        let error = result.unwrap_err();
+        assert!(
+            error.to_string().contains("is not a usable database"),
+            "{error:#}"
+        );
+        assert!(!exists);
+    }
+}
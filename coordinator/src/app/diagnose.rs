@@ -0,0 +1,245 @@
+//! Connectivity check of the hosts in a coordinator config, e.g. when setting up shuthost.
+//!
+//! Every host is polled with the signed status request the coordinator uses, but with a more
+//! generous timeout. Nothing is woken or shut down.
+
+use core::{
+    iter,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+use std::{net::UdpSocket, path::Path};
+
+use eyre::WrapErr as _;
+use futures::future::join_all;
+
+use super::{host_control::HostWithName, runtime::poll_host_status, state::HostState};
+use crate::{
+    config::{ControllerConfig, load},
+    wol::wake_destination,
+};
+
+/// Time each host gets to accept the connection and answer the status request.
+const HOST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Outcome of polling a single configured host.
+#[derive(Debug, PartialEq, Eq)]
+struct HostReport {
+    name: String,
+    addr: SocketAddr,
+    reachable: bool,
+    agent_version: Option<String>,
+    /// Destination of magic packets, or `None` if waking via WOL is disabled.
+    wol_target: Option<IpAddr>,
+    /// Why a reachable agent's answer was not accepted.
+    error: Option<String>,
+}
+
+/// Polls every host in the config at `config`, and checks the WOL socket can be bound.
+///
+/// Prints a table with one row per host on stdout.
+///
+/// # Errors
+///
+/// Returns `Err` if the file cannot be read or parsed, or if any host is unreachable.
+pub(crate) async fn run(config: &str) -> eyre::Result<()> {
+    let parsed = load(Path::new(config))
+        .await
+        .wrap_err(format!("Failed to load config {config}"))?;
+
+    let reports = diagnose_hosts(&parsed).await;
+    print!("{}", render(&reports));
+    match check_wol_socket(&parsed.server.wol_bind_addresses()) {
+        Ok(()) => println!("WOL socket: ok"),
+        Err(e) => println!("WOL socket: failed ({e:#})"),
+    }
+
+    let unreachable = reports.iter().filter(|report| !report.reachable).count();
+    if unreachable > 0 {
+        eyre::bail!("{unreachable} of {} host(s) unreachable", reports.len());
+    }
+    Ok(())
+}
+
+async fn diagnose_hosts(config: &ControllerConfig) -> Vec<HostReport> {
+    let mut hosts: Vec<_> = config.hosts.iter().collect();
+    hosts.sort_unstable_by_key(|&(name, _)| name);
+    join_all(hosts.into_iter().map(|(name, host)| async move {
+        let host = HostWithName {
+            name: name.clone(),
+            host: host.clone(),
+        };
+        let (state, install_info, error) = poll_host_status(&host, HOST_TIMEOUT).await;
+        HostReport {
+            addr: SocketAddr::new(host.host.ip, host.host.port),
+            reachable: state == HostState::Online,
+            agent_version: install_info.and_then(|info| info.agent_version),
+            wol_target: (!host.host.wol_disabled())
+                .then(|| wake_destination(host.host.ip, host.host.wol_broadcast)),
+            error,
+            name: host.name,
+        }
+    }))
+    .await
+}
+
+/// Binds a UDP socket like sending magic packets does, on each configured interface.
+fn check_wol_socket(interfaces: &[IpAddr]) -> eyre::Result<()> {
+    if interfaces.is_empty() {
+        shuthost_common::create_broadcast_socket(0).map_err(|e| eyre::eyre!(e))?;
+    }
+    for &interface in interfaces {
+        UdpSocket::bind((interface, 0))
+            .wrap_err(format!("Failed to bind WOL socket to {interface}"))?;
+    }
+    Ok(())
+}
+
+fn render(reports: &[HostReport]) -> String {
+    let header = [
+        "HOST",
+        "ADDRESS",
+        "REACHABLE",
+        "AGENT VERSION",
+        "WOL TARGET",
+    ]
+    .map(String::from);
+    let rows: Vec<[String; 5]> = reports
+        .iter()
+        .map(|report| {
+            let reachable = match (report.reachable, &report.error) {
+                (true, _) => "yes".to_string(),
+                (false, &Some(ref error)) => format!("no ({error})"),
+                (false, &None) => "no".to_string(),
+            };
+            [
+                report.name.clone(),
+                report.addr.to_string(),
+                reachable,
+                report
+                    .agent_version
+                    .clone()
+                    .unwrap_or_else(|| "-".to_string()),
+                report
+                    .wol_target
+                    .map_or_else(|| "disabled".to_string(), |target| target.to_string()),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.each_ref().map(String::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut out = String::new();
+    for row in iter::once(&header).chain(&rows) {
+        let cells: Vec<_> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncWriteExt as _, net::TcpListener};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_reachable_and_unreachable_hosts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let agent_port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream
+                .write_all(b"OK: status;agent_version=v1.2.3")
+                .await
+                .unwrap();
+        });
+        let closed_port = {
+            let probe = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            probe.local_addr().unwrap().port()
+        };
+        let config: ControllerConfig = toml::from_str(&format!(
+            r#"
+            [server]
+            port = 8080
+            bind = "127.0.0.1"
+
+            [hosts.up]
+            ip = "127.0.0.1"
+            mac = "aa:bb:cc:dd:ee:ff"
+            wol_broadcast = "192.168.1.255"
+            port = {agent_port}
+            shared_secret = "s"
+
+            [hosts.down]
+            ip = "127.0.0.1"
+            mac = "disableWOL"
+            port = {closed_port}
+            shared_secret = "s"
+
+            [clients]
+        "#
+        ))
+        .unwrap();
+
+        let reports = diagnose_hosts(&config).await;
+        assert_eq!(
+            reports,
+            [
+                HostReport {
+                    name: "down".to_string(),
+                    addr: SocketAddr::from(([127, 0, 0, 1], closed_port)),
+                    reachable: false,
+                    agent_version: None,
+                    wol_target: None,
+                    error: None,
+                },
+                HostReport {
+                    name: "up".to_string(),
+                    addr: SocketAddr::from(([127, 0, 0, 1], agent_port)),
+                    reachable: true,
+                    agent_version: Some("v1.2.3".to_string()),
+                    wol_target: Some(IpAddr::from([192, 168, 1, 255])),
+                    error: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_aligned_table() {
+        let reports = [
+            HostReport {
+                name: "nas".to_string(),
+                addr: SocketAddr::from(([192, 168, 1, 10], 5757)),
+                reachable: true,
+                agent_version: Some("v1.2.3".to_string()),
+                wol_target: Some(IpAddr::from([255, 255, 255, 255])),
+                error: None,
+            },
+            HostReport {
+                name: "backup-server".to_string(),
+                addr: SocketAddr::from(([192, 168, 1, 11], 5757)),
+                reachable: false,
+                agent_version: None,
+                wol_target: None,
+                error: Some("Agent rejected status request: ERROR: invalid signature".to_string()),
+            },
+        ];
+        assert_eq!(
+            render(&reports),
+            "HOST           ADDRESS            REACHABLE                                                     AGENT VERSION  WOL TARGET\n\
+             nas            192.168.1.10:5757  yes                                                           v1.2.3         255.255.255.255\n\
+             backup-server  192.168.1.11:5757  no (Agent rejected status request: ERROR: invalid signature)  -              disabled\n"
+        );
+    }
+}
@@ -14,6 +14,7 @@
 use alloc::sync::Arc;
 use std::collections::{HashMap, HashSet};
 
+use chrono::{DateTime, Utc};
 use tokio::sync::{broadcast, mpsc, oneshot, watch};
 use tracing::{debug, warn};
 
@@ -24,8 +25,8 @@ use crate::app::{
 
 /// The full online/offline + transition state map for all known hosts.
 pub type HostStatus = HashMap<String, HostState>;
-/// Watch receiver for [`HostStatus`] snapshots published by the [`HostActorHandle`].
-pub(crate) type HostStatusRx = watch::Receiver<Arc<HostStatus>>;
+/// Wall-clock time each host most recently went online, as recorded by the [`HostActor`].
+pub(crate) type WentOnline = HashMap<String, DateTime<Utc>>;
 
 // ---------------------------------------------------------------------------
 // Public types
@@ -134,6 +135,8 @@ struct HostActor {
     control_active: HashSet<String>,
     /// Watch channel – published to on every state change.
     status_tx: Arc<watch::Sender<Arc<HostStatus>>>,
+    /// Watch channel – published to on every transition to `Online`.
+    went_online_tx: Arc<watch::Sender<Arc<WentOnline>>>,
     /// Broadcast channel – events emitted on state & lease changes.
     event_tx: Arc<broadcast::Sender<FullHostEvent>>,
 }
@@ -157,6 +160,13 @@ impl HostActor {
         }
         self.states.insert(host.to_string(), new_state);
 
+        // Record the time before publishing, so subscribers reacting to the change already see it.
+        if new_state == HostState::Online {
+            self.went_online_tx.send_modify(|went_online| {
+                Arc::make_mut(went_online).insert(host.to_string(), Utc::now());
+            });
+        }
+
         // Publish full snapshot to the watch channel.
         // Use send_replace so the stored value is always updated, even in
         // tests where the initial receiver has been dropped.
@@ -288,6 +298,7 @@ pub(crate) struct HostActorHandle {
     tx: mpsc::Sender<HostCmd>,
     /// Held so callers can call `.subscribe()` / `.borrow()` / `.send_if_modified()`.
     pub(crate) status_tx: Arc<watch::Sender<Arc<HostStatus>>>,
+    went_online_tx: Arc<watch::Sender<Arc<WentOnline>>>,
     /// Held so callers can call `.subscribe()` to receive events.
     event_tx: Arc<broadcast::Sender<FullHostEvent>>,
 }
@@ -297,6 +308,7 @@ impl HostActorHandle {
     pub(crate) fn spawn(initial: HostStatus) -> Self {
         let (status_tx, _) = watch::channel(Arc::new(initial.clone()));
         let status_tx = Arc::new(status_tx);
+        let went_online_tx = Arc::new(watch::Sender::new(Arc::default()));
         let (event_tx, _) = broadcast::channel(256);
         let event_tx = Arc::new(event_tx);
         let (cmd_tx, cmd_rx) = mpsc::channel(256);
@@ -305,6 +317,7 @@ impl HostActorHandle {
             states: initial,
             control_active: HashSet::new(),
             status_tx: Arc::clone(&status_tx),
+            went_online_tx: Arc::clone(&went_online_tx),
             event_tx: Arc::clone(&event_tx),
         };
         tokio::spawn(actor.run(cmd_rx));
@@ -312,6 +325,7 @@ impl HostActorHandle {
         Self {
             tx: cmd_tx,
             status_tx,
+            went_online_tx,
             event_tx,
         }
     }
@@ -430,6 +444,11 @@ impl HostActorHandle {
         self.status_tx.borrow()
     }
 
+    /// Return a snapshot of the time each host most recently went online.
+    pub(crate) fn went_online(&self) -> Arc<WentOnline> {
+        self.went_online_tx.borrow().clone()
+    }
+
     /// Subscribe to future host-status snapshots.
     pub(crate) fn subscribe_status(&self) -> watch::Receiver<Arc<HostStatus>> {
         self.status_tx.subscribe()
@@ -459,6 +478,7 @@ mod tests {
             states: HashMap::new(),
             control_active: HashSet::new(),
             status_tx: Arc::new(status_tx),
+            went_online_tx: Arc::new(watch::Sender::new(Arc::default())),
             event_tx: Arc::new(event_tx),
         }
     }
@@ -668,6 +688,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn going_online_records_time() {
+        let mut actor = make_actor();
+        let poll = |actor: &mut HostActor, state| {
+            let (reply_tx, _reply_rx) = oneshot::channel();
+            actor.handle_cmd(HostCmd::PollResults {
+                results: vec![("srv".to_string(), state)],
+                reply: reply_tx,
+            });
+        };
+
+        poll(&mut actor, HostState::Offline);
+        assert!(actor.went_online_tx.borrow().is_empty());
+
+        poll(&mut actor, HostState::Online);
+        let went_online = actor.went_online_tx.borrow().get("srv").copied();
+        assert!(went_online.is_some());
+
+        // Staying online keeps the time of the transition, going offline keeps the last one.
+        poll(&mut actor, HostState::Online);
+        poll(&mut actor, HostState::Offline);
+        assert_eq!(
+            actor.went_online_tx.borrow().get("srv").copied(),
+            went_online
+        );
+    }
+
     #[test]
     fn lease_changed_emits_event() {
         let mut actor = make_actor();
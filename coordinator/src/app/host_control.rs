@@ -2,20 +2,27 @@
 //! operations for waking/shutting hosts and polling their state.
 
 use alloc::sync::Arc;
-use core::{ops, time::Duration};
-use std::collections::{HashMap, HashSet};
+#[cfg(any(not(coverage), test))]
+use core::iter;
+use core::{
+    net::{IpAddr, SocketAddr},
+    ops,
+    time::Duration,
+};
+use std::collections::HashMap;
 
 use eyre::{Context as _, Report};
+use secrecy::SecretString;
 use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 #[cfg(not(any(coverage, test)))]
-use tokio::time::{MissedTickBehavior, interval};
+use tokio::time::sleep;
 use tokio::{
     io::{AsyncReadExt as _, AsyncWriteExt as _},
     net::TcpStream,
     time::{Instant, timeout_at},
 };
-use tracing::{Instrument as _, debug, info};
+use tracing::{Instrument as _, debug, error, info, warn};
 
 use crate::app::{
     AppState, OperationFailure, OperationKind, hooks,
@@ -24,11 +31,16 @@ use crate::app::{
     runtime::{PollError, poll_until_host_state},
     shared_watch_store::{SharedWatchRx, SharedWatchStore},
     state::HostState,
+    state::OperationFailureStore,
 };
 
-use crate::config::{Host, RuntimeConfig};
 #[cfg(not(any(coverage, test)))]
 use crate::wol;
+use crate::{
+    audit_log::{AuditEventType, AuditOutcome},
+    config::{Host, RuntimeConfig},
+    websocket::{ErrorKind, WsMessage},
+};
 
 /// Combines a host name with its `Host` configuration.
 #[derive(Debug, Clone)]
@@ -54,8 +66,86 @@ impl ops::Deref for ResolvedHost {
     }
 }
 
-/// The set of lease sources for a single host
-pub(crate) type LeaseSources = HashSet<LeaseSource>;
+/// The set of lease sources for a single host.
+///
+/// Each lease optionally expires at an [`Instant`]; leases without an expiry are held until released.
+/// Serializes as a plain list of sources, expiries are not exposed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeaseSources(HashMap<LeaseSource, Option<Instant>>);
+
+impl LeaseSources {
+    /// Adds a lease, or replaces the expiry of an existing one.
+    /// Returns `true` if `source` did not hold a lease before.
+    pub(crate) fn insert_with_expiry(
+        &mut self,
+        source: LeaseSource,
+        expires_at: Option<Instant>,
+    ) -> bool {
+        self.0.insert(source, expires_at).is_none()
+    }
+
+    /// Removes the lease of `source`. Returns `true` if it held one.
+    pub(crate) fn remove(&mut self, source: &LeaseSource) -> bool {
+        self.0.remove(source).is_some()
+    }
+
+    pub(crate) fn contains(&self, source: &LeaseSource) -> bool {
+        self.0.contains_key(source)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &LeaseSource> {
+        self.0.keys()
+    }
+
+    /// Whether any lease expired at or before `now`.
+    pub(crate) fn has_expired(&self, now: Instant) -> bool {
+        self.0
+            .values()
+            .any(|expires_at| expires_at.is_some_and(|at| at <= now))
+    }
+
+    /// Removes all leases that expired at or before `now` and returns their sources.
+    pub(crate) fn remove_expired(&mut self, now: Instant) -> Vec<LeaseSource> {
+        let expired: Vec<LeaseSource> = self
+            .0
+            .iter()
+            .filter(|&(_, expires_at)| expires_at.is_some_and(|at| at <= now))
+            .map(|(source, _)| source.clone())
+            .collect();
+        for source in &expired {
+            self.0.remove(source);
+        }
+        expired
+    }
+}
+
+impl FromIterator<LeaseSource> for LeaseSources {
+    fn from_iter<I: IntoIterator<Item = LeaseSource>>(iter: I) -> Self {
+        Self(iter.into_iter().map(|source| (source, None)).collect())
+    }
+}
+
+impl Serialize for LeaseSources {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for LeaseSources {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Vec::<LeaseSource>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
 
 /// `host_name` => set of lease sources holding lease
 pub(crate) type LeaseMap = HashMap<String, LeaseSources>;
@@ -99,6 +189,44 @@ pub(crate) fn lookup_host(state: &AppState, host: &str) -> Option<Host> {
     cfg_snapshot.hosts.get(host).cloned()
 }
 
+/// Replaces the shared secret of `host` in the in-memory config.
+///
+/// The new secret is used for all subsequent status polls and shutdown commands. The config file
+/// is not written, so the operator has to update it as well for the change to survive a restart.
+/// Returns `false` if `host` is not configured.
+pub(crate) async fn rotate_host_secret(
+    state: &AppState,
+    host: &str,
+    new_secret: SecretString,
+) -> bool {
+    let new_secret = Arc::new(new_secret);
+    let rotated = state.config_tx.send_if_modified(|config| {
+        // Check first, so the config is only cloned if it is actually modified.
+        if !config.hosts.contains_key(host) {
+            return false;
+        }
+        if let Some(host_cfg) = Arc::make_mut(config).hosts.get_mut(host) {
+            host_cfg.shared_secret = new_secret;
+        }
+        true
+    });
+
+    if rotated {
+        warn!(%host, "Shared secret rotated at runtime, update the config file to persist it");
+    }
+    if let Some(ref audit_log) = state.audit_log {
+        audit_log
+            .record(
+                AuditEventType::SecretRotation,
+                host,
+                None,
+                AuditOutcome::from_success(rotated),
+            )
+            .await;
+    }
+    rotated
+}
+
 /// Lookup a host's config from the runtime config and apply any runtime IP/port
 /// overrides stored in `AppState`. Returns `None` if the host is not present
 /// in the configuration.
@@ -110,7 +238,7 @@ pub(crate) async fn lookup_host_with_overrides(
 
     let overrides = state.host_overrides.read().await;
     if let Some(o) = overrides.get(host) {
-        host_cfg.ip = o.ip.clone();
+        host_cfg.ip = o.ip;
         host_cfg.port = o.port;
     }
 
@@ -128,11 +256,23 @@ pub enum LeaseSource {
     WebInterface,
     /// Lease held by a specific client
     Client(String),
+    /// Lease held by a schedule from `[server.schedules]` while its last trigger was a take
+    Schedule { name: String },
 }
 
-/// Interval between `WoL` re-sends during a wake transition.
-#[cfg(not(any(coverage, test)))]
-const WOL_RESEND_INTERVAL: Duration = Duration::from_millis(500);
+/// Delays before each `WoL` re-send of a wake transition.
+///
+/// Starts at `wol_retry_interval_secs` and doubles with every re-send, for at most
+/// `wol_max_retries` re-sends.
+#[cfg(any(not(coverage), test))]
+fn wol_retry_delays(runtime: &RuntimeConfig) -> Vec<Duration> {
+    iter::successors(
+        Some(Duration::from_secs(runtime.wol_retry_interval_secs)),
+        |&delay| Some(delay.saturating_mul(2)),
+    )
+    .take(usize::try_from(runtime.wol_max_retries).unwrap_or(usize::MAX))
+    .collect()
+}
 
 /// Errors returned by high-level host control operations.
 #[derive(Debug, ThisError)]
@@ -159,7 +299,7 @@ enum OperationOrNoop {
 /// having been atomically set before this function is invoked. Because
 /// `begin_transition` already serialises concurrent calls, there is no
 /// need to re-check the current status; we just act on the lease set.
-#[tracing::instrument(skip(state), err(Debug))]
+#[tracing::instrument(skip_all, fields(host = %host), err(Debug))]
 async fn handle_host_state(
     host: &str,
     state: &AppState,
@@ -167,10 +307,7 @@ async fn handle_host_state(
 ) -> Result<OperationOrNoop, HostControlError> {
     let should_be_running = !lease_set.is_empty();
 
-    debug!(
-        "Handling host '{}': should_be_running={}, active_leases={:?}",
-        host, should_be_running, lease_set
-    );
+    debug!(should_be_running, active_leases = ?lease_set, "Handling host");
 
     // Lookup host config and runtime overrides using shared helper.
     let Some(host_with_name) = lookup_host_with_overrides(state, host).await else {
@@ -181,8 +318,11 @@ async fn handle_host_state(
     // ensures at most one control task runs at a time, so we unconditionally
     // perform the requested action.
     if should_be_running {
-        wake_host_and_wait(&host_with_name, &state.runtime).await
+        state.metrics.record_wake(host);
+        let wol_interfaces = state.config_rx.borrow().server.wol_bind_addresses();
+        wake_host_and_wait(&host_with_name, &state.runtime, &wol_interfaces).await
     } else {
+        state.metrics.record_shutdown(host);
         shutdown_host_and_wait(&host_with_name, &state.runtime).await
     }
 }
@@ -252,47 +392,20 @@ pub(crate) fn spawn_handle_host_state(host: &str, state: &AppState) {
                 .transition_complete(&host, transition_result)
                 .await;
 
+            audit_transition(&state, &host, operation_kind, transition_result).await;
+
             // Update the per-host operation failure record.
             match result {
                 Ok(_) => {
                     state.operation_failures.clear(&host).await;
                 }
-                Err(HostControlError::Timeout(_) | HostControlError::OperationFailed { .. }) => {
-                    let is_new_failure = state
-                        .operation_failures
-                        .set(
-                            &host,
-                            OperationFailure {
-                                operation: operation_kind,
-                            },
-                        )
-                        .await;
-
-                    // Webhooks fire on every failure; PWA push is suppressed for repeats
-                    // (is_repeat = !is_new_failure) to avoid spamming the user on retries.
-                    let host_clone = host.clone();
-                    let webhooks = state.config_rx.borrow().notifications.webhooks.clone();
-                    let db_pool = state.db_pool.clone();
-                    let vapid_key = state.vapid_key.clone();
-                    tokio::spawn(async move {
-                        notifications::dispatch(
-                            notifications::NotificationEvent {
-                                host: host_clone,
-                                kind: notifications::EventKind::OperationFailed {
-                                    kind: operation_kind,
-                                    is_repeat: !is_new_failure,
-                                },
-                            },
-                            &webhooks,
-                            db_pool.as_ref(),
-                            vapid_key.as_ref(),
-                        )
-                        .await;
-                    });
-                }
                 Err(HostControlError::NotFound(_)) => {
                     // Config issue, not a runtime failure — leave existing failure state unchanged.
                 }
+                Err(ref e) => {
+                    broadcast_error(&state, &host, operation_kind, e);
+                    record_operation_failure(&state, &host, operation_kind).await;
+                }
             }
 
             if let Err(ref e) = result {
@@ -324,12 +437,99 @@ pub(crate) fn spawn_handle_host_state(host: &str, state: &AppState) {
     );
 }
 
+/// Records the outcome of a completed host control operation in the audit log, if enabled.
+async fn audit_transition(
+    state: &AppState,
+    host: &str,
+    operation_kind: OperationKind,
+    transition_result: TransitionResult,
+) {
+    let Some(ref audit_log) = state.audit_log else {
+        return;
+    };
+    let event_type = match operation_kind {
+        OperationKind::Startup => AuditEventType::HostWake,
+        OperationKind::Shutdown => AuditEventType::HostShutdown,
+    };
+    let succeeded = matches!(
+        transition_result,
+        TransitionResult::WakeOk | TransitionResult::ShutdownOk
+    );
+    audit_log
+        .record(
+            event_type,
+            host,
+            None,
+            AuditOutcome::from_success(succeeded),
+        )
+        .await;
+}
+
+/// Records a failed control operation for `host` and dispatches the failure notifications.
+async fn record_operation_failure(state: &AppState, host: &str, operation_kind: OperationKind) {
+    let is_new_failure = state
+        .operation_failures
+        .set(
+            host,
+            OperationFailure {
+                operation: operation_kind,
+            },
+        )
+        .await;
+
+    // Webhooks fire on every failure; PWA push is suppressed for repeats
+    // (is_repeat = !is_new_failure) to avoid spamming the user on retries.
+    let host = host.to_string();
+    let webhooks = state.config_rx.borrow().notifications.webhooks.clone();
+    let db_pool = state.db_pool.clone();
+    let vapid_key = state.vapid_key.clone();
+    tokio::spawn(async move {
+        notifications::dispatch(
+            notifications::NotificationEvent {
+                host,
+                kind: notifications::EventKind::OperationFailed {
+                    kind: operation_kind,
+                    is_repeat: !is_new_failure,
+                },
+            },
+            &webhooks,
+            db_pool.as_ref(),
+            vapid_key.as_ref(),
+        )
+        .await;
+    });
+}
+
+/// Notifies connected web UIs that a control operation for `host` failed.
+fn broadcast_error(
+    state: &AppState,
+    host: &str,
+    operation_kind: OperationKind,
+    error: &HostControlError,
+) {
+    let kind = match operation_kind {
+        OperationKind::Startup => ErrorKind::WakeFailure,
+        OperationKind::Shutdown => ErrorKind::ShutdownFailure,
+    };
+    let message = match *error {
+        HostControlError::Timeout(ref report)
+        | HostControlError::OperationFailed { ref report, .. } => format!("{report:#}"),
+        HostControlError::NotFound(_) => error.to_string(),
+    };
+    let msg = WsMessage::Error {
+        host: host.to_string(),
+        kind,
+        message,
+    };
+    if state.ws_tx.send(msg).is_err() {
+        debug!("No Websocket Subscribers");
+    }
+}
+
 /// Send a shutdown message to the host described by `host_with_name` and return the textual response.
 async fn send_shutdown_to_address(host_with_name: &ResolvedHost) -> Result<String, Report> {
-    let ip = &host_with_name.host.ip;
-    let port = host_with_name.host.port;
+    let addr = SocketAddr::new(host_with_name.host.ip, host_with_name.host.port);
     let secret = host_with_name.host.shared_secret.as_ref();
-    let addr = format!("{ip}:{port}");
     debug!(%addr, "Connecting to host for shutdown");
 
     let deadline = Instant::now() + Duration::from_secs(6);
@@ -370,21 +570,27 @@ async fn send_shutdown_to_address(host_with_name: &ResolvedHost) -> Result<Strin
 }
 
 /// Send `WoL` packets and poll until the host comes online, re-sending the `WoL`
-/// magic packet every [`WOL_RESEND_INTERVAL`] until the deadline to account for
-/// UDP packet loss during boot. The re-send task is aborted as soon as the host
-/// is confirmed online or the deadline is reached.
+/// magic packet with backoff (see [`wol_retry_delays`]) to account for UDP packet
+/// loss during boot. The re-send task is aborted as soon as the host is confirmed
+/// online or the deadline is reached.
 ///
 /// State writes must be handled by the caller via [`HostActorHandle::transition_complete`].
+#[cfg_attr(
+    any(coverage, test),
+    expect(unused_variables, reason = "WoL packets are not sent in tests")
+)]
+#[tracing::instrument(skip_all, fields(host = %host_with_name.name))]
 async fn wake_host_and_wait(
     host_with_name: &ResolvedHost,
     runtime: &RuntimeConfig,
+    wol_interfaces: &[IpAddr],
 ) -> Result<OperationOrNoop, HostControlError> {
     if let Some(ref hook) = host_with_name.host.pre_startup {
         hooks::run_hook(&host_with_name.name, "pre_startup", hook).await;
     }
 
-    if host_with_name.host.mac.eq_ignore_ascii_case("disablewol") {
-        info!(host = %host_with_name.name, "WOL disabled for host");
+    if host_with_name.host.wol_disabled() {
+        info!("WOL disabled for host");
         return Ok(OperationOrNoop::Noop);
     }
 
@@ -394,39 +600,52 @@ async fn wake_host_and_wait(
         .unwrap_or(runtime.default_wake_timeout_secs);
     let deadline = Instant::now() + Duration::from_secs(wake_secs);
 
-    info!(host = %host_with_name.name, mac = %host_with_name.host.mac, "Sending WoL packet");
+    info!(mac = ?host_with_name.host.mac, "Sending WoL packet");
 
     #[cfg(not(any(coverage, test)))]
-    if let Err(e) = wol::send_magic_packet(&host_with_name.host.mac, "255.255.255.255").await {
+    let wol_destination =
+        wol::wake_destination(host_with_name.host.ip, host_with_name.host.wol_broadcast);
+    #[cfg(not(any(coverage, test)))]
+    if let Err(e) =
+        wol::send_magic_packets(&host_with_name.host.mac, wol_destination, wol_interfaces).await
+    {
         return Err(HostControlError::OperationFailed {
             target: HostState::Online,
             report: e.wrap_err("Failed to send WoL packet"),
         });
     }
 
-    // Re-send WoL every WOL_RESEND_INTERVAL in a background task until we know the host
-    // is online. Aborted when the poll future returns (success or timeout).
+    // Re-send WoL in a background task until we know the host is online.
+    // Aborted when the poll future returns (success or timeout).
     #[cfg(not(any(coverage, test)))]
     let wol_resend_handle = {
-        let mac = host_with_name.host.mac.clone();
-        tokio::spawn(async move {
-            let mut ticker = interval(WOL_RESEND_INTERVAL);
-            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
-            ticker.tick().await; // skip the immediate tick; first re-send is after one interval
-            loop {
-                ticker.tick().await;
-                if let Err(e) = wol::send_magic_packet(&mac, "255.255.255.255").await {
-                    debug!("WoL re-send failed: {e}");
+        let macs = host_with_name.host.mac.clone();
+        let wol_interfaces = wol_interfaces.to_vec();
+        let delays = wol_retry_delays(runtime);
+        tokio::spawn(
+            async move {
+                for (attempt, delay) in (1..).zip(delays) {
+                    sleep(delay).await;
+                    info!(attempt, "Re-sending WoL packet");
+                    if let Err(e) =
+                        wol::send_magic_packets(&macs, wol_destination, &wol_interfaces).await
+                    {
+                        debug!("WoL re-send failed: {e}");
+                    }
                 }
             }
-        })
+            .in_current_span(),
+        )
     };
 
     let poll_result = poll_until_host_state(
         host_with_name,
         HostState::Online,
         deadline,
-        runtime.transition_poll_interval_ms,
+        host_with_name
+            .host
+            .transition_poll_interval_ms
+            .unwrap_or(runtime.transition_poll_interval_ms),
     )
     .await;
 
@@ -441,9 +660,124 @@ async fn wake_host_and_wait(
     }
 }
 
+/// Sends `WoL` packets to `host_name` right away, regardless of its leases.
+///
+/// Meant for manual maintenance: neither the lease map nor the host actor is touched, and the
+/// function returns without waiting for the host to come online. Hosts with `enforce_state`
+/// may be shut down again by the reconciler if they remain unleased.
+#[tracing::instrument(skip(state), fields(host = %host_name))]
+pub(crate) async fn force_wake(host_name: &str, state: &AppState) -> Result<(), HostControlError> {
+    let Some(host_with_name) = lookup_host_with_overrides(state, host_name).await else {
+        return Err(HostControlError::NotFound(host_name.to_string()));
+    };
+    warn!("Manual wake requested, bypassing leases");
+
+    let result = if host_with_name.host.wol_disabled() {
+        Err(HostControlError::OperationFailed {
+            target: HostState::Online,
+            report: eyre::eyre!("WoL is disabled for host '{host_name}'"),
+        })
+    } else {
+        state.metrics.record_wake(host_name);
+        send_wol_packets(&host_with_name, state).await
+    };
+
+    audit_manual_operation(state, host_name, AuditEventType::HostWake, result.is_ok()).await;
+    result
+}
+
+/// Sends a single round of `WoL` packets to `host_with_name`, without waiting for it to come online.
+#[cfg_attr(
+    any(coverage, test),
+    expect(
+        unused_variables,
+        clippy::unused_async,
+        reason = "WoL packets are not sent in tests"
+    )
+)]
+async fn send_wol_packets(
+    host_with_name: &ResolvedHost,
+    state: &AppState,
+) -> Result<(), HostControlError> {
+    info!(mac = ?host_with_name.host.mac, "Sending WoL packet");
+    #[cfg(not(any(coverage, test)))]
+    {
+        let wol_interfaces = state.config_rx.borrow().server.wol_bind_addresses();
+        let wol_destination =
+            wol::wake_destination(host_with_name.host.ip, host_with_name.host.wol_broadcast);
+        wol::send_magic_packets(&host_with_name.host.mac, wol_destination, &wol_interfaces)
+            .await
+            .map_err(|e| HostControlError::OperationFailed {
+                target: HostState::Online,
+                report: e.wrap_err("Failed to send WoL packet"),
+            })?;
+    }
+    Ok(())
+}
+
+/// Sends the shutdown command to `host_name` right away, regardless of its leases.
+///
+/// Like [`force_wake`] this leaves leases and the host actor alone and doesn't wait for the host
+/// to go offline, so a host that is still leased may be woken again by the reconciler.
+#[tracing::instrument(skip(state), fields(host = %host_name))]
+pub(crate) async fn force_shutdown(
+    host_name: &str,
+    state: &AppState,
+) -> Result<(), HostControlError> {
+    let Some(host_with_name) = lookup_host_with_overrides(state, host_name).await else {
+        return Err(HostControlError::NotFound(host_name.to_string()));
+    };
+    warn!("Manual shutdown requested, bypassing leases");
+    state.metrics.record_shutdown(host_name);
+
+    let result = match send_shutdown_to_address(&host_with_name).await {
+        Ok(resp) if resp.contains("ERROR") => {
+            error!(response = %resp, "Agent reported a failed shutdown");
+            Err(HostControlError::OperationFailed {
+                target: HostState::Offline,
+                report: eyre::eyre!("Agent rejected shutdown command: {resp}"),
+            })
+        }
+        Ok(_) => Ok(()),
+        Err(report) => Err(HostControlError::OperationFailed {
+            target: HostState::Offline,
+            report,
+        }),
+    };
+
+    audit_manual_operation(
+        state,
+        host_name,
+        AuditEventType::HostShutdown,
+        result.is_ok(),
+    )
+    .await;
+    result
+}
+
+/// Records a manual host control operation in the audit log, if enabled.
+async fn audit_manual_operation(
+    state: &AppState,
+    host: &str,
+    event_type: AuditEventType,
+    succeeded: bool,
+) {
+    if let Some(ref audit_log) = state.audit_log {
+        audit_log
+            .record(
+                event_type,
+                host,
+                None,
+                AuditOutcome::from_success(succeeded),
+            )
+            .await;
+    }
+}
+
 /// Send shutdown command to host and wait until offline.
 ///
 /// State writes must be handled by the caller via [`HostActorHandle::transition_complete`].
+#[tracing::instrument(skip_all, fields(host = %host_with_name.name))]
 async fn shutdown_host_and_wait(
     host_with_name: &ResolvedHost,
     runtime: &RuntimeConfig,
@@ -460,6 +794,7 @@ async fn shutdown_host_and_wait(
     };
 
     if resp.contains("ERROR") {
+        error!(response = %resp, "Agent reported a failed shutdown");
         return Err(HostControlError::OperationFailed {
             target: HostState::Offline,
             report: eyre::eyre!("Agent rejected shutdown command: {resp}"),
@@ -475,7 +810,10 @@ async fn shutdown_host_and_wait(
         host_with_name,
         HostState::Offline,
         deadline,
-        runtime.transition_poll_interval_ms,
+        host_with_name
+            .host
+            .transition_poll_interval_ms
+            .unwrap_or(runtime.transition_poll_interval_ms),
     )
     .await
     {
@@ -496,9 +834,13 @@ async fn shutdown_host_and_wait(
 /// Used by the M2M API sync path. Unlike [`poll_until_host_state`] this does not
 /// do independent TCP polling; it relies on the background poller and control tasks
 /// to update the actor, which then publishes updates on the watch channel.
+///
+/// Returns early with [`HostControlError::OperationFailed`] once the control task records a
+/// failure of the operation leading to `desired_state`, e.g. a failing shutdown command.
 pub(crate) async fn wait_for_transition(
     host: &str,
     host_actor: &HostActorHandle,
+    operation_failures: &OperationFailureStore,
     desired_state: HostState,
     deadline: Instant,
 ) -> Result<(), HostControlError> {
@@ -506,13 +848,38 @@ pub(crate) async fn wait_for_transition(
     if host_actor.get_current_state(host) == desired_state {
         return Ok(());
     }
+    let expected_failure = if desired_state == HostState::Online {
+        OperationKind::Startup
+    } else {
+        OperationKind::Shutdown
+    };
     let mut rx = host_actor.subscribe_status();
+    let mut failures_rx = operation_failures.subscribe();
     loop {
-        match timeout_at(deadline, rx.changed()).await {
+        let changed = timeout_at(deadline, async {
+            tokio::select! {
+                res = rx.changed() => res,
+                res = failures_rx.changed() => res,
+            }
+        })
+        .await;
+        match changed {
             Ok(Ok(())) => {
                 if host_actor.get_current_state(host) == desired_state {
                     return Ok(());
                 }
+                let failed = failures_rx
+                    .borrow_and_update()
+                    .get(host)
+                    .is_some_and(|failure| failure.operation == expected_failure);
+                if failed {
+                    return Err(HostControlError::OperationFailed {
+                        target: desired_state,
+                        report: eyre::eyre!(
+                            "Host '{host}' failed to become {desired_state:?}, see the coordinator logs"
+                        ),
+                    });
+                }
             }
             Ok(Err(_)) => {
                 // Watch channel closed (coordinator shutting down); treat as success to
@@ -527,3 +894,40 @@ pub(crate) async fn wait_for_transition(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wol_retry_delays_double_up_to_the_retry_cap() {
+        let runtime = RuntimeConfig {
+            wol_retry_interval_secs: 15,
+            wol_max_retries: 4,
+            ..RuntimeConfig::default()
+        };
+        assert_eq!(
+            wol_retry_delays(&runtime),
+            [15, 30, 60, 120].map(Duration::from_secs)
+        );
+
+        let no_retries = RuntimeConfig {
+            wol_max_retries: 0,
+            ..runtime.clone()
+        };
+        assert!(wol_retry_delays(&no_retries).is_empty());
+    }
+
+    #[test]
+    fn wol_retry_delays_saturate() {
+        let runtime = RuntimeConfig {
+            wol_retry_interval_secs: u64::MAX,
+            wol_max_retries: 2,
+            ..RuntimeConfig::default()
+        };
+        assert_eq!(
+            wol_retry_delays(&runtime),
+            [Duration::from_secs(u64::MAX), Duration::MAX]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Discovery of agent ports via mDNS, for hosts configured with `port = 0`.
+//!
+//! Agents started with `--mdns-announce` register under [`MDNS_SERVICE_TYPE`] with their hostname
+//! as the instance name. The discovered port is stored as an override, like the address reported
+//! in a startup broadcast, while the configured IP is kept.
+
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use tracing::{debug, info, warn};
+
+use shuthost_common::MDNS_SERVICE_TYPE;
+
+use super::{AppState, runtime::store_host_override};
+
+/// Browses for announced agents until the daemon stops, storing the ports of matching hosts.
+pub(super) async fn discover_agent_ports(state: AppState) {
+    let daemon = match ServiceDaemon::new() {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            warn!("Failed to start mDNS daemon, agent ports won't be discovered: {e}");
+            return;
+        }
+    };
+    let events = match daemon.browse(MDNS_SERVICE_TYPE) {
+        Ok(events) => events,
+        Err(e) => {
+            warn!("Failed to browse for agents via mDNS: {e}");
+            return;
+        }
+    };
+    info!("Discovering agent ports via mDNS");
+
+    while let Ok(event) = events.recv_async().await {
+        let ServiceEvent::ServiceResolved(service) = event else {
+            continue;
+        };
+        let Some(hostname) = instance_name(&service.fullname, &service.ty_domain) else {
+            debug!(fullname = %service.fullname, "Ignoring malformed mDNS service name");
+            continue;
+        };
+        handle_discovered(&state, &hostname, service.port).await;
+    }
+}
+
+async fn handle_discovered(state: &AppState, hostname: &str, port: u16) {
+    let configured_ip = match state.config_rx.borrow().hosts.get(hostname) {
+        Some(host) if host.port == 0 => host.ip,
+        Some(_) | None => return,
+    };
+    let ip = {
+        let overrides = state.host_overrides.read().await;
+        match overrides.get(hostname) {
+            Some(existing) if existing.port == port => return,
+            Some(existing) => existing.ip,
+            None => configured_ip,
+        }
+    };
+    info!(host = %hostname, port, "Discovered agent port via mDNS");
+    store_host_override(state, hostname, ip, port).await;
+}
+
+/// Extracts the unescaped instance name from the full name of a resolved service.
+fn instance_name(fullname: &str, ty_domain: &str) -> Option<String> {
+    let escaped = fullname.strip_suffix(ty_domain)?.strip_suffix('.')?;
+    let mut name = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(ch) = chars.next() {
+        name.push(if ch == '\\' { chars.next()? } else { ch });
+    }
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_escaped_instance_names() {
+        assert_eq!(
+            instance_name("nas._shuthost._tcp.local.", MDNS_SERVICE_TYPE).as_deref(),
+            Some("nas")
+        );
+        assert_eq!(
+            instance_name(r"my\.host\\1._shuthost._tcp.local.", MDNS_SERVICE_TYPE).as_deref(),
+            Some(r"my.host\1")
+        );
+        assert_eq!(
+            instance_name("nas._http._tcp.local.", MDNS_SERVICE_TYPE),
+            None
+        );
+        assert_eq!(
+            instance_name("._shuthost._tcp.local.", MDNS_SERVICE_TYPE),
+            None
+        );
+    }
+}
@@ -1,10 +1,15 @@
 mod config_watcher;
 pub mod db;
+pub(crate) mod db_backup;
+pub(crate) mod diagnose;
 mod hooks;
 pub(crate) mod host_actor;
 mod host_control;
+#[cfg(feature = "mdns")]
+mod mdns;
 pub(crate) mod notifications;
 mod runtime;
+mod schedules;
 mod shared_watch_store;
 mod startup;
 mod state;
@@ -12,11 +17,11 @@ mod update_check;
 
 // Re-export a curated crate-visible surface for consumers of `crate::app`
 pub(crate) use db::DbPool;
+pub(crate) use host_actor::HostActorHandle;
 pub use host_actor::HostStatus;
-pub(crate) use host_actor::{HostActorHandle, HostStatusRx};
 pub(crate) use host_control::{
-    HostControlError, LeaseMap, LeaseRx, LeaseSource, LeaseSources, LeaseStore, lookup_host,
-    lookup_host_with_overrides, wait_for_transition,
+    HostControlError, LeaseMap, LeaseRx, LeaseSource, LeaseSources, LeaseStore, force_shutdown,
+    force_wake, lookup_host, lookup_host_with_overrides, rotate_host_secret, wait_for_transition,
 };
 pub(crate) use startup::{shutdown_signal, start};
 pub(crate) use state::{AppState, ConfigRx, RwMap, WsTx};
@@ -2,12 +2,15 @@
 
 use alloc::sync::Arc;
 use core::{
+    convert::Infallible,
     net::{IpAddr, SocketAddr},
     time::Duration,
 };
 use std::collections::{HashMap, HashSet};
 
+use chrono::Utc;
 use futures::future;
+use parking_lot::Mutex;
 use thiserror::Error as ThisError;
 use tokio::{
     io::{AsyncReadExt as _, AsyncWriteExt as _},
@@ -23,30 +26,37 @@ use tracing::{debug, error, info, warn};
 use web_push_native::jwt_simple::algorithms::ES256KeyPair;
 
 use shuthost_common::{
-    BroadcastMessage, HmacValidationResult, create_signed_message, parse_hmac_message,
+    BroadcastMessage, HmacValidationResult, NonceCache, create_signed_message, parse_hmac_message,
     protocol::{InitSystem, OsType},
-    validate_hmac_message,
+    validate_hmac_message_with_tolerance,
 };
 
 use super::host_actor::HostStatus;
-use super::state::{ConfigRx, ConfigTx, HostInstallInfo, HostState, OperationKind};
+use super::state::{ConfigRx, HostInstallInfo, HostState, OperationKind};
 use crate::{
     app::{
         AppState, HostActorHandle, LeaseMap, LeaseRx, OperationFailureMap, WsTx,
-        config_watcher::watch_config_file,
+        config_watcher::{watch_config_file, watch_tls_files},
         db,
         host_actor::{FullHostEvent, HostEventType},
         host_control::spawn_handle_host_state,
         notifications::{EventKind, NotificationEvent},
         shared_watch_store::SharedWatchRx,
     },
+    audit_log::{AuditEventType, AuditOutcome},
     config::{Host, StructuredEventFilter, WebhookEventFilter},
-    http::push,
-    websocket::{DynamicConfig, FrontendHostConfig, WsMessage},
+    http::{push, tls::TlsFiles},
+    websocket::{DynamicConfig, ErrorKind, FrontendHostConfig, WsMessage},
 };
 
 use crate::app::{host_control::HostWithName, notifications};
 
+/// Interval between checks for leases whose TTL elapsed.
+const LEASE_EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval between removals of expired entries from the M2M nonce cache.
+const NONCE_EXPIRY_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Receive one event from a broadcast channel, logging a warning on lag and breaking on close.
 ///
 /// Takes a pre-resolved `Result<T, RecvError>` and evaluates to `T`. Must be used inside a `loop`.
@@ -66,32 +76,90 @@ macro_rules! next_broadcast_event {
     };
 }
 
+/// Time a single status poll may take before the host counts as offline.
+const STATUS_POLL_TIMEOUT: Duration = Duration::from_millis(900);
+
 /// Poll a single host for its online status.
-async fn poll_host_status(host: &HostWithName) -> (HostState, Option<HostInstallInfo>) {
-    let addr = format!("{}:{}", host.host.ip, host.host.port);
-    let deadline = Instant::now() + Duration::from_millis(900);
+///
+/// Besides the state and install info, returns a description of the failure if the host was
+/// reachable but the status exchange failed (e.g. the agent rejected the request). Unreachable
+/// hosts are simply offline and yield no failure.
+///
+/// `timeout` bounds the whole exchange, from connecting to reading the response.
+#[tracing::instrument(level = "debug", skip_all, fields(host = %host.name))]
+pub(super) async fn poll_host_status(
+    host: &HostWithName,
+    timeout: Duration,
+) -> (HostState, Option<HostInstallInfo>, Option<String>) {
+    let addr = SocketAddr::new(host.host.ip, host.host.port);
+    let deadline = Instant::now() + timeout;
 
     let Ok(Ok(mut stream)) = timeout_at(deadline, TcpStream::connect(&addr)).await else {
-        return (HostState::Offline, None);
+        return (HostState::Offline, None, None);
     };
 
     let signed_message = create_signed_message("status", host.host.shared_secret.as_ref());
     if let Err(e) = stream.write_all(signed_message.as_bytes()).await {
-        debug!("Failed to write to {}: {}", host.name, e);
-        return (HostState::Offline, None);
+        debug!(error = %e, "Failed to write status request");
+        return (
+            HostState::Offline,
+            None,
+            Some(format!("Failed to send status request: {e}")),
+        );
     }
 
     let mut buf = vec![0u8; 256];
     let Ok(Ok(n)) = timeout_at(deadline, stream.read(&mut buf)).await else {
-        return (HostState::Offline, None);
+        return (HostState::Offline, None, None);
     };
 
     let resp = String::from_utf8_lossy(buf.get(..n).expect("n <= buf.len() by definition"));
+    let resp = resp.trim();
     // Accept any non-error response as online
-    if resp.contains("ERROR") {
-        (HostState::Offline, None)
+    if resp.is_empty() {
+        (
+            HostState::Offline,
+            None,
+            Some("Agent closed the connection without a response".to_string()),
+        )
+    } else if resp.contains("ERROR") {
+        // Agents report errors as `ERROR: <reason>`.
+        let reason = resp.strip_prefix("ERROR:").map_or(resp, str::trim);
+        (
+            HostState::Offline,
+            None,
+            Some(format!("Agent rejected status request: {reason}")),
+        )
     } else {
-        (HostState::Online, parse_install_info(&resp))
+        (HostState::Online, parse_install_info(resp), None)
+    }
+}
+
+/// Broadcasts a [`WsMessage::Error`] for hosts whose status poll newly started failing.
+///
+/// `failing` holds the hosts that failed in the previous cycle, so a persistent failure is only
+/// reported once instead of on every poll.
+fn report_poll_failures<'poll>(
+    state: &AppState,
+    results: impl IntoIterator<Item = (&'poll String, Option<&'poll String>)>,
+    failing: &mut HashSet<String>,
+) {
+    for (host, failure) in results {
+        let Some(message) = failure else {
+            failing.remove(host);
+            continue;
+        };
+        if failing.insert(host.clone()) {
+            warn!(host = %host, "Status poll failed: {message}");
+            let msg = WsMessage::Error {
+                host: host.clone(),
+                kind: ErrorKind::PollFailure,
+                message: message.clone(),
+            };
+            if state.ws_tx.send(msg).is_err() {
+                debug!("No Websocket Subscribers");
+            }
+        }
     }
 }
 
@@ -150,10 +218,24 @@ async fn maybe_update_host_install_info(
     if current == Some(&new_info) {
         return;
     }
+    let version_changed =
+        current.and_then(|info| info.agent_version.as_ref()) != Some(&agent_version);
 
     info_map.insert(hostname.to_string(), new_info);
     drop(info_map);
 
+    if version_changed
+        && state
+            .ws_tx
+            .send(WsMessage::AgentVersion {
+                host: hostname.to_string(),
+                version: agent_version.clone(),
+            })
+            .is_err()
+    {
+        debug!("No Websocket Subscribers for agent version");
+    }
+
     if let &Some(ref pool) = &state.db_pool {
         if let Err(e) = db::upsert_host_install_info(
             pool.clone(),
@@ -207,7 +289,7 @@ pub(super) async fn poll_until_host_state(
     let mut ticker = interval(Duration::from_millis(poll_interval_ms));
     ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
     loop {
-        let (current_state, _) = poll_host_status(host).await;
+        let (current_state, ..) = poll_host_status(host, STATUS_POLL_TIMEOUT).await;
         let tick_fut = ticker.tick();
         if current_state == desired_state {
             // State reached: the caller is responsible for informing the actor
@@ -226,24 +308,35 @@ pub(super) async fn poll_until_host_state(
 
 /// Start all background tasks for the HTTP server.
 /// Returns a [`JoinSet`] that owns all spawned tasks; dropping it aborts them all.
-pub(super) fn start_background_tasks(
-    state: &AppState,
-    config_tx: &ConfigTx,
-    broadcast_socket: UdpSocket,
-) -> JoinSet<()> {
+pub(super) fn start_background_tasks(state: &AppState, broadcast_socket: UdpSocket) -> JoinSet<()> {
     // TODO: move enforce_state handling into a dedicated task that watches host changes instead of inlining it into the polling task etc.
     let mut tasks = JoinSet::new();
 
     tasks.spawn(watch_config_file(
         state.config_path.clone(),
-        config_tx.clone(),
+        state.config_tx.clone(),
+        state.ws_tx.clone(),
+        state.config_error.clone(),
+        state.db_pool.clone(),
     ));
 
+    if let Some(ref rustls_config) = state.rustls_config
+        && let Some(ref tls) = state.config_rx.borrow().server.tls
+    {
+        tasks.spawn(watch_tls_files(
+            TlsFiles::from_config(tls, &state.config_path),
+            rustls_config.clone(),
+        ));
+    }
+
     // Reconcile host state on lease changes (edge-triggered, per-host via actor event stream)
     tasks.spawn(reconcile_on_lease_change(state.clone()));
 
     tasks.spawn(listen_for_agent_startup(state.clone(), broadcast_socket));
 
+    #[cfg(feature = "mdns")]
+    tasks.spawn(super::mdns::discover_agent_ports(state.clone()));
+
     spawn_websocket_forwarders(
         &mut tasks,
         &state.ws_tx,
@@ -267,6 +360,14 @@ pub(super) fn start_background_tasks(
         state.config_rx.clone(),
     ));
 
+    // Release leases whose TTL elapsed; the lease change is handled like any other.
+    tasks.spawn(expire_leases(state.clone()));
+
+    // Spawned regardless of `m2m_replay_protection`, as it can be enabled by a config reload.
+    tasks.spawn(expire_nonces(state.nonce_cache.clone()));
+
+    tasks.spawn(super::schedules::run_schedules(state.clone()));
+
     // Forward lease changes into the HostActor event stream.
     tasks.spawn(forward_lease_events(
         state.leases.subscribe(),
@@ -298,7 +399,8 @@ fn spawn_websocket_forwarders(
     host_actor: HostActorHandle,
 ) {
     // Forwards host state and lease changes to WebSocket clients via the actor event stream.
-    // StateChanged → full HostStatus snapshot (with config-defined hosts filled in as Offline).
+    // StateChanged → full HostStatus snapshot (with config-defined hosts filled in as Offline),
+    //                along with the time each host went online.
     // LeaseChanged  → per-host LeaseUpdate.
     let ws_tx_events = ws_tx.clone();
     let config_rx_for_status = config_rx.clone();
@@ -308,12 +410,14 @@ fn spawn_websocket_forwarders(
             let event = next_broadcast_event!(events_rx.recv().await, "ws_forwarder");
             let msg = match event.event {
                 HostEventType::StateChanged { .. } => {
-                    let mut status_map = host_actor.borrow().as_ref().clone();
-                    let config = config_rx_for_status.borrow();
-                    for host in config.hosts.keys() {
-                        status_map.entry(host.clone()).or_insert(HostState::Offline);
+                    let mut status = host_actor.borrow().as_ref().clone();
+                    for host in config_rx_for_status.borrow().hosts.keys() {
+                        status.entry(host.clone()).or_insert(HostState::Offline);
+                    }
+                    WsMessage::HostStatus {
+                        status,
+                        last_seen: host_actor.went_online().as_ref().clone(),
                     }
-                    WsMessage::HostStatus(status_map)
                 }
                 HostEventType::LeaseChanged { leases, .. } => WsMessage::LeaseUpdate {
                     host: event.host,
@@ -479,12 +583,74 @@ fn should_enforce_action(
     needs_action && stable_for >= threshold
 }
 
+/// Returns the hosts with `enforce_state = true` that are online without any lease, sorted by
+/// name. Enforcement shuts these down once their state is stable.
+fn unleased_online_enforced_hosts<'cfg>(
+    hosts: &'cfg HashMap<String, Host>,
+    leases: &LeaseMap,
+    status: &HostStatus,
+) -> Vec<&'cfg str> {
+    let mut pending: Vec<_> = hosts
+        .iter()
+        .filter(|&(name, host)| {
+            host.enforce_state
+                && leases
+                    .get(name)
+                    .is_none_or(super::host_control::LeaseSources::is_empty)
+                && status.get(name) == Some(&HostState::Online)
+        })
+        .map(|(name, _)| name.as_str())
+        .collect();
+    pending.sort_unstable();
+    pending
+}
+
+/// Warns about each host that enforcement is going to shut down, see
+/// [`unleased_online_enforced_hosts`].
+fn warn_pending_enforced_shutdowns(
+    hosts: &HashMap<String, Host>,
+    leases: &LeaseMap,
+    status: &HostStatus,
+    threshold: Duration,
+) {
+    for host_name in unleased_online_enforced_hosts(hosts, leases, status) {
+        warn!(
+            host = %host_name,
+            "Host is online without leases and has enforce_state enabled, it will be shut down once its state was stable for {}s",
+            threshold.as_secs()
+        );
+    }
+}
+
 /// Background task: periodically polls each host for status by attempting a TCP connection and HMAC ping.
 /// For hosts with `enforce_state = true`, also re-triggers control if the actual state diverges from
 /// the lease-implied desired state (after a stabilization delay).
 ///
 /// The logic determining whether an enforcement action should be triggered is
 /// factored into `should_enforce_action` which makes it easy to unit test.
+/// Stores the install info reported in status replies, for hosts that reported all of it.
+async fn update_install_info_from_polls<'poll>(
+    state: &AppState,
+    results: impl Iterator<Item = (&'poll String, Option<&'poll HostInstallInfo>)>,
+) {
+    for (host_name, install_info) in results {
+        if let Some(info) = install_info.cloned()
+            && let (Some(version), Some(init_system), Some(os)) =
+                (info.agent_version, info.init_system, info.os)
+        {
+            maybe_update_host_install_info(
+                state,
+                host_name,
+                version,
+                init_system,
+                os,
+                info.script_path,
+            )
+            .await;
+        }
+    }
+}
+
 async fn poll_host_statuses(state: AppState) {
     let poll_interval = Duration::from_secs(state.runtime.status_poll_interval_secs);
     let enforce_threshold = Duration::from_secs(state.runtime.enforce_stabilization_threshold_secs);
@@ -492,6 +658,10 @@ async fn poll_host_statuses(state: AppState) {
     ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
     // Tracks when each host's state last changed (to enforce stability when updates come in from multiple sources).
     let mut state_timestamps: HashMap<String, Instant> = HashMap::new();
+    // Hosts whose last status poll failed, to report each failure only once.
+    let mut poll_failures: HashSet<String> = HashSet::new();
+    // Hosts found online at startup are announced before enforcement shuts them down.
+    let mut first_poll = true;
 
     loop {
         let poll_start = Instant::now();
@@ -501,21 +671,21 @@ async fn poll_host_statuses(state: AppState) {
 
         // Read IP/port overrides once per poll cycle into an owned map so the
         // read-guard is dropped before the async join_all below.
-        let ip_overrides: HashMap<String, (String, u16)> = {
+        let ip_overrides: HashMap<String, (IpAddr, u16)> = {
             let overrides = state.host_overrides.read().await;
             overrides
                 .iter()
-                .map(|(k, v)| (k.clone(), (v.ip.clone(), v.port)))
+                .map(|(k, v)| (k.clone(), (v.ip, v.port)))
                 .collect()
         };
 
+        let metrics = &state.metrics;
         let futures = config.hosts.iter().map(|(name, host)| {
             let name = name.clone();
             let mut host_clone = host.clone();
-            let (ip, port) = ip_overrides.get(name.as_str()).map_or_else(
-                || (host_clone.ip.clone(), host_clone.port),
-                |&(ref ip, port)| (ip.clone(), port),
-            );
+            let (ip, port) = ip_overrides
+                .get(name.as_str())
+                .map_or((host_clone.ip, host_clone.port), |&(ip, port)| (ip, port));
             host_clone.ip = ip;
             host_clone.port = port;
             let host_with_name = HostWithName {
@@ -523,10 +693,15 @@ async fn poll_host_statuses(state: AppState) {
                 host: host_clone,
             };
             async move {
-                let polled = poll_host_status(&host_with_name).await;
+                let started = Instant::now();
+                let polled = poll_host_status(&host_with_name, STATUS_POLL_TIMEOUT).await;
+                metrics.observe_poll_duration(&name, started.elapsed());
                 debug!(
-                    "Polled {} at {}:{} - state: {:?}",
-                    host_with_name.name, host_with_name.host.ip, host_with_name.host.port, polled.0
+                    host = %host_with_name.name,
+                    ip = %host_with_name.host.ip,
+                    port = host_with_name.host.port,
+                    state = ?polled.0,
+                    "Polled host"
                 );
                 (name, polled)
             }
@@ -535,22 +710,29 @@ async fn poll_host_statuses(state: AppState) {
         let results = future::join_all(futures).await;
 
         // Update install info from poll results.
-        for &(ref host_name, (_, ref install_info)) in &results {
-            if let Some(info) = install_info.clone()
-                && let (Some(version), Some(init_system), Some(os)) =
-                    (info.agent_version, info.init_system, info.os)
-            {
-                maybe_update_host_install_info(
-                    &state,
-                    host_name,
-                    version,
-                    init_system,
-                    os,
-                    info.script_path,
-                )
-                .await;
-            }
-        }
+        report_poll_failures(
+            &state,
+            results
+                .iter()
+                .map(|&(ref name, (_, _, ref failure))| (name, failure.as_ref())),
+            &mut poll_failures,
+        );
+
+        update_install_info_from_polls(
+            &state,
+            results
+                .iter()
+                .map(|&(ref name, (_, ref info, _))| (name, info.as_ref())),
+        )
+        .await;
+
+        let now = Utc::now();
+        state.last_seen.write().await.extend(
+            results
+                .iter()
+                .filter(|&&(_, (polled_state, _, _))| polled_state == HostState::Online)
+                .map(|&(ref name, _)| (name.clone(), now)),
+        );
 
         // Apply polled states to the actor, which will skip any host with an active control task.
         // The oneshot reply carries the post-apply snapshot, so the change comparison below
@@ -558,7 +740,7 @@ async fn poll_host_statuses(state: AppState) {
         // stale watch state.
         let poll_iter = results
             .iter()
-            .map(|&(ref name, (ref polled_state, _))| (name.clone(), *polled_state));
+            .map(|&(ref name, (ref polled_state, _, _))| (name.clone(), *polled_state));
         let post_poll_status = state.host_actor.apply_poll_results(poll_iter).await;
 
         // TODO: move this elsewhere, into a consumer of the host status stream.
@@ -572,29 +754,50 @@ async fn poll_host_statuses(state: AppState) {
 
         // Enforce state for hosts that opt in, after a stabilization delay.
         let leases_snapshot = state.leases.snapshot();
-        for (host_name, host_cfg) in &config.hosts {
-            let lease_set = leases_snapshot.get(host_name).cloned().unwrap_or_default();
-            let current_state = state.host_actor.get_current_state(host_name);
-
-            let stable_for = state_timestamps
-                .get(host_name)
-                .map_or(enforce_threshold, Instant::elapsed);
-
-            if should_enforce_action(
-                host_cfg,
-                &lease_set,
-                current_state,
-                stable_for,
+        if first_poll {
+            first_poll = false;
+            warn_pending_enforced_shutdowns(
+                &config.hosts,
+                &leases_snapshot,
+                &post_poll_status,
                 enforce_threshold,
-            ) {
-                spawn_handle_host_state(host_name, &state);
-            }
+            );
         }
+        enforce_host_states(
+            &state,
+            &config.hosts,
+            &leases_snapshot,
+            &state_timestamps,
+            enforce_threshold,
+        );
 
         ticker.tick().await;
     }
 }
 
+/// Spawns a control task for each host with `enforce_state = true` whose state diverged from the
+/// one its leases imply, see [`should_enforce_action`].
+fn enforce_host_states(
+    state: &AppState,
+    hosts: &HashMap<String, Host>,
+    leases: &LeaseMap,
+    state_timestamps: &HashMap<String, Instant>,
+    threshold: Duration,
+) {
+    for (host_name, host_cfg) in hosts {
+        let lease_set = leases.get(host_name).cloned().unwrap_or_default();
+        let current_state = state.host_actor.get_current_state(host_name);
+
+        let stable_for = state_timestamps
+            .get(host_name)
+            .map_or(threshold, Instant::elapsed);
+
+        if should_enforce_action(host_cfg, &lease_set, current_state, stable_for, threshold) {
+            spawn_handle_host_state(host_name, state);
+        }
+    }
+}
+
 /// Reads the current webhook config for `hostname` and spawns a deferred timer task
 /// for each `OnlineFor` filter that matches. Each task sleeps until the configured
 /// duration elapses, re-reads the live webhook config (to respect hot-reloads), then
@@ -761,6 +964,75 @@ async fn report_unscheduled_events(
     }
 }
 
+/// Background task: periodically forgets M2M messages whose timestamp left the tolerance window.
+///
+/// Replays of them are rejected by the timestamp check from then on.
+async fn expire_nonces(nonce_cache: Arc<Mutex<NonceCache>>) {
+    let mut ticker = interval(NONCE_EXPIRY_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        nonce_cache.lock().remove_expired();
+    }
+}
+
+/// Background task: periodically releases leases whose TTL elapsed.
+///
+/// The released leases are published through the lease store, so they reach WebSocket clients
+/// and the reconciler the same way as explicitly released ones.
+async fn expire_leases(state: AppState) {
+    let mut ticker = interval(LEASE_EXPIRY_CHECK_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let now = Instant::now();
+        if !state
+            .leases
+            .borrow()
+            .values()
+            .any(|lease_set| lease_set.has_expired(now))
+        {
+            continue;
+        }
+
+        let expired = state
+            .leases
+            .update({
+                let db_pool = state.db_pool.clone();
+                async move |map| {
+                    let mut expired = Vec::new();
+                    for (host, lease_set) in map.iter_mut() {
+                        for source in lease_set.remove_expired(now) {
+                            if let Some(ref pool) = db_pool
+                                && let Err(e) = db::remove_lease(pool, host, &source).await
+                            {
+                                error!("Failed to remove expired lease from database: {e}");
+                            }
+                            expired.push((host.clone(), source));
+                        }
+                    }
+                    Ok::<_, Infallible>(expired)
+                }
+            })
+            .await
+            .unwrap_or_else(|e| match e {});
+
+        for (host, source) in expired {
+            info!(%host, lease_source = %source, "Lease expired");
+            if let Some(ref audit_log) = state.audit_log {
+                audit_log
+                    .record(
+                        AuditEventType::LeaseRelease,
+                        &host,
+                        Some(&source),
+                        AuditOutcome::Ok,
+                    )
+                    .await;
+            }
+        }
+    }
+}
+
 /// Background task: watches the lease store and forwards per-host lease changes
 /// into the [`HostActorHandle`] event stream so all consumers can use a single stream.
 async fn forward_lease_events(mut leases_rx: LeaseRx, host_actor: HostActorHandle) {
@@ -859,11 +1131,12 @@ async fn handle_startup_packet(data: &[u8], peer_addr: SocketAddr, state: &AppSt
         return;
     };
 
-    if !validate_startup_hmac(raw, &host_cfg, peer_addr, hostname) {
+    let tolerance_secs = state.config_rx.borrow().server.hmac_tolerance_secs;
+    if !validate_startup_hmac(raw, &host_cfg, tolerance_secs, peer_addr, hostname) {
         return;
     }
 
-    info!("Received valid startup broadcast from host '{hostname}' at {peer_addr}");
+    info!(host = %hostname, %peer_addr, "Received valid startup broadcast");
 
     state.host_actor.startup_broadcast(hostname).await;
 
@@ -904,7 +1177,7 @@ fn lookup_host_config(state: &AppState, hostname: &str, peer_addr: SocketAddr) -
     match config.hosts.get(hostname).cloned() {
         Some(cfg) => Some(cfg),
         None => {
-            debug!("Startup broadcast for unknown host '{hostname}' from {peer_addr}, ignoring");
+            debug!(host = %hostname, %peer_addr, "Startup broadcast for unknown host, ignoring");
             None
         }
     }
@@ -913,15 +1186,16 @@ fn lookup_host_config(state: &AppState, hostname: &str, peer_addr: SocketAddr) -
 fn validate_startup_hmac(
     raw: &str,
     host_cfg: &Host,
+    tolerance_secs: u64,
     peer_addr: SocketAddr,
     hostname: &str,
 ) -> bool {
     let mac_is_valid = matches!(
-        validate_hmac_message(raw, &host_cfg.shared_secret),
+        validate_hmac_message_with_tolerance(raw, &host_cfg.shared_secret, tolerance_secs),
         HmacValidationResult::Valid(_)
     );
     if !mac_is_valid {
-        debug!("Invalid HMAC on startup broadcast from {peer_addr} claiming to be '{hostname}'");
+        debug!(host = %hostname, %peer_addr, "Invalid HMAC on startup broadcast");
     }
     mac_is_valid
 }
@@ -937,37 +1211,22 @@ async fn persist_host_override_if_needed(
 
     // Validate the agent-reported IP address before trusting/persisting it.
     let agent_ip_trimmed = agent_ip.trim();
-    let parsed_ip = agent_ip_trimmed.parse::<IpAddr>();
-    if let Err(e) = parsed_ip {
-        warn!(
-            "Ignoring invalid agent IP address '{}' for host '{}': {e}",
-            agent_ip, hostname
-        );
-        return;
-    }
+    let parsed_ip = match agent_ip_trimmed.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(e) => {
+            warn!(host = %hostname, "Ignoring invalid agent IP address '{agent_ip}': {e}");
+            return;
+        }
+    };
 
-    if agent_ip != &host_cfg.ip || agent_port != host_cfg.port {
+    if parsed_ip != host_cfg.ip || agent_port != host_cfg.port {
         warn!(
-            "Host '{hostname}' address differs from config: config={}:{}, agent={}:{}; storing override",
-            host_cfg.ip, host_cfg.port, agent_ip, agent_port
+            host = %hostname,
+            "Address differs from config: config={}:{}, agent={}:{}; storing override",
+            host_cfg.ip, host_cfg.port, parsed_ip, agent_port
         );
 
-        {
-            let mut overrides = state.host_overrides.write().await;
-            overrides.insert(
-                hostname.to_string(),
-                db::HostOverride {
-                    ip: agent_ip.clone(),
-                    port: agent_port,
-                },
-            );
-        }
-
-        if let Some(ref pool) = state.db_pool
-            && let Err(e) = db::upsert_host_ip_override(pool, hostname, agent_ip, agent_port).await
-        {
-            error!("Failed to persist IP override for '{hostname}': {e}");
-        }
+        store_host_override(state, hostname, parsed_ip, agent_port).await;
     } else {
         // The agent-reported address matches the static config again.
         // Clear any existing override from memory and the database.
@@ -983,11 +1242,25 @@ async fn persist_host_override_if_needed(
             && let Some(ref pool) = state.db_pool
             && let Err(e) = db::delete_host_ip_override(pool, hostname).await
         {
-            error!("Failed to clear IP override for '{hostname}': {e}");
+            error!(host = %hostname, "Failed to clear IP override: {e}");
         }
     }
 }
 
+/// Stores the address an agent was found at, in memory and in the database if enabled.
+pub(super) async fn store_host_override(state: &AppState, hostname: &str, ip: IpAddr, port: u16) {
+    {
+        let mut overrides = state.host_overrides.write().await;
+        overrides.insert(hostname.to_string(), db::HostOverride { ip, port });
+    }
+
+    if let Some(ref pool) = state.db_pool
+        && let Err(e) = db::upsert_host_ip_override(pool, hostname, ip, port).await
+    {
+        error!(host = %hostname, "Failed to persist IP override: {e}");
+    }
+}
+
 /// If `event` represents an unscheduled host state transition, returns the target
 /// [`HostState`]; otherwise returns `None`.
 ///
@@ -1030,28 +1303,31 @@ mod tests {
     use crate::app::host_control::{LeaseSource, LeaseSources};
     use alloc::sync::Arc;
     use core::time::Duration;
-    use std::collections::HashSet;
 
     const ENFORCE_STABILIZATION_THRESHOLD: Duration = Duration::from_secs(5);
 
     fn make_host(enforce: bool) -> Host {
         Host {
-            ip: String::new(),
-            mac: String::new(),
+            ip: IpAddr::from([0, 0, 0, 0]),
+            mac: Vec::new(),
+            wol_broadcast: None,
             port: 0,
             shared_secret: Arc::new(secrecy::SecretString::new(String::new().into())),
             enforce_state: enforce,
             wake_timeout_secs: None,
             shutdown_timeout_secs: None,
+            transition_poll_interval_ms: None,
             pre_startup: None,
             post_shutdown: None,
+            tags: Vec::new(),
+            notes: None,
         }
     }
 
     #[test]
     fn should_enforce_respects_flag_and_state() {
         let cfg = make_host(false);
-        let lease_set: LeaseSources = HashSet::new();
+        let lease_set = LeaseSources::default();
 
         // enforce_state disabled -> never trigger
         assert!(!should_enforce_action(
@@ -1082,6 +1358,30 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn finds_unleased_online_enforced_hosts() {
+        let hosts = HashMap::from([
+            ("pending".to_string(), make_host(true)),
+            ("leased".to_string(), make_host(true)),
+            ("offline".to_string(), make_host(true)),
+            ("unenforced".to_string(), make_host(false)),
+        ]);
+        let leases = LeaseMap::from([(
+            "leased".to_string(),
+            LeaseSources::from_iter([LeaseSource::WebInterface]),
+        )]);
+        let status = HostStatus::from([
+            ("pending".to_string(), HostState::Online),
+            ("leased".to_string(), HostState::Online),
+            ("offline".to_string(), HostState::Offline),
+            ("unenforced".to_string(), HostState::Online),
+        ]);
+        assert_eq!(
+            unleased_online_enforced_hosts(&hosts, &leases, &status),
+            ["pending"]
+        );
+    }
+
     #[test]
     fn should_enforce_checks_threshold() {
         let cfg = make_host(true);
@@ -1148,7 +1448,7 @@ mod tests {
         let e = FullHostEvent {
             host: "h".to_string(),
             event: HostEventType::LeaseChanged {
-                leases: HashSet::new(),
+                leases: LeaseSources::default(),
                 all_leases: empty.clone(),
             },
         };
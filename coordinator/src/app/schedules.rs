@@ -0,0 +1,184 @@
+//! Background task applying the lease schedules from `[server.schedules]`.
+//!
+//! Each schedule holds a lease named after it, taken and released by its entries when their cron
+//! expressions fire. On startup the latest firing of the last [`CATCH_UP_WINDOW`] is applied for
+//! each schedule and host, so a window that opened or closed while the coordinator was down still
+//! takes effect. Schedule leases whose schedule or host no longer appears in the config are
+//! released.
+
+use core::time::Duration;
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local, TimeZone, Timelike as _};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+    app::{AppState, LeaseSource},
+    config::{LeaseAction, ScheduleEntry},
+    http::api::update_lease,
+};
+
+/// How far back startup looks for the latest firing of each schedule.
+const CATCH_UP_WINDOW: Duration = Duration::from_hours(7 * 24);
+
+/// Extra delay after each full minute, so the clock is safely past it when evaluating.
+const TICK_OFFSET: Duration = Duration::from_millis(100);
+
+type Schedules = HashMap<String, Vec<ScheduleEntry>>;
+
+/// Background task: takes and releases schedule leases as their cron expressions fire.
+///
+/// Schedules are read once; changing them requires a restart.
+pub(super) async fn run_schedules(state: AppState) {
+    let schedules = state.config_rx.borrow().server.schedules.clone();
+
+    release_unconfigured(&state, &schedules).await;
+    for (name, host, action) in catch_up_actions(&schedules, &Local::now()) {
+        apply(&state, name, host, action).await;
+    }
+    if schedules.is_empty() {
+        return;
+    }
+
+    let mut last_minute = None;
+    loop {
+        let started = Local::now();
+        let into_minute = Duration::new(u64::from(started.second()), started.nanosecond());
+        sleep(Duration::from_mins(1).saturating_sub(into_minute) + TICK_OFFSET).await;
+
+        let now = Local::now();
+        let minute = now.with_second(0).and_then(|time| time.with_nanosecond(0));
+        if minute == last_minute {
+            continue;
+        }
+        last_minute = minute;
+        for (name, entries) in &schedules {
+            for entry in entries.iter().filter(|entry| entry.cron.matches(&now)) {
+                apply(&state, name, &entry.host, entry.action).await;
+            }
+        }
+    }
+}
+
+/// Returns the action of the entry that fired last within [`CATCH_UP_WINDOW`] before `now`, for
+/// each schedule and host.
+fn catch_up_actions<'cfg, Tz: TimeZone>(
+    schedules: &'cfg Schedules,
+    now: &DateTime<Tz>,
+) -> Vec<(&'cfg str, &'cfg str, LeaseAction)> {
+    let mut latest = HashMap::new();
+    for (name, entries) in schedules {
+        for entry in entries {
+            let Some(fired_at) = entry.cron.last_fire_at_or_before(now, CATCH_UP_WINDOW) else {
+                continue;
+            };
+            // Later entries win ties, matching the order they are applied in while running.
+            latest
+                .entry((name.as_str(), entry.host.as_str()))
+                .and_modify(|current: &mut (DateTime<Tz>, LeaseAction)| {
+                    if fired_at >= current.0 {
+                        *current = (fired_at.clone(), entry.action);
+                    }
+                })
+                .or_insert((fired_at, entry.action));
+        }
+    }
+    let mut actions: Vec<_> = latest
+        .into_iter()
+        .map(|((name, host), (_, action))| (name, host, action))
+        .collect();
+    actions.sort_unstable_by_key(|&(name, host, _)| (name, host));
+    actions
+}
+
+/// Releases persisted schedule leases that no configured entry could take or release anymore.
+async fn release_unconfigured(state: &AppState, schedules: &Schedules) {
+    let unconfigured: Vec<_> = state
+        .leases
+        .snapshot()
+        .iter()
+        .flat_map(|(host, sources)| {
+            sources.iter().filter_map(move |source| match *source {
+                LeaseSource::Schedule { ref name }
+                    if !schedules
+                        .get(name)
+                        .is_some_and(|entries| entries.iter().any(|entry| entry.host == *host)) =>
+                {
+                    Some((host.clone(), name.clone()))
+                }
+                LeaseSource::Schedule { .. }
+                | LeaseSource::WebInterface
+                | LeaseSource::Client(_) => None,
+            })
+        })
+        .collect();
+    for (host, name) in unconfigured {
+        info!(%host, schedule = %name, "Releasing lease of removed schedule");
+        apply(state, &name, &host, LeaseAction::Release).await;
+    }
+}
+
+async fn apply(state: &AppState, name: &str, host: &str, action: LeaseAction) {
+    let source = LeaseSource::Schedule {
+        name: name.to_string(),
+    };
+    if let Err(e) = update_lease(host, source, action, None, state).await {
+        warn!(%host, schedule = %name, "Failed to apply schedule: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn entry(host: &str, cron: &str, action: LeaseAction) -> ScheduleEntry {
+        ScheduleEntry {
+            host: host.to_string(),
+            cron: cron.parse().unwrap(),
+            action,
+        }
+    }
+
+    #[test]
+    fn catch_up_applies_latest_firing_per_host() {
+        let schedules = Schedules::from([
+            (
+                "backup-window".to_string(),
+                vec![
+                    entry("nas", "0 2 * * *", LeaseAction::Take),
+                    entry("nas", "0 4 * * *", LeaseAction::Release),
+                    entry("backup", "0 2 * * *", LeaseAction::Take),
+                    entry("backup", "0 4 * * *", LeaseAction::Release),
+                ],
+            ),
+            (
+                "yearly".to_string(),
+                vec![entry("nas", "0 0 1 1 *", LeaseAction::Take)],
+            ),
+        ]);
+        let at = |hour, minute| {
+            NaiveDate::from_ymd_opt(2026, 10, 14)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+                .and_utc()
+        };
+        assert_eq!(
+            catch_up_actions(&schedules, &at(3, 0)),
+            [
+                ("backup-window", "backup", LeaseAction::Take),
+                ("backup-window", "nas", LeaseAction::Take),
+            ]
+        );
+        assert_eq!(
+            catch_up_actions(&schedules, &at(12, 0)),
+            [
+                ("backup-window", "backup", LeaseAction::Release),
+                ("backup-window", "nas", LeaseAction::Release),
+            ]
+        );
+    }
+}
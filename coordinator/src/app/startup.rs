@@ -1,19 +1,22 @@
 use alloc::string;
-use core::net::{IpAddr, SocketAddr};
+use core::{
+    future,
+    net::{IpAddr, SocketAddr},
+    sync::atomic::Ordering,
+    time::Duration,
+};
 use std::path::Path;
 
+use axum_server::{Handle, tls_rustls::RustlsConfig as AxumRustlsConfig};
 use eyre::WrapErr as _;
-use tokio::{net, signal};
+use tokio::{net, signal, sync::oneshot, time};
 use tracing::Instrument as _;
 
 use super::{
     runtime::start_background_tasks,
-    state::{self, AppState},
-};
-use crate::{
-    config::TlsConfig,
-    http::{router, tls::setup_tls_config},
+    state::{self, AppState, ConfigRx},
 };
+use crate::http::{router, tls::setup_tls_config};
 
 /// Creates a future that resolves when a shutdown signal is received.
 pub(crate) async fn shutdown_signal() {
@@ -29,40 +32,63 @@ pub(crate) async fn shutdown_signal() {
     }
 }
 
-/// Start the HTTP server with optional TLS.
-#[tracing::instrument(skip(app_state, config_path))]
+/// Waits for a shutdown signal and returns the configured grace period.
+async fn wait_for_shutdown(config_rx: &ConfigRx) -> Duration {
+    shutdown_signal().await;
+    let grace_period = Duration::from_secs(config_rx.borrow().server.shutdown_grace_period_secs);
+    tracing::info!(
+        ?grace_period,
+        "Received shutdown, waiting for in-flight requests to complete"
+    );
+    grace_period
+}
+
+/// Start the HTTP server, with TLS if `rustls_config` is set.
+///
+/// On a shutdown signal the server stops accepting connections, but in-flight requests (e.g.
+/// synchronous M2M lease requests waiting for a host) get `[server].shutdown_grace_period_secs`
+/// to complete before the remaining connections are closed.
+#[tracing::instrument(skip(app_state, rustls_config))]
 async fn start_server(
     app_state: AppState,
-    listen_ip: IpAddr,
-    listen_port: u16,
-    tls_opt: Option<&TlsConfig>,
-    config_path: &Path,
+    addr: SocketAddr,
+    rustls_config: Option<AxumRustlsConfig>,
 ) -> eyre::Result<()> {
+    let config_rx = app_state.config_rx.clone();
     let app = router::create_app(app_state);
 
-    let addr = SocketAddr::from((listen_ip, listen_port));
-
-    match tls_opt {
-        Some(tls_cfg) => {
-            let rustls_cfg = setup_tls_config(tls_cfg, config_path, listen_ip, addr)
-                .in_current_span()
-                .await?;
-            let server = axum_server::bind_rustls(addr, rustls_cfg).serve(app);
-            tokio::select! {
-                res = server => res?,
-                () = shutdown_signal() => {
-                    tracing::info!("Received shutdown, shutting down");
+    match rustls_config {
+        Some(rustls_cfg) => {
+            let handle = Handle::new();
+            tokio::spawn({
+                let handle = handle.clone();
+                async move {
+                    handle.graceful_shutdown(Some(wait_for_shutdown(&config_rx).await));
                 }
-            }
+            });
+            axum_server::bind_rustls(addr, rustls_cfg)
+                .handle(handle)
+                .serve(app)
+                .await?;
         }
-        _ => {
+        None => {
             tracing::info!("Listening on http://{}", addr);
             let listener = net::TcpListener::bind(addr).in_current_span().await?;
-            let server = axum::serve(listener, app);
+            let (grace_period_tx, grace_period_rx) = oneshot::channel();
+            let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+                let _ = grace_period_tx.send(wait_for_shutdown(&config_rx).await);
+            });
+            let grace_period_elapsed = async {
+                match grace_period_rx.await {
+                    Ok(grace_period) => time::sleep(grace_period).await,
+                    // The server stopped without a shutdown signal, the other branch wins.
+                    Err(_) => future::pending().await,
+                }
+            };
             tokio::select! {
                 res = server => res?,
-                () = shutdown_signal() => {
-                    tracing::info!("Received shutdown, shutting down");
+                () = grace_period_elapsed => {
+                    tracing::warn!("Shutdown grace period elapsed, closing remaining connections");
                 }
             }
         }
@@ -91,7 +117,7 @@ pub(crate) async fn start(
 ) -> eyre::Result<()> {
     tracing::info!("Starting HTTP server...");
 
-    let (app_state, tls_opt, config_tx) = state::initialize_state(config_path).await?;
+    let (mut app_state, tls_opt) = state::initialize_state(config_path).await?;
 
     // Apply optional overrides from CLI/tests
     let listen_port = port_override.unwrap_or(app_state.config_rx.borrow().server.port);
@@ -101,6 +127,16 @@ pub(crate) async fn start(
     );
 
     let listen_ip: IpAddr = bind_str.parse()?;
+    let addr = SocketAddr::from((listen_ip, listen_port));
+
+    // Set up TLS before the background tasks start, so they can reload the certificate.
+    if let Some(ref tls_cfg) = tls_opt {
+        app_state.rustls_config = Some(
+            setup_tls_config(tls_cfg, config_path, listen_ip, addr)
+                .in_current_span()
+                .await?,
+        );
+    }
 
     // Bind the UDP broadcast socket early so failures are fatal on startup.
     let broadcast_port =
@@ -114,14 +150,9 @@ pub(crate) async fn start(
     tracing::info!("Listening for agent startup broadcasts on {broadcast_addr}");
 
     // Hold the JoinSet for the lifetime of the server — dropping it aborts all background tasks.
-    let _background_tasks = start_background_tasks(&app_state, &config_tx, broadcast_socket);
+    let _background_tasks = start_background_tasks(&app_state, broadcast_socket);
+    app_state.ready.store(true, Ordering::Release);
 
-    start_server(
-        app_state,
-        listen_ip,
-        listen_port,
-        tls_opt.as_ref(),
-        config_path,
-    )
-    .await
+    let rustls_config = app_state.rustls_config.clone();
+    start_server(app_state, addr, rustls_config).await
 }
@@ -1,13 +1,20 @@
 use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicUsize};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
 };
 use tokio::time::Instant;
 
+use axum_server::tls_rustls::RustlsConfig as AxumRustlsConfig;
+use chrono::{DateTime, Utc};
 use eyre::WrapErr as _;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use shuthost_common::protocol::{InitSystem, OsType};
+use shuthost_common::{
+    NonceCache,
+    protocol::{InitSystem, OsType},
+};
 use tokio::sync::{RwLock, broadcast, watch};
 use tracing::info;
 use web_push_native::jwt_simple::algorithms::ES256KeyPair;
@@ -20,10 +27,16 @@ use crate::{
         host_actor::HostActorHandle,
         host_control::LeaseStore,
     },
+    audit_log::AuditLog,
     config::{
-        ControllerConfig, DbConfig, RuntimeConfig, TlsConfig, load, resolve_config_relative_paths,
+        AuditLogConfig, ControllerConfig, DbConfig, RuntimeConfig, TlsConfig, load,
+        resolve_config_relative_paths,
+    },
+    http::{
+        EXPECTED_AUTH_EXCEPTIONS_VERSION, auth,
+        m2m::{HmacCache, PEER_RATE_LIMIT_BURST, PEER_RATE_LIMIT_RPS, RateLimiter},
     },
-    http::{EXPECTED_AUTH_EXCEPTIONS_VERSION, auth},
+    metrics,
     websocket::WsMessage,
 };
 
@@ -47,7 +60,7 @@ impl HostState {
 }
 
 pub(crate) type ConfigRx = watch::Receiver<Arc<ControllerConfig>>;
-pub(super) type ConfigTx = watch::Sender<Arc<ControllerConfig>>;
+pub(crate) type ConfigTx = watch::Sender<Arc<ControllerConfig>>;
 pub(crate) type OperationFailureStore = SharedWatchStore<OperationFailureMap>;
 pub(crate) type WsTx = broadcast::Sender<WsMessage>;
 
@@ -106,6 +119,9 @@ pub(crate) struct HostInstallInfo {
 
 pub(crate) type RwMap<V> = Arc<RwLock<HashMap<String, V>>>;
 
+/// See [`AppState::config_error`].
+pub(crate) type ConfigError = Arc<RwLock<Option<String>>>;
+
 /// Latest GitHub release info, populated when an update is available.
 #[derive(Debug, Clone, Serialize)]
 pub(crate) struct LatestReleaseInfo {
@@ -122,6 +138,10 @@ pub(crate) struct AppState {
     /// Receiver for updated `ControllerConfig` when the file changes.
     pub config_rx: ConfigRx,
 
+    /// Sender for `ControllerConfig` updates, used by the config file watcher and for runtime
+    /// changes such as secret rotation.
+    pub config_tx: ConfigTx,
+
     /// Single-owner host state machine actor.
     pub host_actor: HostActorHandle,
 
@@ -143,6 +163,10 @@ pub(crate) struct AppState {
     /// Whether the HTTP server was started with TLS enabled (true for HTTPS)
     pub tls_enabled: bool,
 
+    /// TLS configuration of the running server, reloaded when the certificate files change.
+    /// `None` until the server is set up, and when TLS is disabled.
+    pub rustls_config: Option<AxumRustlsConfig>,
+
     /// Runtime tuning parameters (poll intervals, default timeouts, etc.).
     /// Snapshotted at startup; a restart is required to apply changes.
     pub runtime: RuntimeConfig,
@@ -163,9 +187,44 @@ pub(crate) struct AppState {
     /// session.
     pub online_since: RwMap<Instant>,
 
+    /// Wall-clock time of the most recent poll that found each host online (ephemeral, not persisted).
+    pub last_seen: RwMap<DateTime<Utc>>,
+
     /// Latest GitHub release info. `Some` only when an update is available.
     /// `None` until the first check completes or if the running version is up to date.
     pub latest_release: Arc<RwLock<Option<LatestReleaseInfo>>>,
+
+    /// Error of the last config file reload. `Some` while the file can't be loaded and the last
+    /// valid config keeps being used.
+    pub config_error: ConfigError,
+
+    /// Per-client rate limiter for the M2M endpoints.
+    /// Snapshotted at startup; a restart is required to apply changes.
+    pub m2m_rate_limiter: Arc<RateLimiter>,
+
+    /// Per-IP rate limiter for unauthenticated M2M requests, i.e. the `/api/m2m/nonce` endpoint
+    /// and requests that fail authentication.
+    pub m2m_peer_rate_limiter: Arc<RateLimiter>,
+
+    /// Recent M2M signature checks, see [`HmacCache`].
+    /// Snapshotted at startup; a restart is required to apply changes.
+    pub hmac_cache: Arc<HmacCache>,
+
+    /// M2M messages accepted within the HMAC tolerance window, used to reject replays when
+    /// `m2m_replay_protection` is enabled.
+    pub nonce_cache: Arc<Mutex<NonceCache>>,
+
+    /// Writer for the JSON audit log. `None` when the audit log is disabled.
+    pub audit_log: Option<Arc<AuditLog>>,
+
+    /// Prometheus metrics, recorded regardless of whether the metrics endpoint is enabled.
+    pub metrics: Arc<metrics::Registry>,
+
+    /// Number of currently open `WebUI` WebSocket connections, limited by `max_connections`.
+    pub ws_connections: Arc<AtomicUsize>,
+
+    /// Set once startup has completed, reported by the `/ready` probe.
+    pub ready: Arc<AtomicBool>,
 }
 
 /// Initialize database pool based on configuration.
@@ -182,9 +241,10 @@ async fn initialize_database(
         Some(DbConfig {
             enable: true,
             ref path,
+            pool_size,
         }) => {
             let db_path = resolve_config_relative_paths(config_path, path);
-            let pool = db::init(&db_path).await.wrap_err(format!(
+            let pool = db::init(&db_path, pool_size).await.wrap_err(format!(
                 "Failed to initialize database at: {}",
                 db_path.display()
             ))?;
@@ -201,6 +261,29 @@ async fn initialize_database(
     })
 }
 
+/// Open the audit log if one is configured and enabled.
+///
+/// Relative paths are resolved relative to the config file.
+#[tracing::instrument(skip_all)]
+async fn initialize_audit_log(
+    initial_config: &ControllerConfig,
+    config_path: &Path,
+) -> eyre::Result<Option<Arc<AuditLog>>> {
+    Ok(match initial_config.server.audit_log {
+        Some(AuditLogConfig {
+            enable: true,
+            ref path,
+            format,
+        }) => {
+            let log_path = resolve_config_relative_paths(config_path, path);
+            let audit_log = AuditLog::open(&log_path, format).await?;
+            info!("Writing audit log to: {}", log_path.display());
+            Some(Arc::new(audit_log))
+        }
+        _ => None,
+    })
+}
+
 // TODO: consider showing warning in gui as well
 pub fn emit_warning_on_unsaved_sync_state(app_state: &ControllerConfig) {
     if !matches!(app_state.db, Some(DbConfig { enable: true, .. })) {
@@ -259,12 +342,24 @@ fn emit_startup_warnings(app_state: &AppState, app_config: &ControllerConfig) {
         _ => {}
     }
 
+    if let Some(max_connections) = app_config.server.max_connections
+        && (1..5).contains(&max_connections)
+    {
+        tracing::warn!(
+            "server.max_connections is set to {max_connections}. A single browser may open several WebSocket connections (e.g. multiple tabs or reloads), so a limit this low may lock out the WebUI."
+        );
+    }
+
     emit_warning_on_unsaved_sync_state(app_config);
 }
 
-async fn load_leases(db_pool: Option<&DbPool>) -> eyre::Result<Arc<LeaseStore>> {
+async fn load_leases(
+    db_pool: Option<&DbPool>,
+    initial_config: &ControllerConfig,
+) -> eyre::Result<Arc<LeaseStore>> {
     let mut initial_leases = LeaseMap::default();
     if let Some(pool) = db_pool {
+        db::remove_orphaned_leases(pool, |host| initial_config.hosts.contains_key(host)).await?;
         db::load_leases(pool, &mut initial_leases).await?;
         info!("Loaded leases from database");
     } else {
@@ -282,7 +377,7 @@ async fn load_host_overrides(
         let overrides = db::load_host_ip_overrides(pool).await?;
         for (name, o) in &overrides {
             if let Some(h) = initial_config.hosts.get(name)
-                && (h.ip != o.ip || h.port != o.port)
+                && (o.ip != h.ip || h.port != o.port)
             {
                 tracing::warn!(
                     "Host '{name}' has a stored IP/port override: config={}:{}, stored={}:{}",
@@ -293,6 +388,10 @@ async fn load_host_overrides(
                 );
             }
         }
+        info!(
+            "Loaded {} host IP override(s) from database",
+            overrides.len()
+        );
         overrides
     } else {
         HashMap::default()
@@ -352,7 +451,7 @@ async fn load_vapid_key(db_pool: Option<&DbPool>) -> eyre::Result<Option<Arc<ES2
 #[tracing::instrument(skip_all)]
 pub(super) async fn initialize_state(
     config_path: &Path,
-) -> eyre::Result<(AppState, Option<TlsConfig>, ConfigTx)> {
+) -> eyre::Result<(AppState, Option<TlsConfig>)> {
     let initial_config = Arc::new(load(config_path).await?);
 
     let (config_tx, config_rx) = watch::channel(initial_config.clone());
@@ -361,7 +460,7 @@ pub(super) async fn initialize_state(
     let (operation_failures, _) = OperationFailureStore::new(OperationFailureMap::new());
 
     let db_pool = initialize_database(&initial_config, config_path).await?;
-    let leases = load_leases(db_pool.as_ref()).await?;
+    let leases = load_leases(db_pool.as_ref(), &initial_config).await?;
     let host_overrides = load_host_overrides(db_pool.as_ref(), &initial_config).await?;
     let host_install_info = load_host_install_info(db_pool.as_ref()).await?;
 
@@ -374,9 +473,11 @@ pub(super) async fn initialize_state(
     };
 
     let vapid_key = load_vapid_key(db_pool.as_ref()).await?;
+    let audit_log = initialize_audit_log(&initial_config, config_path).await?;
 
     let app_state = AppState {
         config_rx,
+        config_tx,
         host_actor,
         ws_tx,
         config_path: config_path.to_path_buf(),
@@ -385,15 +486,32 @@ pub(super) async fn initialize_state(
         host_install_info,
         auth: auth_runtime.clone(),
         tls_enabled: tls_opt.is_some(),
+        rustls_config: None,
         runtime: initial_config.server.runtime.clone(),
         db_pool,
         vapid_key,
         operation_failures,
         online_since: RwMap::default(),
+        last_seen: RwMap::default(),
         latest_release: Arc::default(),
+        config_error: Arc::default(),
+        m2m_rate_limiter: Arc::new(RateLimiter::new(
+            initial_config.server.m2m_rate_limit_rps,
+            initial_config.server.m2m_rate_limit_burst,
+        )),
+        m2m_peer_rate_limiter: Arc::new(RateLimiter::new(
+            PEER_RATE_LIMIT_RPS,
+            PEER_RATE_LIMIT_BURST,
+        )),
+        hmac_cache: Arc::new(HmacCache::new(initial_config.server.hmac_cache_size)),
+        nonce_cache: Arc::default(),
+        audit_log,
+        metrics: Arc::default(),
+        ws_connections: Arc::default(),
+        ready: Arc::default(),
     };
 
     emit_startup_warnings(&app_state, &initial_config);
 
-    Ok((app_state, tls_opt, config_tx))
+    Ok((app_state, tls_opt))
 }
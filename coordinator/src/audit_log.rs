@@ -0,0 +1,261 @@
+//! Append-only JSON audit log for lease changes and host control operations.
+//!
+//! Intended for operators who need a machine-readable trail (e.g. for a SIEM) in addition to
+//! the human-readable `tracing` output. Enabled through the optional `[server.audit_log]` table.
+//!
+//! Every record is a single line. With [`AuditLogFormat::Ndjson`] the lines are just appended,
+//! with [`AuditLogFormat::Json`] they are elements of a JSON array, whose closing bracket is
+//! moved behind every new record.
+
+use std::{
+    io::{self, SeekFrom},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use eyre::{Result, WrapErr as _, bail};
+use serde::Serialize;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt as _, AsyncSeekExt as _, AsyncWriteExt as _},
+    sync::Mutex,
+};
+use tracing::error;
+
+use crate::{app::LeaseSource, config::AuditLogFormat};
+
+/// Kind of event recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AuditEventType {
+    LeaseTake,
+    LeaseRelease,
+    HostWake,
+    HostShutdown,
+    /// A host's shared secret was replaced at runtime.
+    SecretRotation,
+}
+
+/// Whether the audited operation succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AuditOutcome {
+    Ok,
+    Error,
+}
+
+impl AuditOutcome {
+    pub(crate) const fn from_success(success: bool) -> Self {
+        if success { Self::Ok } else { Self::Error }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AuditRecord<'event> {
+    timestamp: DateTime<Utc>,
+    event_type: AuditEventType,
+    host: &'event str,
+    /// The lease holder for lease events; `None` for host control events.
+    source: Option<&'event LeaseSource>,
+    outcome: AuditOutcome,
+}
+
+/// End of a non-empty audit log in the [`AuditLogFormat::Json`] format, overwritten by the next
+/// record.
+const JSON_ARRAY_END: [u8; 3] = *b"\n]\n";
+/// Offset of [`JSON_ARRAY_END`] from the end of the file.
+const JSON_ARRAY_END_OFFSET: i64 = -3;
+
+/// Audit log writer appending one JSON record per event to a file.
+pub(crate) struct AuditLog {
+    file: Mutex<File>,
+    format: AuditLogFormat,
+}
+
+impl AuditLog {
+    /// Opens (or creates) the audit log at `path` for appending.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, or with [`AuditLogFormat::Json`] if it
+    /// isn't empty and doesn't end like a JSON array written by the audit log.
+    pub(crate) async fn open(path: &Path, format: AuditLogFormat) -> Result<Self> {
+        let mut options = OpenOptions::new();
+        options.create(true);
+        match format {
+            AuditLogFormat::Json => options.read(true).write(true),
+            AuditLogFormat::Ndjson => options.append(true),
+        };
+        let mut file = options
+            .open(path)
+            .await
+            .wrap_err(format!("Failed to open audit log at: {}", path.display()))?;
+        if format == AuditLogFormat::Json && !is_empty_or_json_array(&mut file).await? {
+            bail!(
+                "Audit log at {} is not a JSON array, move it away or use the \"ndjson\" format",
+                path.display()
+            );
+        }
+        Ok(Self {
+            file: Mutex::new(file),
+            format,
+        })
+    }
+
+    /// Appends a record for the given event.
+    ///
+    /// Write failures are logged but not propagated, so a broken audit log never blocks host control.
+    pub(crate) async fn record(
+        &self,
+        event_type: AuditEventType,
+        host: &str,
+        source: Option<&LeaseSource>,
+        outcome: AuditOutcome,
+    ) {
+        let record = AuditRecord {
+            timestamp: Utc::now(),
+            event_type,
+            host,
+            source,
+            outcome,
+        };
+        // Line-oriented consumers split the log on newlines, so a record must never span several.
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to serialize audit record: {e}");
+                return;
+            }
+        };
+
+        let mut file = self.file.lock().await;
+        if let Err(e) = write_record(&mut file, &line, self.format).await {
+            error!("Failed to write audit record: {e}");
+        } else if let Err(e) = file.flush().await {
+            error!("Failed to flush audit log: {e}");
+        }
+    }
+}
+
+/// Writes the serialized record `line` to the end of the audit log `file`.
+async fn write_record(file: &mut File, line: &str, format: AuditLogFormat) -> io::Result<()> {
+    match format {
+        AuditLogFormat::Ndjson => file.write_all(format!("{line}\n").as_bytes()).await,
+        AuditLogFormat::Json => {
+            let end = file.seek(SeekFrom::End(0)).await?;
+            let separator = if end == 0 {
+                "["
+            } else {
+                file.seek(SeekFrom::End(JSON_ARRAY_END_OFFSET)).await?;
+                ","
+            };
+            file.write_all(format!("{separator}\n{line}\n]\n").as_bytes())
+                .await
+        }
+    }
+}
+
+/// Whether `file` is empty or ends with [`JSON_ARRAY_END`].
+async fn is_empty_or_json_array(file: &mut File) -> Result<bool> {
+    if file.seek(SeekFrom::End(0)).await? == 0 {
+        return Ok(true);
+    }
+    let mut end = [0u8; JSON_ARRAY_END.len()];
+    if file
+        .seek(SeekFrom::End(JSON_ARRAY_END_OFFSET))
+        .await
+        .is_err()
+    {
+        return Ok(false);
+    }
+    file.read_exact(&mut end).await?;
+    Ok(end == JSON_ARRAY_END)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use tokio::fs;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn appends_ndjson_records() {
+        let path = env::temp_dir().join(format!("shuthost_audit_{}.log", process::id()));
+        drop(fs::remove_file(&path).await);
+
+        let audit_log = AuditLog::open(&path, AuditLogFormat::Ndjson).await.unwrap();
+        audit_log
+            .record(
+                AuditEventType::LeaseTake,
+                "host1",
+                Some(&LeaseSource::Client("client1".to_string())),
+                AuditOutcome::Ok,
+            )
+            .await;
+        audit_log
+            .record(AuditEventType::HostWake, "host1", None, AuditOutcome::Error)
+            .await;
+
+        let contents = fs::read_to_string(&path).await.unwrap();
+        drop(fs::remove_file(&path).await);
+        let records: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["event_type"], "lease_take");
+        assert_eq!(records[0]["host"], "host1");
+        assert_eq!(records[0]["source"]["value"], "client1");
+        assert_eq!(records[0]["outcome"], "ok");
+        assert_eq!(records[1]["event_type"], "host_wake");
+        assert!(records[1]["source"].is_null());
+        assert_eq!(records[1]["outcome"], "error");
+        assert!(records[1]["timestamp"].is_string());
+    }
+
+    #[tokio::test]
+    async fn json_format_keeps_an_array_of_single_line_records() {
+        let path = env::temp_dir().join(format!("shuthost_audit_{}.json", process::id()));
+        drop(fs::remove_file(&path).await);
+
+        for host in ["host1", "host2"] {
+            // Reopened like on a coordinator restart, so the array gets continued.
+            let audit_log = AuditLog::open(&path, AuditLogFormat::Json).await.unwrap();
+            audit_log
+                .record(
+                    AuditEventType::LeaseTake,
+                    host,
+                    Some(&LeaseSource::Client("client1".to_string())),
+                    AuditOutcome::Ok,
+                )
+                .await;
+        }
+        let audit_log = AuditLog::open(&path, AuditLogFormat::Json).await.unwrap();
+        audit_log
+            .record(AuditEventType::HostWake, "host1", None, AuditOutcome::Error)
+            .await;
+
+        let contents = fs::read_to_string(&path).await.unwrap();
+        drop(fs::remove_file(&path).await);
+        let records: Vec<serde_json::Value> = serde_json::from_str(&contents).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["host"], "host1");
+        assert_eq!(records[1]["host"], "host2");
+        assert_eq!(records[2]["event_type"], "host_wake");
+        // The brackets and every record are lines of their own.
+        assert_eq!(contents.lines().count(), 5, "{contents}");
+    }
+
+    #[tokio::test]
+    async fn json_format_rejects_other_files() {
+        let path = env::temp_dir().join(format!("shuthost_audit_{}.ndjson", process::id()));
+        fs::write(&path, "{\"host\":\"host1\"}\n").await.unwrap();
+
+        let result = AuditLog::open(&path, AuditLogFormat::Json).await;
+        drop(fs::remove_file(&path).await);
+        assert!(result.is_err());
+    }
+}
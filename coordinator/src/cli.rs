@@ -3,15 +3,28 @@
 //! This module contains the CLI argument parsing structures and enums
 //! used by the main coordinator binary.
 
-use std::env;
+use std::{env, path::PathBuf, sync::Once};
 
-use crate::VERSION;
 #[cfg(unix)]
 use crate::install;
+use crate::{VERSION, config, demo};
 use clap::{Parser, Subcommand, ValueEnum};
+use tracing_subscriber::filter::LevelFilter;
 
 pub const BINARY_NAME: &str = env!("CARGO_PKG_NAME");
 
+/// Environment variable consulted for the config path when `--config` is not given.
+pub const CONFIG_PATH_ENV: &str = "SHUTHOST_COORDINATOR_CONFIG_PATH";
+
+/// Former name of [`CONFIG_PATH_ENV`], still consulted when neither it nor `--config` is given.
+pub const DEPRECATED_CONFIG_PATH_ENV: &str = "SHUTHOST_CONTROLLER_CONFIG_PATH";
+
+/// Config path used when neither `--config` nor an environment variable is given.
+const DEFAULT_CONFIG_PATH: &str = "shuthost_coordinator.toml";
+
+/// Environment variable consulted for the log level when `--log-level` is not given.
+pub const LOG_LEVEL_ENV: &str = "SHUTHOST_LOG_LEVEL";
+
 /// Top-level command-line interface definition.
 #[derive(Debug, Parser)]
 #[command(name = BINARY_NAME)]
@@ -32,9 +45,57 @@ pub enum Command {
     /// Install the coordinator service to start on boot.
     Install(install::Args),
 
+    #[cfg(unix)]
+    /// Stop and remove the coordinator service, its binary and (optionally) its config file.
+    Uninstall(install::uninstall::Args),
+
+    /// Interactively create a starter config file.
+    GenerateConfig(config::generate::Args),
+
+    /// Check a config file for errors without starting the service.
+    ValidateConfig {
+        /// Path to the configuration file.
+        /// Defaults to `shuthost_coordinator.toml` in the working directory.
+        #[arg(short, long, env = CONFIG_PATH_ENV)]
+        config: Option<String>,
+    },
+
+    /// Check that every configured host agent is reachable, without starting the service.
+    Diagnose {
+        /// Path to the configuration file.
+        /// Defaults to `shuthost_coordinator.toml` in the working directory.
+        #[arg(short, long, env = CONFIG_PATH_ENV)]
+        config: Option<String>,
+    },
+
+    /// Copy the database to a backup file, while the service may keep running.
+    BackupDb {
+        /// Path to the configuration file.
+        /// Defaults to `shuthost_coordinator.toml` in the working directory.
+        #[arg(short, long, env = CONFIG_PATH_ENV)]
+        config: Option<String>,
+        /// Path of the backup file to create
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Replace the database with a backup file. The service must be stopped.
+    RestoreDb {
+        /// Path to the configuration file.
+        /// Defaults to `shuthost_coordinator.toml` in the working directory.
+        #[arg(short, long, env = CONFIG_PATH_ENV)]
+        config: Option<String>,
+        /// Path of the backup file to restore
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+
+    /// Print the effective config (file plus overrides) as TOML, with secrets redacted.
+    ExportConfig(config::export::Args),
+
     /// Serve only static assets for demo mode (no backend, no state).
     DemoService {
-        #[arg(long, default_value = "8080")]
+        #[arg(long, default_value_t = shuthost_common::DEFAULT_COORDINATOR_PORT)]
         port: u16,
         #[arg(long, default_value = "0.0.0.0")]
         bind: String,
@@ -42,22 +103,42 @@ pub enum Command {
         /// Defaults to `/` and is a positional argument.
         #[arg(default_value = "/")]
         subpath: String,
+        #[command(flatten)]
+        demo: demo::DemoConfig,
     },
 }
 
+/// Returns the config path from [`DEPRECATED_CONFIG_PATH_ENV`], warning once that it's deprecated.
+pub(crate) fn deprecated_config_path() -> Option<String> {
+    static WARNING: Once = Once::new();
+
+    let path = env::var(DEPRECATED_CONFIG_PATH_ENV).ok()?;
+    // Logging isn't set up while parsing the command line.
+    WARNING.call_once(|| {
+        eprintln!(
+            "Warning: {DEPRECATED_CONFIG_PATH_ENV} is deprecated and will be removed in a future release, use {CONFIG_PATH_ENV} instead"
+        );
+    });
+    Some(path)
+}
+
+/// Resolves a `--config` argument, falling back to [`DEPRECATED_CONFIG_PATH_ENV`] and then the
+/// default path when neither the flag nor [`CONFIG_PATH_ENV`] is given.
+pub(crate) fn resolve_config_path(config: Option<String>) -> String {
+    config
+        .or_else(deprecated_config_path)
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_owned())
+}
+
 /// Arguments for the control service command.
 #[derive(Debug, Parser)]
 pub struct ServiceArgs {
-    /// Path to the configuration file
-    #[arg(
-        short,
-        long,
-        env = "SHUTHOST_CONTROLLER_CONFIG_PATH",
-        default_value = "shuthost_coordinator.toml"
-    )]
-    pub config: String,
+    /// Path to the configuration file.
+    /// Defaults to `shuthost_coordinator.toml` in the working directory.
+    #[arg(short, long, env = CONFIG_PATH_ENV)]
+    pub config: Option<String>,
     /// Optional override for the listen port (overrides port in config)
-    #[arg(long)]
+    #[arg(long, short)]
     pub port: Option<u16>,
 
     /// Optional override for the bind address (overrides bind in config)
@@ -70,6 +151,10 @@ pub struct ServiceArgs {
     /// Logging format
     #[arg(long, value_enum, default_value_t = LogFormat::default())]
     pub log_format: LogFormat,
+
+    /// Log level used when `RUST_LOG` is not set
+    #[arg(long, env = LOG_LEVEL_ENV, default_value_t = LevelFilter::INFO)]
+    pub log_level: LevelFilter,
 }
 
 /// Available logging formats for console output.
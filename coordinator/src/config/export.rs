@@ -0,0 +1,148 @@
+//! Printing of the effective coordinator config, i.e. the config file with CLI overrides applied.
+//!
+//! Useful to check what a running coordinator would use, or to share a config in bug reports.
+//! Secrets are replaced by [`REDACTED`], so the output is not a drop-in replacement for the file.
+
+use std::path::Path;
+
+use clap::Parser;
+use eyre::WrapErr as _;
+
+use crate::{
+    cli::{CONFIG_PATH_ENV, resolve_config_path},
+    config::{ControllerConfig, REDACTED, load},
+};
+
+/// Arguments for the `export-config` subcommand of the coordinator.
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Path to the configuration file.
+    /// Defaults to `shuthost_coordinator.toml` in the working directory.
+    #[arg(short, long, env = CONFIG_PATH_ENV)]
+    config: Option<String>,
+
+    /// Override for the listen port, as accepted by `control-service`
+    #[arg(long, short)]
+    port: Option<u16>,
+
+    /// Override for the bind address, as accepted by `control-service`
+    #[arg(long)]
+    bind: Option<String>,
+
+    /// Override for the UDP broadcast listen port, as accepted by `control-service`
+    #[arg(long)]
+    broadcast_port: Option<u16>,
+}
+
+/// Loads the config, applies the overrides and prints the result as TOML on stdout.
+///
+/// # Errors
+///
+/// Returns `Err` if the file cannot be read or parsed, or the config cannot be serialized.
+pub(crate) async fn run(args: &Args) -> eyre::Result<()> {
+    let config_path = resolve_config_path(args.config.clone());
+    let mut config = load(Path::new(&config_path))
+        .await
+        .wrap_err(format!("Failed to load config {config_path}"))?;
+    apply_overrides(&mut config, args);
+    print!("{}", render(&config)?);
+    Ok(())
+}
+
+fn apply_overrides(config: &mut ControllerConfig, args: &Args) {
+    if let Some(port) = args.port {
+        config.server.port = port;
+    }
+    if let Some(ref bind) = args.bind {
+        config.server.bind.clone_from(bind);
+    }
+    if let Some(broadcast_port) = args.broadcast_port {
+        config.server.broadcast_port = broadcast_port;
+    }
+}
+
+fn render(config: &ControllerConfig) -> eyre::Result<String> {
+    Ok(format!(
+        "# Effective config exported by `shuthost_coordinator export-config`.\n\
+         # Secrets are replaced by \"{REDACTED}\".\n\n{}",
+        toml::to_string_pretty(config).wrap_err("Failed to serialize config")?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AuthMode;
+
+    const CONFIG: &str = r#"
+        [server]
+        port = 8080
+        bind = "127.0.0.1"
+
+        [server.auth.token]
+        token = "web-token"
+
+        [hosts.nas]
+        ip = "192.168.1.10"
+        mac = ["aa:bb:cc:dd:ee:ff", "aa:bb:cc:dd:ee:00"]
+        port = 5757
+        shared_secret = "host-secret"
+        tags = ["storage"]
+
+        [hosts.nas.pre_startup]
+        type = "http"
+        url = "http://power.local/on"
+        method = "PUT"
+
+        [clients.laptop]
+        shared_secret = "client-secret"
+
+        [[notifications.webhooks]]
+        url = "http://hooks.local"
+        events = ["unscheduled", { type = "online_for", duration_secs = 60 }]
+        headers = { Authorization = "Bearer header-secret" }
+        secret = "webhook-secret"
+    "#;
+
+    #[test]
+    fn applies_overrides_and_redacts_secrets() {
+        let mut config: ControllerConfig = toml::from_str(CONFIG).unwrap();
+        let args = Args::parse_from(["export-config", "--port", "9090", "--bind", "0.0.0.0"]);
+        apply_overrides(&mut config, &args);
+
+        let out = render(&config).unwrap();
+        for secret in [
+            "web-token",
+            "host-secret",
+            "client-secret",
+            "header-secret",
+            "webhook-secret",
+        ] {
+            assert!(!out.contains(secret), "{secret} leaked:\n{out}");
+        }
+
+        let exported: ControllerConfig = toml::from_str(&out).unwrap();
+        assert_eq!(exported.server.port, 9090);
+        assert_eq!(exported.server.bind, "0.0.0.0");
+        assert!(matches!(
+            exported.server.auth.mode,
+            AuthMode::Token { token: Some(_) }
+        ));
+        let nas = &exported.hosts["nas"];
+        assert_eq!(nas.mac.len(), 2);
+        assert_eq!(nas.tags, ["storage"]);
+        assert_eq!(nas.pre_startup, config.hosts["nas"].pre_startup);
+        assert_eq!(
+            exported.notifications.webhooks[0].events,
+            config.notifications.webhooks[0].events
+        );
+        assert!(exported.clients.contains_key("laptop"));
+    }
+
+    #[test]
+    fn default_config_round_trips() {
+        let config = ControllerConfig::default();
+        let exported: ControllerConfig = toml::from_str(&render(&config).unwrap()).unwrap();
+        assert_eq!(exported, config);
+    }
+}
@@ -0,0 +1,347 @@
+//! Interactive generator for a starter coordinator config file.
+//!
+//! Asks for the few settings every deployment needs and writes a minimal, commented TOML file.
+//! See `docs/examples/example_config.toml` for the full set of options.
+
+use core::net::IpAddr;
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use clap::Parser;
+use eyre::WrapErr as _;
+
+use crate::config::ControllerConfig;
+
+/// Arguments for the `generate-config` subcommand of the coordinator.
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Path the generated config file is written to.
+    #[arg(long, short, default_value = "shuthost_coordinator.toml")]
+    output: PathBuf,
+
+    /// Overwrite an existing file without asking.
+    #[arg(long)]
+    force: bool,
+}
+
+/// Authentication mode chosen for the Web UI.
+#[derive(Debug, PartialEq, Eq)]
+enum AuthChoice {
+    None,
+    /// An empty token lets the coordinator generate one on startup.
+    Token {
+        token: String,
+    },
+    Oidc {
+        issuer: String,
+        client_id: String,
+        client_secret: String,
+    },
+}
+
+#[derive(Debug)]
+struct Answers {
+    bind: String,
+    port: u16,
+    auth: AuthChoice,
+    /// An empty path disables the database.
+    db_path: String,
+}
+
+/// Prompts for the basic settings on stdin and writes the resulting config file.
+///
+/// # Errors
+///
+/// Returns `Err` if reading the answers or writing the file fails.
+pub(crate) fn run(args: &Args) -> eyre::Result<()> {
+    generate(args, &mut io::stdin().lock(), &mut io::stdout())
+}
+
+fn generate(args: &Args, input: &mut impl BufRead, output: &mut impl Write) -> eyre::Result<()> {
+    if args.output.exists() && !args.force {
+        let question = format!("{} already exists. Overwrite?", args.output.display());
+        if !confirm(input, output, &question)? {
+            writeln!(
+                output,
+                "Aborted, {} was left unchanged.",
+                args.output.display()
+            )?;
+            return Ok(());
+        }
+    }
+
+    let answers = ask(input, output)?;
+    let content = render(&answers);
+    toml::from_str::<ControllerConfig>(&content)
+        .wrap_err("Generated config failed to parse, this is a bug")?;
+
+    fs::write(&args.output, &content)
+        .wrap_err(format!("Failed to write {}", args.output.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        // The file may contain secrets.
+        fs::set_permissions(&args.output, fs::Permissions::from_mode(0o600)).wrap_err(format!(
+            "Failed to set permissions of {}",
+            args.output.display()
+        ))?;
+    }
+    writeln!(output, "Wrote config to {}", args.output.display())?;
+    Ok(())
+}
+
+fn ask(input: &mut impl BufRead, output: &mut impl Write) -> eyre::Result<Answers> {
+    let bind = loop {
+        let bind = prompt(input, output, "Bind address", "127.0.0.1")?;
+        if bind.parse::<IpAddr>().is_ok() {
+            break bind;
+        }
+        writeln!(output, "'{bind}' is not a valid IP address.")?;
+    };
+    let port = loop {
+        let port = prompt(
+            input,
+            output,
+            "Port",
+            &shuthost_common::DEFAULT_COORDINATOR_PORT.to_string(),
+        )?;
+        if let Ok(port) = port.parse() {
+            break port;
+        }
+        writeln!(output, "'{port}' is not a valid port.")?;
+    };
+    let auth = loop {
+        match prompt(input, output, "Auth mode (none/token/oidc)", "none")?.as_str() {
+            "none" => break AuthChoice::None,
+            "token" => {
+                let token = prompt(
+                    input,
+                    output,
+                    "Token (leave empty to generate one on startup)",
+                    "",
+                )?;
+                break AuthChoice::Token { token };
+            }
+            "oidc" => {
+                break AuthChoice::Oidc {
+                    issuer: prompt(input, output, "OIDC issuer URL", "")?,
+                    client_id: prompt(input, output, "OIDC client ID", "shuthost")?,
+                    client_secret: prompt(input, output, "OIDC client secret", "")?,
+                };
+            }
+            other => writeln!(output, "Unknown auth mode '{other}'.")?,
+        }
+    };
+    let db_path = prompt(
+        input,
+        output,
+        "Database path (enter - to disable)",
+        "./shuthost.db",
+    )?;
+    let db_path = if db_path == "-" {
+        String::new()
+    } else {
+        db_path
+    };
+
+    Ok(Answers {
+        bind,
+        port,
+        auth,
+        db_path,
+    })
+}
+
+/// Asks `question` and returns the trimmed answer, or `default` if the answer is empty.
+fn prompt(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: &str,
+) -> eyre::Result<String> {
+    if default.is_empty() {
+        write!(output, "{question}: ")?;
+    } else {
+        write!(output, "{question} [{default}]: ")?;
+    }
+    output.flush()?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        eyre::bail!("Input ended before all questions were answered");
+    }
+    let answer = line.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+fn confirm(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+) -> eyre::Result<bool> {
+    let answer = prompt(input, output, &format!("{question} (y/N)"), "")?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Quotes `value` as a TOML string.
+fn quoted(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+fn render(answers: &Answers) -> String {
+    let mut sections = vec![
+        "# Generated by `shuthost_coordinator generate-config`.\n\
+         # See https://github.com/9SMTM6/shuthost/blob/main/docs/examples/example_config.toml for all options.\n"
+            .to_string(),
+        format!(
+            "[server]\nport = {}\nbind = {}\n",
+            answers.port,
+            quoted(&answers.bind)
+        ),
+    ];
+
+    match answers.auth {
+        AuthChoice::None => {}
+        AuthChoice::Token { ref token } if token.is_empty() => {
+            sections.push("[server.auth.token]\n".to_string());
+        }
+        AuthChoice::Token { ref token } => {
+            sections.push(format!("[server.auth.token]\ntoken = {}\n", quoted(token)));
+        }
+        AuthChoice::Oidc {
+            ref issuer,
+            ref client_id,
+            ref client_secret,
+        } => sections.push(format!(
+            "[server.auth.oidc]\nissuer = {}\nclient_id = {}\nclient_secret = {}\n",
+            quoted(issuer),
+            quoted(client_id),
+            quoted(client_secret)
+        )),
+    }
+
+    if answers.auth == AuthChoice::None {
+        sections.push(
+            "# Serve HTTPS, with a persisted self-signed certificate unless cert_path/key_path exist.\n\
+             # [server.tls]\n\
+             # cert_path = \"./tls_cert.pem\"\n\
+             # key_path = \"./tls_key.pem\"\n"
+                .to_string(),
+        );
+    } else {
+        sections.push(
+            "# Login requires HTTPS. Remove this table if TLS is terminated by a reverse proxy.\n\
+             # Uses a persisted self-signed certificate unless cert_path/key_path exist.\n\
+             [server.tls]\n\
+             # cert_path = \"./tls_cert.pem\"\n\
+             # key_path = \"./tls_key.pem\"\n"
+                .to_string(),
+        );
+    }
+
+    if !answers.db_path.is_empty() {
+        sections.push(format!("[db]\npath = {}\n", quoted(&answers.db_path)));
+    }
+
+    sections.push(
+        "[hosts]\n\
+         # [hosts.my-host-name]\n\
+         # ip = \"192.168.1.100\"\n\
+         # mac = \"aa:bb:cc:dd:ee:ff\"\n\
+         # port = 5757\n\
+         # shared_secret = \"your-generated-secret\"\n"
+            .to_string(),
+    );
+    sections.push(
+        "[clients]\n\
+         # [clients.my-client-name]\n\
+         # shared_secret = \"your-generated-secret\"\n"
+            .to_string(),
+    );
+
+    sections.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::Cursor, process};
+
+    use super::*;
+    use crate::config::AuthMode;
+
+    fn temp_output(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("shuthost_generate_{name}_{}.toml", process::id()));
+        drop(fs::remove_file(&path));
+        path
+    }
+
+    #[test]
+    fn generates_parseable_config_from_answers() {
+        let path = temp_output("answers");
+        let args = Args {
+            output: path.clone(),
+            force: false,
+        };
+        let mut input = Cursor::new("0.0.0.0\nnot-a-port\n9090\ntoken\nsecret\"token\n\n");
+        let mut output = Vec::new();
+
+        generate(&args, &mut input, &mut output).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        drop(fs::remove_file(&path));
+        let config: ControllerConfig = toml::from_str(&content).unwrap();
+        assert_eq!(config.server.bind, "0.0.0.0");
+        assert_eq!(config.server.port, 9090);
+        assert!(matches!(
+            config.server.auth.mode,
+            AuthMode::Token { token: Some(_) }
+        ));
+        assert!(config.server.tls.is_some(), "token auth enables TLS");
+        assert_eq!(config.db.unwrap().path, "./shuthost.db");
+        assert!(config.hosts.is_empty());
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("not a valid port")
+        );
+    }
+
+    #[test]
+    fn disabled_db_and_no_auth() {
+        let settings = Answers {
+            bind: "127.0.0.1".to_string(),
+            port: 8080,
+            auth: AuthChoice::None,
+            db_path: String::new(),
+        };
+        let config: ControllerConfig = toml::from_str(&render(&settings)).unwrap();
+        assert!(matches!(config.server.auth.mode, AuthMode::None));
+        assert!(config.server.tls.is_none());
+        assert!(config.db.is_none());
+    }
+
+    #[test]
+    fn existing_file_is_kept_unless_confirmed() {
+        let path = temp_output("existing");
+        fs::write(&path, "keep me").unwrap();
+        let args = Args {
+            output: path.clone(),
+            force: false,
+        };
+
+        generate(&args, &mut Cursor::new("n\n"), &mut Vec::new()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "keep me");
+
+        let forced = Args {
+            output: path.clone(),
+            force: true,
+        };
+        generate(&forced, &mut Cursor::new("\n\n\n\n"), &mut Vec::new()).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        drop(fs::remove_file(&path));
+        assert!(content.contains("[server]"));
+    }
+}
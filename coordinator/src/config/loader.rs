@@ -3,12 +3,33 @@
 //! This module provides functions for reading and parsing
 //! configuration files from disk.
 
-use std::path::Path;
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    path::{Path, PathBuf},
+};
 
 use eyre::WrapErr as _;
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::{Deserialize, de::DeserializeOwned};
 use tokio::fs;
+use toml::de;
 
-use crate::config::ControllerConfig;
+use crate::config::{AuthMode, Client, ControllerConfig, Host, resolve_config_relative_paths};
+
+/// Contents of a file pulled in through `include`.
+///
+/// Only hosts, clients and further includes are allowed, so server settings stay in the main
+/// config file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IncludedConfig {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    hosts: HashMap<String, Host>,
+    #[serde(default)]
+    clients: HashMap<String, Client>,
+}
 
 /// Reads and parses the coordinator config from a TOML file.
 ///
@@ -21,21 +42,195 @@ use crate::config::ControllerConfig;
 /// Returns an error if the config file cannot be read or parsed.
 pub(crate) async fn load<P: AsRef<Path>>(path: P) -> eyre::Result<ControllerConfig> {
     let path_ref = path.as_ref();
-    let content = fs::read_to_string(&path).await.wrap_err(format!(
-        "Failed to read config file at: {}",
-        path_ref.display()
-    ))?;
-    let config: ControllerConfig = toml::from_str(&content).wrap_err(format!(
-        "Failed to parse config as TOML at: {}",
-        path_ref.display()
-    ))?;
+    let mut config: ControllerConfig = parse_file(path_ref).await?;
+    merge_includes(&mut config, path_ref).await?;
+    for field in padded_secret_fields(&config) {
+        tracing::warn!(
+            "{field} has leading or trailing whitespace, which is part of the secret. Remove it if it was pasted by accident."
+        );
+    }
     Ok(config)
 }
 
+async fn parse_file<T: DeserializeOwned>(path: &Path) -> eyre::Result<T> {
+    let content = fs::read_to_string(path)
+        .await
+        .wrap_err(format!("Failed to read config file at: {}", path.display()))?;
+    toml::from_str(&content)
+        .map_err(|e| match describe_duplicate_key(&content, &e) {
+            Some(description) => eyre::Report::new(e).wrap_err(description),
+            None => eyre::Report::new(e),
+        })
+        .wrap_err(format!(
+            "Failed to parse config as TOML at: {}",
+            path.display()
+        ))
+}
+
+/// Merges the hosts and clients of all (nested) includes of `config` into it.
+///
+/// Includes are processed depth-first in the order they are listed. A file may only be included
+/// once, and a host or client may only be defined in one file.
+async fn merge_includes(config: &mut ControllerConfig, config_path: &Path) -> eyre::Result<()> {
+    let root = fs::canonicalize(config_path).await.wrap_err(format!(
+        "Failed to resolve config file at: {}",
+        config_path.display()
+    ))?;
+    // Each entry carries the chain of files that led to it, to tell cycles from repeats.
+    let mut pending: Vec<(PathBuf, Vec<PathBuf>)> = config
+        .include
+        .iter()
+        .rev()
+        .map(|include| {
+            (
+                resolve_config_relative_paths(config_path, include),
+                vec![root.clone()],
+            )
+        })
+        .collect();
+
+    while let Some((path, mut chain)) = pending.pop() {
+        let canonical = fs::canonicalize(&path).await.wrap_err(format!(
+            "Failed to read included config file at: {}",
+            path.display()
+        ))?;
+        if chain.contains(&canonical) {
+            let cycle: Vec<String> = chain
+                .iter()
+                .chain([&canonical])
+                .map(|file| file.display().to_string())
+                .collect();
+            eyre::bail!("Circular include: {}", cycle.join(" -> "));
+        }
+        if config.included_files.contains(&canonical) {
+            eyre::bail!("{} is included more than once", path.display());
+        }
+
+        let included: IncludedConfig = parse_file(&path).await?;
+        for (name, host) in included.hosts {
+            match config.hosts.entry(name) {
+                Entry::Occupied(entry) => eyre::bail!(
+                    "Duplicate host key: '{}', included again from {}",
+                    entry.key(),
+                    path.display()
+                ),
+                Entry::Vacant(entry) => {
+                    entry.insert(host);
+                }
+            }
+        }
+        for (name, client) in included.clients {
+            match config.clients.entry(name) {
+                Entry::Occupied(entry) => eyre::bail!(
+                    "Duplicate client key: '{}', included again from {}",
+                    entry.key(),
+                    path.display()
+                ),
+                Entry::Vacant(entry) => {
+                    entry.insert(client);
+                }
+            }
+        }
+
+        chain.push(canonical.clone());
+        pending.extend(
+            included
+                .include
+                .iter()
+                .rev()
+                .map(|include| (resolve_config_relative_paths(&path, include), chain.clone())),
+        );
+        config.included_files.push(canonical);
+    }
+    Ok(())
+}
+
+/// Lists the secrets in `config` that start or end with whitespace.
+///
+/// Such whitespace is usually a copy-paste accident, and makes every signature check fail
+/// without a hint why.
+fn padded_secret_fields(config: &ControllerConfig) -> Vec<String> {
+    let is_padded = |secret: &SecretString| {
+        let secret = secret.expose_secret();
+        secret.trim() != secret
+    };
+
+    let mut fields: Vec<String> = config
+        .hosts
+        .iter()
+        .filter(|&(_, host)| is_padded(&host.shared_secret))
+        .map(|(name, _)| format!("hosts.{name}.shared_secret"))
+        .chain(
+            config
+                .clients
+                .iter()
+                .filter(|&(_, client)| is_padded(&client.shared_secret))
+                .map(|(name, _)| format!("clients.{name}.shared_secret")),
+        )
+        .collect();
+    fields.sort();
+
+    match config.server.auth.mode {
+        AuthMode::Token {
+            token: Some(ref token),
+        } if is_padded(token) => fields.push("server.auth.token.token".to_string()),
+        AuthMode::Oidc(ref oidc) if is_padded(&oidc.client_secret) => {
+            fields.push("server.auth.oidc.client_secret".to_string());
+        }
+        _ => {}
+    }
+    fields
+}
+
+/// Names the host or client behind a TOML "duplicate key" error.
+///
+/// The TOML parser already refuses keys defined twice, so a typo can't silently replace another
+/// host. Its message doesn't say which entry clashed though, so this recovers the key and the
+/// table it was defined in from the error span.
+fn describe_duplicate_key(content: &str, error: &de::Error) -> Option<String> {
+    if error.message() != "duplicate key" {
+        return None;
+    }
+    let span = error.span()?;
+    let key = content.get(span.clone())?.trim().trim_matches(['"', '\'']);
+    let line_start = content.get(..span.start)?.rfind('\n').map_or(0, |i| i + 1);
+    let before_key = content.get(line_start..span.start)?.trim_start();
+
+    let table = if let Some(header) = before_key.strip_prefix('[') {
+        header.trim().trim_end_matches('.').trim().to_string()
+    } else {
+        // A key/value line belongs to the closest table header above it.
+        let current_table = content
+            .get(..line_start)?
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find_map(|line| line.strip_prefix('[')?.split(']').next())
+            .map_or("", str::trim);
+        let dotted = before_key.trim_end_matches('.').trim();
+        [current_table, dotted]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+
+    match table.as_str() {
+        "hosts" => Some(format!("Duplicate host key: '{key}'")),
+        "clients" => Some(format!("Duplicate client key: '{key}'")),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::sync::Arc;
-    use std::{env, fs, path::PathBuf, process::Command};
+    use core::net::IpAddr;
+    use std::{
+        env, fs,
+        path::PathBuf,
+        process::{self, Command},
+    };
 
     use secrecy::{ExposeSecret as _, SecretString};
 
@@ -60,6 +255,7 @@ mod tests {
 
             [clients.bar]
             shared_secret = "s2"
+            max_leases = 2
         "#;
         let tmp = env::temp_dir().join("test_config.toml");
         fs::write(&tmp, toml_str).unwrap();
@@ -71,12 +267,398 @@ mod tests {
         );
         assert_eq!(cfg.server.bind, "0.0.0.0");
         let host = cfg.hosts.get("foo").unwrap();
-        assert_eq!(host.ip, "1.2.3.4");
-        assert_eq!(host.mac, "aa:aa:aa:aa:aa:aa");
+        assert_eq!(host.ip, IpAddr::from([1, 2, 3, 4]));
+        assert_eq!(host.mac, ["aa:aa:aa:aa:aa:aa"]);
         assert_eq!(host.port, 5678);
         assert_eq!((*host.shared_secret).expose_secret(), "s1");
         let client = cfg.clients.get("bar").unwrap();
         assert_eq!((*client.shared_secret).expose_secret(), "s2");
+        assert_eq!(client.max_leases, 2);
+    }
+
+    fn include_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("shuthost_include_{name}_{}", process::id()));
+        drop(fs::remove_dir_all(&dir));
+        fs::create_dir_all(dir.join("hosts.d")).unwrap();
+        dir
+    }
+
+    fn host_entry(name: &str) -> String {
+        format!(
+            "[hosts.{name}]\nip = \"1.2.3.4\"\nmac = \"aa:aa:aa:aa:aa:aa\"\nport = 5678\nshared_secret = \"s\"\n"
+        )
+    }
+
+    #[tokio::test]
+    async fn includes_are_merged() {
+        let dir = include_dir("merged");
+        fs::write(
+            dir.join("config.toml"),
+            format!(
+                "include = [\"hosts.d/lab.toml\"]\n[server]\n{}",
+                host_entry("main")
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("hosts.d/lab.toml"),
+            format!(
+                "include = [\"more.toml\"]\n{}[clients.ci]\nshared_secret = \"c\"\n",
+                host_entry("lab")
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("hosts.d/more.toml"), host_entry("more")).unwrap();
+
+        let cfg = load(dir.join("config.toml")).await.unwrap();
+        let canonical_dir = fs::canonicalize(&dir).unwrap();
+        drop(fs::remove_dir_all(&dir));
+        let mut hosts: Vec<_> = cfg.hosts.keys().map(String::as_str).collect();
+        hosts.sort_unstable();
+        assert_eq!(hosts, ["lab", "main", "more"]);
+        assert!(cfg.clients.contains_key("ci"));
+        assert_eq!(
+            cfg.included_files,
+            [
+                canonical_dir.join("hosts.d/lab.toml"),
+                canonical_dir.join("hosts.d/more.toml")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn include_errors_are_detected() {
+        let dir = include_dir("errors");
+        let main = dir.join("config.toml");
+        let load_err = |main_content: &str, included: &str| {
+            fs::write(&main, main_content).unwrap();
+            fs::write(dir.join("hosts.d/a.toml"), included).unwrap();
+            let main = main.clone();
+            async move { format!("{:#}", load(main).await.unwrap_err()) }
+        };
+
+        let circular = load_err(
+            "include = [\"hosts.d/a.toml\"]\n[server]\n",
+            "include = [\"../config.toml\"]\n",
+        )
+        .await;
+        assert!(circular.contains("Circular include"), "{circular}");
+
+        let twice = load_err(
+            "include = [\"hosts.d/a.toml\", \"hosts.d/../hosts.d/a.toml\"]\n[server]\n",
+            "",
+        )
+        .await;
+        assert!(twice.contains("included more than once"), "{twice}");
+
+        let duplicate = load_err(
+            &format!(
+                "include = [\"hosts.d/a.toml\"]\n[server]\n{}",
+                host_entry("nas")
+            ),
+            &host_entry("nas"),
+        )
+        .await;
+        assert!(
+            duplicate.contains("Duplicate host key: 'nas', included again from"),
+            "{duplicate}"
+        );
+
+        let server = load_err(
+            "include = [\"hosts.d/a.toml\"]\n[server]\n",
+            "[server]\nport = 1\n",
+        )
+        .await;
+        drop(fs::remove_dir_all(&dir));
+        assert!(server.contains("unknown field `server`"), "{server}");
+    }
+
+    #[test]
+    fn padded_secrets_are_reported() {
+        let config: ControllerConfig = toml::from_str(
+            r#"
+            [server]
+            port = 8080
+            bind = "127.0.0.1"
+
+            [server.auth.token]
+            token = "token\n"
+
+            [hosts.padded]
+            ip = "1.2.3.4"
+            mac = "aa:aa:aa:aa:aa:aa"
+            port = 5678
+            shared_secret = " abc123 "
+
+            [hosts.clean]
+            ip = "1.2.3.5"
+            mac = "aa:aa:aa:aa:aa:ab"
+            port = 5678
+            shared_secret = "a b"
+
+            [clients.ci]
+            shared_secret = "abc\t"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            padded_secret_fields(&config),
+            [
+                "clients.ci.shared_secret",
+                "hosts.padded.shared_secret",
+                "server.auth.token.token",
+            ]
+        );
+    }
+
+    #[test]
+    fn host_mac_accepts_list() {
+        let toml_str = r#"
+            [server]
+            port = 8080
+            bind = "127.0.0.1"
+
+            [hosts.bonded]
+            ip = "1.2.3.4"
+            mac = ["aa:aa:aa:aa:aa:aa", "bb:bb:bb:bb:bb:bb"]
+            port = 5678
+            shared_secret = "s1"
+
+            [clients]
+
+            [hosts.broken]
+            ip = "1.2.3.5"
+            mac = []
+            port = 5678
+            shared_secret = "s2"
+        "#;
+        let err = toml::from_str::<ControllerConfig>(toml_str).unwrap_err();
+        assert!(err.message().contains("at least one MAC address"), "{err}");
+
+        let (valid, _) = toml_str.split_once("[hosts.broken]").unwrap();
+        let cfg: ControllerConfig = toml::from_str(valid).unwrap();
+        assert_eq!(
+            cfg.hosts["bonded"].mac,
+            ["aa:aa:aa:aa:aa:aa", "bb:bb:bb:bb:bb:bb"]
+        );
+    }
+
+    #[test]
+    fn host_mac_is_validated() {
+        let config_with_mac = |mac: &str| {
+            format!(
+                r#"
+                [server]
+                port = 8080
+                bind = "127.0.0.1"
+
+                [hosts.foo]
+                ip = "1.2.3.4"
+                mac = {mac}
+                port = 5678
+                shared_secret = "s1"
+
+                [clients]
+            "#
+            )
+        };
+
+        let cfg: ControllerConfig =
+            toml::from_str(&config_with_mac(r#"["aa-bb-cc-dd-ee-ff", "disableWOL"]"#)).unwrap();
+        assert_eq!(cfg.hosts["foo"].mac, ["aa:bb:cc:dd:ee:ff", "disableWOL"]);
+
+        let err = toml::from_str::<ControllerConfig>(&config_with_mac(
+            r#"["aa:bb:cc:dd:ee:ff", "aa:bb:cc:dd:ee"]"#,
+        ))
+        .unwrap_err();
+        assert!(
+            err.message()
+                .contains("invalid MAC address 'aa:bb:cc:dd:ee': expected 6 octets, found 5"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn request_timeouts_have_a_minimum() {
+        let config_with_timeout = |line: &str| {
+            format!(
+                r#"
+                [server]
+                port = 8080
+                bind = "127.0.0.1"
+                {line}
+
+                [hosts]
+
+                [clients]
+            "#
+            )
+        };
+
+        let cfg: ControllerConfig = toml::from_str(&config_with_timeout("")).unwrap();
+        assert_eq!(cfg.server.request_timeout_secs, 30);
+        assert_eq!(cfg.server.m2m_request_timeout_secs, 300);
+        assert_eq!(cfg.server.shutdown_grace_period_secs, 30);
+        let cfg: ControllerConfig =
+            toml::from_str(&config_with_timeout("m2m_request_timeout_secs = 5")).unwrap();
+        assert_eq!(cfg.server.m2m_request_timeout_secs, 5);
+
+        for line in ["request_timeout_secs = 4", "m2m_request_timeout_secs = 0"] {
+            let err = toml::from_str::<ControllerConfig>(&config_with_timeout(line)).unwrap_err();
+            assert!(err.message().contains("must be at least 5s"), "{err}");
+        }
+    }
+
+    #[test]
+    fn wol_retry_interval_must_not_be_zero() {
+        let config = |interval: u64| {
+            format!(
+                r#"
+                [server]
+                port = 8080
+                bind = "127.0.0.1"
+
+                [server.runtime]
+                wol_retry_interval_secs = {interval}
+
+                [hosts]
+
+                [clients]
+            "#
+            )
+        };
+
+        let cfg: ControllerConfig = toml::from_str(&config(1)).unwrap();
+        assert_eq!(cfg.server.runtime.wol_retry_interval_secs, 1);
+        let err = toml::from_str::<ControllerConfig>(&config(0)).unwrap_err();
+        assert!(err.message().contains("must not be 0"), "{err}");
+    }
+
+    #[test]
+    fn zero_db_pool_size_is_rejected() {
+        let config = |pool_size: u32| {
+            format!(
+                r#"
+                [server]
+                port = 8080
+                bind = "127.0.0.1"
+
+                [db]
+                pool_size = {pool_size}
+
+                [hosts]
+
+                [clients]
+            "#
+            )
+        };
+
+        let cfg: ControllerConfig = toml::from_str(&config(1)).unwrap();
+        assert_eq!(cfg.db.unwrap().pool_size, 1);
+        let err = toml::from_str::<ControllerConfig>(&config(0)).unwrap_err();
+        assert!(err.message().contains("must not be 0"), "{err}");
+    }
+
+    #[test]
+    fn bind_wol_adds_to_wol_interfaces() {
+        let config_with_wol = |lines: &str| {
+            format!(
+                r#"
+                [server]
+                port = 8080
+                bind = "127.0.0.1"
+                {lines}
+
+                [hosts]
+
+                [clients]
+            "#
+            )
+        };
+        let addresses = |lines: &str| {
+            toml::from_str::<ControllerConfig>(&config_with_wol(lines))
+                .unwrap()
+                .server
+                .wol_bind_addresses()
+        };
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+
+        assert!(addresses("").is_empty());
+        assert_eq!(
+            addresses(r#"bind_wol = "192.168.1.2""#),
+            [ip("192.168.1.2")]
+        );
+        assert_eq!(
+            addresses("bind_wol = \"10.0.0.2\"\nwol_interfaces = [\"192.168.1.2\", \"10.0.0.2\"]"),
+            [ip("192.168.1.2"), ip("10.0.0.2")]
+        );
+    }
+
+    #[test]
+    fn host_tags_are_validated() {
+        let config_with_tags = |tags: &str| {
+            format!(
+                r#"
+                [server]
+                port = 8080
+                bind = "127.0.0.1"
+
+                [hosts.foo]
+                ip = "1.2.3.4"
+                mac = "aa:aa:aa:aa:aa:aa"
+                port = 5678
+                shared_secret = "s1"
+                tags = {tags}
+
+                [clients]
+            "#
+            )
+        };
+
+        let cfg: ControllerConfig =
+            toml::from_str(&config_with_tags(r#"["storage", "rack-2"]"#)).unwrap();
+        assert_eq!(cfg.hosts["foo"].tags, ["storage", "rack-2"]);
+
+        for invalid in [r#"["gpu box"]"#, r#"["gpu_box"]"#, r#"[""]"#] {
+            let err = toml::from_str::<ControllerConfig>(&config_with_tags(invalid)).unwrap_err();
+            assert!(err.message().contains("invalid tag"), "{err}");
+        }
+    }
+
+    #[test]
+    fn host_notes_are_limited_and_rendered() {
+        let config_with_notes = |notes: &str| {
+            format!(
+                r#"
+                [server]
+                port = 8080
+                bind = "127.0.0.1"
+
+                [hosts.foo]
+                ip = "1.2.3.4"
+                mac = "aa:aa:aa:aa:aa:aa"
+                port = 5678
+                shared_secret = "s1"
+                notes = {notes:?}
+
+                [clients]
+            "#
+            )
+        };
+
+        let cfg: ControllerConfig = toml::from_str(&config_with_notes(
+            "**GPU server**, rack 3 <script>alert(1)</script>",
+        ))
+        .unwrap();
+        assert_eq!(
+            cfg.hosts["foo"].notes_html().as_deref(),
+            Some("<p><strong>GPU server</strong>, rack 3 </p>\n")
+        );
+
+        toml::from_str::<ControllerConfig>(&config_with_notes(&"ä".repeat(500))).unwrap();
+        let err =
+            toml::from_str::<ControllerConfig>(&config_with_notes(&"a".repeat(501))).unwrap_err();
+        assert!(err.message().contains("must be at most 500"), "{err}");
     }
 
     #[tokio::test]
@@ -94,6 +676,32 @@ mod tests {
         assert!(res.is_err(), "Expected error for invalid TOML");
     }
 
+    #[tokio::test]
+    async fn load_coordinator_config_duplicate_keys() {
+        let cases = [
+            (
+                "[hosts.myserver]\nip = \"1.2.3.4\"\n[hosts.other]\nip = \"1.2.3.5\"\n[hosts.myserver]\nip = \"1.2.3.6\"\n",
+                "Duplicate host key: 'myserver'",
+            ),
+            (
+                "[hosts]\n[clients]\nlaptop = { shared_secret = \"a\" }\nlaptop = { shared_secret = \"b\" }\n",
+                "Duplicate client key: 'laptop'",
+            ),
+        ];
+        for (i, (toml_str, expected)) in cases.into_iter().enumerate() {
+            let tmp = env::temp_dir().join(format!("test_config_duplicate_{i}.toml"));
+            fs::write(
+                &tmp,
+                format!("[server]\nport = 8080\nbind = \"127.0.0.1\"\n{toml_str}"),
+            )
+            .unwrap();
+            let err = load(&tmp)
+                .await
+                .expect_err("Expected error for duplicate key");
+            assert!(format!("{err:#}").contains(expected), "{err:#}");
+        }
+    }
+
     #[tokio::test]
     async fn tls_absent_field_results_in_none() {
         let toml_str = r#"
@@ -250,13 +858,16 @@ mod tests {
             .hosts
             .get("my-host-name")
             .expect("host 'my-host-name' missing");
-        assert_eq!(host.ip, "192.168.1.100");
-        assert_eq!(host.mac, "AA:BB:CC:DD:EE:FF");
+        assert_eq!(host.ip, IpAddr::from([192, 168, 1, 100]));
+        assert_eq!(host.mac, ["aa:bb:cc:dd:ee:ff"]);
+        assert_eq!(host.wol_broadcast, Some(IpAddr::from([192, 168, 1, 255])));
         assert_eq!(host.port, 9090);
         assert_eq!(host.shared_secret.expose_secret(), "your-generated-secret");
         assert!(!host.enforce_state);
+        assert_eq!(host.tags, ["storage", "rack-2"]);
         assert_eq!(host.wake_timeout_secs, Some(120));
         assert_eq!(host.shutdown_timeout_secs, Some(20));
+        assert_eq!(host.transition_poll_interval_ms, Some(200));
 
         let pre = host.pre_startup.as_ref().expect("pre_startup hook missing");
         assert_eq!(
@@ -3,8 +3,11 @@
 //! This module provides a unified interface to all configuration-related functionality,
 //! including data types, loading utilities, and file watching capabilities.
 
+pub mod export;
+pub mod generate;
 mod loader;
 mod types;
+pub mod validate;
 
 pub(crate) use loader::*;
 pub(crate) use types::*;
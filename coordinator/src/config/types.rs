@@ -3,18 +3,22 @@
 //! This module contains all the data structures used for configuration,
 //! including host, client, server, TLS, and authentication settings.
 
-use alloc::sync::Arc;
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::{fmt::Display, net::IpAddr};
 use std::{
     collections::HashMap,
     path::{Component, Path, PathBuf},
 };
 
+use pulldown_cmark::{Parser, html};
 use reqwest::Method;
 use secrecy::{ExposeSecret as _, SecretString};
-use serde::{Deserialize, de};
+use serde::{Deserialize, Serialize, Serializer, de, ser::SerializeMap as _};
+
+use crate::cron::CronExpr;
 
 /// Action to execute as a pre-startup or post-shutdown hook.
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub(crate) enum HookAction {
     /// Directly execute a program with arguments — no shell involved.
@@ -28,10 +32,15 @@ pub(crate) enum HookAction {
     /// Send an HTTP request. See the example config for timing caveats with pre-startup hooks.
     Http {
         /// The URL to send the request to.
+        #[serde(serialize_with = "serialize_display")]
         url: reqwest::Url,
         /// HTTP method. Defaults to `POST` when omitted.
         /// Validated at parse time — an invalid method string is a configuration error.
-        #[serde(default = "POST", deserialize_with = "deserialize_http_method")]
+        #[serde(
+            default = "POST",
+            deserialize_with = "deserialize_http_method",
+            serialize_with = "serialize_display"
+        )]
         method: Method,
         /// Optional request body sent as a raw string.
         #[serde(default)]
@@ -40,7 +49,7 @@ pub(crate) enum HookAction {
 }
 
 /// Configuration for a single pre-startup or post-shutdown hook.
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub(crate) struct HookConfig {
     /// The action to execute.
     #[serde(flatten)]
@@ -67,23 +76,172 @@ where
     reqwest::Method::from_bytes(s.as_bytes()).map_err(de::Error::custom)
 }
 
+/// Deserializes one MAC address or a list of them, for hosts with several (e.g. bonded) NICs.
+///
+/// Every address except the `disableWOL` marker must be accepted by
+/// [`shuthost_common::parse_mac`], and is normalized to lowercase, colon-separated octets.
+fn deserialize_macs<'de, D>(de: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    let macs = match OneOrMany::deserialize(de)? {
+        OneOrMany::One(mac) => vec![mac],
+        OneOrMany::Many(macs) if macs.is_empty() => {
+            return Err(de::Error::custom("expected at least one MAC address"));
+        }
+        OneOrMany::Many(macs) => macs,
+    };
+    macs.into_iter()
+        .map(|mac| {
+            if mac.eq_ignore_ascii_case("disablewol") {
+                Ok(mac)
+            } else {
+                shuthost_common::normalize_mac(&mac).map_err(de::Error::custom)
+            }
+        })
+        .collect()
+}
+
+/// Deserializes host tags, which may only contain ASCII letters, digits and hyphens.
+fn deserialize_tags<'de, D>(de: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let tags = Vec::<String>::deserialize(de)?;
+    if let Some(tag) = tags
+        .iter()
+        .find(|tag| tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+    {
+        return Err(de::Error::custom(format!(
+            "invalid tag '{tag}': only letters, digits and hyphens are allowed"
+        )));
+    }
+    Ok(tags)
+}
+
+/// Longest accepted host notes, in characters.
+const MAX_NOTES_CHARS: usize = 500;
+
+/// Deserializes host notes of at most [`MAX_NOTES_CHARS`] characters.
+fn deserialize_notes<'de, D>(de: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let notes = Option::<String>::deserialize(de)?;
+    if let Some(ref notes) = notes {
+        let chars = notes.chars().count();
+        if chars > MAX_NOTES_CHARS {
+            return Err(de::Error::custom(format!(
+                "notes are {chars} characters long: must be at most {MAX_NOTES_CHARS}"
+            )));
+        }
+    }
+    Ok(notes)
+}
+
+/// Smallest accepted HTTP request timeout, so a typo can't make every request fail.
+const MIN_REQUEST_TIMEOUT_SECS: u64 = 5;
+
+/// Deserializes a request timeout in seconds of at least [`MIN_REQUEST_TIMEOUT_SECS`].
+fn deserialize_request_timeout<'de, D>(de: D) -> Result<u64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let secs = u64::deserialize(de)?;
+    if secs < MIN_REQUEST_TIMEOUT_SECS {
+        return Err(de::Error::custom(format!(
+            "request timeout of {secs}s is too short: must be at least {MIN_REQUEST_TIMEOUT_SECS}s"
+        )));
+    }
+    Ok(secs)
+}
+
+/// Deserializes a number that must not be 0, e.g. an interval that would otherwise make a loop spin.
+fn deserialize_nonzero<'de, D, T>(de: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + Default + PartialEq,
+{
+    let value = T::deserialize(de)?;
+    if value == T::default() {
+        return Err(de::Error::custom("must not be 0"));
+    }
+    Ok(value)
+}
+
+/// Written in place of secrets when serializing the config, e.g. for `export-config`.
+pub(crate) const REDACTED: &str = "<redacted>";
+
+fn serialize_redacted<S: Serializer>(_: &Arc<SecretString>, ser: S) -> Result<S::Ok, S::Error> {
+    ser.serialize_str(REDACTED)
+}
+
+#[expect(
+    clippy::ref_option,
+    reason = "serialize_with passes a reference to the field"
+)]
+fn serialize_redacted_option<S: Serializer>(
+    secret: &Option<Arc<SecretString>>,
+    ser: S,
+) -> Result<S::Ok, S::Error> {
+    match *secret {
+        Some(_) => ser.serialize_some(REDACTED),
+        None => ser.serialize_none(),
+    }
+}
+
+fn serialize_redacted_values<S: Serializer>(
+    map: &HashMap<String, Arc<SecretString>>,
+    ser: S,
+) -> Result<S::Ok, S::Error> {
+    let sorted: BTreeMap<_, _> = map.keys().map(|key| (key, REDACTED)).collect();
+    sorted.serialize(ser)
+}
+
+/// Serializes a map sorted by key, so the output is stable.
+fn serialize_sorted<S: Serializer, V: Serialize>(
+    map: &HashMap<String, V>,
+    ser: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(ser)
+}
+
+fn serialize_display<S: Serializer>(value: &impl Display, ser: S) -> Result<S::Ok, S::Error> {
+    ser.collect_str(value)
+}
+
 const fn default_hook_timeout_secs() -> u64 {
     30
 }
 
 /// Represents a configured host entry with network and security parameters.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub(crate) struct Host {
-    /// IP address of the host agent.
-    pub ip: String,
-    /// MAC address of the host agent's network interface, required for WOL.
+    /// IP address of the host agent (IPv4 or IPv6).
+    pub ip: IpAddr,
+    /// MAC addresses of the host agent's network interfaces, required for WOL.
+    /// Accepts a single address or a list; a magic packet is sent to each of them.
+    /// Addresses are stored as lowercase, colon-separated octets, whatever the config used.
     /// There is an undocumented feature where setting this to disableWOL disables waking per WOL.
     /// In the future we may offer alternative wake options, then this will be documented,
     /// as of now this is primarily for tests
-    pub mac: String,
+    #[serde(deserialize_with = "deserialize_macs")]
+    pub mac: Vec<String>,
+    /// Destination for Wake-on-LAN packets, e.g. the subnet's directed broadcast address.
+    /// When `None`, the limited broadcast (or all-nodes multicast for IPv6) is used.
+    #[serde(default)]
+    pub wol_broadcast: Option<IpAddr>,
     /// TCP port the host agent listens on.
     pub port: u16,
     /// Shared secret for HMAC authentication.
+    #[serde(serialize_with = "serialize_redacted")]
     pub shared_secret: Arc<SecretString>,
     /// When `true`, the coordinator will periodically enforce the desired host state
     /// (derived from the current lease set) by sending wake or shutdown commands even
@@ -98,45 +256,83 @@ pub(crate) struct Host {
     /// When `None`, the runtime-configured default shutdown timeout is used.
     #[serde(default)]
     pub shutdown_timeout_secs: Option<u64>,
+    /// Interval in milliseconds between state checks during a wake/shutdown transition.
+    /// When `None`, the runtime-configured transition poll interval is used.
+    #[serde(default)]
+    pub transition_poll_interval_ms: Option<u64>,
     /// Optional hook to execute before sending the wake-on-LAN packet.
     #[serde(default)]
     pub pre_startup: Option<HookConfig>,
     /// Optional hook to execute after the host is confirmed offline.
     #[serde(default)]
     pub post_shutdown: Option<HookConfig>,
+    /// Labels for grouping hosts by role, e.g. `storage` or `gpu`.
+    #[serde(default, deserialize_with = "deserialize_tags")]
+    pub tags: Vec<String>,
+    /// Markdown shown with the host in the web UI, e.g. its location or owner.
+    #[serde(default, deserialize_with = "deserialize_notes")]
+    pub notes: Option<String>,
+}
+
+impl Host {
+    /// Whether waking via WOL was disabled by setting the MAC to `disableWOL`.
+    pub(crate) fn wol_disabled(&self) -> bool {
+        self.mac
+            .iter()
+            .any(|mac| mac.eq_ignore_ascii_case("disablewol"))
+    }
+
+    /// The notes rendered to HTML, with anything but basic formatting and links stripped.
+    pub(crate) fn notes_html(&self) -> Option<String> {
+        self.notes.as_deref().map(|notes| {
+            let mut unsafe_html = String::new();
+            html::push_html(&mut unsafe_html, Parser::new(notes));
+            ammonia::clean(&unsafe_html)
+        })
+    }
 }
 
 impl PartialEq for Host {
     fn eq(&self, other: &Self) -> bool {
         self.ip == other.ip
             && self.mac == other.mac
+            && self.wol_broadcast == other.wol_broadcast
             && self.port == other.port
             && self.enforce_state == other.enforce_state
             && self.wake_timeout_secs == other.wake_timeout_secs
             && self.shutdown_timeout_secs == other.shutdown_timeout_secs
+            && self.transition_poll_interval_ms == other.transition_poll_interval_ms
             && self.shared_secret.expose_secret() == other.shared_secret.expose_secret()
             && self.pre_startup == other.pre_startup
             && self.post_shutdown == other.post_shutdown
+            && self.tags == other.tags
+            && self.notes == other.notes
     }
 }
 
 /// Configuration for a client with its shared secret.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub(crate) struct Client {
     /// Shared secret used for authenticating callbacks.
+    #[serde(serialize_with = "serialize_redacted")]
     pub shared_secret: Arc<SecretString>,
+    /// Maximum number of hosts this client may hold leases on at the same time.
+    /// `0` (the default) means unlimited.
+    #[serde(default)]
+    pub max_leases: u32,
 }
 
 impl PartialEq for Client {
     fn eq(&self, other: &Self) -> bool {
         self.shared_secret.expose_secret() == other.shared_secret.expose_secret()
+            && self.max_leases == other.max_leases
     }
 }
 
 /// Runtime tuning parameters for the coordinator.
 ///
 /// These are read once at startup (restart required to change them).
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(default)]
 pub(crate) struct RuntimeConfig {
     /// Default seconds to wait for a host to come online after sending `WoL` packets.
@@ -148,10 +344,17 @@ pub(crate) struct RuntimeConfig {
     /// Interval in seconds between background host-status poll cycles.
     pub status_poll_interval_secs: u64,
     /// Interval in milliseconds between state checks during a wake/shutdown transition.
+    /// Can be overridden per host with `transition_poll_interval_ms`.
     pub transition_poll_interval_ms: u64,
     /// Seconds a diverged enforced-host state must be stable before the enforcer
     /// re-triggers a wake / shutdown (prevents hammering during transitions).
     pub enforce_stabilization_threshold_secs: u64,
+    /// Seconds before the first `WoL` re-send while a woken host isn't online yet. The interval
+    /// doubles after every re-send.
+    #[serde(deserialize_with = "deserialize_nonzero")]
+    pub wol_retry_interval_secs: u64,
+    /// Maximum number of `WoL` re-sends per wake transition.
+    pub wol_max_retries: u32,
 }
 
 impl Default for RuntimeConfig {
@@ -162,12 +365,14 @@ impl Default for RuntimeConfig {
             status_poll_interval_secs: 2,
             transition_poll_interval_ms: 200,
             enforce_stabilization_threshold_secs: 5,
+            wol_retry_interval_secs: 15,
+            wol_max_retries: 4,
         }
     }
 }
 
 /// HTTP server binding configuration section.
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(default)]
 pub(crate) struct ServerConfig {
     /// TCP port for the web control service.
@@ -179,42 +384,136 @@ pub(crate) struct ServerConfig {
     /// Optional TLS configuration for serving HTTPS.
     pub tls: Option<TlsConfig>,
     /// Authentication configuration (defaults to no auth when omitted)
+    #[serde(skip_serializing_if = "AuthConfig::is_none")]
     pub auth: AuthConfig,
     /// Runtime tuning parameters (poll intervals, default timeouts, etc.).
     pub runtime: RuntimeConfig,
     /// When `false`, disables the periodic GitHub release check. Defaults to `true`.
     pub check_for_updates: bool,
+    /// Sustained number of M2M requests per second allowed per client. `0` disables rate limiting.
+    pub m2m_rate_limit_rps: u32,
+    /// Number of M2M requests a client may send in a burst before being rate limited.
+    pub m2m_rate_limit_burst: u32,
+    /// Maximum clock skew in seconds accepted on HMAC-signed messages from clients and agents.
+    pub hmac_tolerance_secs: u64,
+    /// Number of recent M2M signature checks to cache. `0` disables the cache.
+    pub hmac_cache_size: usize,
+    /// Rejects M2M requests whose signed message was already accepted within the tolerance
+    /// window. Replaces the signature cache, as identical requests are then rejected anyway.
+    pub m2m_replay_protection: bool,
+    /// Optional append-only audit log of lease and host control events.
+    pub audit_log: Option<AuditLogConfig>,
+    /// Local interface addresses to send Wake-on-LAN packets from. Empty uses the default route.
+    pub wol_interfaces: Vec<IpAddr>,
+    /// Single local address to send Wake-on-LAN packets from, in addition to `wol_interfaces`.
+    pub bind_wol: Option<IpAddr>,
+    /// Optional Prometheus metrics endpoint.
+    pub metrics: Option<MetricsConfig>,
+    /// Maximum number of simultaneous `WebUI` WebSocket connections. `None` or `0` means unlimited.
+    pub max_connections: Option<usize>,
+    /// Named schedules, each taking or releasing a lease on hosts when its cron expressions fire.
+    #[serde(serialize_with = "serialize_sorted")]
+    pub schedules: HashMap<String, Vec<ScheduleEntry>>,
+    /// Seconds after which a request is answered with 408 Request Timeout.
+    #[serde(deserialize_with = "deserialize_request_timeout")]
+    pub request_timeout_secs: u64,
+    /// Like `request_timeout_secs`, but for the M2M API, whose synchronous lease requests wait
+    /// for the host to boot or shut down.
+    #[serde(deserialize_with = "deserialize_request_timeout")]
+    pub m2m_request_timeout_secs: u64,
+    /// Seconds in-flight requests may still take to complete after a shutdown signal, before
+    /// their connections are closed.
+    pub shutdown_grace_period_secs: u64,
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
-            port: 8080,
+            port: shuthost_common::DEFAULT_COORDINATOR_PORT,
             bind: "127.0.0.1".to_string(),
             broadcast_port: shuthost_common::DEFAULT_COORDINATOR_BROADCAST_PORT,
             tls: None,
             auth: AuthConfig::default(),
             runtime: RuntimeConfig::default(),
             check_for_updates: true,
+            m2m_rate_limit_rps: 10,
+            m2m_rate_limit_burst: 20,
+            hmac_tolerance_secs: shuthost_common::ALLOWED_WINDOW,
+            hmac_cache_size: 1024,
+            m2m_replay_protection: false,
+            audit_log: None,
+            wol_interfaces: Vec::new(),
+            bind_wol: None,
+            metrics: None,
+            max_connections: None,
+            schedules: HashMap::new(),
+            request_timeout_secs: 30,
+            m2m_request_timeout_secs: 300,
+            shutdown_grace_period_secs: 30,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Local addresses Wake-on-LAN packets are sent from, i.e. `wol_interfaces` plus `bind_wol`.
+    /// Empty uses the default route.
+    pub(crate) fn wol_bind_addresses(&self) -> Vec<IpAddr> {
+        let mut addresses = self.wol_interfaces.clone();
+        if let Some(bind_wol) = self.bind_wol
+            && !addresses.contains(&bind_wol)
+        {
+            addresses.push(bind_wol);
         }
+        addresses
     }
 }
 
+/// Lease action for lease endpoints (shared between web and m2m) and schedules.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LeaseAction {
+    Take,
+    Release,
+}
+
+/// One trigger of a schedule in `[server.schedules]`.
+///
+/// The lease is held by [`LeaseSource::Schedule`](crate::app::LeaseSource::Schedule) named
+/// after the schedule, so a `take` and a `release` entry of the same schedule form a window.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub(crate) struct ScheduleEntry {
+    /// Host the lease is taken or released on.
+    pub host: String,
+    /// When the action runs, evaluated in the coordinator's local time zone.
+    pub cron: CronExpr,
+    /// Whether the lease is taken or released when `cron` fires.
+    pub action: LeaseAction,
+}
+
 /// TLS configuration for the HTTP server.
 ///
 /// Paths in the config are interpreted relative to the config file when not absolute.
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(default)]
 pub(crate) struct TlsConfig {
-    /// Optional path to a certificate PEM file. If present, enables TLS when paired with `key_path`.
+    /// Optional path to a certificate PEM file, or to the bundle in `pkcs12` format.
+    /// If present, enables TLS when paired with `key_path`.
     pub cert_path: String,
 
     /// Optional path to a private key PEM file. If present, enables TLS when paired with `cert_path`.
+    /// Unused in `pkcs12` format.
     pub key_path: String,
 
+    /// Format of the certificate files.
+    pub format: TlsFormat,
+
+    /// Password protecting the PKCS#12 bundle, if any.
+    #[serde(serialize_with = "serialize_redacted_option")]
+    pub pkcs12_password: Option<Arc<SecretString>>,
+
     /// When true (default), if no cert/key are provided a self-signed
     /// certificate will be generated and written next to the coordinator
-    /// config so it persists across restarts.
+    /// config so it persists across restarts. Only supported in `pem` format.
     pub persist_self_signed: bool,
     /// Whether TLS is enabled. When false the server will serve plain HTTP even if the
     /// `tls` table is present. Defaults to true.
@@ -226,14 +525,44 @@ impl Default for TlsConfig {
         Self {
             cert_path: "./tls_cert.pem".to_string(),
             key_path: "./tls_key.pem".to_string(),
+            format: TlsFormat::Pem,
+            pkcs12_password: None,
             persist_self_signed: true,
             enable: true,
         }
     }
 }
 
+impl PartialEq for TlsConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.cert_path == other.cert_path
+            && self.key_path == other.key_path
+            && self.format == other.format
+            && match (&self.pkcs12_password, &other.pkcs12_password) {
+                (&Some(ref p1), &Some(ref p2)) => p1.expose_secret() == p2.expose_secret(),
+                (&None, &None) => true,
+                _ => false,
+            }
+            && self.persist_self_signed == other.persist_self_signed
+            && self.enable == other.enable
+    }
+}
+
+/// Format of the files configured in `[server.tls]`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TlsFormat {
+    /// PEM certificate chain in `cert_path` and private key in `key_path`.
+    Pem,
+    /// PKCS#12 (`.p12`/`.pfx`) bundle in `cert_path` holding the certificate chain and key.
+    Pkcs12,
+}
+
+/// Default for [`DbConfig::pool_size`].
+pub(crate) const DEFAULT_DB_POOL_SIZE: u32 = 4;
+
 /// Configuration for an optional local `SQLite` database.
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(default)]
 pub(crate) struct DbConfig {
     /// Path to the `SQLite` database file. Relative paths are resolved relative to the config file.
@@ -241,6 +570,9 @@ pub(crate) struct DbConfig {
     /// Whether the local DB is enabled. When false the coordinator will act as if
     /// no DB is configured even if this table exists in the config file.
     pub enable: bool,
+    /// Maximum number of simultaneous database connections.
+    #[serde(deserialize_with = "deserialize_nonzero")]
+    pub pool_size: u32,
 }
 
 impl Default for DbConfig {
@@ -248,6 +580,69 @@ impl Default for DbConfig {
         Self {
             path: "./shuthost.db".to_string(),
             enable: true,
+            pool_size: DEFAULT_DB_POOL_SIZE,
+        }
+    }
+}
+
+/// Configuration for the JSON audit log.
+///
+/// Paths in the config are interpreted relative to the config file when not absolute.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub(crate) struct AuditLogConfig {
+    /// Path to the audit log file. The file is created if missing, existing records are never
+    /// changed.
+    pub path: String,
+    /// Serialization of the individual records.
+    pub format: AuditLogFormat,
+    /// Whether the audit log is enabled. When false no audit log is written even if
+    /// this table exists in the config file.
+    #[serde(alias = "enabled")]
+    pub enable: bool,
+}
+
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        Self {
+            path: "./audit.log".to_string(),
+            format: AuditLogFormat::default(),
+            enable: true,
+        }
+    }
+}
+
+/// Output format of the audit log.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AuditLogFormat {
+    /// A JSON array with every record on a line of its own.
+    Json,
+    /// One compact JSON object per line.
+    #[default]
+    Ndjson,
+}
+
+/// Configuration for the Prometheus metrics endpoint.
+///
+/// The endpoint is served without authentication so scrapers can reach it.
+/// Read once at startup (restart required to change it).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub(crate) struct MetricsConfig {
+    /// Route the metrics are served on.
+    pub path: String,
+    /// Whether the endpoint is enabled. When false no route is added even if
+    /// this table exists in the config file.
+    #[serde(alias = "enabled")]
+    pub enable: bool,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            path: "/metrics".to_string(),
+            enable: true,
         }
     }
 }
@@ -308,11 +703,12 @@ fn normalize_path(path: &Path) -> PathBuf {
 
 /// Stored OIDC configuration. Keeping this around allows the runtime to
 /// rebuild the client if discovery needs to be retried - e.g. on JWKS failure.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub(crate) struct OidcConfig {
     pub issuer: String,
     #[serde(default = "default_oidc_client_id")]
     pub client_id: String,
+    #[serde(serialize_with = "serialize_redacted")]
     pub client_secret: Arc<SecretString>,
     #[serde(default = "default_oidc_scopes")]
     pub scopes: Vec<String>,
@@ -369,6 +765,38 @@ impl PartialEq for AuthMode {
     }
 }
 
+/// Serialized as a map with at most one entry, as `AuthConfig` flattens the mode and serde
+/// cannot flatten the unit variant `None`.
+impl Serialize for AuthMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Token {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            token: Option<&'static str>,
+        }
+        #[derive(Serialize)]
+        struct External {
+            exceptions_version: u32,
+        }
+
+        let mut map = serializer.serialize_map(None)?;
+        match *self {
+            Self::None => {}
+            Self::Token { ref token } => map.serialize_entry(
+                "token",
+                &Token {
+                    token: token.as_ref().map(|_| REDACTED),
+                },
+            )?,
+            Self::Oidc(ref oidc) => map.serialize_entry("oidc", oidc)?,
+            Self::External { exceptions_version } => {
+                map.serialize_entry("external", &External { exceptions_version })?;
+            }
+        }
+        map.end()
+    }
+}
+
 // Defaults for OIDC fields used by serde(default = ...)
 fn default_oidc_scopes() -> Vec<String> {
     vec!["openid".to_string(), "profile".to_string()]
@@ -379,15 +807,22 @@ fn default_oidc_client_id() -> String {
 }
 
 /// Authentication configuration wrapper
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub(crate) struct AuthConfig {
     #[serde(flatten)]
     pub mode: AuthMode,
     /// Optional base64-encoded cookie key (32 bytes). If omitted, a random key is generated and persisted to database if available.
-    #[serde(default)]
+    #[serde(default, serialize_with = "serialize_redacted_option")]
     pub cookie_secret: Option<Arc<SecretString>>,
 }
 
+impl AuthConfig {
+    /// Whether no auth is configured, which is only expressible by omitting the table.
+    const fn is_none(&self) -> bool {
+        matches!(self.mode, AuthMode::None)
+    }
+}
+
 impl PartialEq for AuthConfig {
     fn eq(&self, other: &Self) -> bool {
         self.mode == other.mode && {
@@ -401,7 +836,7 @@ impl PartialEq for AuthConfig {
 }
 
 /// A simple (string) event filter that matches all hosts for that event type.
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum SimpleEventFilter {
     Unscheduled,
@@ -412,7 +847,7 @@ pub(crate) type Hosts = Option<Vec<String>>;
 
 /// A structured (table) event filter, allowing host scoping and carrying
 /// extra data for event types that need it (e.g. `OnlineFor`).
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub(crate) enum StructuredEventFilter {
     Unscheduled {
@@ -436,7 +871,7 @@ pub(crate) enum StructuredEventFilter {
 /// Serde tries `Simple` first (string match) and falls back to `Structured` (table).
 /// Note: `#[serde(untagged)]` produces poor error messages on typos — acceptable for
 /// a config file where the operator can inspect the file directly.
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(untagged)]
 pub(crate) enum WebhookEventFilter {
     /// Plain string shorthand for events that don't require extra data and match all hosts.
@@ -446,7 +881,7 @@ pub(crate) enum WebhookEventFilter {
 }
 
 /// Configuration for a single webhook endpoint.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub(crate) struct WebhookConfig {
     /// The URL to POST notifications to.
     pub url: String,
@@ -459,11 +894,12 @@ pub(crate) struct WebhookConfig {
     #[serde(default)]
     pub events: Option<Vec<WebhookEventFilter>>,
     /// Optional extra HTTP headers (e.g. `Authorization = "Bearer token"`).
-    #[serde(default)]
+    #[serde(default, serialize_with = "serialize_redacted_values")]
     pub headers: HashMap<String, Arc<SecretString>>,
     /// Optional shared secret. When set, each POST includes an
     /// `X-ShutHost-Signature: sha256=<hex>` header containing the HMAC-SHA256
     /// of the raw JSON body, signed with this secret.
+    #[serde(serialize_with = "serialize_redacted_option")]
     pub secret: Option<Arc<SecretString>>,
 }
 
@@ -487,7 +923,7 @@ impl PartialEq for WebhookConfig {
 }
 
 /// Top-level notifications configuration block.
-#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
 #[serde(default)]
 pub(crate) struct NotificationsConfig {
     /// List of webhook endpoints to fire on notification events.
@@ -496,13 +932,24 @@ pub(crate) struct NotificationsConfig {
 
 /// Root config structure for the coordinator, including server settings, hosts, and clients.
 /// ```
-#[derive(Debug, Deserialize, Default, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
 pub(crate) struct ControllerConfig {
+    /// Further config files whose `[hosts]` and `[clients]` are merged into this config, e.g.
+    /// a host list managed by configuration management. Relative paths are resolved relative to
+    /// the including file.
+    #[serde(default, skip_serializing)]
+    pub include: Vec<String>,
+    /// Resolved paths of all files merged in through `include`, including nested ones.
+    /// Filled in by the loader.
+    #[serde(skip)]
+    pub included_files: Vec<PathBuf>,
     /// HTTP server binding configuration.
     pub server: ServerConfig,
     /// Map of host identifiers to host configurations.
+    #[serde(default, serialize_with = "serialize_sorted")]
     pub hosts: HashMap<String, Host>,
     /// Map of client identifiers to client configurations.
+    #[serde(default, serialize_with = "serialize_sorted")]
     pub clients: HashMap<String, Client>,
     /// Optional top-level database configuration. When omitted DB persistence is disabled.
     #[serde(default)]
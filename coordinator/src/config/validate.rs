@@ -0,0 +1,239 @@
+//! Offline validation of a coordinator config file, e.g. for CI or pre-commit hooks.
+//!
+//! Only the file and the TLS files it references are inspected. Nothing is bound, no host is
+//! contacted and the OIDC issuer is not queried.
+
+use core::net::IpAddr;
+use std::{fs, path::Path};
+
+use eyre::WrapErr as _;
+use secrecy::ExposeSecret as _;
+
+use crate::{
+    config::{ControllerConfig, TlsFormat, load, resolve_config_relative_paths},
+    http::tls::parse_pkcs12,
+};
+
+/// Loads the config at `config` and reports every problem found.
+///
+/// Prints `Config valid` on success, or one line per problem on stderr.
+///
+/// # Errors
+///
+/// Returns `Err` if the file cannot be read or parsed, or if any check failed.
+pub(crate) async fn run(config: &str) -> eyre::Result<()> {
+    let config_path = Path::new(config);
+    let parsed = load(config_path)
+        .await
+        .wrap_err(format!("Config invalid: {config}"))?;
+
+    let errors = check(&parsed, config_path);
+    if errors.is_empty() {
+        println!("Config valid");
+        return Ok(());
+    }
+    for error in &errors {
+        eprintln!("- {error}");
+    }
+    eyre::bail!(
+        "Config invalid: found {} error(s) in {config}",
+        errors.len()
+    )
+}
+
+/// Runs the checks that go beyond parsing, returning one message per problem.
+fn check(config: &ControllerConfig, config_path: &Path) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if config.server.bind.parse::<IpAddr>().is_err() {
+        errors.push(format!(
+            "server.bind '{}' is not a valid IP address",
+            config.server.bind
+        ));
+    }
+
+    if let Some(ref tls) = config.server.tls
+        && tls.enable
+        && tls.format == TlsFormat::Pkcs12
+    {
+        let path = resolve_config_relative_paths(config_path, &tls.cert_path);
+        let password = tls
+            .pkcs12_password
+            .as_ref()
+            .map_or("", |password| password.expose_secret());
+        match fs::read(&path) {
+            Ok(data) => {
+                if let Err(e) = parse_pkcs12(&data, password) {
+                    errors.push(format!(
+                        "server.tls: cert_path '{}' is not a usable PKCS#12 bundle: {e:#}",
+                        tls.cert_path
+                    ));
+                }
+            }
+            Err(e) => errors.push(format!(
+                "server.tls: cert_path '{}' can't be read: {e}",
+                tls.cert_path
+            )),
+        }
+    } else if let Some(ref tls) = config.server.tls
+        && tls.enable
+    {
+        let cert_exists = resolve_config_relative_paths(config_path, &tls.cert_path).exists();
+        let key_exists = resolve_config_relative_paths(config_path, &tls.key_path).exists();
+        match (cert_exists, key_exists) {
+            (true, true) => {}
+            (false, false) if tls.persist_self_signed => {}
+            (false, false) => errors.push(format!(
+                "server.tls: cert_path '{}' and key_path '{}' don't exist and persist_self_signed is disabled",
+                tls.cert_path, tls.key_path
+            )),
+            (true, false) => errors.push(format!(
+                "server.tls: key_path '{}' doesn't exist, but cert_path does",
+                tls.key_path
+            )),
+            (false, true) => errors.push(format!(
+                "server.tls: cert_path '{}' doesn't exist, but key_path does",
+                tls.cert_path
+            )),
+        }
+    }
+
+    let mut schedules: Vec<_> = config.server.schedules.iter().collect();
+    schedules.sort_unstable_by_key(|&(name, _)| name);
+    for (name, entries) in schedules {
+        for entry in entries {
+            if !config.hosts.contains_key(&entry.host) {
+                errors.push(format!(
+                    "server.schedules.{name}: host '{}' is not configured",
+                    entry.host
+                ));
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    fn parse(toml_str: &str) -> ControllerConfig {
+        toml::from_str(toml_str).unwrap()
+    }
+
+    #[test]
+    fn valid_config_has_no_errors() {
+        let config = parse(
+            r#"
+            [server]
+            port = 8080
+            bind = "127.0.0.1"
+
+            [server.tls]
+
+            [hosts.a]
+            ip = "1.2.3.4"
+            mac = ["aa:bb:cc:dd:ee:ff", "AA:BB:CC:DD:EE:00"]
+            port = 5757
+            shared_secret = "s"
+
+            [hosts.b]
+            ip = "1.2.3.5"
+            mac = "disableWOL"
+            port = 5757
+            shared_secret = "s"
+
+            [clients]
+        "#,
+        );
+        assert_eq!(
+            check(&config, Path::new("/nonexistent/config.toml")),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let dir = env::temp_dir().join(format!("shuthost_validate_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("cert.pem"), "").unwrap();
+        let config = parse(
+            r#"
+            [server]
+            port = 8080
+            bind = "localhost"
+
+            [server.tls]
+            cert_path = "cert.pem"
+            key_path = "key.pem"
+
+            [[server.schedules.nightly]]
+            host = "nas"
+            cron = "0 2 * * *"
+            action = "take"
+
+            [hosts]
+
+            [clients]
+        "#,
+        );
+
+        let errors = check(&config, &dir.join("config.toml"));
+        drop(fs::remove_dir_all(&dir));
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert!(errors[0].contains("server.bind 'localhost'"));
+        assert!(errors[1].contains("key_path 'key.pem' doesn't exist"));
+        assert!(errors[2].contains("server.schedules.nightly: host 'nas'"));
+    }
+
+    #[test]
+    fn pkcs12_bundle_must_decrypt() {
+        let dir = env::temp_dir().join(format!("shuthost_validate_p12_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("bundle.p12"), "not a bundle").unwrap();
+        let config = parse(
+            r#"
+            [server]
+            port = 8080
+            bind = "::1"
+
+            [server.tls]
+            format = "pkcs12"
+            cert_path = "bundle.p12"
+            pkcs12_password = "secret"
+
+            [hosts]
+
+            [clients]
+        "#,
+        );
+        let errors = check(&config, &dir.join("config.toml"));
+        drop(fs::remove_dir_all(&dir));
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].contains("not a usable PKCS#12 bundle"));
+    }
+
+    #[test]
+    fn missing_tls_files_need_self_signed() {
+        let config = parse(
+            r#"
+            [server]
+            port = 8080
+            bind = "::1"
+
+            [server.tls]
+            persist_self_signed = false
+
+            [hosts]
+
+            [clients]
+        "#,
+        );
+        let errors = check(&config, Path::new("/nonexistent/config.toml"));
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].contains("persist_self_signed is disabled"));
+    }
+}
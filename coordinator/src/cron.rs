@@ -0,0 +1,277 @@
+//! Minimal cron expressions for the lease schedules in `[server.schedules]`.
+//!
+//! Supports the classic five fields (minute, hour, day of month, month, day of week) with `*`,
+//! single values, ranges (`1-5`), lists (`1,3,5`) and steps (`*/15`, `0-30/10`). Day of week
+//! counts from Sunday (`0`, or `7`). Names like `MON` and macros like `@daily` are not supported.
+//!
+//! As in Vixie cron, a day that matches either the day of month or the day of week matches when
+//! both fields are restricted.
+
+use core::{fmt, str::FromStr, time::Duration};
+
+use chrono::{DateTime, Datelike as _, TimeDelta, TimeZone, Timelike as _};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use thiserror::Error as ThisError;
+
+/// Reasons a cron expression is rejected.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub(crate) enum CronParseError {
+    #[error("invalid cron expression '{expr}': expected 5 fields, found {found}")]
+    FieldCount { expr: String, found: usize },
+    #[error("invalid cron expression '{expr}': '{value}' is not a valid {field}")]
+    InvalidField {
+        expr: String,
+        field: &'static str,
+        value: String,
+    },
+}
+
+/// A parsed cron expression, matched against local time by the scheduler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CronExpr {
+    /// The expression as written, used for display and serialization.
+    source: String,
+    /// Bit `n` is set if the value `n` matches, for each of the five fields.
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether the day fields are something other than `*`, which changes how they combine.
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+impl CronExpr {
+    /// Whether the expression fires in the minute containing `time`.
+    pub(crate) fn matches<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        let is_set = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day_of_month = is_set(self.days_of_month, time.day());
+        let day_of_week = is_set(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = if self.day_of_month_restricted && self.day_of_week_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        };
+        day && is_set(self.minutes, time.minute())
+            && is_set(self.hours, time.hour())
+            && is_set(self.months, time.month())
+    }
+
+    /// Returns the start of the latest minute at or before `now` in which the expression
+    /// fired, looking back at most `lookback`.
+    pub(crate) fn last_fire_at_or_before<Tz: TimeZone>(
+        &self,
+        now: &DateTime<Tz>,
+        lookback: Duration,
+    ) -> Option<DateTime<Tz>> {
+        let start = now.with_second(0)?.with_nanosecond(0)?;
+        let minutes = lookback.as_secs() / 60;
+        (0..=minutes)
+            .filter_map(|minute| {
+                start
+                    .clone()
+                    .checked_sub_signed(TimeDelta::minutes(i64::try_from(minute).ok()?))
+            })
+            .find(|time| self.matches(time))
+    }
+}
+
+/// Parses one field into a bit mask of the values in `min..=max` it matches.
+fn parse_field(
+    expr: &str,
+    spec: &str,
+    field: &'static str,
+    min: u32,
+    max: u32,
+) -> Result<u64, CronParseError> {
+    let invalid = |value: &str| CronParseError::InvalidField {
+        expr: expr.to_string(),
+        field,
+        value: value.to_string(),
+    };
+    let parse_value = |value: &str| {
+        value
+            .parse::<u32>()
+            .ok()
+            .filter(|v| (min..=max).contains(v))
+            .ok_or_else(|| invalid(value))
+    };
+
+    let mut mask = 0u64;
+    for item in spec.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|&s| s > 0)
+                    .ok_or_else(|| invalid(item))?,
+            ),
+            None => (item, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start)?, parse_value(end)?)
+        } else {
+            let value = parse_value(range)?;
+            // `5/10` means every 10 starting at 5, like `5-max/10`.
+            (value, if item.contains('/') { max } else { value })
+        };
+        if start > end {
+            return Err(invalid(item));
+        }
+        for value in (start..=end).step_by(usize::try_from(step).unwrap_or(usize::MAX)) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl FromStr for CronExpr {
+    type Err = CronParseError;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let &[minute, hour, day_of_month, month, day_of_week] = fields.as_slice() else {
+            return Err(CronParseError::FieldCount {
+                expr: expr.to_string(),
+                found: fields.len(),
+            });
+        };
+        let mut weekdays = parse_field(expr, day_of_week, "day of week", 0, 7)?;
+        // Both 0 and 7 are Sunday.
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            source: expr.to_string(),
+            minutes: parse_field(expr, minute, "minute", 0, 59)?,
+            hours: parse_field(expr, hour, "hour", 0, 23)?,
+            days_of_month: parse_field(expr, day_of_month, "day of month", 1, 31)?,
+            months: parse_field(expr, month, "month", 1, 12)?,
+            days_of_week: weekdays,
+            day_of_month_restricted: !day_of_month.starts_with('*'),
+            day_of_week_restricted: !day_of_week.starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for CronExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for CronExpr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for CronExpr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Utc};
+
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 30)
+            .unwrap()
+            .and_utc()
+    }
+
+    fn cron(expr: &str) -> CronExpr {
+        expr.parse().unwrap()
+    }
+
+    #[test]
+    fn matches_weekday_window_start() {
+        let expr = cron("0 2 * * 1-5");
+        // 2026-10-12 is a Monday, 2026-10-17 a Saturday.
+        assert!(expr.matches(&at(2026, 10, 12, 2, 0)));
+        assert!(!expr.matches(&at(2026, 10, 12, 2, 1)));
+        assert!(!expr.matches(&at(2026, 10, 12, 3, 0)));
+        assert!(!expr.matches(&at(2026, 10, 17, 2, 0)));
+    }
+
+    #[test]
+    fn supports_lists_steps_and_sunday_as_seven() {
+        let expr = cron("*/15 8,20 * * 7");
+        // 2026-10-18 is a Sunday.
+        for minute in [0, 15, 30, 45] {
+            assert!(expr.matches(&at(2026, 10, 18, 20, minute)), "{minute}");
+        }
+        assert!(!expr.matches(&at(2026, 10, 18, 20, 10)));
+        assert!(!expr.matches(&at(2026, 10, 19, 8, 0)));
+        assert!(cron("5/20 * * * *").matches(&at(2026, 10, 18, 0, 45)));
+    }
+
+    #[test]
+    fn restricted_day_fields_combine_with_or() {
+        let expr = cron("0 0 1 * 1");
+        // 2026-10-01 is a Thursday, 2026-10-05 a Monday.
+        assert!(expr.matches(&at(2026, 10, 1, 0, 0)));
+        assert!(expr.matches(&at(2026, 10, 5, 0, 0)));
+        assert!(!expr.matches(&at(2026, 10, 6, 0, 0)));
+    }
+
+    #[test]
+    fn finds_last_fire_time() {
+        let expr = cron("0 6 * * *");
+        let now = at(2026, 10, 14, 5, 59);
+        assert_eq!(
+            expr.last_fire_at_or_before(&now, Duration::from_hours(48)),
+            Some(at(2026, 10, 13, 6, 0).with_second(0).unwrap())
+        );
+        assert_eq!(
+            expr.last_fire_at_or_before(&now, Duration::from_hours(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert_eq!(
+            "0 2 * *".parse::<CronExpr>(),
+            Err(CronParseError::FieldCount {
+                expr: "0 2 * *".to_string(),
+                found: 4
+            })
+        );
+        for expr in [
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "5-1 * * * *",
+            "*/0 * * * *",
+            "MON * * * *",
+        ] {
+            assert!(
+                matches!(
+                    expr.parse::<CronExpr>(),
+                    Err(CronParseError::InvalidField { .. })
+                ),
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        assert_eq!(cron("0 2 * * 1-5").to_string(), "0 2 * * 1-5");
+    }
+}
@@ -4,9 +4,12 @@
 //! without any backend state or functionality.
 
 use alloc::sync::Arc;
+use core::sync::atomic::AtomicBool;
 use std::{collections::HashMap, path};
 
 use axum::{http::Response, response::IntoResponse as _};
+use clap::Args;
+use serde::Serialize;
 use tokio::{
     net::TcpListener,
     sync::{broadcast, watch},
@@ -22,16 +25,48 @@ use crate::{
     http::{
         assets::{UiMode, render_ui_html},
         auth,
+        m2m::{HmacCache, RateLimiter},
         server::router::create_app_router,
     },
 };
 
+/// Parameters of the hosts simulated by the `WebUI` in demo mode.
+#[derive(Debug, Clone, Args, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "Just using 'Config' would be confused with the coordinator config."
+)]
+pub struct DemoConfig {
+    /// Number of simulated hosts.
+    #[arg(long, default_value_t = 3)]
+    pub num_hosts: u16,
+    /// Milliseconds a simulated host takes to come online after being woken.
+    #[arg(long, default_value_t = 2000)]
+    pub wake_delay_ms: u64,
+    /// Milliseconds a simulated host takes to go offline after being shut down.
+    #[arg(long, default_value_t = 1500)]
+    pub shutdown_delay_ms: u64,
+    /// Probability of each simulated host starting offline, from 0.0 to 1.0.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_probability)]
+    pub offline_probability: f64,
+}
+
+fn parse_probability(arg: &str) -> Result<f64, String> {
+    let value: f64 = arg.parse().map_err(|e| format!("invalid number: {e}"))?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{value} is not between 0.0 and 1.0"))
+    }
+}
+
 /// Run the demo service on the specified port and bind address.
 ///
 /// # Panics
 ///
 /// Panics if the TCP listener cannot be bound to the specified address.
-pub(crate) async fn run_demo_service(port: u16, bind: &str, subpath: &str) {
+pub(crate) async fn run_demo_service(port: u16, bind: &str, subpath: &str, demo: DemoConfig) {
     let addr = format!("{bind}:{port}");
     info!("Starting demo service on http://{}", addr);
 
@@ -39,7 +74,10 @@ pub(crate) async fn run_demo_service(port: u16, bind: &str, subpath: &str) {
     let serve_demo_ui = {
         let subpath = subpath.to_string();
         move |_: AppState| {
-            let html = render_ui_html(&UiMode::Demo { subpath: &subpath });
+            let html = render_ui_html(&UiMode::Demo {
+                subpath: &subpath,
+                config: &demo,
+            });
             Response::builder()
                 .header("Content-Type", "text/html")
                 .body(html)
@@ -49,10 +87,12 @@ pub(crate) async fn run_demo_service(port: u16, bind: &str, subpath: &str) {
     };
 
     let hoststatus = HostActorHandle::spawn(HashMap::new());
+    let (config_tx, config_rx) = watch::channel(Arc::new(ControllerConfig::default()));
 
     let app_state = AppState {
         config_path: path::PathBuf::from("demo"),
-        config_rx: watch::channel(Arc::new(ControllerConfig::default())).1,
+        config_rx,
+        config_tx,
         host_actor: hoststatus,
         ws_tx: broadcast::channel(1).0,
         leases: LeaseStore::new(LeaseMap::default()).0,
@@ -64,15 +104,26 @@ pub(crate) async fn run_demo_service(port: u16, bind: &str, subpath: &str) {
                 .expect("failed to initialize auth runtime"),
         ),
         tls_enabled: false,
+        rustls_config: None,
         runtime: RuntimeConfig::default(),
         db_pool: None,
         vapid_key: None,
         operation_failures: OperationFailureStore::new(HashMap::new()).0,
         online_since: RwMap::default(),
+        last_seen: RwMap::default(),
         latest_release: Arc::default(),
+        config_error: Arc::default(),
+        m2m_rate_limiter: Arc::new(RateLimiter::new(0, 0)),
+        m2m_peer_rate_limiter: Arc::new(RateLimiter::new(0, 0)),
+        hmac_cache: Arc::new(HmacCache::new(0)),
+        nonce_cache: Arc::default(),
+        audit_log: None,
+        metrics: Arc::default(),
+        ws_connections: Arc::default(),
+        ready: Arc::new(AtomicBool::new(true)),
     };
 
-    let app = create_app_router(&app_state.auth, serve_demo_ui).with_state(app_state);
+    let app = create_app_router(&app_state, serve_demo_ui).with_state(app_state);
 
     let listener = TcpListener::bind(&addr)
         .await
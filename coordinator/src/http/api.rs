@@ -1,32 +1,65 @@
+use alloc::collections::BTreeMap;
 use core::{
     convert::Infallible,
     fmt::{self, Display},
+    net::IpAddr,
+    time::Duration,
 };
+use std::collections::HashMap;
 
 use axum::{
     Router,
-    extract::{Path, State},
-    response::IntoResponse,
-    routing::{get, post},
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
 };
 use axum_extra::{TypedHeader, headers::ContentType};
+use chrono::{DateTime, TimeDelta, Utc};
 use hyper::StatusCode;
+use secrecy::{ExposeSecret as _, SecretString};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info, warn};
+use tokio::{task, time::Instant};
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    app::{AppState, LeaseSource, db, lookup_host},
-    include_utf8_asset,
+    app::{
+        AppState, DbPool, HostControlError, HostState, HostStatus, LeaseMap, LeaseSource,
+        LeaseSources, db, force_shutdown, force_wake, lookup_host, lookup_host_with_overrides,
+        rotate_host_secret, wait_for_transition,
+    },
+    audit_log::{AuditEventType, AuditOutcome},
+    http::{
+        auth,
+        error::{ApiError, json_error},
+    },
+    include_utf8_asset, wol,
 };
 
+pub(crate) use crate::config::LeaseAction;
+
 pub(crate) fn routes() -> Router<AppState> {
     Router::new()
         .route("/lease/{hostname}/{action}", post(handle_web_lease_action))
+        .route("/reset_leases", post(handle_reset_all_leases))
         .route(
             "/reset_leases/{client_id}",
             post(handle_reset_client_leases),
         )
         .route("/hosts_status", get(get_hosts_status))
+        .route("/leases", get(get_leases))
+        .route("/leases/{hostname}", get(get_host_leases))
+        .route("/hosts", get(get_hosts))
+        .route("/hosts/{hostname}", get(get_host_details))
+        .route("/hosts/{hostname}/wake", post(handle_force_wake))
+        .route("/hosts/{hostname}/shutdown", post(handle_force_shutdown))
+        .route("/hosts/{hostname}/secret", put(handle_rotate_host_secret))
+        .route("/hosts/{hostname}/test_wol", post(handle_test_host_wol))
+        .route("/clients", get(get_clients))
+        .route("/auth/rotate-token", post(auth::token::rotate_token))
+        .route(
+            "/clients/{client_id}/leases",
+            get(get_client_leases).delete(handle_release_client_leases),
+        )
         .route("/dependency-data.json", get(serve_dependency_data))
         .route("/update", get(get_latest_release))
 }
@@ -46,19 +79,12 @@ async fn serve_dependency_data() -> impl IntoResponse {
     )
 }
 
-/// Lease action for lease endpoints (shared between web and m2m)
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
-pub(crate) enum LeaseAction {
-    Take,
-    Release,
-}
-
 impl Display for LeaseSource {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match *self {
             LeaseSource::WebInterface => write!(f, "web-interface"),
             LeaseSource::Client(ref id) => write!(f, "client-{id}"),
+            LeaseSource::Schedule { ref name } => write!(f, "schedule-{name}"),
         }
     }
 }
@@ -67,51 +93,459 @@ impl Display for LeaseSource {
 pub(crate) enum UpdateLeaseError {
     #[error("Host not found: {hostname}")]
     HostNotFound { hostname: String },
+    #[error("Lease limit of {limit} exceeded")]
+    LeaseLimitExceeded { limit: u32 },
     #[error(transparent)]
     DatabaseError(#[from] sqlx::Error),
 }
 
+impl IntoResponse for UpdateLeaseError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::HostNotFound { ref hostname } => host_not_found(hostname),
+            Self::LeaseLimitExceeded { .. } => json_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                "lease_limit_exceeded",
+                &self.to_string(),
+            ),
+            Self::DatabaseError(ref e) => {
+                error!("Failed to update lease: {e}");
+                json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "database_error",
+                    "Failed to update lease",
+                )
+            }
+        }
+    }
+}
+
+/// Outcome of [`update_lease`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LeaseUpdate {
+    /// `false` if the lease was already held (take) or not held (release).
+    pub changed: bool,
+    /// Whether the host holds no leases after the update.
+    pub lease_set_empty: bool,
+}
+
 /// Updates the lease set for a host and persists to database if available.
+///
+/// Taking a lease with a `ttl` makes it expire after that duration unless it is taken again;
+/// taking it without one keeps it until released. `ttl` is ignored on release.
 #[tracing::instrument(skip(state))]
 pub(crate) async fn update_lease(
     hostname: &str,
     lease_source: LeaseSource,
     action: LeaseAction,
+    ttl: Option<Duration>,
     state: &AppState,
-) -> Result<bool, UpdateLeaseError> {
+) -> Result<LeaseUpdate, UpdateLeaseError> {
     // Ensure that the host exists, to avoid creating lease entries for non-existent hosts.
     lookup_host(state, hostname).ok_or_else(|| UpdateLeaseError::HostNotFound {
         hostname: hostname.to_string(),
     })?;
-    state
+    let max_leases = max_leases(state, &lease_source);
+    let result = state
         .leases
         .update({
             let hostname = hostname.to_string();
             let lease_source = lease_source.clone();
             let db_pool = state.db_pool.clone();
             async move |map| {
-                let lease_set = map.entry(hostname.clone()).or_default();
-                use LeaseAction as LA;
-                match action {
-                    LA::Take => {
-                        lease_set.insert(lease_source.clone());
-                        info!(%lease_source, "Lease taken");
-                        if let Some(ref pool) = db_pool {
-                            db::add_lease(pool, &hostname, &lease_source).await?;
-                        }
+                apply_lease_update(
+                    map,
+                    &hostname,
+                    &lease_source,
+                    action,
+                    ttl,
+                    max_leases,
+                    db_pool.as_ref(),
+                )
+                .await
+            }
+        })
+        .await;
+
+    if let Some(ref audit_log) = state.audit_log {
+        let event_type = match action {
+            LeaseAction::Take => AuditEventType::LeaseTake,
+            LeaseAction::Release => AuditEventType::LeaseRelease,
+        };
+        audit_log
+            .record(
+                event_type,
+                hostname,
+                Some(&lease_source),
+                AuditOutcome::from_success(result.is_ok()),
+            )
+            .await;
+    }
+
+    result
+}
+
+/// Outcome of [`update_leases`].
+#[derive(Debug, Default)]
+pub(crate) struct BatchLeaseUpdate {
+    /// Hosts whose lease was updated, including those where nothing changed.
+    pub succeeded: Vec<String>,
+    pub failed: BTreeMap<String, UpdateLeaseError>,
+}
+
+/// Updates the lease sets of several hosts at once, like [`update_lease`] does for one.
+///
+/// All hosts are updated under a single lock of the lease map and published as one snapshot, so
+/// the reconciler sees either none or all of the changes. A host that can't be updated (e.g. it
+/// doesn't exist or the lease limit is reached) is reported in `failed` without affecting the
+/// others.
+#[tracing::instrument(skip(state))]
+pub(crate) async fn update_leases(
+    hostnames: &[String],
+    lease_source: LeaseSource,
+    action: LeaseAction,
+    state: &AppState,
+) -> BatchLeaseUpdate {
+    let mut hostnames = hostnames.to_vec();
+    hostnames.sort_unstable();
+    hostnames.dedup();
+    let max_leases = max_leases(state, &lease_source);
+    let host_exists: Vec<bool> = {
+        let config = state.config_rx.borrow();
+        hostnames
+            .iter()
+            .map(|hostname| config.hosts.contains_key(hostname))
+            .collect()
+    };
+
+    let Ok(batch) = state
+        .leases
+        .update({
+            let lease_source = lease_source.clone();
+            let db_pool = state.db_pool.clone();
+            async move |map| {
+                let mut batch = BatchLeaseUpdate::default();
+                for (hostname, exists) in hostnames.into_iter().zip(host_exists) {
+                    if !exists {
+                        let error = UpdateLeaseError::HostNotFound {
+                            hostname: hostname.clone(),
+                        };
+                        batch.failed.insert(hostname, error);
+                        continue;
                     }
-                    LA::Release => {
-                        lease_set.remove(&lease_source);
-                        info!(%lease_source, "Lease released");
-                        if let Some(ref pool) = db_pool {
-                            db::remove_lease(pool, &hostname, &lease_source).await?;
+                    // Restored if persisting fails, so memory and database stay in sync.
+                    let previous = map.get(&hostname).cloned();
+                    match apply_lease_update(
+                        map,
+                        &hostname,
+                        &lease_source,
+                        action,
+                        None,
+                        max_leases,
+                        db_pool.as_ref(),
+                    )
+                    .await
+                    {
+                        Ok(_) => batch.succeeded.push(hostname),
+                        Err(e) => {
+                            if let UpdateLeaseError::DatabaseError(_) = e {
+                                match previous {
+                                    Some(lease_set) => map.insert(hostname.clone(), lease_set),
+                                    None => map.remove(&hostname),
+                                };
+                            }
+                            batch.failed.insert(hostname, e);
                         }
                     }
                 }
-                Ok(lease_set.is_empty())
+                Ok::<_, Infallible>(batch)
             }
         })
-        .await
+        .await;
+
+    if let Some(ref audit_log) = state.audit_log {
+        let event_type = match action {
+            LeaseAction::Take => AuditEventType::LeaseTake,
+            LeaseAction::Release => AuditEventType::LeaseRelease,
+        };
+        let outcomes = batch
+            .succeeded
+            .iter()
+            .map(|hostname| (hostname, true))
+            .chain(batch.failed.keys().map(|hostname| (hostname, false)));
+        for (hostname, success) in outcomes {
+            audit_log
+                .record(
+                    event_type,
+                    hostname,
+                    Some(&lease_source),
+                    AuditOutcome::from_success(success),
+                )
+                .await;
+        }
+    }
+
+    batch
+}
+
+/// Maximum number of leases `lease_source` may hold across all hosts, `0` meaning unlimited.
+fn max_leases(state: &AppState, lease_source: &LeaseSource) -> u32 {
+    match *lease_source {
+        LeaseSource::Client(ref client_id) => state
+            .config_rx
+            .borrow()
+            .clients
+            .get(client_id)
+            .map_or(0, |client| client.max_leases),
+        LeaseSource::WebInterface | LeaseSource::Schedule { .. } => 0,
+    }
+}
+
+/// Applies `action` for `lease_source` on `hostname` in `map`, and persists it if a database
+/// is configured.
+///
+/// Persisting is critical: a lease missing from the database would be lost on restart, and a
+/// released one would come back. A failure is returned as [`UpdateLeaseError::DatabaseError`],
+/// and the caller discards the change to `map`.
+async fn apply_lease_update(
+    map: &mut LeaseMap,
+    hostname: &str,
+    lease_source: &LeaseSource,
+    action: LeaseAction,
+    ttl: Option<Duration>,
+    max_leases: u32,
+    db_pool: Option<&DbPool>,
+) -> Result<LeaseUpdate, UpdateLeaseError> {
+    if action == LeaseAction::Take && exceeds_lease_limit(map, hostname, lease_source, max_leases) {
+        return Err(UpdateLeaseError::LeaseLimitExceeded { limit: max_leases });
+    }
+    let lease_set = map.entry(hostname.to_string()).or_default();
+    let expiry = ttl.and_then(lease_expiry);
+    let changed = apply_lease_action(
+        lease_set,
+        lease_source.clone(),
+        action,
+        expiry.map(|(deadline, _)| deadline),
+    );
+    use LeaseAction as LA;
+    match action {
+        LA::Take => {
+            if changed {
+                info!(%lease_source, ?ttl, "Lease taken");
+            } else {
+                debug!(%lease_source, ?ttl, "Lease already held");
+            }
+            // Also persist re-takes, as they replace the expiry.
+            if let Some(pool) = db_pool {
+                let expires_at = expiry.map(|(_, expires_at)| expires_at);
+                db::add_lease(pool, hostname, lease_source, expires_at).await?;
+            }
+        }
+        LA::Release if changed => {
+            info!(%lease_source, "Lease released");
+            if let Some(pool) = db_pool {
+                db::remove_lease(pool, hostname, lease_source).await?;
+            }
+        }
+        LA::Release => debug!(%lease_source, "Lease not held"),
+    }
+    Ok(LeaseUpdate {
+        changed,
+        lease_set_empty: lease_set.is_empty(),
+    })
+}
+
+/// Applies `action` for `lease_source` to `lease_set` and returns whether the set changed.
+///
+/// Taking an already held lease only replaces its expiry and does not count as a change.
+fn apply_lease_action(
+    lease_set: &mut LeaseSources,
+    lease_source: LeaseSource,
+    action: LeaseAction,
+    deadline: Option<Instant>,
+) -> bool {
+    match action {
+        LeaseAction::Take => lease_set.insert_with_expiry(lease_source, deadline),
+        LeaseAction::Release => lease_set.remove(&lease_source),
+    }
+}
+
+/// Response body for a lease action that left the lease set unchanged.
+pub(crate) const fn unchanged_lease_response(action: LeaseAction) -> &'static str {
+    match action {
+        LeaseAction::Take => "Lease already held",
+        LeaseAction::Release => "Lease not held",
+    }
+}
+
+/// Converts a lease TTL into the in-memory deadline and the persisted expiry time.
+///
+/// Returns `None` for TTLs too large to represent, which then never expire.
+fn lease_expiry(ttl: Duration) -> Option<(Instant, DateTime<Utc>)> {
+    let deadline = Instant::now().checked_add(ttl)?;
+    let expires_at = Utc::now().checked_add_signed(TimeDelta::from_std(ttl).ok()?)?;
+    Some((deadline, expires_at))
+}
+
+/// Whether `lease_source` taking a lease on `hostname` would exceed `max_leases`, counted across all hosts.
+///
+/// A `max_leases` of `0` means unlimited. Re-taking a lease that is already held never exceeds the limit.
+fn exceeds_lease_limit(
+    leases: &LeaseMap,
+    hostname: &str,
+    lease_source: &LeaseSource,
+    max_leases: u32,
+) -> bool {
+    if max_leases == 0
+        || leases
+            .get(hostname)
+            .is_some_and(|lease_set| lease_set.contains(lease_source))
+    {
+        return false;
+    }
+    let held = leases
+        .values()
+        .filter(|lease_set| lease_set.contains(lease_source))
+        .count();
+    held >= usize::try_from(max_leases).unwrap_or(usize::MAX)
+}
+
+/// Query parameters shared by the web and m2m lease endpoints.
+#[derive(Deserialize)]
+pub(crate) struct LeaseActionQuery {
+    #[serde(default)]
+    pub r#async: Option<bool>,
+    /// Seconds after which a taken lease expires, unless it is taken again.
+    #[serde(default)]
+    pub ttl: Option<u64>,
+}
+
+impl LeaseActionQuery {
+    pub(crate) fn ttl(&self) -> Option<Duration> {
+        self.ttl.map(Duration::from_secs)
+    }
+}
+
+/// Builds the response for a lease action after the lease set was updated.
+///
+/// The reconciler background task reacts to the lease change and drives the host towards its
+/// new desired state. In asynchronous mode this returns immediately; otherwise it waits until
+/// the host reached the desired state or the host's wake/shutdown timeout elapsed.
+pub(crate) async fn respond_to_lease_update(
+    state: &AppState,
+    host: &str,
+    action: LeaseAction,
+    lease_set_empty: bool,
+    is_async: bool,
+) -> Result<Response, Response> {
+    let ultimately_desired_state = if lease_set_empty {
+        HostState::Offline
+    } else {
+        HostState::Online
+    };
+
+    let current_state = state.host_actor.get_current_state(host);
+    if current_state == ultimately_desired_state {
+        return Ok(current_state_response(action, ultimately_desired_state).into_response());
+    }
+
+    if is_async {
+        return Ok(async_response(action).into_response());
+    }
+
+    perform_sync_wait(state, host, action, ultimately_desired_state).await
+}
+
+fn host_not_found(host: &str) -> Response {
+    json_error(
+        StatusCode::NOT_FOUND,
+        "host_not_found",
+        &format!("No configuration found for host {host}"),
+    )
+}
+
+fn client_not_found(client_id: &str) -> Response {
+    json_error(
+        StatusCode::NOT_FOUND,
+        "client_not_found",
+        &format!("No configuration found for client {client_id}"),
+    )
+}
+
+fn current_state_response(action: LeaseAction, state: HostState) -> &'static str {
+    match (action, state) {
+        (LeaseAction::Take, HostState::Online) => "Lease taken, host is already online",
+        (LeaseAction::Release, HostState::Offline) => "Lease released, host is already offline",
+        (LeaseAction::Release, HostState::Online) => "Lease released, but host remains online",
+        (LeaseAction::Take, HostState::Offline) => unreachable!(
+            "taking a lease on an offline host should not assure that the lease_set is not empty"
+        ),
+        _ => unreachable!("current_state_response can only be called for stable host states"),
+    }
+}
+
+const fn async_response(action: LeaseAction) -> &'static str {
+    match action {
+        LeaseAction::Take => "Lease taken (async)",
+        LeaseAction::Release => "Lease released (async)",
+    }
+}
+
+async fn perform_sync_wait(
+    state: &AppState,
+    host: &str,
+    action: LeaseAction,
+    ultimately_desired_state: HostState,
+) -> Result<Response, Response> {
+    let Some(host_with_name) = lookup_host_with_overrides(state, host).await else {
+        return Err(host_not_found(host));
+    };
+
+    let timeout_secs = if ultimately_desired_state == HostState::Online {
+        host_with_name
+            .host
+            .wake_timeout_secs
+            .unwrap_or(state.runtime.default_wake_timeout_secs)
+    } else {
+        host_with_name
+            .host
+            .shutdown_timeout_secs
+            .unwrap_or(state.runtime.default_shutdown_timeout_secs)
+    };
+    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+
+    wait_for_transition(
+        host,
+        &state.host_actor,
+        &state.operation_failures,
+        ultimately_desired_state,
+        deadline,
+    )
+    .await
+    .map(|()| {
+        match (action, ultimately_desired_state) {
+            (LeaseAction::Take, HostState::Online) => "Lease taken, host is now online",
+            (LeaseAction::Release, HostState::Offline) => "Lease released, host is now offline",
+            _ => unreachable!("unexpected (action, ultimately_desired_state) combination"),
+        }
+        .into_response()
+    })
+    .map_err(|err| host_control_error_response(&err))
+}
+
+fn host_control_error_response(err: &HostControlError) -> Response {
+    use HostControlError as HCE;
+
+    let (status, code, message) = match *err {
+        HCE::NotFound(_) => (StatusCode::NOT_FOUND, "host_not_found", err.to_string()),
+        HCE::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, "timeout", err.to_string()),
+        HCE::OperationFailed { ref report, .. } => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "operation_failed",
+            report.to_string(),
+        ),
+    };
+    json_error(status, code, &message)
 }
 
 /// Handles taking or releasing a lease on a host via the web interface.
@@ -120,32 +554,72 @@ pub(crate) async fn update_lease(
 /// any client authentication or HMAC signature, unlike the m2m `handle_lease` endpoint.
 /// The lease is attributed to the web interface and is visible to all clients.
 ///
+/// Unlike the m2m endpoint this defaults to asynchronous mode; pass `?async=false` to wait until the
+/// host reached its desired state. Hosts with `enforce_state = true` are driven the same way as for
+/// m2m leases, i.e. releasing the last lease shuts the host down right away.
+///
 /// Use this for user-initiated actions from the web dashboard. For programmatic or
 /// machine-to-machine lease management, use the `/m2m/lease/{hostname}/{action}` endpoint.
 #[axum::debug_handler]
-#[tracing::instrument(skip(state))]
+#[tracing::instrument(skip(state, query))]
 async fn handle_web_lease_action(
     Path((hostname, action)): Path<(String, LeaseAction)>,
     State(state): State<AppState>,
+    Query(query): Query<LeaseActionQuery>,
 ) -> impl IntoResponse {
     let lease_source = LeaseSource::WebInterface;
-    match update_lease(&hostname, lease_source, action, &state).await {
-        Ok(_) => {
-            // Reconciler task handles the host control action.
-            match action {
-                LeaseAction::Take => "Lease taken (async)".into_response(),
-                LeaseAction::Release => "Lease released (async)".into_response(),
+    let lease_set_empty =
+        match update_lease(&hostname, lease_source, action, query.ttl(), &state).await {
+            Ok(update) => update.lease_set_empty,
+            Err(e) => {
+                if let UpdateLeaseError::HostNotFound { .. } = e {
+                    warn!("Attempted to {action:?} lease for unknown host: {hostname}",);
+                }
+                return e.into_response();
             }
-        }
-        Err(UpdateLeaseError::HostNotFound { .. }) => {
-            warn!("Attempted to {action:?} lease for unknown host: {hostname}",);
-            return StatusCode::NOT_FOUND.into_response();
-        }
-        Err(e) => {
-            error!("Failed to update lease: {}", e);
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-        }
-    }
+        };
+
+    // The web UI follows host state over the websocket, so don't block it by default.
+    let is_async = query.r#async.unwrap_or(true);
+    respond_to_lease_update(&state, &hostname, action, lease_set_empty, is_async)
+        .await
+        .into_response()
+}
+
+/// Removes every lease held by `client_id` and returns the affected hosts, sorted.
+///
+/// Lease updates reach WebSocket clients via the `LeaseRx` watch channel, and the reconciler
+/// background task brings the affected hosts to their new desired state.
+///
+/// If the removal can't be persisted, the leases are kept in memory as well and the error is
+/// returned, as they would otherwise come back on the next restart.
+async fn release_client_leases(
+    state: &AppState,
+    client_id: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut released_hosts = state
+        .leases
+        .update({
+            let client_id = client_id.to_string();
+            let db_pool = state.db_pool.clone();
+            async move |map| {
+                // Remove all leases associated with the client from memory (atomically)
+                let lease = LeaseSource::Client(client_id.clone());
+                let released_hosts: Vec<String> = map
+                    .iter_mut()
+                    .filter_map(|(host, lease_set)| lease_set.remove(&lease).then(|| host.clone()))
+                    .collect();
+                // Persist the removal
+                if let Some(ref pool) = db_pool {
+                    db::remove_client_leases(pool, &client_id).await?;
+                }
+                Ok::<_, sqlx::Error>(released_hosts)
+            }
+        })
+        .await?;
+    released_hosts.sort();
+    info!(?released_hosts, "Released all leases of client");
+    Ok(released_hosts)
 }
 
 /// This function is used by the web UI to reset all leases associated with a client.
@@ -156,42 +630,628 @@ async fn handle_web_lease_action(
 async fn handle_reset_client_leases(
     Path(client_id): Path<String>,
     State(state): State<AppState>,
-) -> impl IntoResponse {
-    state
+) -> Result<String, ApiError> {
+    release_client_leases(&state, &client_id).await?;
+    Ok(format!(
+        "All leases for client '{client_id}' have been reset."
+    ))
+}
+
+/// Hosts whose leases were removed by [`handle_release_client_leases`].
+#[derive(Debug, Serialize)]
+struct ReleasedClientLeases {
+    released_hosts: Vec<String>,
+}
+
+/// Releases every lease of an M2M client, e.g. when it is decommissioned or misbehaving.
+///
+/// Requires a web UI session rather than the client's HMAC signature, and also works for
+/// clients that were already removed from the config.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state))]
+async fn handle_release_client_leases(
+    Path(client_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<axum::Json<ReleasedClientLeases>, ApiError> {
+    let released_hosts = release_client_leases(&state, &client_id).await?;
+    Ok(axum::Json(ReleasedClientLeases { released_hosts }))
+}
+
+/// Hosts whose leases were removed by [`handle_reset_all_leases`].
+#[derive(Debug, Serialize)]
+struct ResetAllLeases {
+    cleared_hosts: Vec<String>,
+}
+
+/// Removes every lease of every host, e.g. to recover from stale leases after a crash.
+///
+/// Like [`handle_reset_client_leases`], the reconciler brings the affected hosts to their new
+/// desired state, and the leases are kept if their removal can't be persisted.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state))]
+async fn handle_reset_all_leases(
+    State(state): State<AppState>,
+) -> Result<axum::Json<ResetAllLeases>, ApiError> {
+    let mut cleared_hosts = state
         .leases
         .update({
-            let client_id = client_id.clone();
             let db_pool = state.db_pool.clone();
             async move |map| {
-                // Remove all leases associated with the client from memory (atomically)
-                for lease_set in map.values_mut() {
-                    lease_set.retain(
-                        |lease| !matches!(lease, LeaseSource::Client(id) if id == &client_id),
-                    );
+                let cleared_hosts: Vec<String> = map
+                    .iter_mut()
+                    .filter(|&(_, ref lease_set)| !lease_set.is_empty())
+                    .map(|(host, lease_set)| {
+                        *lease_set = LeaseSources::default();
+                        host.clone()
+                    })
+                    .collect();
+                if let Some(ref pool) = db_pool {
+                    db::remove_all_leases(pool).await?;
                 }
-                // Persist the removal
-                if let Some(ref pool) = db_pool
-                    && let Err(e) = db::remove_client_leases(pool, &client_id).await
-                {
-                    tracing::error!("Failed to remove client leases from database: {}", e);
-                }
-                Ok::<(), Infallible>(())
+                Ok::<_, sqlx::Error>(cleared_hosts)
             }
         })
+        .await?;
+    cleared_hosts.sort();
+
+    // Lease updates are broadcast to WebSocket clients via the LeaseRx watch channel, and the
+    // reconciler shuts down hosts that are no longer leased.
+    info!(?cleared_hosts, "Reset all leases");
+    Ok(axum::Json(ResetAllLeases { cleared_hosts }))
+}
+
+/// Returns the online status of all hosts as a JSON object.
+///
+/// Each `tag` query parameter restricts the result to hosts carrying that tag.
+/// See [`hosts_status_response`] for the `format` query parameter.
+#[axum::debug_handler]
+async fn get_hosts_status(
+    Query(params): Query<Vec<(String, String)>>,
+    State(state): State<AppState>,
+) -> Response {
+    hosts_status_response(&state, &params).await
+}
+
+/// Entry of the hosts status, unless requested with `?format=legacy`.
+#[derive(Debug, Serialize)]
+struct DetailedHostStatus {
+    state: HostState,
+    online: bool,
+    /// Time the host most recently went online, `None` if it wasn't seen online since the
+    /// coordinator started.
+    last_seen: Option<DateTime<Utc>>,
+}
+
+/// Responds with the status of the hosts selected by the `tag` query `params`.
+///
+/// By default each hostname maps to a [`DetailedHostStatus`], which also tells when the host last
+/// went online. With `format=legacy` it maps to just its state instead.
+pub(crate) async fn hosts_status_response(
+    state: &AppState,
+    params: &[(String, String)],
+) -> Response {
+    let detailed = match params
+        .iter()
+        .rev()
+        .find_map(|&(ref key, ref value)| (key == "format").then_some(value.as_str()))
+    {
+        None | Some("detailed") => true,
+        Some("legacy") => false,
+        Some(other) => {
+            return ApiError::BadRequest(format!(
+                "Unknown format '{other}', expected 'detailed' or 'legacy'"
+            ))
+            .into_response();
+        }
+    };
+
+    let hoststatus = hosts_status_with_tags(state, params);
+    if !detailed {
+        return axum::Json(hoststatus).into_response();
+    }
+
+    let went_online = state.host_actor.went_online();
+    let detailed_status: HashMap<String, DetailedHostStatus> = hoststatus
+        .into_iter()
+        .map(|(name, host_state)| {
+            let entry = DetailedHostStatus {
+                state: host_state,
+                online: host_state == HostState::Online,
+                last_seen: went_online.get(&name).copied(),
+            };
+            (name, entry)
+        })
+        .collect();
+    axum::Json(detailed_status).into_response()
+}
+
+/// Current status of the hosts tagged with every `tag` value among the query `params`.
+fn hosts_status_with_tags(state: &AppState, params: &[(String, String)]) -> HostStatus {
+    let hoststatus = state.host_actor.borrow().clone();
+    let tags: Vec<&str> = params
+        .iter()
+        .filter_map(|&(ref key, ref value)| (key == "tag").then_some(value.as_str()))
+        .collect();
+    if tags.is_empty() {
+        return (*hoststatus).clone();
+    }
+
+    let config = state.config_rx.borrow().clone();
+    hoststatus
+        .iter()
+        .filter(|&(name, _)| {
+            config.hosts.get(name).is_some_and(|host| {
+                tags.iter()
+                    .all(|&tag| host.tags.iter().any(|host_tag| host_tag == tag))
+            })
+        })
+        .map(|(name, &host_state)| (name.clone(), host_state))
+        .collect()
+}
+
+/// Returns the active leases of all hosts as a JSON object keyed by hostname.
+#[axum::debug_handler]
+async fn get_leases(State(state): State<AppState>) -> impl IntoResponse {
+    let leases = state.leases.snapshot();
+    axum::Json((*leases).clone())
+}
+
+/// Returns the active leases of a single host as a JSON array.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state))]
+async fn get_host_leases(
+    Path(hostname): Path<String>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    if lookup_host(&state, &hostname).is_none() {
+        return host_not_found(&hostname);
+    }
+    axum::Json(state.leases.get_host(&hostname)).into_response()
+}
+
+/// Returns the hosts an M2M client holds a lease on as a sorted JSON array.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state))]
+async fn get_client_leases(
+    Path(client_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    if !state.config_rx.borrow().clients.contains_key(&client_id) {
+        return client_not_found(&client_id);
+    }
+    let lease = LeaseSource::Client(client_id);
+    let mut hosts: Vec<String> = state
+        .leases
+        .snapshot()
+        .iter()
+        .filter(|&(_, lease_set)| lease_set.contains(&lease))
+        .map(|(host, _)| host.clone())
+        .collect();
+    hosts.sort();
+    axum::Json(hosts).into_response()
+}
+
+/// Entry of the host list returned by `GET /api/hosts`.
+///
+/// The host config without its shared secret and hooks, which may contain credentials.
+#[derive(Debug, Serialize)]
+struct HostSummary {
+    name: String,
+    /// Effective address, i.e. including runtime overrides learned from agent broadcasts.
+    ip: IpAddr,
+    mac: Vec<String>,
+    wol_broadcast: Option<IpAddr>,
+    port: u16,
+    online: bool,
+    enforce_state: bool,
+    tags: Vec<String>,
+    /// Notes as configured, in Markdown.
+    notes: Option<String>,
+}
+
+/// Returns all configured hosts with their metadata and online status, sorted by name.
+#[axum::debug_handler]
+async fn get_hosts(State(state): State<AppState>) -> impl IntoResponse {
+    let mut names: Vec<String> = state.config_rx.borrow().hosts.keys().cloned().collect();
+    names.sort();
+
+    let mut hosts = Vec::with_capacity(names.len());
+    for name in names {
+        // The config may have been reloaded in the meantime.
+        let Some(resolved) = lookup_host_with_overrides(&state, &name).await else {
+            continue;
+        };
+        let host = resolved.host.clone();
+        hosts.push(HostSummary {
+            online: state.host_actor.get_current_state(&name) == HostState::Online,
+            ip: host.ip,
+            mac: host.mac,
+            wol_broadcast: host.wol_broadcast,
+            port: host.port,
+            enforce_state: host.enforce_state,
+            tags: host.tags,
+            notes: host.notes,
+            name,
+        });
+    }
+    axum::Json(hosts)
+}
+
+/// Details of a single host as returned by `GET /api/hosts/{hostname}`.
+#[derive(Debug, Serialize)]
+struct HostDetails {
+    hostname: String,
+    /// Effective address, i.e. including runtime overrides learned from agent broadcasts.
+    ip: IpAddr,
+    mac: Vec<String>,
+    port: u16,
+    online: bool,
+    leases: LeaseSources,
+    enforce_state: bool,
+    /// Time of the most recent poll that found the host online.
+    last_seen: Option<DateTime<Utc>>,
+    /// Version of the host agent, as last reported in a status reply.
+    agent_version: Option<String>,
+    /// Notes as configured, in Markdown.
+    notes: Option<String>,
+    /// The notes rendered to sanitized HTML.
+    notes_html: Option<String>,
+}
+
+/// Returns configuration, current status and active leases of a single host.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state))]
+async fn get_host_details(
+    Path(hostname): Path<String>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let Some(resolved) = lookup_host_with_overrides(&state, &hostname).await else {
+        return host_not_found(&hostname);
+    };
+    let last_seen = state.last_seen.read().await.get(&hostname).copied();
+    let agent_version = state
+        .host_install_info
+        .read()
         .await
-        .unwrap_or_else(|e| match e {});
+        .get(&hostname)
+        .and_then(|info| info.agent_version.clone());
 
-    // Broadcast updated lease information to WebSocket clients
-    // (the broadcast_lease_updates background task handles this via the LeaseRx watch channel)
+    axum::Json(HostDetails {
+        online: state.host_actor.get_current_state(&hostname) == HostState::Online,
+        leases: state.leases.get_host(&hostname),
+        ip: resolved.host.ip,
+        mac: resolved.host.mac.clone(),
+        port: resolved.host.port,
+        enforce_state: resolved.host.enforce_state,
+        last_seen,
+        agent_version,
+        notes_html: resolved.host.notes_html(),
+        notes: resolved.host.notes.clone(),
+        hostname,
+    })
+    .into_response()
+}
+
+/// Sends `WoL` packets to a host right away, without taking a lease.
+///
+/// For manual maintenance, e.g. to wake a host that no client currently needs. The lease map is
+/// left untouched, so the reconciler may shut a host with `enforce_state` down again.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state))]
+async fn handle_force_wake(
+    Path(hostname): Path<String>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match force_wake(&hostname, &state).await {
+        Ok(()) => "WoL packets sent".into_response(),
+        Err(e) => host_control_error_response(&e),
+    }
+}
 
-    // Reconciler will handle host control for any newly unleased hosts.
+/// Query parameters of [`handle_test_host_wol`].
+#[derive(Debug, Deserialize)]
+struct HostWolTestQuery {
+    /// UDP port of the agent's `test-wol` listener. Defaults to the agent port + 1, like the
+    /// listener itself.
+    port: Option<u16>,
+}
 
-    format!("All leases for client '{client_id}' have been reset.").into_response()
+/// Response body of [`handle_test_host_wol`].
+#[derive(Debug, Serialize)]
+struct HostWolTestResponse {
+    /// Whether packets sent the way the host is woken reach it, i.e. `broadcast_reached`.
+    reachable: bool,
+    broadcast_reached: bool,
+    unicast_reached: bool,
+    /// Why the test could not be run, e.g. because a packet could not be sent.
+    error: Option<String>,
 }
 
-/// Returns the online status of all hosts as a JSON object.
+/// Tests whether packets from the coordinator reach a host, both via its `WoL` destination and
+/// directly at its IP.
+///
+/// Requires `shuthost_host_agent test-wol` to be running on the host, which answers the test
+/// packets. Takes up to two seconds if they are not answered.
 #[axum::debug_handler]
-async fn get_hosts_status(State(state): State<AppState>) -> impl IntoResponse {
-    let hoststatus = state.host_actor.borrow().clone();
-    axum::Json((*hoststatus).clone())
+#[tracing::instrument(skip(state))]
+async fn handle_test_host_wol(
+    Path(hostname): Path<String>,
+    Query(query): Query<HostWolTestQuery>,
+    State(state): State<AppState>,
+) -> Response {
+    let Some(resolved) = lookup_host_with_overrides(&state, &hostname).await else {
+        return host_not_found(&hostname);
+    };
+    let (ip, wol_broadcast) = (resolved.host.ip, resolved.host.wol_broadcast);
+    let port = query
+        .port
+        .unwrap_or_else(|| resolved.host.port.saturating_add(1));
+    let interfaces = state.config_rx.borrow().server.wol_bind_addresses();
+
+    // The test blocks on socket reads, so it must not run on the async runtime.
+    let result = match task::spawn_blocking(move || {
+        wol::test_host_wol_reachability(ip, wol_broadcast, port, &interfaces)
+    })
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => Err(e.into()),
+    };
+    let response = match result {
+        Ok(test) => HostWolTestResponse {
+            reachable: test.broadcast_reached,
+            broadcast_reached: test.broadcast_reached,
+            unicast_reached: test.unicast_reached,
+            error: None,
+        },
+        Err(e) => {
+            warn!("WoL test failed: {e:#}");
+            HostWolTestResponse {
+                reachable: false,
+                broadcast_reached: false,
+                unicast_reached: false,
+                error: Some(format!("{e:#}")),
+            }
+        }
+    };
+    axum::Json(response).into_response()
+}
+
+/// Request body of [`handle_force_shutdown`].
+#[derive(Debug, Deserialize)]
+struct ForceShutdownRequest {
+    /// Must be `true`, to guard against accidental shutdowns.
+    #[serde(default)]
+    confirm: bool,
+}
+
+/// Sends the shutdown command to a host right away, ignoring its leases.
+///
+/// Requires `{"confirm": true}` as body. Leases are left untouched, so the reconciler may wake a
+/// host with `enforce_state` again if it is still leased.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state, body))]
+async fn handle_force_shutdown(
+    Path(hostname): Path<String>,
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<ForceShutdownRequest>,
+) -> Result<&'static str, Response> {
+    if !body.confirm {
+        return Err(
+            ApiError::BadRequest("Shutdown requires {\"confirm\": true}".to_string())
+                .into_response(),
+        );
+    }
+    force_shutdown(&hostname, &state)
+        .await
+        .map_err(|e| host_control_error_response(&e))?;
+    Ok("Shutdown command sent")
+}
+
+/// Request body of [`handle_rotate_host_secret`].
+#[derive(Deserialize)]
+struct RotateSecretRequest {
+    new_secret: SecretString,
+}
+
+/// Response of [`handle_rotate_host_secret`].
+#[derive(Debug, Serialize)]
+struct RotatedSecret {
+    hostname: String,
+    message: &'static str,
+}
+
+/// Replaces the shared secret of a host without restarting the coordinator.
+///
+/// Only the in-memory config is updated, the operator has to change the config file (and restart
+/// the host agent with the new secret) separately.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state, body))]
+async fn handle_rotate_host_secret(
+    Path(hostname): Path<String>,
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<RotateSecretRequest>,
+) -> Result<axum::Json<RotatedSecret>, Response> {
+    if body.new_secret.expose_secret().trim().is_empty() {
+        return Err(
+            ApiError::BadRequest("The new secret must not be empty".to_string()).into_response(),
+        );
+    }
+    if !rotate_host_secret(&state, &hostname, body.new_secret).await {
+        return Err(host_not_found(&hostname));
+    }
+    Ok(axum::Json(RotatedSecret {
+        hostname,
+        message: "Shared secret rotated, update the config file to persist it",
+    }))
+}
+
+/// A M2M client as returned by `GET /api/clients`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct ClientInfo {
+    client_id: String,
+    /// Time of the client's most recent M2M request, `None` if it never made one.
+    last_used: Option<DateTime<Utc>>,
+}
+
+/// Lists the configured clients and those with recorded usage, sorted by ID.
+///
+/// Clients that were removed from the config but still have usage recorded are included,
+/// so their last activity can still be audited.
+fn list_clients<'cfg>(
+    configured: impl Iterator<Item = &'cfg String>,
+    usage: HashMap<String, db::ClientStats>,
+) -> Vec<ClientInfo> {
+    let mut clients: BTreeMap<String, Option<DateTime<Utc>>> = configured
+        .map(|client_id| (client_id.clone(), None))
+        .collect();
+    for (client_id, client_stats) in usage {
+        clients.insert(client_id, client_stats.last_used);
+    }
+    clients
+        .into_iter()
+        .map(|(client_id, last_used)| ClientInfo {
+            client_id,
+            last_used,
+        })
+        .collect()
+}
+
+/// Returns the M2M clients and when they last made a request as a JSON array.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state))]
+async fn get_clients(State(state): State<AppState>) -> impl IntoResponse {
+    let usage = match state.db_pool {
+        Some(ref pool) => match db::get_all_client_stats(pool).await {
+            Ok(usage) => usage,
+            Err(e) => {
+                error!("Failed to load client stats: {e}");
+                return json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "database_error",
+                    "Failed to load client stats",
+                );
+            }
+        },
+        None => HashMap::new(),
+    };
+    let clients = list_clients(state.config_rx.borrow().clients.keys(), usage);
+    axum::Json(clients).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(id: &str) -> LeaseSource {
+        LeaseSource::Client(id.to_string())
+    }
+
+    #[test]
+    fn taking_held_lease_is_unchanged() {
+        let mut lease_set = LeaseSources::default();
+        assert!(apply_lease_action(
+            &mut lease_set,
+            client("c"),
+            LeaseAction::Take,
+            None
+        ));
+        assert!(!apply_lease_action(
+            &mut lease_set,
+            client("c"),
+            LeaseAction::Take,
+            None
+        ));
+        assert_eq!(lease_set.len(), 1);
+        assert_eq!(
+            unchanged_lease_response(LeaseAction::Take),
+            "Lease already held"
+        );
+    }
+
+    #[test]
+    fn releasing_unheld_lease_is_unchanged() {
+        let mut lease_set: LeaseSources = vec![client("other")].into_iter().collect();
+        assert!(!apply_lease_action(
+            &mut lease_set,
+            client("c"),
+            LeaseAction::Release,
+            None
+        ));
+        assert!(lease_set.contains(&client("other")));
+        assert!(apply_lease_action(
+            &mut lease_set,
+            client("other"),
+            LeaseAction::Release,
+            None
+        ));
+        assert_eq!(
+            unchanged_lease_response(LeaseAction::Release),
+            "Lease not held"
+        );
+    }
+
+    #[test]
+    fn lease_limit_counts_across_hosts() {
+        let leases = LeaseMap::from([
+            ("a".to_string(), vec![client("c")].into_iter().collect()),
+            ("b".to_string(), vec![client("other")].into_iter().collect()),
+        ]);
+
+        assert!(exceeds_lease_limit(&leases, "b", &client("c"), 1));
+        assert!(!exceeds_lease_limit(&leases, "b", &client("c"), 2));
+        assert!(
+            !exceeds_lease_limit(&leases, "a", &client("c"), 1),
+            "re-taking a held lease is allowed"
+        );
+        assert!(
+            !exceeds_lease_limit(&leases, "b", &client("c"), 0),
+            "0 is unlimited"
+        );
+    }
+
+    #[test]
+    fn clients_include_unused_and_removed_ones() {
+        let used_at = DateTime::parse_from_rfc3339("2024-01-15T10:30:00Z")
+            .unwrap()
+            .to_utc();
+        let configured = ["ci-runner".to_string(), "laptop".to_string()];
+        let stats = HashMap::from([
+            (
+                "ci-runner".to_string(),
+                db::ClientStats {
+                    last_used: Some(used_at),
+                },
+            ),
+            (
+                "removed".to_string(),
+                db::ClientStats {
+                    last_used: Some(used_at),
+                },
+            ),
+        ]);
+
+        let clients = list_clients(configured.iter(), stats);
+        assert_eq!(
+            clients,
+            [
+                ClientInfo {
+                    client_id: "ci-runner".to_string(),
+                    last_used: Some(used_at),
+                },
+                ClientInfo {
+                    client_id: "laptop".to_string(),
+                    last_used: None,
+                },
+                ClientInfo {
+                    client_id: "removed".to_string(),
+                    last_used: Some(used_at),
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&clients[0]).unwrap(),
+            serde_json::json!({"client_id": "ci-runner", "last_used": "2024-01-15T10:30:00Z"})
+        );
+    }
 }
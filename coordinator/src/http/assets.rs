@@ -18,7 +18,9 @@ use axum_extra::{
 use mime::{IMAGE_SVG, TEXT_CSS};
 use serde::Serialize;
 
-use crate::{app::AppState, http::EXPECTED_AUTH_EXCEPTIONS_VERSION, http::auth::Resolved};
+use crate::{
+    app::AppState, demo::DemoConfig, http::EXPECTED_AUTH_EXCEPTIONS_VERSION, http::auth::Resolved,
+};
 
 #[expect(
     nonstandard_style,
@@ -147,6 +149,7 @@ pub(crate) enum UiMode<'params> {
     },
     Demo {
         subpath: &'params str,
+        config: &'params DemoConfig,
     },
 }
 
@@ -159,6 +162,9 @@ struct UiServerData<'strings> {
     /// Demo mode signal: `Some` means demo mode, `None` means normal mode.
     #[serde(skip_serializing_if = "Option::is_none")]
     demo_subpath: Option<&'strings str>,
+    /// Parameters of the simulated hosts, only in demo mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    demo_config: Option<&'strings DemoConfig>,
     auth_mode: &'strings str,
     broadcast_port: u16,
     db_enabled: bool,
@@ -178,14 +184,16 @@ pub(crate) fn render_ui_html(mode: &UiMode<'_>) -> String {
             config_path: config_path.to_string_lossy(),
             auth_warning,
             demo_subpath: None,
+            demo_config: None,
             auth_mode,
             broadcast_port,
             db_enabled,
         },
-        UiMode::Demo { subpath } => UiServerData {
+        UiMode::Demo { subpath, config } => UiServerData {
             config_path: borrow::Cow::Borrowed("/this/is/a/demo.toml"),
             auth_warning: false,
             demo_subpath: Some(subpath),
+            demo_config: Some(config),
             auth_mode: "disabled",
             broadcast_port: shuthost_common::DEFAULT_COORDINATOR_BROADCAST_PORT,
             db_enabled: true,
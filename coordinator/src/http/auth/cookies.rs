@@ -129,6 +129,19 @@ pub(crate) fn create_protected_cookie(
         .build()
 }
 
+/// Whether `return_to` is a path on this server, so redirecting to it after login can't send the
+/// user to another site. Protocol-relative URLs like `//evil.example` are rejected as well, and so
+/// is any control or whitespace character, since browsers strip some of them from URLs, turning
+/// e.g. `/\t/evil.example` into a protocol-relative URL.
+pub(crate) fn is_local_return_to(return_to: &str) -> bool {
+    return_to.starts_with('/')
+        && !return_to.starts_with("//")
+        && !return_to.starts_with("/\\")
+        && !return_to
+            .chars()
+            .any(|c| c.is_ascii_control() || c.is_ascii_whitespace())
+}
+
 /// Create a return-to cookie for redirect-after-login functionality.
 pub(crate) fn create_return_to_cookie(return_to: String) -> Cookie<'static> {
     create_protected_cookie(COOKIE_RETURN_TO, return_to, CookieDuration::minutes(10))
@@ -175,7 +188,36 @@ pub(crate) fn extract_return_to_and_remove_cookie(
 ) -> (String, SignedCookieJar) {
     let return_to = jar
         .get(COOKIE_RETURN_TO)
-        .map_or_else(|| "/".to_string(), |c| c.value().to_string());
+        .map(|c| c.value().to_string())
+        .filter(|return_to| is_local_return_to(return_to))
+        .unwrap_or_else(|| "/".to_string());
     let jar = jar.remove(Cookie::build(COOKIE_RETURN_TO).path("/").build());
     (return_to, jar)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_return_to_accepts_paths() {
+        assert!(is_local_return_to("/"));
+        assert!(is_local_return_to("/some/page?x=1"));
+    }
+
+    #[test]
+    fn local_return_to_rejects_other_sites() {
+        assert!(!is_local_return_to("https://evil.example/"));
+        assert!(!is_local_return_to("//evil.example/"));
+        assert!(!is_local_return_to("/\\evil.example/"));
+    }
+
+    #[test]
+    fn local_return_to_rejects_control_and_whitespace_characters() {
+        assert!(!is_local_return_to("/\t/evil.example"));
+        assert!(!is_local_return_to("/\n/evil.example"));
+        assert!(!is_local_return_to("/\r/evil.example"));
+        assert!(!is_local_return_to("/ /evil.example"));
+        assert!(!is_local_return_to("/page\u{7f}"));
+    }
+}
@@ -3,18 +3,21 @@
 use axum::{
     body::Body,
     extract::State,
-    http::{HeaderMap, Request, StatusCode, header},
+    http::{HeaderMap, Request, header},
     middleware::Next,
     response::{IntoResponse as _, Redirect, Response},
 };
 use axum_extra::extract::cookie::SignedCookieJar;
 
-use crate::http::auth::{
-    LOGIN_ERROR_SESSION_EXPIRED, LayerState, Resolved,
-    cookies::{
-        create_return_to_cookie, get_oidc_session_from_cookie, get_token_session_from_cookie,
+use crate::http::{
+    auth::{
+        LOGIN_ERROR_SESSION_EXPIRED, LayerState, Resolved,
+        cookies::{
+            create_return_to_cookie, get_oidc_session_from_cookie, get_token_session_from_cookie,
+        },
+        login_error_redirect,
     },
-    login_error_redirect,
+    error::ApiError,
 };
 
 /// Middleware that enforces authentication depending on configured mode.
@@ -32,51 +35,52 @@ pub(crate) async fn require(
         // external auth is not acknowledged or has mismatched version.
         Resolved::Disabled | Resolved::External { .. } => next.run(req).await,
         Resolved::Token { ref token } => {
+            let token = token.read().clone();
             // Token auth uses a signed cookie with claims (iat, exp, token_hash)
             if let Some(claims) = get_token_session_from_cookie(&jar) {
                 if claims.is_expired() {
-                    tracing::info!("require: token session expired, redirecting to login");
-                    return redirect_with_return_to(
+                    tracing::info!("require: token session expired");
+                    return unauthenticated(
                         jar,
                         &req,
                         login_error_redirect(LOGIN_ERROR_SESSION_EXPIRED),
                     );
                 }
-                if claims.matches_token(token) {
+                if claims.matches_token(&token) {
                     return next.run(req).await;
                 }
             }
-            if wants_html(headers) {
-                // remember path for redirect-after-login
-                redirect_with_return_to(jar, &req, Redirect::temporary("/login"))
-            } else {
-                StatusCode::UNAUTHORIZED.into_response()
-            }
+            unauthenticated(jar, &req, Redirect::temporary("/login"))
         }
         Resolved::Oidc { .. } => {
             // Check signed session cookie via headers
             if let Some(sess) = get_oidc_session_from_cookie(&jar) {
                 return if sess.is_expired() {
-                    tracing::info!("require: OIDC session expired, redirecting to login");
-                    redirect_with_return_to(
-                        jar,
-                        &req,
-                        login_error_redirect(LOGIN_ERROR_SESSION_EXPIRED),
-                    )
+                    tracing::info!("require: OIDC session expired");
+                    unauthenticated(jar, &req, login_error_redirect(LOGIN_ERROR_SESSION_EXPIRED))
                 } else {
                     next.run(req).await
                 };
             }
-            tracing::info!("require: no valid session cookie, redirecting to /login");
-            if wants_html(headers) {
-                redirect_with_return_to(jar, &req, Redirect::temporary("/login"))
-            } else {
-                StatusCode::UNAUTHORIZED.into_response()
-            }
+            tracing::info!("require: no valid session cookie");
+            unauthenticated(jar, &req, Redirect::temporary("/login"))
         }
     }
 }
 
+/// Response for a request without a valid session.
+///
+/// Browsers are sent to the login page via `redirect`, other clients such as scripts get a
+/// JSON `401` with a `WWW-Authenticate` header instead.
+fn unauthenticated(jar: SignedCookieJar, req: &Request<Body>, redirect: Redirect) -> Response {
+    if prefers_html(req.headers()) {
+        // remember path for redirect-after-login
+        redirect_with_return_to(jar, req, redirect)
+    } else {
+        ApiError::Unauthorized.into_response()
+    }
+}
+
 /// Helper function to redirect with `return_to` cookie set.
 fn redirect_with_return_to(
     jar: SignedCookieJar,
@@ -89,12 +93,32 @@ fn redirect_with_return_to(
     (jar, redirect).into_response()
 }
 
-/// Check if the request wants HTML content based on Accept header.
-fn wants_html(headers: &HeaderMap) -> bool {
-    headers
-        .get(header::ACCEPT)
-        .and_then(|v| v.to_str().ok())
-        .is_some_and(|s| s.contains("text/html"))
+/// Whether the `Accept` header prefers HTML over JSON, i.e. the request likely comes from a
+/// browser navigating to the page. HTML must be listed explicitly, wildcards don't count.
+fn prefers_html(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let html = accept_quality(accept, "text/html");
+    html > 0.0 && html >= accept_quality(accept, "application/json")
+}
+
+/// Highest quality value `accept` assigns to `media_type` explicitly, 0 if it isn't listed.
+fn accept_quality(accept: &str, media_type: &str) -> f32 {
+    accept
+        .split(',')
+        .filter_map(|entry| {
+            let mut params = entry.split(';').map(str::trim);
+            if !params.next()?.eq_ignore_ascii_case(media_type) {
+                return None;
+            }
+            Some(
+                params
+                    .find_map(|param| param.strip_prefix("q="))
+                    .map_or(1.0, |q| q.parse::<f32>().unwrap_or(0.0)),
+            )
+        })
+        .fold(0.0, f32::max)
 }
 
 /// Determine whether the incoming request should be considered secure.
@@ -124,3 +148,33 @@ pub(crate) fn request_is_secure(headers: &HeaderMap, tls_enabled: bool) -> bool
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn browsers_prefer_html() {
+        assert!(prefers_html(&accept(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+        )));
+        assert!(prefers_html(&accept("application/json;q=0.5, text/html")));
+    }
+
+    #[test]
+    fn api_clients_do_not_prefer_html() {
+        assert!(!prefers_html(&HeaderMap::new()));
+        assert!(!prefers_html(&accept("*/*")));
+        assert!(!prefers_html(&accept("application/json")));
+        assert!(!prefers_html(&accept("application/json, text/html;q=0.5")));
+        assert!(!prefers_html(&accept("text/html;q=0")));
+    }
+}
@@ -23,6 +23,7 @@ use axum::{extract::FromRef, response::Redirect};
 use axum_extra::extract::cookie::Key;
 use base64::{Engine as _, engine::general_purpose::STANDARD as base64_gp_STANDARD};
 use eyre::Context as _;
+use parking_lot::RwLock;
 use secrecy::{ExposeSecret as _, SecretString};
 use tracing::{Instrument as _, info, warn};
 
@@ -59,7 +60,8 @@ pub(crate) type SharedOidcClient = OidcClientReady;
 pub(crate) enum Resolved {
     Disabled,
     Token {
-        token: Arc<SecretString>,
+        /// Behind a lock so [`Runtime::rotate_token`] can replace it while running.
+        token: RwLock<Arc<SecretString>>,
     },
     /// Resolved OIDC mode. The `config` field retains the original values from
     /// configuration so the client can be rebuilt on demand (e.g. when a
@@ -82,6 +84,14 @@ impl Resolved {
             Self::External { .. } => "external",
         }
     }
+
+    /// The current token, if in token mode.
+    pub(crate) fn token(&self) -> Option<Arc<SecretString>> {
+        match *self {
+            Self::Token { ref token } => Some(token.read().clone()),
+            Self::Disabled | Self::Oidc { .. } | Self::External { .. } => None,
+        }
+    }
 }
 
 /// Custom debug impl to cut down on logging clutter
@@ -159,6 +169,29 @@ impl Runtime {
 
         Ok(Self { mode, cookie_key })
     }
+
+    /// Replaces the token with a newly generated one, persisted if a database is available.
+    ///
+    /// Sessions created with the old token are rejected afterwards. Returns `None` when not in
+    /// token mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if storing the new token fails, in which case the old token stays valid.
+    pub(crate) async fn rotate_token(
+        &self,
+        db_pool: Option<&DbPool>,
+    ) -> eyre::Result<Option<Arc<SecretString>>> {
+        let Resolved::Token { ref token } = self.mode else {
+            return Ok(None);
+        };
+        let generated = cookies::generate_token();
+        if let Some(pool) = db_pool {
+            db::store_kv(pool, KV_AUTH_TOKEN, generated.expose_secret()).await?;
+        }
+        *token.write() = generated.clone();
+        Ok(Some(generated))
+    }
 }
 
 /// Set up the cookie key from config or database.
@@ -224,7 +257,9 @@ async fn resolve_token_auth(
         resolve_auto_token(db_pool).in_current_span().await?
     };
 
-    Ok(Resolved::Token { token })
+    Ok(Resolved::Token {
+        token: RwLock::new(token),
+    })
 }
 
 /// Resolve token when not configured (try DB, then generate).
@@ -272,11 +307,11 @@ impl FromRef<AppState> for Key {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::AuthConfig;
+    use crate::config::{AuthConfig, DEFAULT_DB_POOL_SIZE};
     use std::path::Path;
 
     async fn setup_db() -> eyre::Result<DbPool> {
-        db::init(Path::new(":memory:")).await
+        db::init(Path::new(":memory:"), DEFAULT_DB_POOL_SIZE).await
     }
 
     #[tokio::test]
@@ -312,10 +347,51 @@ mod tests {
         assert!(db::get_kv(&pool, KV_COOKIE_SECRET).await.unwrap().is_none());
 
         // runtime should use configured token
-        match runtime.mode {
-            Resolved::Token { token } => assert_eq!((*token).expose_secret(), "configured_token"),
-            _ => panic!("expected token mode"),
-        }
+        assert_eq!(
+            runtime.mode.token().unwrap().expose_secret(),
+            "configured_token"
+        );
+    }
+
+    #[tokio::test]
+    async fn rotated_token_replaces_stored_one() {
+        let pool = setup_db().await.unwrap();
+        let cfg = AuthConfig {
+            mode: AuthMode::Token { token: None },
+            cookie_secret: None,
+        };
+        let runtime = Runtime::from_config(&cfg, Some(&pool)).await.unwrap();
+        let old = runtime.mode.token().unwrap();
+        let old_session = cookies::TokenSessionClaims::new(old.expose_secret());
+
+        let rotated = runtime.rotate_token(Some(&pool)).await.unwrap().unwrap();
+
+        assert_ne!(rotated.expose_secret(), old.expose_secret());
+        assert_eq!(
+            runtime.mode.token().unwrap().expose_secret(),
+            rotated.expose_secret()
+        );
+        assert_eq!(
+            db::get_kv(&pool, KV_AUTH_TOKEN).await.unwrap().as_deref(),
+            Some(rotated.expose_secret())
+        );
+        assert!(!old_session.matches_token(&rotated));
+
+        let restarted = Runtime::from_config(&cfg, Some(&pool)).await.unwrap();
+        assert_eq!(
+            restarted.mode.token().unwrap().expose_secret(),
+            rotated.expose_secret()
+        );
+    }
+
+    #[tokio::test]
+    async fn rotation_requires_token_mode() {
+        let cfg = AuthConfig {
+            mode: AuthMode::None,
+            cookie_secret: None,
+        };
+        let runtime = Runtime::from_config(&cfg, None).await.unwrap();
+        assert!(runtime.rotate_token(None).await.unwrap().is_none());
     }
 
     #[tokio::test]
@@ -1,22 +1,28 @@
 use axum::{
-    Form,
+    Form, Json,
     extract::State,
-    http,
-    response::{IntoResponse, Redirect},
+    http::{self, StatusCode},
+    response::{IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::cookie::SignedCookieJar;
 use cookie::time::Duration as CookieDuration;
 use secrecy::{ExposeSecret as _, SecretString};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
 
 use crate::{
     app::AppState,
-    http::auth::{
-        LOGIN_ERROR_INSECURE, LOGIN_ERROR_TOKEN, Resolved,
-        cookies::{
-            TokenSessionClaims, create_token_session_cookie, extract_return_to_and_remove_cookie,
+    config::AuthMode,
+    http::{
+        auth::{
+            LOGIN_ERROR_INSECURE, LOGIN_ERROR_TOKEN,
+            cookies::{
+                TokenSessionClaims, create_token_session_cookie,
+                extract_return_to_and_remove_cookie,
+            },
+            login_error_redirect, request_is_secure,
         },
-        login_error_redirect, request_is_secure,
+        error::json_error,
     },
 };
 
@@ -41,12 +47,9 @@ pub(crate) async fn login_post(
         );
         return login_error_redirect(LOGIN_ERROR_INSECURE).into_response();
     }
-    match &auth.mode {
-        &Resolved::Token {
-            token: ref expected,
-            ..
-        } if token.expose_secret() == expected.expose_secret() => {
-            let claims = TokenSessionClaims::new((*expected).expose_secret());
+    match auth.mode.token() {
+        Some(expected) if token.expose_secret() == expected.expose_secret() => {
+            let claims = TokenSessionClaims::new(expected.expose_secret());
             let cookie = create_token_session_cookie(
                 &claims,
                 CookieDuration::seconds(
@@ -63,3 +66,53 @@ pub(crate) async fn login_post(
         _ => login_error_redirect(LOGIN_ERROR_TOKEN).into_response(),
     }
 }
+
+/// Response body of [`rotate_token`].
+#[derive(Serialize)]
+struct RotateTokenResponse {
+    message: &'static str,
+}
+
+/// Replaces the auto-generated login token with a new one, e.g. after it leaked.
+///
+/// The new token is logged like a freshly generated one, and all sessions, including the one
+/// making this request, have to log in again. A token set in the config file can't be rotated
+/// here, as the config would override it on the next start.
+#[axum::debug_handler]
+#[tracing::instrument(skip(state))]
+pub(crate) async fn rotate_token(State(state): State<AppState>) -> Response {
+    if matches!(
+        state.config_rx.borrow().server.auth.mode,
+        AuthMode::Token { token: Some(_) }
+    ) {
+        return json_error(
+            StatusCode::CONFLICT,
+            "token_configured",
+            "The token is set in the config file, change it there instead",
+        );
+    }
+    match state.auth.rotate_token(state.db_pool.as_ref()).await {
+        Ok(Some(token)) => {
+            info!("Auth token rotated");
+            // We expose the generated token in logs once for operator use
+            info!("Token: {}", token.expose_secret());
+            Json(RotateTokenResponse {
+                message: "Token rotated. New token has been logged.",
+            })
+            .into_response()
+        }
+        Ok(None) => json_error(
+            StatusCode::CONFLICT,
+            "not_token_auth",
+            "Token rotation requires token authentication",
+        ),
+        Err(e) => {
+            error!("Failed to rotate token: {e}");
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "database_error",
+                "Failed to store the new token",
+            )
+        }
+    }
+}
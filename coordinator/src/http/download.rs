@@ -1,10 +1,21 @@
-use axum::{Router, http::StatusCode, response::IntoResponse, routing::get};
+use axum::{
+    Router,
+    extract::Query,
+    http::{StatusCode, header::CONTENT_DISPOSITION},
+    response::{IntoResponse, Response},
+    routing::get,
+};
 use axum_extra::{
     TypedHeader,
     headers::{ContentLength, ContentType},
 };
+use reqwest::Url;
+use serde::Deserialize;
 
-use crate::app::AppState;
+use crate::{
+    app::AppState,
+    http::error::{ApiError, json_error},
+};
 
 /// Macro to define a download handler function for a static plain text document
 macro_rules! static_text_download_handler {
@@ -38,6 +49,10 @@ macro_rules! host_agent_handler {
             (
                 TypedHeader(ContentType::from(mime::APPLICATION_OCTET_STREAM)),
                 TypedHeader(ContentLength(AGENT_BINARY.len() as u64)),
+                [(
+                    CONTENT_DISPOSITION,
+                    concat!("attachment; filename=\"shuthost_host_agent", $ext, "\""),
+                )],
                 AGENT_BINARY,
             )
         }
@@ -51,10 +66,7 @@ macro_rules! host_agent_handler {
         #[cfg(not(feature = $feature))]
         #[axum::debug_handler]
         async fn $name() -> impl IntoResponse {
-            (
-                StatusCode::NOT_FOUND,
-                "This agent is not available in this build.",
-            )
+            ApiError::NotFound("This agent is not available in this build.".to_string())
         }
     };
 }
@@ -99,6 +111,95 @@ static_text_download_handler!(fn download_client_installer_ps1, file = "scripts/
 static_text_download_handler!(fn download_client_script, file = "scripts/enduser_templates/shuthost_client.tmpl.sh");
 static_text_download_handler!(fn download_client_script_ps1, file = "scripts/enduser_templates/shuthost_client.tmpl.ps1");
 
+/// Values to fill into a downloaded client script.
+#[derive(Debug, Default, Deserialize)]
+struct ClientScriptQuery {
+    /// Base URL of the coordinator the client talks to.
+    coordinator_url: Option<String>,
+    /// ID the client is registered under in `[clients]`.
+    client_id: Option<String>,
+}
+
+/// Why a client script couldn't be rendered: status, error code and message for [`json_error`].
+type Rejection = (StatusCode, &'static str, &'static str);
+
+/// Fills the `{embedded_remote_url}` and `{client_id}` placeholders of a client script template.
+///
+/// Placeholders without a value are left for the installer (or the operator) to fill in. The
+/// shared secret is never filled in, as this route is public.
+///
+/// # Errors
+///
+/// Rejects values that are invalid or could break out of the quoted string they're inserted into.
+fn render_client_script(template: &str, query: &ClientScriptQuery) -> Result<String, Rejection> {
+    let mut script = template.to_string();
+
+    if let Some(ref raw_url) = query.coordinator_url {
+        let url = Url::parse(raw_url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or((
+                StatusCode::BAD_REQUEST,
+                "invalid_coordinator_url",
+                "coordinator_url must be an http(s) URL",
+            ))?;
+        let url = url.as_str().trim_end_matches('/');
+        let is_safe = url.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '-' | '.' | '_' | '~' | ':' | '/' | '%' | '[' | ']')
+        });
+        if !is_safe {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "invalid_coordinator_url",
+                "coordinator_url must not contain a query, quotes or shell metacharacters",
+            ));
+        }
+        script = script.replace("{embedded_remote_url}", url);
+    }
+
+    if let Some(ref client_id) = query.client_id {
+        let is_safe = client_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if client_id.is_empty() || !is_safe {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "invalid_client_id",
+                "client_id may only contain letters, digits, '-', '_' and '.'",
+            ));
+        }
+        script = script.replace("{client_id}", client_id);
+    }
+
+    Ok(script)
+}
+
+/// Defines a handler serving a client script template with the values of [`ClientScriptQuery`] filled in.
+macro_rules! client_script_handler {
+    (fn $name:ident, file=$file:expr, filename=$filename:expr) => {
+        #[axum::debug_handler]
+        async fn $name(Query(query): Query<ClientScriptQuery>) -> Response {
+            const TEMPLATE: &str = include_str!(concat!("../../../", $file));
+            match render_client_script(TEMPLATE, &query) {
+                Ok(script) => (
+                    TypedHeader(ContentType::text()),
+                    [(
+                        CONTENT_DISPOSITION,
+                        concat!("attachment; filename=\"", $filename, "\""),
+                    )],
+                    script,
+                )
+                    .into_response(),
+                Err((status, code, message)) => json_error(status, code, message),
+            }
+        }
+    };
+}
+
+client_script_handler!(fn download_filled_client_script, file = "scripts/enduser_templates/shuthost_client.tmpl.sh", filename = "shuthost_client.sh");
+client_script_handler!(fn download_filled_client_script_ps1, file = "scripts/enduser_templates/shuthost_client.tmpl.ps1", filename = "shuthost_client.ps1");
+
 pub(crate) fn routes() -> Router<AppState> {
     Router::new()
         .route(
@@ -113,6 +214,8 @@ pub(crate) fn routes() -> Router<AppState> {
         .route("/client_installer.ps1", get(download_client_installer_ps1))
         .route("/shuthost_client.sh", get(download_client_script))
         .route("/shuthost_client.ps1", get(download_client_script_ps1))
+        .route("/client.sh", get(download_filled_client_script))
+        .route("/client.ps1", get(download_filled_client_script_ps1))
         .route("/host_agent/macos/aarch64", get(host_agent_macos_aarch64))
         .route("/host_agent/macos/x86_64", get(host_agent_macos_x86_64))
         .route(
@@ -129,3 +232,72 @@ pub(crate) fn routes() -> Router<AppState> {
             get(host_agent_windows_aarch64),
         )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str =
+        "URL=\"{embedded_remote_url}\"\nID=\"{client_id}\"\nSECRET=\"{shared_secret}\"\n";
+
+    fn query(coordinator_url: Option<&str>, client_id: Option<&str>) -> ClientScriptQuery {
+        ClientScriptQuery {
+            coordinator_url: coordinator_url.map(ToOwned::to_owned),
+            client_id: client_id.map(ToOwned::to_owned),
+        }
+    }
+
+    #[test]
+    fn fills_in_given_placeholders() {
+        let script = render_client_script(
+            TEMPLATE,
+            &query(
+                Some("https://coordinator.example.com/shuthost/"),
+                Some("ci_job-1"),
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            script,
+            "URL=\"https://coordinator.example.com/shuthost\"\nID=\"ci_job-1\"\nSECRET=\"{shared_secret}\"\n"
+        );
+        assert_eq!(
+            render_client_script(TEMPLATE, &ClientScriptQuery::default()).unwrap(),
+            TEMPLATE
+        );
+    }
+
+    #[test]
+    fn rejects_unsafe_values() {
+        for url in [
+            "not a url",
+            "ftp://example.com",
+            "https://example.com/$(reboot)",
+            "https://example.com/a;b",
+            "https://example.com/?a=b",
+        ] {
+            let (status, code, _) =
+                render_client_script(TEMPLATE, &query(Some(url), None)).unwrap_err();
+            assert_eq!(
+                (status, code),
+                (StatusCode::BAD_REQUEST, "invalid_coordinator_url"),
+                "{url}"
+            );
+        }
+        // Quotes are percent-encoded by the URL parser, so they can't end the shell string.
+        assert!(
+            render_client_script(TEMPLATE, &query(Some("https://example.com/\""), None))
+                .unwrap()
+                .contains("https://example.com/%22\"")
+        );
+        for client_id in ["", "a b", "a\"b", "$(reboot)"] {
+            let (status, code, _) =
+                render_client_script(TEMPLATE, &query(None, Some(client_id))).unwrap_err();
+            assert_eq!(
+                (status, code),
+                (StatusCode::BAD_REQUEST, "invalid_client_id"),
+                "{client_id}"
+            );
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Structured JSON error responses for the HTTP API.
+//!
+//! Error bodies have the form `{"error": code, "message": message}`: `code` is a stable,
+//! machine-readable `snake_case` identifier, `message` is meant for humans. The `x-request-id`
+//! response header correlates an error with the server logs.
+//!
+//! Handlers whose failures need a specific code use [`json_error`] directly. The others return
+//! [`ApiError`], which picks a generic code from the kind of failure.
+
+use axum::{
+    Json,
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use thiserror::Error as ThisError;
+use tracing::error;
+
+/// Builds a JSON error response with the given status, error code and message.
+pub(crate) fn json_error(status: StatusCode, code: &str, message: &str) -> Response {
+    (status, Json(json!({ "error": code, "message": message }))).into_response()
+}
+
+/// Error returned by HTTP handlers, rendered as a JSON error body.
+///
+/// `?` on an [`eyre::Result`] turns the report into [`ApiError::InternalError`], on a
+/// [`sqlx::Result`] the error into [`ApiError::Database`]. Their details are only logged, the
+/// client gets a generic message.
+#[derive(Debug, ThisError)]
+pub(crate) enum ApiError {
+    #[error("{0}")]
+    NotFound(String),
+    /// Rendered with a `WWW-Authenticate` header, so HTTP clients can discover the auth scheme.
+    #[error("Authentication required")]
+    Unauthorized,
+    #[error("Internal server error")]
+    InternalError(eyre::Report),
+    /// A database operation the request depends on failed, so its effect was not persisted.
+    #[error("Database operation failed")]
+    Database(#[from] sqlx::Error),
+    #[error("{0}")]
+    BadRequest(String),
+    /// The request conflicts with the current state, e.g. of a host.
+    #[error("{0}")]
+    Conflict(String),
+    /// A feature the request needs is not available, e.g. because persistence is disabled.
+    #[error("{0}")]
+    Unavailable(String),
+}
+
+impl ApiError {
+    const fn status_and_code(&self) -> (StatusCode, &'static str) {
+        match *self {
+            Self::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            Self::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized"),
+            Self::InternalError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+            Self::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "database_error"),
+            Self::BadRequest(_) => (StatusCode::BAD_REQUEST, "bad_request"),
+            Self::Conflict(_) => (StatusCode::CONFLICT, "conflict"),
+            Self::Unavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, "service_unavailable"),
+        }
+    }
+}
+
+/// `WWW-Authenticate` challenge sent with [`ApiError::Unauthorized`].
+const WWW_AUTHENTICATE_CHALLENGE: &str = "Bearer realm=\"shuthost\"";
+
+impl From<eyre::Report> for ApiError {
+    fn from(report: eyre::Report) -> Self {
+        Self::InternalError(report)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::InternalError(ref report) => error!("{report:#}"),
+            Self::Database(ref e) => error!("Database error: {e}"),
+            _ => {}
+        }
+        let (status, code) = self.status_and_code();
+        let mut response = json_error(status, code, &self.to_string());
+        if matches!(self, Self::Unauthorized) {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(WWW_AUTHENTICATE_CHALLENGE),
+            );
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body, http::header::CONTENT_TYPE};
+
+    use super::*;
+
+    async fn body_json(response: Response) -> serde_json::Value {
+        let body = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn json_error_has_code_and_message() {
+        let response = json_error(StatusCode::NOT_FOUND, "host_not_found", "Unknown host");
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(
+            body_json(response).await,
+            json!({ "error": "host_not_found", "message": "Unknown host" })
+        );
+    }
+
+    #[tokio::test]
+    async fn api_error_maps_to_status_and_body() {
+        let cases = [
+            (
+                ApiError::NotFound("No such host".to_string()),
+                StatusCode::NOT_FOUND,
+                json!({ "error": "not_found", "message": "No such host" }),
+            ),
+            (
+                ApiError::Unauthorized,
+                StatusCode::UNAUTHORIZED,
+                json!({ "error": "unauthorized", "message": "Authentication required" }),
+            ),
+            (
+                ApiError::Conflict("Host is busy".to_string()),
+                StatusCode::CONFLICT,
+                json!({ "error": "conflict", "message": "Host is busy" }),
+            ),
+            (
+                ApiError::BadRequest("Missing field".to_string()),
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "bad_request", "message": "Missing field" }),
+            ),
+        ];
+        for (error, status, body) in cases {
+            let response = error.into_response();
+            assert_eq!(response.status(), status);
+            assert_eq!(body_json(response).await, body);
+        }
+    }
+
+    #[test]
+    fn only_unauthorized_has_www_authenticate_challenge() {
+        let response = ApiError::Unauthorized.into_response();
+        assert_eq!(
+            response.headers()[header::WWW_AUTHENTICATE],
+            WWW_AUTHENTICATE_CHALLENGE
+        );
+
+        let response = ApiError::NotFound("No such host".to_string()).into_response();
+        assert!(!response.headers().contains_key(header::WWW_AUTHENTICATE));
+    }
+
+    #[tokio::test]
+    async fn eyre_reports_become_opaque_internal_errors() {
+        fn failing() -> Result<(), ApiError> {
+            Err(eyre::eyre!("database file is locked"))?;
+            Ok(())
+        }
+
+        let response = failing().unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            body_json(response).await,
+            json!({ "error": "internal_error", "message": "Internal server error" })
+        );
+    }
+
+    #[tokio::test]
+    async fn sqlx_errors_become_database_errors() {
+        fn failing() -> Result<(), ApiError> {
+            Err(sqlx::Error::PoolTimedOut)?;
+            Ok(())
+        }
+
+        let response = failing().unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            body_json(response).await,
+            json!({ "error": "database_error", "message": "Database operation failed" })
+        );
+    }
+}
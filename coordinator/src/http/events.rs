@@ -0,0 +1,96 @@
+//! Server-Sent Events stream of host status and lease changes.
+//!
+//! A read-only alternative to the WebSocket for clients that only want to follow state changes,
+//! e.g. scripts using `curl`. It forwards the same broadcasts as the WebSocket.
+
+use core::convert::Infallible;
+
+use axum::{
+    Router,
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+};
+use futures::{Stream, StreamExt as _, stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+use crate::{
+    app::{AppState, HostState},
+    websocket::WsMessage,
+};
+
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new().route("/api/events", get(events))
+}
+
+/// Streams `host_status` and `lease_update` events, with the corresponding [`WsMessage`] as JSON
+/// data, i.e. the same JSON as sent over the WebSocket.
+///
+/// Starts with a `host_status` event of the current status, so clients don't have to fetch it
+/// separately.
+#[tracing::instrument(skip_all)]
+async fn events(
+    State(AppState {
+        ws_tx,
+        host_actor,
+        config_rx,
+        ..
+    }): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    // Subscribe before taking the snapshot, so no update between the two is lost.
+    let updates = ws_tx.subscribe();
+    // Like the WebSocket, report configured hosts that weren't seen yet as offline.
+    let mut status = (*host_actor.snapshot()).clone();
+    for host in config_rx.borrow().hosts.keys() {
+        status.entry(host.clone()).or_insert(HostState::Offline);
+    }
+    let initial = sse_event(&WsMessage::HostStatus {
+        status,
+        last_seen: host_actor.went_online().as_ref().clone(),
+    });
+
+    let events = stream::iter(initial)
+        .chain(stream::unfold(updates, next_event))
+        .map(Ok);
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// Waits for the next broadcast that is forwarded as an event, ending the stream when the
+/// broadcast channel closes.
+async fn next_event(
+    mut updates: broadcast::Receiver<WsMessage>,
+) -> Option<(Event, broadcast::Receiver<WsMessage>)> {
+    loop {
+        match updates.recv().await {
+            Ok(msg) => {
+                if let Some(event) = sse_event(&msg) {
+                    return Some((event, updates));
+                }
+            }
+            Err(RecvError::Lagged(n)) => {
+                warn!("SSE stream missed {n} events (broadcast channel lagged)");
+            }
+            Err(RecvError::Closed) => {
+                debug!("Broadcast channel closed, ending SSE stream");
+                return None;
+            }
+        }
+    }
+}
+
+/// Converts the messages forwarded over SSE into events, named after their type.
+fn sse_event(msg: &WsMessage) -> Option<Event> {
+    let name = match *msg {
+        WsMessage::HostStatus { .. } => "host_status",
+        WsMessage::LeaseUpdate { .. } => "lease_update",
+        _ => return None,
+    };
+    match Event::default().event(name).json_data(msg) {
+        Ok(event) => Some(event),
+        Err(e) => {
+            warn!(%e, "Failed to serialize SSE event");
+            None
+        }
+    }
+}
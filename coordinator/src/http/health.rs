@@ -0,0 +1,81 @@
+//! Liveness and readiness probes for container orchestrators.
+//!
+//! Both endpoints are public, so probes don't need credentials.
+
+use core::{sync::atomic::Ordering, time::Duration};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse as _, Response},
+    routing::get,
+};
+use serde::Serialize;
+use tokio::time;
+
+use crate::{
+    app::{AppState, db},
+    http::error::json_error,
+};
+
+/// How long the readiness probe waits for the database to answer.
+const DB_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    version: &'static str,
+}
+
+const HEALTHY: Health = Health {
+    status: "ok",
+    version: env!("CARGO_PKG_VERSION"),
+};
+
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+}
+
+/// Liveness probe, succeeds as long as the process serves requests.
+#[axum::debug_handler]
+async fn health() -> Json<Health> {
+    Json(HEALTHY)
+}
+
+/// Readiness probe, succeeds once startup has completed and the database (if enabled) answers.
+#[axum::debug_handler]
+async fn ready(State(state): State<AppState>) -> Response {
+    if !state.ready.load(Ordering::Acquire) {
+        return json_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "not_ready",
+            "Coordinator is still starting",
+        );
+    }
+    if let Some(ref pool) = state.db_pool {
+        let reachable = match time::timeout(DB_PING_TIMEOUT, db::ping(pool)).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                tracing::warn!("Readiness check failed, database is unreachable: {e:#}");
+                false
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "Readiness check failed, database did not answer within {DB_PING_TIMEOUT:?}"
+                );
+                false
+            }
+        };
+        if !reachable {
+            return json_error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "database_unavailable",
+                "Database is unreachable",
+            );
+        }
+    }
+    Json(HEALTHY).into_response()
+}
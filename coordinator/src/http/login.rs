@@ -2,22 +2,26 @@
 
 use axum::{
     Router,
-    extract::State,
+    extract::{Query, State},
     http::HeaderMap,
     response::{IntoResponse, Redirect},
     routing::{get, post},
 };
 use axum_extra::{TypedHeader, extract::cookie::SignedCookieJar, headers::ContentType};
-use reqwest::StatusCode;
+use serde::Deserialize;
 
 use crate::{
     app::AppState,
     http::assets::{UiMode, render_ui_html},
     http::auth::{
         Resolved,
-        cookies::{self, get_oidc_session_from_cookie, get_token_session_from_cookie},
+        cookies::{
+            self, create_return_to_cookie, get_oidc_session_from_cookie,
+            get_token_session_from_cookie, is_local_return_to,
+        },
         oidc, token,
     },
+    http::error::ApiError,
 };
 
 /// Returns a router with all authentication-related routes.
@@ -29,8 +33,18 @@ pub(crate) fn routes() -> Router<AppState> {
         .route("/oidc/callback", get(oidc::callback))
 }
 
+/// Query parameters of the login page.
+#[derive(Deserialize)]
+pub(crate) struct LoginQuery {
+    /// Where to go after logging in. Only paths on this server are accepted.
+    return_to: Option<String>,
+}
+
 /// Handle GET requests to the login page. Redirects if already authenticated;
 /// otherwise serves the SPA shell — `SolidJS` Router renders `/login` client-side.
+///
+/// A `return_to` query parameter is remembered in the same cookie that is set when an
+/// unauthenticated request is redirected here, so both login methods redirect there afterwards.
 #[axum::debug_handler]
 pub(crate) async fn page(
     State(AppState {
@@ -39,6 +53,7 @@ pub(crate) async fn page(
         config_rx,
         ..
     }): State<AppState>,
+    Query(LoginQuery { return_to }): Query<LoginQuery>,
     headers: HeaderMap,
 ) -> impl IntoResponse {
     type A = Resolved;
@@ -46,20 +61,26 @@ pub(crate) async fn page(
     let jar = SignedCookieJar::from_headers(&headers, auth.cookie_key.clone());
     let is_authenticated = match auth.mode {
         A::Token { ref token } => get_token_session_from_cookie(&jar)
-            .is_some_and(|session| !session.is_expired() && session.matches_token(token)),
+            .is_some_and(|session| !session.is_expired() && session.matches_token(&token.read())),
         A::Oidc { .. } => {
             get_oidc_session_from_cookie(&jar).is_some_and(|session| !session.is_expired())
         }
         A::Disabled | A::External { .. } => true,
     };
+    let return_to = return_to.filter(|return_to| is_local_return_to(return_to));
     if is_authenticated {
-        return Redirect::to("/").into_response();
+        return Redirect::to(return_to.as_deref().unwrap_or("/")).into_response();
     }
+    let jar = match return_to {
+        Some(return_to) => jar.add(create_return_to_cookie(return_to)),
+        None => jar,
+    };
 
     let auth_mode = auth.mode.auth_mode_str();
     let broadcast_port = config_rx.borrow().server.broadcast_port;
 
     (
+        jar,
         TypedHeader(ContentType::html()),
         render_ui_html(&UiMode::Normal {
             config_path: &config_path,
@@ -91,7 +112,7 @@ pub(crate) async fn logout(
         && !orig_s.contains(host_s)
     {
         tracing::warn!(origin = %orig_s, host = %host_s, "logout: origin/referrer mismatch");
-        return StatusCode::BAD_REQUEST.into_response();
+        return ApiError::BadRequest("Origin does not match the host".to_string()).into_response();
     }
 
     let jar = cookies::invalidate_session(jar);
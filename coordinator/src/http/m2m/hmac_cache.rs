@@ -0,0 +1,238 @@
+//! Short-lived cache of HMAC signature checks for M2M requests.
+//!
+//! Clients that send the same signed request repeatedly (e.g. a CI system releasing many leases in
+//! the same second) would otherwise pay for an HMAC-SHA256 computation every time. The cache
+//! remembers whether the signature of an `X-Request` header was valid for a few seconds. Only the
+//! signature check is cached: the timestamp is still checked against the tolerance on every request,
+//! and an entry is only used while the client's shared secret is unchanged.
+
+use alloc::collections::VecDeque;
+use core::time::Duration;
+use std::{collections::HashMap, time::Instant};
+
+use parking_lot::Mutex;
+use secrecy::{ExposeSecret as _, SecretString};
+use shuthost_common::{
+    HmacValidationResult, is_timestamp_within_tolerance, parse_hmac_message, verify_hmac,
+};
+
+/// How long a signature check result is reused.
+const ENTRY_TTL: Duration = Duration::from_secs(5);
+
+type Key = (String, String);
+
+struct Entry {
+    valid: bool,
+    /// Secret the signature was checked against.
+    secret: SecretString,
+    inserted: Instant,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<Key, Entry>,
+    /// Keys in insertion order, for evicting the oldest entry once the cache is full.
+    order: VecDeque<Key>,
+}
+
+/// Cache of signature check results keyed by client ID and raw `X-Request` header.
+///
+/// Public only for the `hmac_cache` benchmark.
+pub struct HmacCache {
+    /// Maximum number of cached results. `0` disables the cache.
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl HmacCache {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Validates `data` like [`shuthost_common::validate_hmac_message_with_tolerance`], reusing a
+    /// recent signature check of the same request by `client_id` if there is one.
+    #[must_use]
+    pub fn validate(
+        &self,
+        client_id: &str,
+        data: &str,
+        secret: &SecretString,
+        tolerance_secs: u64,
+    ) -> HmacValidationResult {
+        self.validate_at(client_id, data, secret, tolerance_secs, Instant::now())
+    }
+
+    fn validate_at(
+        &self,
+        client_id: &str,
+        data: &str,
+        secret: &SecretString,
+        tolerance_secs: u64,
+        now: Instant,
+    ) -> HmacValidationResult {
+        let Some((timestamp, message, signature)) = parse_hmac_message(data) else {
+            return HmacValidationResult::MalformedMessage;
+        };
+        if !is_timestamp_within_tolerance(timestamp, tolerance_secs) {
+            return HmacValidationResult::InvalidTimestamp;
+        }
+
+        let key = (client_id.to_string(), data.to_string());
+        let valid = self.cached(&key, secret, now).unwrap_or_else(|| {
+            let valid = verify_hmac(&format!("{timestamp}|{message}"), &signature, secret);
+            self.insert(key, valid, secret, now);
+            valid
+        });
+
+        if valid {
+            HmacValidationResult::Valid(message)
+        } else {
+            HmacValidationResult::InvalidHmac
+        }
+    }
+
+    fn cached(&self, key: &Key, secret: &SecretString, now: Instant) -> Option<bool> {
+        let entries = self.entries.lock();
+        let entry = entries.map.get(key)?;
+        (now.saturating_duration_since(entry.inserted) < ENTRY_TTL
+            && entry.secret.expose_secret() == secret.expose_secret())
+        .then_some(entry.valid)
+    }
+
+    fn insert(&self, key: Key, valid: bool, secret: &SecretString, now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut guard = self.entries.lock();
+        let entries = &mut *guard;
+
+        // Drop expired entries; insertion order means they're all at the front.
+        while let Some(oldest) = entries.order.front()
+            && entries
+                .map
+                .get(oldest)
+                .is_none_or(|e| now.saturating_duration_since(e.inserted) >= ENTRY_TTL)
+        {
+            if let Some(expired) = entries.order.pop_front() {
+                entries.map.remove(&expired);
+            }
+        }
+
+        if entries.map.contains_key(&key) {
+            // Replaces an entry checked against a different secret.
+            entries.order.retain(|k| *k != key);
+        } else if entries.map.len() >= self.capacity
+            && let Some(oldest) = entries.order.pop_front()
+        {
+            entries.map.remove(&oldest);
+        }
+        entries.order.push_back(key.clone());
+        entries.map.insert(
+            key,
+            Entry {
+                valid,
+                secret: secret.clone(),
+                inserted: now,
+            },
+        );
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().map.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shuthost_common::create_signed_message;
+
+    use super::*;
+
+    const TOLERANCE: u64 = shuthost_common::ALLOWED_WINDOW;
+
+    #[test]
+    fn caches_valid_and_invalid_signatures() {
+        let cache = HmacCache::new(16);
+        let secret = SecretString::from("secret");
+        let now = Instant::now();
+        let signed = create_signed_message("take", &secret);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                cache.validate_at("ci", &signed, &secret, TOLERANCE, now),
+                HmacValidationResult::Valid(ref m) if m == "take"
+            ));
+        }
+        assert_eq!(cache.len(), 1);
+
+        let forged = create_signed_message("take", &SecretString::from("wrong"));
+        for _ in 0..2 {
+            assert!(matches!(
+                cache.validate_at("ci", &forged, &secret, TOLERANCE, now),
+                HmacValidationResult::InvalidHmac
+            ));
+        }
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn entries_are_bound_to_the_secret() {
+        let cache = HmacCache::new(16);
+        let old = SecretString::from("old");
+        let new = SecretString::from("new");
+        let now = Instant::now();
+        let signed = create_signed_message("take", &old);
+
+        assert!(matches!(
+            cache.validate_at("ci", &signed, &old, TOLERANCE, now),
+            HmacValidationResult::Valid(_)
+        ));
+        assert!(matches!(
+            cache.validate_at("ci", &signed, &new, TOLERANCE, now),
+            HmacValidationResult::InvalidHmac
+        ));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn stale_timestamps_are_rejected_before_the_cache() {
+        let cache = HmacCache::new(16);
+        let secret = SecretString::from("secret");
+        let stale = format!("1|take|{}", shuthost_common::sign_hmac("1|take", &secret));
+
+        assert!(matches!(
+            cache.validate("ci", &stale, &secret, TOLERANCE),
+            HmacValidationResult::InvalidTimestamp
+        ));
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn evicts_expired_and_oldest_entries() {
+        let cache = HmacCache::new(2);
+        let secret = SecretString::from("secret");
+        let now = Instant::now();
+        let messages: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|m| create_signed_message(m, &secret))
+            .collect();
+
+        for message in &messages {
+            cache.validate_at("ci", message, &secret, TOLERANCE, now);
+        }
+        assert_eq!(cache.len(), 2);
+
+        cache.validate_at("ci", &messages[0], &secret, TOLERANCE, now + ENTRY_TTL);
+        assert_eq!(cache.len(), 1, "expired entries are dropped on insert");
+
+        assert!(matches!(
+            HmacCache::new(0).validate_at("ci", &messages[0], &secret, TOLERANCE, now),
+            HmacValidationResult::Valid(_)
+        ));
+    }
+}
@@ -5,28 +5,37 @@
     expect(dead_code, reason = "For some reason clippy sets coverage cfg?")
 )]
 
+mod hmac_cache;
+mod rate_limit;
 mod validation;
 
-use core::{iter, time::Duration};
+pub use hmac_cache::HmacCache;
+pub(crate) use rate_limit::{
+    PEER_RATE_LIMIT_BURST, PEER_RATE_LIMIT_RPS, RateLimiter, limit as rate_limit,
+};
+
+use core::{iter, net::SocketAddr};
 
 use axum::{
     Json,
-    extract::{Path, Query, State},
+    extract::{ConnectInfo, Path, Query, Request, State},
     http::{HeaderMap, StatusCode as SC},
     response::{IntoResponse, Response},
     routing::{get, post},
 };
 use chrono::Utc;
 use serde_json::json;
-use tokio::time::Instant;
-use tracing::{debug, error};
+use tracing::{Span, debug, field};
 
 use crate::{
-    app::{
-        AppState, HostControlError, HostState as HS, LeaseSource, db, lookup_host_with_overrides,
-        wait_for_transition,
+    app::{AppState, LeaseSource, db},
+    http::{
+        api::{
+            LeaseAction as LA, LeaseActionQuery, UpdateLeaseError, hosts_status_response,
+            respond_to_lease_update, unchanged_lease_response, update_lease, update_leases,
+        },
+        error::json_error,
     },
-    http::api::{LeaseAction as LA, UpdateLeaseError, update_lease},
     websocket::WsMessage,
     wol,
 };
@@ -34,8 +43,34 @@ use crate::{
 pub(crate) fn routes() -> axum::Router<AppState> {
     axum::Router::new()
         .route("/lease/{hostname}/{action}", post(handle_m2m_lease_action))
+        .route("/batch_lease", post(handle_m2m_batch_lease))
         .route("/status/{hostname}", get(handle_m2m_status))
+        .route("/hosts_status", get(handle_m2m_hosts_status))
         .route("/test_wol", post(test_wol))
+        .route("/nonce", get(handle_m2m_nonce))
+}
+
+/// Returns the coordinator's current Unix time and the HMAC tolerance window, so clients with a
+/// skewed clock can correct the timestamps of their signed requests.
+///
+/// Unauthenticated, as neither value is secret; rate limited per peer IP instead. Behind a
+/// reverse proxy all clients share the proxy's limit.
+#[axum::debug_handler]
+async fn handle_m2m_nonce(State(state): State<AppState>, req: Request) -> Response {
+    // Absent when served without connection info, e.g. by the demo service.
+    if let Some(&ConnectInfo(peer)) = req.extensions().get::<ConnectInfo<SocketAddr>>()
+        && let Err(retry_after) = state.m2m_peer_rate_limiter.check(&peer.ip().to_string())
+    {
+        debug!(%peer, "Rate limit exceeded for nonce request");
+        return rate_limit::rate_limited(retry_after);
+    }
+
+    let tolerance_secs = state.config_rx.borrow().server.hmac_tolerance_secs;
+    Json(json!({
+        "timestamp": shuthost_common::unix_time_seconds(),
+        "tolerance_secs": tolerance_secs,
+    }))
+    .into_response()
 }
 
 #[derive(serde::Deserialize)]
@@ -45,24 +80,36 @@ pub(crate) struct WolTestQuery {
 
 #[cfg(not(coverage))]
 #[axum::debug_handler]
-async fn test_wol(Query(params): Query<WolTestQuery>) -> impl IntoResponse {
-    match wol::test_wol_reachability(params.port) {
+async fn test_wol(
+    Query(params): Query<WolTestQuery>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let interfaces = state.config_rx.borrow().server.wol_bind_addresses();
+    match wol::test_wol_reachability(params.port, &interfaces) {
         Ok(broadcast) => Ok(Json(json!({
             "broadcast": broadcast
         }))
         .into_response()),
-        Err(e) => Err((SC::INTERNAL_SERVER_ERROR, e.to_string()).into_response()),
+        Err(e) => Err(json_error(
+            SC::INTERNAL_SERVER_ERROR,
+            "wol_test_failed",
+            &e.to_string(),
+        )),
     }
 }
 
 #[cfg(coverage)]
 #[axum::debug_handler]
 async fn test_wol() -> impl IntoResponse {
-    (SC::INTERNAL_SERVER_ERROR, "Unimplemented in coverage").into_response()
+    json_error(
+        SC::INTERNAL_SERVER_ERROR,
+        "wol_test_failed",
+        "Unimplemented in coverage",
+    )
 }
 
 #[axum::debug_handler]
-#[tracing::instrument(skip(headers, state))]
+#[tracing::instrument(skip(headers, state), fields(client_id = field::Empty))]
 async fn handle_m2m_status(
     Path(host): Path<String>,
     headers: HeaderMap,
@@ -70,16 +117,18 @@ async fn handle_m2m_status(
 ) -> impl IntoResponse {
     let client_id = match validation::validate_m2m_status_request(&headers, &state) {
         Ok(id) => id,
-        Err((sc, err)) => return Err((sc, err.to_owned())),
+        Err(rejection) => return Err(rejection.into_response()),
     };
 
-    tracing::info!(%client_id, "Accepted m2m status request");
+    Span::current().record("client_id", client_id.as_str());
+    tracing::info!("Accepted m2m status request");
 
     let host_exists = state.config_rx.borrow().hosts.contains_key(&host);
     if !host_exists {
-        return Err((
+        return Err(json_error(
             SC::NOT_FOUND,
-            format!("No configuration found for host {host}"),
+            "host_not_found",
+            &format!("No configuration found for host {host}"),
         ));
     }
 
@@ -96,10 +145,25 @@ async fn handle_m2m_status(
     .into_response())
 }
 
-#[derive(serde::Deserialize)]
-pub(crate) struct LeaseActionQuery {
-    #[serde(default)]
-    r#async: Option<bool>,
+/// Returns the state of all hosts, like `/api/hosts_status`, for HMAC-authenticated clients.
+///
+/// The signed action in `X-Request` must be `status`.
+#[axum::debug_handler]
+#[tracing::instrument(skip(headers, state), fields(client_id = field::Empty))]
+async fn handle_m2m_hosts_status(
+    headers: HeaderMap,
+    Query(params): Query<Vec<(String, String)>>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let client_id = match validation::validate_m2m_status_request(&headers, &state) {
+        Ok(id) => id,
+        Err(rejection) => return Err(rejection.into_response()),
+    };
+
+    Span::current().record("client_id", client_id.as_str());
+    tracing::info!("Accepted m2m hosts status request");
+
+    Ok(hosts_status_response(&state, &params).await)
 }
 
 /// Handles machine-to-machine lease actions (take/release) for a host.
@@ -122,7 +186,7 @@ pub(crate) struct LeaseActionQuery {
 /// This is distinct from the web interface lease endpoints, which do not require authentication and are used for
 /// user-initiated actions from the web UI. Use this endpoint for secure, automated lease management by trusted clients.
 #[axum::debug_handler]
-#[tracing::instrument(skip(headers, state, query))]
+#[tracing::instrument(skip(headers, state, query), fields(client_id = field::Empty))]
 async fn handle_m2m_lease_action(
     Path((host, action)): Path<(String, LA)>,
     headers: HeaderMap,
@@ -131,55 +195,90 @@ async fn handle_m2m_lease_action(
 ) -> impl IntoResponse {
     let client_id = match validation::validate_m2m_request(&headers, &state, action) {
         Ok(res) => res,
-        Err((sc, err)) => return Err((sc, err.to_owned())),
+        Err(rejection) => return Err(rejection.into_response()),
     };
 
-    tracing::info!(%client_id, "Accepted m2m request");
+    Span::current().record("client_id", client_id.as_str());
+    tracing::info!("Accepted m2m request");
     update_client_usage(&state, &client_id).await;
 
     let lease_source = LeaseSource::Client(client_id);
     let is_async = query.r#async.unwrap_or(false);
 
-    let lease_set_empty = update_lease(&host, lease_source, action, &state)
+    let update = update_lease(&host, lease_source, action, query.ttl(), &state)
         .await
-        .map_err(|error| {
-            use UpdateLeaseError as ULE;
+        .map_err(IntoResponse::into_response)?;
+    if !update.changed {
+        // Nothing changed, so there is no host transition to wait for.
+        return Ok(unchanged_lease_response(action).into_response());
+    }
 
-            match error {
-                ULE::HostNotFound { hostname: _ } => (
-                    SC::NOT_FOUND,
-                    format!("No configuration found for host {host}"),
-                ),
-                ULE::DatabaseError(_) => {
-                    error!("Failed to update lease: {}", error);
-                    (
-                        SC::INTERNAL_SERVER_ERROR,
-                        "Failed to update lease".to_string(),
-                    )
-                }
-            }
-        })?;
+    respond_to_lease_update(&state, &host, action, update.lease_set_empty, is_async).await
+}
 
-    let ultimately_desired_state = if lease_set_empty {
-        HS::Offline
-    } else {
-        HS::Online
+/// Request body of [`handle_m2m_batch_lease`].
+#[derive(serde::Deserialize)]
+struct BatchLeaseRequest {
+    action: LA,
+    hosts: Vec<String>,
+}
+
+/// Takes or releases the calling client's lease on several hosts at once.
+///
+/// The signed action in `X-Request` must be `batch_take` or `batch_release`, matching `action` in
+/// the body. All leases are updated together, after which hosts are woken or shut down in the
+/// background, like with `?async=true` on the single-host endpoint. Responds with the hosts
+/// whose lease was updated, and an error message for each host that failed.
+#[axum::debug_handler]
+#[tracing::instrument(skip(headers, state, request), fields(client_id = field::Empty))]
+async fn handle_m2m_batch_lease(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<BatchLeaseRequest>,
+) -> impl IntoResponse {
+    let client_id = match validation::validate_m2m_batch_request(&headers, &state, request.action) {
+        Ok(id) => id,
+        Err(rejection) => return Err(rejection.into_response()),
     };
 
-    let current_state = state.host_actor.get_current_state(&host);
-    if current_state == ultimately_desired_state {
-        return Ok(current_state_response(action, ultimately_desired_state).into_response());
-    }
+    Span::current().record("client_id", client_id.as_str());
+    tracing::info!(hosts = ?request.hosts, "Accepted m2m batch request");
+    update_client_usage(&state, &client_id).await;
 
-    if is_async {
-        return Ok(async_response(action).into_response());
-    }
+    let batch = update_leases(
+        &request.hosts,
+        LeaseSource::Client(client_id),
+        request.action,
+        &state,
+    )
+    .await;
+    let failed: serde_json::Map<_, _> = batch
+        .failed
+        .into_iter()
+        .map(|(host, e)| {
+            let message = match e {
+                UpdateLeaseError::DatabaseError(ref e) => {
+                    tracing::error!(%host, "Failed to update lease: {e}");
+                    "Failed to update lease".to_string()
+                }
+                UpdateLeaseError::HostNotFound { .. }
+                | UpdateLeaseError::LeaseLimitExceeded { .. } => e.to_string(),
+            };
+            (host, message.into())
+        })
+        .collect();
 
-    perform_sync_wait(&state, &host, action, ultimately_desired_state)
-        .await
-        .map(IntoResponse::into_response)
+    Ok(Json(json!({
+        "succeeded": batch.succeeded,
+        "failed": failed,
+    }))
+    .into_response())
 }
 
+/// Records when `client_id` last made a request, for display in the web UI.
+///
+/// Best effort: the statistic is informational, so a failure is logged rather than failing the
+/// request.
 async fn update_client_usage(state: &AppState, client_id: &str) {
     if let Some(ref pool) = state.db_pool {
         match db::update_client_last_used(pool, client_id, Utc::now()).await {
@@ -196,70 +295,3 @@ async fn update_client_usage(state: &AppState, client_id: &str) {
         }
     }
 }
-
-fn current_state_response(action: LA, state: HS) -> &'static str {
-    match (action, state) {
-        (LA::Take, HS::Online) => "Lease taken, host is already online",
-        (LA::Release, HS::Offline) => "Lease released, host is already offline",
-        (LA::Release, HS::Online) => "Lease released, but host remains online",
-        (LA::Take, HS::Offline) => unreachable!(
-            "taking a lease on an offline host should not assure that the lease_set is not empty"
-        ),
-        _ => unreachable!("current_state_response can only be called for stable host states"),
-    }
-}
-
-const fn async_response(action: LA) -> &'static str {
-    match action {
-        LA::Take => "Lease taken (async)",
-        LA::Release => "Lease released (async)",
-    }
-}
-
-async fn perform_sync_wait(
-    state: &AppState,
-    host: &str,
-    action: LA,
-    ultimately_desired_state: HS,
-) -> Result<Response, (SC, String)> {
-    use HostControlError as HCE;
-
-    let Some(host_with_name) = lookup_host_with_overrides(state, host).await else {
-        return Err((
-            SC::NOT_FOUND,
-            format!("No configuration found for host {host}"),
-        ));
-    };
-
-    let timeout_secs = if ultimately_desired_state == HS::Online {
-        host_with_name
-            .host
-            .wake_timeout_secs
-            .unwrap_or(state.runtime.default_wake_timeout_secs)
-    } else {
-        host_with_name
-            .host
-            .shutdown_timeout_secs
-            .unwrap_or(state.runtime.default_shutdown_timeout_secs)
-    };
-    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
-
-    wait_for_transition(host, &state.host_actor, ultimately_desired_state, deadline)
-        .await
-        .map(|()| {
-            match (action, ultimately_desired_state) {
-                (LA::Take, HS::Online) => "Lease taken, host is now online",
-                (LA::Release, HS::Offline) => "Lease released, host is now offline",
-                _ => unreachable!("unexpected (action, ultimately_desired_state) combination"),
-            }
-            .into_response()
-        })
-        .map_err(|err| {
-            let status = match err {
-                HCE::NotFound(_) => SC::NOT_FOUND,
-                HCE::Timeout(_) => SC::GATEWAY_TIMEOUT,
-                HCE::OperationFailed { .. } => SC::INTERNAL_SERVER_ERROR,
-            };
-            (status, err.to_string())
-        })
-}
@@ -0,0 +1,198 @@
+//! Rate limiting for M2M endpoints.
+//!
+//! Every known client gets a token bucket that refills at `m2m_rate_limit_rps` tokens per second
+//! up to `m2m_rate_limit_burst` tokens. Each authenticated M2M request consumes one token, charged
+//! only after its signature was validated, so others can't use up a client's tokens by sending
+//! requests with its ID. Requests without a token available are rejected with
+//! `429 Too Many Requests` and a `Retry-After` header.
+//!
+//! Unauthenticated traffic, i.e. the `/api/m2m/nonce` endpoint and requests that fail
+//! authentication, is limited per peer IP instead, see [`PEER_RATE_LIMIT_RPS`].
+
+use core::{net::SocketAddr, time::Duration};
+use std::{collections::HashMap, time::Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::Response,
+};
+use parking_lot::Mutex;
+use tracing::info;
+
+use crate::{app::AppState, http::error::json_error};
+
+/// Tokens per second of the per-IP limiter of unauthenticated M2M requests.
+pub(crate) const PEER_RATE_LIMIT_RPS: u32 = 1;
+/// Bucket size of the per-IP limiter of unauthenticated M2M requests, i.e. 60 requests per minute.
+pub(crate) const PEER_RATE_LIMIT_BURST: u32 = 60;
+
+/// Marks responses to M2M requests that failed authentication, so [`limit`] charges them to the
+/// peer IP.
+#[derive(Clone, Copy)]
+pub(crate) struct Unauthenticated;
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket rate limiter keyed by client ID or peer IP.
+pub(crate) struct RateLimiter {
+    /// Tokens added per second. `0` disables rate limiting.
+    rps: u32,
+    /// Maximum number of tokens a bucket can hold.
+    burst: u32,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(rps: u32, burst: u32) -> Self {
+        Self {
+            rps,
+            burst: burst.max(1),
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Consumes a token for `key`, a client ID or peer IP.
+    ///
+    /// # Errors
+    ///
+    /// Returns the time until the next token becomes available if the key exceeded its limit.
+    pub(crate) fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        if self.rps == 0 {
+            return Ok(());
+        }
+        let rps = f64::from(self.rps);
+        let burst = f64::from(self.burst);
+
+        let mut buckets = self.buckets.lock();
+        if !buckets.contains_key(key) {
+            // A bucket that refilled completely acts like a new one, so it can be forgotten.
+            // This bounds the map when keys are not, like peer IPs.
+            let refill_time = Duration::from_secs_f64(burst / rps);
+            buckets.retain(|_, bucket| {
+                now.saturating_duration_since(bucket.last_refill) < refill_time
+            });
+        }
+        let bucket = buckets
+            .entry(key.to_string())
+            .or_insert_with(|| TokenBucket {
+                tokens: burst,
+                last_refill: now,
+            });
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = elapsed.as_secs_f64().mul_add(rps, bucket.tokens).min(burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rps))
+        }
+    }
+}
+
+/// Middleware limiting M2M requests that failed authentication per peer IP.
+///
+/// The requests are handled first, as only the handlers validate signatures; once the peer's
+/// tokens are used up, their rejection is turned into `429 Too Many Requests`. Authenticated
+/// requests are limited per client while being validated, so clients sharing an IP, e.g. behind
+/// a reverse proxy, aren't locked out by failed requests of others.
+pub(crate) async fn limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    // Absent when served without connection info, e.g. by the demo service.
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|&ConnectInfo(peer)| peer);
+
+    let response = next.run(req).await;
+
+    if response.extensions().get::<Unauthenticated>().is_some()
+        && let Some(peer) = peer
+        && let Err(retry_after) = state.m2m_peer_rate_limiter.check(&peer.ip().to_string())
+    {
+        info!(%peer, "Rate limit exceeded for unauthenticated M2M requests");
+        return rate_limited(retry_after);
+    }
+    response
+}
+
+/// Builds the `429 Too Many Requests` response, with `retry_after` rounded up to whole seconds.
+pub(crate) fn rate_limited(retry_after: Duration) -> Response {
+    let retry_after_secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let mut response = json_error(
+        StatusCode::TOO_MANY_REQUESTS,
+        "rate_limited",
+        "Rate limit exceeded",
+    );
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_burst_then_limits() {
+        let limiter = RateLimiter::new(1, 3);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check_at("client", now).is_ok(), "within burst");
+        }
+        let retry_after = limiter.check_at("client", now).unwrap_err();
+        assert!(
+            retry_after <= Duration::from_secs(1),
+            "one token per second"
+        );
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let limiter = RateLimiter::new(2, 1);
+        let now = Instant::now();
+        assert!(limiter.check_at("client", now).is_ok(), "initial token");
+        assert!(limiter.check_at("client", now).is_err(), "bucket empty");
+        let later = now + Duration::from_millis(500);
+        assert!(limiter.check_at("client", later).is_ok(), "refilled");
+    }
+
+    #[test]
+    fn clients_are_limited_independently() {
+        let limiter = RateLimiter::new(1, 1);
+        let now = Instant::now();
+        assert!(limiter.check_at("a", now).is_ok(), "a has a token");
+        assert!(limiter.check_at("a", now).is_err(), "a is limited");
+        assert!(limiter.check_at("b", now).is_ok(), "b is unaffected");
+    }
+
+    #[test]
+    fn refilled_buckets_are_forgotten() {
+        let limiter = RateLimiter::new(1, 2);
+        let now = Instant::now();
+        assert!(limiter.check_at("a", now).is_ok(), "a has a token");
+        let later = now + Duration::from_secs(2);
+        assert!(limiter.check_at("b", later).is_ok(), "b has a token");
+        assert_eq!(limiter.buckets.lock().len(), 1, "a was refilled");
+    }
+
+    #[test]
+    fn zero_rps_disables_limit() {
+        let limiter = RateLimiter::new(0, 1);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert!(limiter.check_at("client", now).is_ok(), "unlimited");
+        }
+    }
+}
@@ -1,117 +1,233 @@
 //! HMAC validation and request parsing for M2M endpoints.
 
-use axum::http::{HeaderMap, StatusCode};
-use shuthost_common::validate_hmac_message;
+use core::time::Duration;
+
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
 use tracing::{info, warn};
 
-use crate::{app::AppState, http::api::LeaseAction};
+use crate::{
+    app::AppState,
+    http::{
+        api::LeaseAction,
+        error::json_error,
+        m2m::rate_limit::{self, Unauthenticated},
+    },
+};
+
+/// Why an M2M request was rejected.
+pub(crate) enum Rejection {
+    /// The request failed authentication: status, error code and message for [`json_error`].
+    Unauthenticated(StatusCode, &'static str, &'static str),
+    /// The authenticated request is invalid: status, error code and message for [`json_error`].
+    Invalid(StatusCode, &'static str, &'static str),
+    /// The authenticated client exceeded its rate limit, retry after the duration.
+    RateLimited(Duration),
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Unauthenticated(status, code, message) => {
+                let mut response = json_error(status, code, message);
+                response.extensions_mut().insert(Unauthenticated);
+                response
+            }
+            Self::Invalid(status, code, message) => json_error(status, code, message),
+            Self::RateLimited(retry_after) => rate_limit::rate_limited(retry_after),
+        }
+    }
+}
 
-/// Validates M2M lease action request headers and returns (`client_id`, `LeaseAction`)
+/// Validates M2M lease action request headers and returns the `client_id`.
 pub(crate) fn validate_m2m_request(
     headers: &HeaderMap,
     state: &AppState,
     expected_action: LeaseAction,
-) -> Result<String, (StatusCode, &'static str)> {
-    let client_id = headers
-        .get("X-Client-ID")
-        .and_then(|v| v.to_str().ok())
-        .ok_or((StatusCode::BAD_REQUEST, "Missing X-Client-ID"))?;
+) -> Result<String, Rejection> {
+    let (client_id, command) = validate_signed_request(headers, state)?;
 
-    let data_str = headers
-        .get("X-Request")
-        .and_then(|v| v.to_str().ok())
-        .ok_or((StatusCode::BAD_REQUEST, "Missing X-Request"))?;
+    let command_action: LeaseAction = serde_plain::from_str(&command).map_err(|_| {
+        Rejection::Invalid(
+            StatusCode::BAD_REQUEST,
+            "invalid_action",
+            "Invalid action in X-Request",
+        )
+    })?;
 
-    let parts: Vec<&str> = data_str.split('|').collect();
-    if parts.len() != 3 {
-        return Err((StatusCode::BAD_REQUEST, "Invalid request format"));
+    if command_action != expected_action {
+        return Err(Rejection::Invalid(
+            StatusCode::BAD_REQUEST,
+            "action_mismatch",
+            "Action mismatch",
+        ));
     }
 
-    // potential enumeration issue, if thats something we want to cover.
-    let shared_secret = {
-        let config = state.config_rx.borrow();
-        config
-            .clients
-            .get(client_id)
-            .ok_or_else(|| {
-                warn!("Unknown client '{}'", client_id);
-                (StatusCode::FORBIDDEN, "Unknown client")
-            })?
-            .shared_secret
-            .clone()
-    };
-
-    let command = match validate_hmac_message(data_str, shared_secret.as_ref()) {
-        shuthost_common::HmacValidationResult::Valid(valid_message) => valid_message,
-        shuthost_common::HmacValidationResult::InvalidTimestamp => {
-            info!("Timestamp out of range for client '{}'", client_id);
-            return Err((StatusCode::UNAUTHORIZED, "Timestamp out of range"));
-        }
-        shuthost_common::HmacValidationResult::InvalidHmac => {
-            info!("Invalid HMAC signature for client '{}'", client_id);
-            return Err((StatusCode::UNAUTHORIZED, "Invalid HMAC signature"));
-        }
-        shuthost_common::HmacValidationResult::MalformedMessage => {
-            return Err((StatusCode::BAD_REQUEST, "Invalid request format"));
-        }
-    };
+    Ok(client_id)
+}
 
-    let command_action: LeaseAction = serde_plain::from_str(&command)
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid action in X-Request"))?;
+/// Validates M2M batch lease request headers and returns the `client_id`.
+///
+/// The signed command is the action prefixed with `batch_`, e.g. `batch_take`, so a signed
+/// single-host request can't be replayed against the batch endpoint.
+pub(crate) fn validate_m2m_batch_request(
+    headers: &HeaderMap,
+    state: &AppState,
+    expected_action: LeaseAction,
+) -> Result<String, Rejection> {
+    let (client_id, command) = validate_signed_request(headers, state)?;
+
+    let command_action: LeaseAction = command
+        .strip_prefix("batch_")
+        .and_then(|action| serde_plain::from_str(action).ok())
+        .ok_or(Rejection::Invalid(
+            StatusCode::BAD_REQUEST,
+            "invalid_action",
+            "Invalid action in X-Request",
+        ))?;
 
     if command_action != expected_action {
-        return Err((StatusCode::BAD_REQUEST, "Action mismatch"));
+        return Err(Rejection::Invalid(
+            StatusCode::BAD_REQUEST,
+            "action_mismatch",
+            "Action mismatch",
+        ));
     }
 
-    Ok(client_id.to_string())
+    Ok(client_id)
 }
 
 /// Validates M2M status request headers and returns `client_id`.
 pub(crate) fn validate_m2m_status_request(
     headers: &HeaderMap,
     state: &AppState,
-) -> Result<String, (StatusCode, &'static str)> {
+) -> Result<String, Rejection> {
+    let (client_id, command) = validate_signed_request(headers, state)?;
+
+    if command != "status" {
+        return Err(Rejection::Invalid(
+            StatusCode::BAD_REQUEST,
+            "action_mismatch",
+            "Action mismatch",
+        ));
+    }
+
+    Ok(client_id)
+}
+
+/// Checks the `X-Client-ID` and HMAC-signed `X-Request` headers and returns the client ID and
+/// the signed command.
+///
+/// Consumes a token of the client's rate limit once the signature is valid.
+fn validate_signed_request(
+    headers: &HeaderMap,
+    state: &AppState,
+) -> Result<(String, String), Rejection> {
     let client_id = headers
         .get("X-Client-ID")
         .and_then(|v| v.to_str().ok())
-        .ok_or((StatusCode::BAD_REQUEST, "Missing X-Client-ID"))?;
+        .ok_or(Rejection::Unauthenticated(
+            StatusCode::BAD_REQUEST,
+            "missing_client_id",
+            "Missing X-Client-ID",
+        ))?;
 
     let data_str = headers
         .get("X-Request")
         .and_then(|v| v.to_str().ok())
-        .ok_or((StatusCode::BAD_REQUEST, "Missing X-Request"))?;
+        .ok_or(Rejection::Unauthenticated(
+            StatusCode::BAD_REQUEST,
+            "missing_request",
+            "Missing X-Request",
+        ))?;
+
+    if data_str.split('|').count() != 3 {
+        return Err(Rejection::Unauthenticated(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_format",
+            "Invalid request format",
+        ));
+    }
 
-    let shared_secret = {
+    // potential enumeration issue, if thats something we want to cover.
+    let (shared_secret, tolerance_secs, replay_protection) = {
         let config = state.config_rx.borrow();
-        config
+        let shared_secret = config
             .clients
             .get(client_id)
             .ok_or_else(|| {
-                warn!("Unknown client '{}'", client_id);
-                (StatusCode::FORBIDDEN, "Unknown client")
+                warn!(%client_id, "Unknown client");
+                Rejection::Unauthenticated(
+                    StatusCode::FORBIDDEN,
+                    "unknown_client",
+                    "Unknown client",
+                )
             })?
             .shared_secret
-            .clone()
+            .clone();
+        (
+            shared_secret,
+            config.server.hmac_tolerance_secs,
+            config.server.m2m_replay_protection,
+        )
+    };
+
+    // Replay protection rejects repeated identical requests, so caching their checks is pointless.
+    let result = if replay_protection {
+        shuthost_common::validate_hmac_message_no_replay(
+            data_str,
+            shared_secret.as_ref(),
+            tolerance_secs,
+            &mut state.nonce_cache.lock(),
+        )
+    } else {
+        state
+            .hmac_cache
+            .validate(client_id, data_str, shared_secret.as_ref(), tolerance_secs)
     };
 
-    let command = match validate_hmac_message(data_str, shared_secret.as_ref()) {
+    let command = match result {
         shuthost_common::HmacValidationResult::Valid(valid_message) => valid_message,
         shuthost_common::HmacValidationResult::InvalidTimestamp => {
-            info!("Timestamp out of range for client '{}'", client_id);
-            return Err((StatusCode::UNAUTHORIZED, "Timestamp out of range"));
+            info!(%client_id, "Timestamp out of range");
+            return Err(Rejection::Unauthenticated(
+                StatusCode::UNAUTHORIZED,
+                "timestamp_out_of_range",
+                "Timestamp out of range",
+            ));
         }
         shuthost_common::HmacValidationResult::InvalidHmac => {
-            info!("Invalid HMAC signature for client '{}'", client_id);
-            return Err((StatusCode::UNAUTHORIZED, "Invalid HMAC signature"));
+            info!(%client_id, "Invalid HMAC signature");
+            return Err(Rejection::Unauthenticated(
+                StatusCode::UNAUTHORIZED,
+                "invalid_signature",
+                "Invalid HMAC signature",
+            ));
+        }
+        shuthost_common::HmacValidationResult::Replayed => {
+            warn!(%client_id, "Replayed request");
+            return Err(Rejection::Unauthenticated(
+                StatusCode::UNAUTHORIZED,
+                "replayed_request",
+                "Request was already processed",
+            ));
         }
         shuthost_common::HmacValidationResult::MalformedMessage => {
-            return Err((StatusCode::BAD_REQUEST, "Invalid request format"));
+            return Err(Rejection::Unauthenticated(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_format",
+                "Invalid request format",
+            ));
         }
     };
 
-    if command != "status" {
-        return Err((StatusCode::BAD_REQUEST, "Action mismatch"));
+    // Charged only now, so requests forged with the client's ID can't use up its tokens.
+    if let Err(retry_after) = state.m2m_rate_limiter.check(client_id) {
+        info!(%client_id, "Rate limit exceeded for client");
+        return Err(Rejection::RateLimited(retry_after));
     }
 
-    Ok(client_id.to_string())
+    Ok((client_id.to_string(), command))
 }
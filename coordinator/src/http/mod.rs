@@ -6,6 +6,9 @@ pub mod api;
 pub mod assets;
 pub mod auth;
 pub mod download;
+pub(crate) mod error;
+pub(crate) mod events;
+pub(crate) mod health;
 pub mod login;
 pub mod m2m;
 pub mod push;
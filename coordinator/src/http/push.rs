@@ -16,6 +16,7 @@ use axum::{
 };
 use axum_extra::{TypedHeader, headers::ContentType};
 use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use eyre::WrapErr as _;
 use hyper::{StatusCode, Uri};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -26,36 +27,34 @@ use web_push_native::{
     p256::{PublicKey, elliptic_curve::Error as P256Error},
 };
 
-use crate::app::{AppState, db};
+use crate::{
+    app::{AppState, db},
+    http::error::ApiError,
+};
 
-macro_rules! require_db_pool {
-    ($state:expr) => {{
-        let Some(ref pool) = $state.db_pool else {
-            return StatusCode::SERVICE_UNAVAILABLE;
-        };
-        pool
-    }};
-    (response; $state:expr) => {{
-        let Some(ref pool) = $state.db_pool else {
-            return StatusCode::SERVICE_UNAVAILABLE.into_response();
-        };
-        pool
-    }};
+const PERSISTENCE_REQUIRED: &str = "Push notifications require database persistence to be enabled";
+
+fn require_db_pool(state: &AppState) -> Result<&db::DbPool, ApiError> {
+    state
+        .db_pool
+        .as_ref()
+        .ok_or_else(|| ApiError::Unavailable(PERSISTENCE_REQUIRED.to_string()))
 }
 
-macro_rules! validate_push_subscription {
-    ($sub:expr) => {{
-        if let Err(e) = build_web_push_builder(&$sub.endpoint, &$sub.keys.p256dh, &$sub.keys.auth) {
+fn validate_push_subscription(sub: &PushSubscriptionJson) -> Result<(), ApiError> {
+    build_web_push_builder(&sub.endpoint, &sub.keys.p256dh, &sub.keys.auth)
+        .map(drop)
+        .map_err(|e| {
             warn!("Invalid push subscription: {e}");
-            return StatusCode::BAD_REQUEST;
-        }
-    }};
-    (response; $sub:expr) => {{
-        if let Err(e) = build_web_push_builder(&$sub.endpoint, &$sub.keys.p256dh, &$sub.keys.auth) {
-            warn!("Invalid push subscription: {e}");
-            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
-        }
-    }};
+            ApiError::BadRequest(format!("Invalid push subscription: {e}"))
+        })
+}
+
+/// Stores the browser's subscription, returning its id for linking it to notifications.
+async fn upsert_subscription(pool: &db::DbPool, sub: &PushSubscriptionJson) -> eyre::Result<i64> {
+    db::upsert_push_subscription(pool, &sub.endpoint, &sub.keys.p256dh, &sub.keys.auth)
+        .await
+        .wrap_err("Failed to upsert push subscription")
 }
 
 pub(crate) fn routes() -> Router<AppState> {
@@ -130,23 +129,15 @@ struct CheckHostSubscriptionResponse {
 
 const MAX_HOST_ONLINE_FOR_DURATION_SECS: i64 = 86_400 * 30; // 30 days
 
-fn validate_host_online_for_duration_secs(duration_secs: i64) -> Result<u64, Box<Response>> {
+fn validate_host_online_for_duration_secs(duration_secs: i64) -> Result<u64, ApiError> {
     if duration_secs <= 0 {
-        return Err(Box::new(
-            (
-                StatusCode::BAD_REQUEST,
-                "duration_secs must be greater than 0",
-            )
-                .into_response(),
+        return Err(ApiError::BadRequest(
+            "duration_secs must be greater than 0".to_string(),
         ));
     }
     if duration_secs > MAX_HOST_ONLINE_FOR_DURATION_SECS {
-        return Err(Box::new(
-            (
-                StatusCode::BAD_REQUEST,
-                "duration_secs must be 30 days or less",
-            )
-                .into_response(),
+        return Err(ApiError::BadRequest(
+            "duration_secs must be 30 days or less".to_string(),
         ));
     }
 
@@ -213,25 +204,21 @@ struct VapidPublicKeyResponse {
 /// Returns the VAPID public key as URL-safe base64 (no padding).
 /// The frontend passes this to `PushManager.subscribe({ applicationServerKey })`.
 #[axum::debug_handler]
-async fn get_vapid_public_key(State(state): State<AppState>) -> impl IntoResponse {
+async fn get_vapid_public_key(State(state): State<AppState>) -> Result<Response, ApiError> {
     let Some(ref vapid_key) = state.vapid_key else {
-        return (
-            StatusCode::SERVICE_UNAVAILABLE,
-            "Push notifications require database persistence to be enabled",
-        )
-            .into_response();
+        return Err(ApiError::Unavailable(PERSISTENCE_REQUIRED.to_string()));
     };
 
     let public_key_bytes = vapid_key.key_pair().public_key().to_bytes_uncompressed();
     let public_key_b64 = URL_SAFE_NO_PAD.encode(&public_key_bytes);
 
-    (
+    Ok((
         TypedHeader(ContentType::json()),
         axum::Json(VapidPublicKeyResponse {
             public_key: public_key_b64,
         }),
     )
-        .into_response()
+        .into_response())
 }
 
 /// Returns whether the given push endpoint is subscribed to unscheduled-event notifications
@@ -240,16 +227,14 @@ async fn get_vapid_public_key(State(state): State<AppState>) -> impl IntoRespons
 async fn check_host_unscheduled_subscription(
     State(state): State<AppState>,
     Query(params): Query<HostSubscriptionData>,
-) -> impl IntoResponse {
-    let pool = require_db_pool!(response; state);
+) -> Result<impl IntoResponse, ApiError> {
+    let pool = require_db_pool(&state)?;
 
-    match db::is_subscribed_to_host_unscheduled(pool, &params.endpoint, &params.hostname).await {
-        Ok(subscribed) => axum::Json(CheckHostSubscriptionResponse { subscribed }).into_response(),
-        Err(e) => {
-            error!("Failed to check push subscription: {e:#}");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
-    }
+    let subscribed =
+        db::is_subscribed_to_host_unscheduled(pool, &params.endpoint, &params.hostname)
+            .await
+            .wrap_err("Failed to check push subscription")?;
+    Ok(axum::Json(CheckHostSubscriptionResponse { subscribed }))
 }
 
 /// Removes the unscheduled-event subscription link for a specific endpoint + host pair.
@@ -257,15 +242,13 @@ async fn check_host_unscheduled_subscription(
 async fn unsubscribe_host_unscheduled(
     State(state): State<AppState>,
     axum::Json(body): axum::Json<HostSubscriptionData>,
-) -> impl IntoResponse {
-    let pool = require_db_pool!(state);
+) -> Result<StatusCode, ApiError> {
+    let pool = require_db_pool(&state)?;
 
-    if let Err(e) = db::unsubscribe_host_unscheduled(pool, &body.endpoint, &body.hostname).await {
-        error!("Failed to unsubscribe from host unscheduled events: {e:#}");
-        return StatusCode::INTERNAL_SERVER_ERROR;
-    }
-
-    StatusCode::NO_CONTENT
+    db::unsubscribe_host_unscheduled(pool, &body.endpoint, &body.hostname)
+        .await
+        .wrap_err("Failed to unsubscribe from host unscheduled events")?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 /// Registers a browser push subscription for unscheduled-event notifications.
@@ -273,32 +256,15 @@ async fn unsubscribe_host_unscheduled(
 async fn subscribe_host_unscheduled(
     State(state): State<AppState>,
     axum::Json(body): axum::Json<HostSubscriptionRequest>,
-) -> impl IntoResponse {
-    let pool = require_db_pool!(state);
-
-    validate_push_subscription!(body.subscription);
-
-    let sub_id = match db::upsert_push_subscription(
-        pool,
-        &body.subscription.endpoint,
-        &body.subscription.keys.p256dh,
-        &body.subscription.keys.auth,
-    )
-    .await
-    {
-        Ok(id) => id,
-        Err(e) => {
-            error!("Failed to upsert push subscription: {e:#}");
-            return StatusCode::INTERNAL_SERVER_ERROR;
-        }
-    };
-
-    if let Err(e) = db::subscribe_host_unscheduled(pool, sub_id, &body.hostname).await {
-        error!("Failed to subscribe to host unscheduled events: {e:#}");
-        return StatusCode::INTERNAL_SERVER_ERROR;
-    }
+) -> Result<StatusCode, ApiError> {
+    let pool = require_db_pool(&state)?;
+    validate_push_subscription(&body.subscription)?;
 
-    StatusCode::NO_CONTENT
+    let sub_id = upsert_subscription(pool, &body.subscription).await?;
+    db::subscribe_host_unscheduled(pool, sub_id, &body.hostname)
+        .await
+        .wrap_err("Failed to subscribe to host unscheduled events")?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 // ──────────────────────────────────────────────
@@ -311,17 +277,14 @@ async fn subscribe_host_unscheduled(
 async fn check_host_operation_failed_subscription(
     State(state): State<AppState>,
     Query(params): Query<HostSubscriptionData>,
-) -> impl IntoResponse {
-    let pool = require_db_pool!(response; state);
+) -> Result<impl IntoResponse, ApiError> {
+    let pool = require_db_pool(&state)?;
 
-    match db::is_subscribed_to_host_operation_failed(pool, &params.endpoint, &params.hostname).await
-    {
-        Ok(subscribed) => axum::Json(CheckHostSubscriptionResponse { subscribed }).into_response(),
-        Err(e) => {
-            error!("Failed to check operation-failed push subscription: {e:#}");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
-    }
+    let subscribed =
+        db::is_subscribed_to_host_operation_failed(pool, &params.endpoint, &params.hostname)
+            .await
+            .wrap_err("Failed to check operation-failed push subscription")?;
+    Ok(axum::Json(CheckHostSubscriptionResponse { subscribed }))
 }
 
 /// Registers a browser push subscription for operation-failed notifications.
@@ -329,34 +292,15 @@ async fn check_host_operation_failed_subscription(
 async fn subscribe_host_operation_failed(
     State(state): State<AppState>,
     axum::Json(body): axum::Json<HostSubscriptionRequest>,
-) -> impl IntoResponse {
-    let pool = require_db_pool!(state);
-
-    validate_push_subscription!(body.subscription);
+) -> Result<StatusCode, ApiError> {
+    let pool = require_db_pool(&state)?;
+    validate_push_subscription(&body.subscription)?;
 
-    let sub_id = match db::upsert_push_subscription(
-        pool,
-        &body.subscription.endpoint,
-        &body.subscription.keys.p256dh,
-        &body.subscription.keys.auth,
-    )
-    .await
-    {
-        Ok(id) => id,
-        Err(e) => {
-            error!("Failed to upsert push subscription: {e:#}");
-            return StatusCode::INTERNAL_SERVER_ERROR;
-        }
-    };
-
-    if let Err(e) =
-        db::add_push_subscription_host_operation_failed(pool, sub_id, &body.hostname).await
-    {
-        error!("Failed to subscribe to host operation-failed events: {e:#}");
-        return StatusCode::INTERNAL_SERVER_ERROR;
-    }
-
-    StatusCode::NO_CONTENT
+    let sub_id = upsert_subscription(pool, &body.subscription).await?;
+    db::add_push_subscription_host_operation_failed(pool, sub_id, &body.hostname)
+        .await
+        .wrap_err("Failed to subscribe to host operation-failed events")?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 /// Removes the operation-failed subscription link for a specific endpoint + host pair.
@@ -364,17 +308,13 @@ async fn subscribe_host_operation_failed(
 async fn unsubscribe_host_operation_failed(
     State(state): State<AppState>,
     axum::Json(body): axum::Json<HostSubscriptionData>,
-) -> impl IntoResponse {
-    let pool = require_db_pool!(state);
-
-    if let Err(e) =
-        db::unsubscribe_host_operation_failed(pool, &body.endpoint, &body.hostname).await
-    {
-        error!("Failed to unsubscribe from host operation-failed events: {e:#}");
-        return StatusCode::INTERNAL_SERVER_ERROR;
-    }
+) -> Result<StatusCode, ApiError> {
+    let pool = require_db_pool(&state)?;
 
-    StatusCode::NO_CONTENT
+    db::unsubscribe_host_operation_failed(pool, &body.endpoint, &body.hostname)
+        .await
+        .wrap_err("Failed to unsubscribe from host operation-failed events")?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 // ──────────────────────────────────────────────
@@ -387,20 +327,17 @@ async fn unsubscribe_host_operation_failed(
 async fn check_host_online_for_subscription(
     State(state): State<AppState>,
     Query(params): Query<HostSubscriptionData>,
-) -> impl IntoResponse {
-    let pool = require_db_pool!(response; state);
+) -> Result<impl IntoResponse, ApiError> {
+    let pool = require_db_pool(&state)?;
 
-    match db::is_subscribed_to_host_online_for(pool, &params.endpoint, &params.hostname).await {
-        Ok(duration_secs) => axum::Json(CheckHostOnlineForResponse {
-            subscribed: duration_secs.is_some(),
-            duration_secs,
-        })
-        .into_response(),
-        Err(e) => {
-            error!("Failed to check online-for push subscription: {e:#}");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
-    }
+    let duration_secs =
+        db::is_subscribed_to_host_online_for(pool, &params.endpoint, &params.hostname)
+            .await
+            .wrap_err("Failed to check online-for push subscription")?;
+    Ok(axum::Json(CheckHostOnlineForResponse {
+        subscribed: duration_secs.is_some(),
+        duration_secs,
+    }))
 }
 
 /// Registers a browser push subscription for recurring online-for notifications.
@@ -408,40 +345,16 @@ async fn check_host_online_for_subscription(
 async fn subscribe_host_online_for(
     State(state): State<AppState>,
     axum::Json(body): axum::Json<HostOnlineForRequest>,
-) -> Response {
-    let pool = require_db_pool!(response; state);
-
-    if let Err(error_response) =
-        validate_host_online_for_duration_secs(body.duration_secs).map(|_| ())
-    {
-        return *error_response;
-    }
+) -> Result<StatusCode, ApiError> {
+    let pool = require_db_pool(&state)?;
+    validate_host_online_for_duration_secs(body.duration_secs)?;
+    validate_push_subscription(&body.subscription)?;
 
-    validate_push_subscription!(response; body.subscription);
-
-    let sub_id = match db::upsert_push_subscription(
-        pool,
-        &body.subscription.endpoint,
-        &body.subscription.keys.p256dh,
-        &body.subscription.keys.auth,
-    )
-    .await
-    {
-        Ok(id) => id,
-        Err(e) => {
-            error!("Failed to upsert push subscription: {e:#}");
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-        }
-    };
-
-    if let Err(e) =
-        db::subscribe_host_online_for(pool, sub_id, &body.hostname, body.duration_secs).await
-    {
-        error!("Failed to subscribe to host online-for events: {e:#}");
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-    }
-
-    StatusCode::NO_CONTENT.into_response()
+    let sub_id = upsert_subscription(pool, &body.subscription).await?;
+    db::subscribe_host_online_for(pool, sub_id, &body.hostname, body.duration_secs)
+        .await
+        .wrap_err("Failed to subscribe to host online-for events")?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 /// Removes the online-for subscription link for a specific endpoint + host pair.
@@ -449,15 +362,13 @@ async fn subscribe_host_online_for(
 async fn unsubscribe_host_online_for(
     State(state): State<AppState>,
     axum::Json(body): axum::Json<HostSubscriptionData>,
-) -> impl IntoResponse {
-    let pool = require_db_pool!(state);
-
-    if let Err(e) = db::unsubscribe_host_online_for(pool, &body.endpoint, &body.hostname).await {
-        error!("Failed to unsubscribe from host online-for events: {e:#}");
-        return StatusCode::INTERNAL_SERVER_ERROR;
-    }
+) -> Result<StatusCode, ApiError> {
+    let pool = require_db_pool(&state)?;
 
-    StatusCode::NO_CONTENT
+    db::unsubscribe_host_online_for(pool, &body.endpoint, &body.hostname)
+        .await
+        .wrap_err("Failed to unsubscribe from host online-for events")?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 /// Registers a one-shot push notification that fires once the given host has been
@@ -470,26 +381,24 @@ async fn unsubscribe_host_online_for(
 async fn subscribe_host_online_for_oneshot(
     State(state): State<AppState>,
     axum::Json(body): axum::Json<HostOnlineForRequest>,
-) -> Response {
-    let pool = require_db_pool!(response; state);
-    let pool = pool.clone();
+) -> Result<StatusCode, ApiError> {
+    let pool = require_db_pool(&state)?.clone();
 
     let duration_secs = body.duration_secs;
-    let duration = match validate_host_online_for_duration_secs(duration_secs) {
-        Ok(duration) => duration,
-        Err(error_response) => return *error_response,
-    };
-
-    validate_push_subscription!(response; body.subscription);
+    let duration = validate_host_online_for_duration_secs(duration_secs)?;
+    validate_push_subscription(&body.subscription)?;
 
     let Some(vapid_key) = state.vapid_key.clone() else {
-        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        return Err(ApiError::Unavailable(PERSISTENCE_REQUIRED.to_string()));
     };
 
     let session_start = {
         let guard = state.online_since.read().await;
         let Some(&instant) = guard.get(&body.hostname) else {
-            return StatusCode::CONFLICT.into_response();
+            return Err(ApiError::Conflict(format!(
+                "Host {} is not online",
+                body.hostname
+            )));
         };
         instant
     };
@@ -504,17 +413,7 @@ async fn subscribe_host_online_for_oneshot(
     let hostname = body.hostname.clone();
 
     // Upsert the push subscription so future sends work correctly.
-    if let Err(e) = db::upsert_push_subscription(
-        &pool,
-        &body.subscription.endpoint,
-        &body.subscription.keys.p256dh,
-        &body.subscription.keys.auth,
-    )
-    .await
-    {
-        error!("Failed to upsert push subscription for oneshot: {e:#}");
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-    }
+    upsert_subscription(&pool, &body.subscription).await?;
 
     tokio::spawn(async move {
         sleep(duration).await;
@@ -529,7 +428,7 @@ async fn subscribe_host_online_for_oneshot(
         send_push_notifications(&vapid_key, &pool, &[sub], &payload).await;
     });
 
-    StatusCode::NO_CONTENT.into_response()
+    Ok(StatusCode::NO_CONTENT)
 }
 
 // ──────────────────────────────────────────────
@@ -10,7 +10,7 @@ use axum::{
 use hyper::StatusCode;
 use tower_http::{
     classify,
-    trace::{DefaultOnFailure, OnFailure},
+    trace::{DefaultOnFailure, MakeSpan, OnFailure},
 };
 
 /// Custom failure handling for the trace layer. 503 responses are logged
@@ -38,6 +38,27 @@ impl OnFailure<classify::ServerErrorsFailureClass> for LevelAdjustingOnFailure {
     }
 }
 
+/// Request span for the trace layer that records the `x-request-id` header,
+/// so log lines can be correlated with the ID returned to the client.
+#[derive(Clone, Copy)]
+pub(crate) struct RequestIdMakeSpan;
+
+impl<B> MakeSpan<B> for RequestIdMakeSpan {
+    fn make_span(&mut self, request: &Request<B>) -> tracing::Span {
+        let request_id = request
+            .headers()
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        tracing::info_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            request_id,
+        )
+    }
+}
+
 /// Middleware to set security headers on all responses
 ///
 /// This is less strict than possible. It avoids using CORS, X-Frame-Options: DENY
@@ -5,6 +5,9 @@
 //! This file contains the public re-exports and shared constants/macros for the
 //! split `server` submodules (`state`, `tls`, `router`, `middleware`, `run`).
 
+use axum::Json;
+use serde_json::json;
+
 /// Version number for validating external authentication exceptions.
 ///
 /// This constant ensures compatibility with external authentication systems by checking
@@ -15,7 +18,13 @@
 /// defined there include authentication endpoints (e.g., login, logout, OIDC callbacks) whose behavior and
 /// accessibility may depend on this version when handling external authentication modes.
 /// When routes get added to public routes, this needs to be bumped.
-pub(crate) const EXPECTED_AUTH_EXCEPTIONS_VERSION: u32 = 2;
+pub(crate) const EXPECTED_AUTH_EXCEPTIONS_VERSION: u32 = 4;
+
+/// Returns [`EXPECTED_AUTH_EXCEPTIONS_VERSION`], so config tooling can stamp `exceptions_version`
+/// without parsing the binary.
+pub(crate) async fn serve_auth_exceptions_version() -> Json<serde_json::Value> {
+    Json(json!({ "expected_version": EXPECTED_AUTH_EXCEPTIONS_VERSION }))
+}
 
 #[macro_export]
 macro_rules! cfg_if_expr {
@@ -1,16 +1,15 @@
-use alloc::sync::Arc;
-use core::time::Duration;
+use core::{net::SocketAddr, time::Duration};
 
 use axum::{
     Router,
-    extract::State,
+    extract::{State, connect_info::IntoMakeServiceWithConnectInfo},
     http::{
         Method, StatusCode,
         header::{AUTHORIZATION, COOKIE},
     },
     middleware::{self as ax_middleware},
     response::{IntoResponse as _, Response},
-    routing::{IntoMakeService, any, get},
+    routing::{any, get},
 };
 use tower::ServiceBuilder;
 use tower_http::{
@@ -19,34 +18,64 @@ use tower_http::{
 
 use crate::{
     app::AppState,
-    http::{auth, middleware::LevelAdjustingOnFailure},
-    websocket,
+    config::MetricsConfig,
+    http::{auth, error::ApiError, middleware::LevelAdjustingOnFailure, server},
+    metrics, websocket,
 };
 
-use crate::http::{api, assets, download, login, m2m, push};
+use crate::http::{api, assets, download, events, health, login, m2m, push};
 
-use crate::http::server::middleware::secure_headers_middleware;
+use crate::http::server::middleware::{RequestIdMakeSpan, secure_headers_middleware};
 
 /// Creates the main application router by merging public and private routes.
 ///
 /// Public routes include authentication endpoints (login, logout, OIDC), static assets,
-/// downloads, and M2M APIs that are accessible without authentication.
-/// Private routes include the main UI, API endpoints, and WebSocket handler, protected by auth middleware.
+/// downloads, the auth exceptions version, the health probes, and M2M APIs that are accessible
+/// without authentication.
+/// Private routes include the main UI, API endpoints, the WebSocket handler and the SSE event
+/// stream, protected by auth middleware.
+///
+/// M2M routes are additionally rate limited per client, and per peer IP for requests that fail
+/// authentication.
+///
+/// Requests time out after `[server].request_timeout_secs`, M2M requests after
+/// `[server].m2m_request_timeout_secs` instead.
+///
+/// The Prometheus metrics route is public as well, but only added when enabled in `[server.metrics]`.
 ///
 /// When routes get added to public routes, [`crate::http::server::EXPECTED_AUTH_EXCEPTIONS_VERSION`] needs to be bumped.
 pub(crate) fn create_app_router(
-    auth_runtime: &Arc<auth::Runtime>,
+    app_state: &AppState,
     spa_handler: impl Fn(AppState) -> Response + Send + Sync + Clone + 'static,
 ) -> Router<AppState> {
+    let (request_timeout, m2m_request_timeout) = {
+        let server = &app_state.config_rx.borrow().server;
+        (
+            Duration::from_secs(server.request_timeout_secs),
+            Duration::from_secs(server.m2m_request_timeout_secs),
+        )
+    };
+
     let public = Router::new()
         .merge(login::routes())
         .merge(assets::routes())
+        .merge(health::routes())
         .nest("/download", download::routes())
-        .nest("/api/m2m", m2m::routes());
+        .route(
+            "/api/auth_exceptions_version",
+            get(server::serve_auth_exceptions_version),
+        );
+    let public = match app_state.config_rx.borrow().server.metrics {
+        Some(ref metrics_cfg @ MetricsConfig { enable: true, .. }) => {
+            public.route(&metrics_cfg.path, get(metrics::serve_metrics))
+        }
+        _ => public,
+    };
 
     let private = Router::new()
         .nest("/api", api::routes())
         .nest("/api/push", push::routes())
+        .merge(events::routes())
         .route(
             "/",
             get({
@@ -57,16 +86,38 @@ pub(crate) fn create_app_router(
         .route("/ws", any(websocket::ws_handler))
         .route_layer(ax_middleware::from_fn_with_state(
             auth::LayerState {
-                auth: auth_runtime.clone(),
+                auth: app_state.auth.clone(),
             },
             auth::require,
         ));
 
+    // Synchronous M2M lease requests wait for the host, so they get their own timeout.
+    let m2m = Router::new().nest(
+        "/api/m2m",
+        m2m::routes()
+            .route_layer(ax_middleware::from_fn_with_state(
+                app_state.clone(),
+                m2m::rate_limit,
+            ))
+            .layer(TimeoutLayer::with_status_code(
+                StatusCode::REQUEST_TIMEOUT,
+                m2m_request_timeout,
+            )),
+    );
+
     public
         .merge(private)
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            request_timeout,
+        ))
+        .merge(m2m)
         // Any unmatched /api/* path gets a clean 404; this must be registered
         // before the fallback so it is matched with higher precedence.
-        .route("/api/{*path}", any(|| async { StatusCode::NOT_FOUND }))
+        .route(
+            "/api/{*path}",
+            any(|| async { ApiError::NotFound("No such API endpoint".to_string()) }),
+        )
         .fallback(async move |method: Method, State(state): State<AppState>| {
             // Fallback handler for unmatched routes: serves the SPA shell for GET/HEAD
             // requests (letting the client-side router render the correct page, including
@@ -79,13 +130,21 @@ pub(crate) fn create_app_router(
         })
 }
 
-pub(crate) fn create_app(app_state: AppState) -> IntoMakeService<Router<()>> {
+/// Builds the complete service. The peer address is made available as
+/// [`ConnectInfo`](axum::extract::ConnectInfo), e.g. for per-IP rate limiting.
+pub(crate) fn create_app(
+    app_state: AppState,
+) -> IntoMakeServiceWithConnectInfo<Router<()>, SocketAddr> {
     #[expect(clippy::absolute_paths, reason = "I dont want conditional imports")]
     let middleware_stack = ServiceBuilder::new()
         .sensitive_headers([AUTHORIZATION, COOKIE])
         .set_x_request_id(MakeRequestUuid)
         .propagate_x_request_id()
-        .layer(TraceLayer::new_for_http().on_failure(LevelAdjustingOnFailure))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(RequestIdMakeSpan)
+                .on_failure(LevelAdjustingOnFailure),
+        )
         .layer(cfg_if_expr!(
             #[cfg(any(
                 feature = "compression-br",
@@ -97,15 +156,11 @@ pub(crate) fn create_app(app_state: AppState) -> IntoMakeService<Router<()>> {
             #[cfg(not)]
             tower::layer::util::Identity::new(),
         ))
-        .layer(TimeoutLayer::with_status_code(
-            StatusCode::REQUEST_TIMEOUT,
-            Duration::from_secs(30),
-        ))
         .layer(ax_middleware::from_fn(secure_headers_middleware));
 
-    let app = create_app_router(&app_state.auth, assets::serve_ui)
+    let app = create_app_router(&app_state, assets::serve_ui)
         .with_state(app_state)
         .layer(middleware_stack);
 
-    app.into_make_service()
+    app.into_make_service_with_connect_info::<SocketAddr>()
 }
@@ -1,16 +1,121 @@
+use alloc::sync::Arc;
 use core::net::{IpAddr, SocketAddr};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use axum_server::tls_rustls::RustlsConfig as AxumRustlsConfig;
 use eyre::{WrapErr as _, eyre};
-use secrecy::{ExposeSecret as _, SecretBox};
+use p12_keystore::{KeyStore, Pkcs12ImportPolicy};
+use secrecy::{ExposeSecret as _, SecretBox, SecretString};
 use tokio::fs as t_fs;
 
-use crate::config::{TlsConfig, resolve_config_relative_paths};
+use crate::config::{TlsConfig, TlsFormat, resolve_config_relative_paths};
+
+/// The certificate files of a TLS config, resolved relative to the config file.
+#[derive(Debug, Clone)]
+pub(crate) enum TlsFiles {
+    Pem {
+        cert_path: PathBuf,
+        key_path: PathBuf,
+    },
+    Pkcs12 {
+        path: PathBuf,
+        password: Option<Arc<SecretString>>,
+    },
+}
+
+impl TlsFiles {
+    pub(crate) fn from_config(tls_cfg: &TlsConfig, config_path: &Path) -> Self {
+        let cert_path = resolve_config_relative_paths(config_path, &tls_cfg.cert_path);
+        match tls_cfg.format {
+            TlsFormat::Pem => Self::Pem {
+                cert_path,
+                key_path: resolve_config_relative_paths(config_path, &tls_cfg.key_path),
+            },
+            TlsFormat::Pkcs12 => Self::Pkcs12 {
+                path: cert_path,
+                password: tls_cfg.pkcs12_password.clone(),
+            },
+        }
+    }
+
+    /// The files to watch for certificate changes.
+    pub(crate) fn paths(&self) -> Vec<&Path> {
+        match *self {
+            Self::Pem {
+                ref cert_path,
+                ref key_path,
+            } => vec![cert_path, key_path],
+            Self::Pkcs12 { ref path, .. } => vec![path],
+        }
+    }
+
+    /// Replaces the certificate served by `rustls_config` with the one in the files.
+    pub(crate) async fn reload(&self, rustls_config: &AxumRustlsConfig) -> eyre::Result<()> {
+        match *self {
+            Self::Pem {
+                ref cert_path,
+                ref key_path,
+            } => rustls_config
+                .reload_from_pem_file(cert_path, key_path)
+                .await
+                .wrap_err("Failed to load PEM certificate"),
+            Self::Pkcs12 {
+                ref path,
+                ref password,
+            } => {
+                let (chain, key) = read_pkcs12(path, password.as_deref()).await?;
+                rustls_config
+                    .reload_from_der(chain, key)
+                    .await
+                    .wrap_err("Failed to use certificate from PKCS#12 bundle")
+            }
+        }
+    }
+}
+
+/// Reads the certificate chain and PKCS#8 private key from the PKCS#12 bundle at `path`.
+///
+/// The bundle must hold exactly the one key the certificate chain belongs to. A missing password
+/// is treated as the empty one.
+pub(crate) async fn read_pkcs12(
+    path: &Path,
+    password: Option<&SecretString>,
+) -> eyre::Result<(Vec<Vec<u8>>, Vec<u8>)> {
+    let data = t_fs::read(path).await.wrap_err(format!(
+        "Failed to read PKCS#12 bundle at {}",
+        path.display()
+    ))?;
+    parse_pkcs12(
+        &data,
+        password.map_or("", |password| password.expose_secret()),
+    )
+    .wrap_err(format!("Invalid PKCS#12 bundle at {}", path.display()))
+}
+
+/// Extracts the certificate chain and PKCS#8 private key from PKCS#12 `data`.
+pub(crate) fn parse_pkcs12(data: &[u8], password: &str) -> eyre::Result<(Vec<Vec<u8>>, Vec<u8>)> {
+    let keystore = KeyStore::from_pkcs12(data, password, Pkcs12ImportPolicy::Relaxed)
+        .wrap_err("Failed to decrypt, is the password correct?")?;
+    let (_, chain) = keystore
+        .private_key_chain()
+        .ok_or_else(|| eyre!("No private key with certificate chain found"))?;
+    if chain.certs().is_empty() {
+        eyre::bail!("No certificate found for the private key");
+    }
+    Ok((
+        chain
+            .certs()
+            .iter()
+            .map(|cert| cert.as_der().to_vec())
+            .collect(),
+        chain.key().as_der().to_vec(),
+    ))
+}
 
 /// Setup TLS configuration for HTTPS server.
 ///
-/// Use provided certs when both files exist. Otherwise, if `persist_self_signed` is true
+/// In `pkcs12` format the bundle must exist. In `pem` format, use provided certs when both files
+/// exist. Otherwise, if `persist_self_signed` is true
 /// (default), generate and persist self-signed cert/key next to the config file.
 #[tracing::instrument]
 pub(crate) async fn setup_tls_config(
@@ -19,6 +124,19 @@ pub(crate) async fn setup_tls_config(
     listen_ip: IpAddr,
     addr: SocketAddr,
 ) -> eyre::Result<AxumRustlsConfig> {
+    if tls_cfg.format == TlsFormat::Pkcs12 {
+        let path = resolve_config_relative_paths(config_path, &tls_cfg.cert_path);
+        let (chain, key) = read_pkcs12(&path, tls_cfg.pkcs12_password.as_deref()).await?;
+        let rustls_cfg = AxumRustlsConfig::from_der(chain, key)
+            .await
+            .wrap_err(format!(
+                "Failed to use certificate from PKCS#12 bundle at {}",
+                path.display()
+            ))?;
+        tracing::info!("Listening on https://{} (provided PKCS#12 bundle)", addr);
+        return Ok(rustls_cfg);
+    }
+
     let cert_path_cfg = tls_cfg.cert_path.as_str();
     let key_path_cfg = tls_cfg.key_path.as_str();
 
@@ -86,3 +204,43 @@ pub(crate) async fn setup_tls_config(
 
     Ok(rustls_cfg)
 }
+
+#[cfg(test)]
+mod tests {
+    use p12_keystore::{Certificate, KeyStoreEntry, PrivateKey, PrivateKeyChain};
+
+    use super::*;
+
+    fn bundle(password: &str) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let rcgen::CertifiedKey { cert, signing_key } =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let key_der = signing_key.serialize_der();
+        let chain = PrivateKeyChain::new(
+            "shuthost",
+            PrivateKey::from_der(&key_der).unwrap(),
+            [Certificate::from_der(cert.der()).unwrap()],
+        );
+        let mut keystore = KeyStore::new();
+        keystore.add_entry("shuthost", KeyStoreEntry::PrivateKeyChain(chain));
+        (
+            keystore.writer(password).write().unwrap(),
+            cert.der().to_vec(),
+            key_der,
+        )
+    }
+
+    #[test]
+    fn extracts_chain_and_key() {
+        let (data, cert_der, key_der) = bundle("secret");
+        let (chain, key) = parse_pkcs12(&data, "secret").unwrap();
+        assert_eq!(chain, [cert_der]);
+        assert_eq!(key, key_der);
+    }
+
+    #[test]
+    fn rejects_wrong_password() {
+        let (data, _, _) = bundle("secret");
+        let err = parse_pkcs12(&data, "wrong").unwrap_err();
+        assert!(format!("{err:#}").contains("password"), "{err:#}");
+    }
+}
@@ -0,0 +1,222 @@
+//! Side-effecting operations of the coordinator installer.
+//!
+//! All filesystem writes and service manager calls go through the [`Installer`] trait, so that
+//! `install --dry-run` can print the planned actions instead of performing them.
+
+use std::{
+    fs,
+    os::unix::fs::{self as unix_fs, PermissionsExt as _},
+    path::Path,
+};
+
+use eyre::WrapErr as _;
+use nix::unistd::User;
+
+#[cfg(target_os = "linux")]
+use shuthost_common::{is_openrc, is_systemd};
+
+/// Service manager the coordinator gets registered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ServiceManager {
+    #[cfg(target_os = "linux")]
+    Systemd,
+    #[cfg(target_os = "linux")]
+    OpenRC,
+    #[cfg(target_os = "macos")]
+    Launchd,
+}
+
+impl ServiceManager {
+    /// Detects the service manager of the running system.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if no supported service manager is found.
+    #[cfg_attr(
+        target_os = "macos",
+        expect(clippy::unnecessary_wraps, reason = "detection can only fail on linux")
+    )]
+    pub(super) fn detect() -> eyre::Result<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            if is_systemd() {
+                Ok(Self::Systemd)
+            } else if is_openrc() {
+                Ok(Self::OpenRC)
+            } else {
+                eyre::bail!("Unsupported init system: expected systemd, OpenRC or sysvinit style.");
+            }
+        }
+        #[cfg(target_os = "macos")]
+        {
+            Ok(Self::Launchd)
+        }
+    }
+
+    fn service_path(self, name: &str) -> String {
+        match self {
+            #[cfg(target_os = "linux")]
+            Self::Systemd => shuthost_common::systemd::get_service_path(name),
+            #[cfg(target_os = "linux")]
+            Self::OpenRC => shuthost_common::openrc::get_service_path(name),
+            #[cfg(target_os = "macos")]
+            Self::Launchd => shuthost_common::macos::get_service_path(name),
+        }
+    }
+}
+
+/// Operations with side effects performed while installing the coordinator.
+pub(super) trait Installer {
+    /// Installs the current binary and registers it as service `name` using `service_file`.
+    fn install_service(
+        &self,
+        manager: ServiceManager,
+        name: &str,
+        service_file: &str,
+    ) -> eyre::Result<()>;
+
+    /// Enables the service `name` to start on boot and starts it.
+    fn start_and_enable_service(&self, manager: ServiceManager, name: &str) -> eyre::Result<()>;
+
+    /// Creates `path` and all missing parent directories.
+    fn create_dir_all(&self, path: &Path) -> eyre::Result<()>;
+
+    /// Moves `from` to `to`.
+    fn rename(&self, from: &Path, to: &Path) -> eyre::Result<()>;
+
+    /// Creates (or truncates) the file at `path` and writes `content` to it.
+    fn write_file(&self, path: &Path, content: &str) -> eyre::Result<()>;
+
+    /// Sets the unix permission bits of `path`.
+    fn set_mode(&self, path: &Path, mode: u32) -> eyre::Result<()>;
+
+    /// Transfers ownership of `path` to `user` and their primary group.
+    fn chown(&self, path: &Path, user: &User) -> eyre::Result<()>;
+}
+
+/// Performs the installation on the running system.
+pub(super) struct SystemInstaller;
+
+impl Installer for SystemInstaller {
+    fn install_service(
+        &self,
+        manager: ServiceManager,
+        name: &str,
+        service_file: &str,
+    ) -> eyre::Result<()> {
+        match manager {
+            #[cfg(target_os = "linux")]
+            ServiceManager::Systemd => {
+                shuthost_common::systemd::install_self_as_service(name, service_file)
+            }
+            #[cfg(target_os = "linux")]
+            ServiceManager::OpenRC => {
+                shuthost_common::openrc::install_self_as_service(name, service_file)
+            }
+            #[cfg(target_os = "macos")]
+            ServiceManager::Launchd => {
+                shuthost_common::macos::install_self_as_service(name, service_file)
+            }
+        }
+        .map_err(eyre::Report::msg)
+    }
+
+    fn start_and_enable_service(&self, manager: ServiceManager, name: &str) -> eyre::Result<()> {
+        match manager {
+            #[cfg(target_os = "linux")]
+            ServiceManager::Systemd => {
+                shuthost_common::systemd::start_and_enable_self_as_service(name)
+            }
+            #[cfg(target_os = "linux")]
+            ServiceManager::OpenRC => {
+                shuthost_common::openrc::start_and_enable_self_as_service(name)
+            }
+            #[cfg(target_os = "macos")]
+            ServiceManager::Launchd => {
+                shuthost_common::macos::start_and_enable_self_as_service(name)
+            }
+        }
+        .map_err(eyre::Report::msg)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> eyre::Result<()> {
+        fs::create_dir_all(path).wrap_err(format!("Failed to create directory {}", path.display()))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> eyre::Result<()> {
+        fs::rename(from, to).wrap_err(format!(
+            "Failed to move {} to {}",
+            from.display(),
+            to.display()
+        ))?;
+        println!("Moved {from:?} to {to:?}");
+        Ok(())
+    }
+
+    fn write_file(&self, path: &Path, content: &str) -> eyre::Result<()> {
+        fs::write(path, content).wrap_err(format!("Failed to write {}", path.display()))?;
+        println!("Created {path:?}");
+        Ok(())
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> eyre::Result<()> {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .wrap_err(format!("Failed to set permissions of {}", path.display()))
+    }
+
+    fn chown(&self, path: &Path, user: &User) -> eyre::Result<()> {
+        unix_fs::chown(path, Some(user.uid.into()), Some(user.gid.into()))
+            .wrap_err(format!("Failed to chown {}", path.display()))?;
+        println!("Chowned {path:?} for {}", user.name);
+        Ok(())
+    }
+}
+
+/// Prints every action instead of performing it.
+pub(super) struct DryRunInstaller;
+
+impl Installer for DryRunInstaller {
+    fn install_service(
+        &self,
+        manager: ServiceManager,
+        name: &str,
+        service_file: &str,
+    ) -> eyre::Result<()> {
+        println!("[dry-run] Would install the current binary as {manager:?} service {name}");
+        println!(
+            "[dry-run] Would write service file {} with content:\n{service_file}",
+            manager.service_path(name)
+        );
+        Ok(())
+    }
+
+    fn start_and_enable_service(&self, manager: ServiceManager, name: &str) -> eyre::Result<()> {
+        println!("[dry-run] Would enable and start {manager:?} service {name}");
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> eyre::Result<()> {
+        println!("[dry-run] Would create directory {path:?}");
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> eyre::Result<()> {
+        println!("[dry-run] Would move {from:?} to {to:?}");
+        Ok(())
+    }
+
+    fn write_file(&self, path: &Path, content: &str) -> eyre::Result<()> {
+        println!("[dry-run] Would write {path:?} with content:\n{content}");
+        Ok(())
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> eyre::Result<()> {
+        println!("[dry-run] Would set permissions of {path:?} to {mode:o}");
+        Ok(())
+    }
+
+    fn chown(&self, path: &Path, user: &User) -> eyre::Result<()> {
+        println!("[dry-run] Would chown {path:?} to {}", user.name);
+        Ok(())
+    }
+}
@@ -1,14 +1,9 @@
-use std::{
-    fs,
-    path::{Path, PathBuf},
-};
-
-#[cfg(any(target_os = "linux", target_os = "macos"))]
-use std::os::unix::fs::{self as unix_fs, PermissionsExt as _};
+use std::path::{Path, PathBuf};
 
 use eyre::WrapErr as _;
 use nix::unistd::User;
 
+use super::installer::Installer;
 use crate::install::BINARY_NAME;
 
 /// Migrates old config file and associated files from the old location to the new location.
@@ -19,6 +14,7 @@ use crate::install::BINARY_NAME;
 ///
 /// # Arguments
 ///
+/// * `installer` - Performs (or, for dry runs, prints) the filesystem operations.
 /// * `user` - The username for ownership.
 /// * `new_config_location` - Path to the new config file location.
 ///
@@ -26,7 +22,11 @@ use crate::install::BINARY_NAME;
 ///
 /// Returns an error if file operations fail.
 #[cfg(any(target_os = "linux", target_os = "macos"))]
-pub fn migrate_old_config(user: &str, new_config_location: &Path) -> eyre::Result<()> {
+pub(super) fn migrate_old_config(
+    installer: &dyn Installer,
+    user: &str,
+    new_config_location: &Path,
+) -> eyre::Result<()> {
     #[cfg(target_os = "linux")]
     let old_config_location = PathBuf::from(format!("/home/{user}/.config/{BINARY_NAME}.toml"));
     #[cfg(target_os = "macos")]
@@ -38,15 +38,10 @@ pub fn migrate_old_config(user: &str, new_config_location: &Path) -> eyre::Resul
         if let Some(parent_dir) = new_config_location.parent()
             && !parent_dir.exists()
         {
-            fs::create_dir_all(parent_dir).wrap_err("Failed to create config directory")?;
+            installer.create_dir_all(parent_dir)?;
             created_new_dir = true;
         }
-        fs::rename(&old_config_location, new_config_location).wrap_err(format!(
-            "Failed to move config file from {} to {}",
-            old_config_location.display(),
-            new_config_location.display()
-        ))?;
-        println!("Moved config file from {old_config_location:?} to {new_config_location:?}");
+        installer.rename(&old_config_location, new_config_location)?;
 
         // Also move associated files (database and certificates) from old directory to new directory
         if let (Some(old_dir), Some(new_dir)) =
@@ -63,31 +58,19 @@ pub fn migrate_old_config(user: &str, new_config_location: &Path) -> eyre::Resul
                 let old_file = old_dir.join(file_name);
                 let new_file = new_dir.join(file_name);
                 if old_file.exists() && !new_file.exists() {
-                    fs::rename(&old_file, &new_file).wrap_err(format!(
-                        "Failed to move {} from {} to {}",
-                        file_name,
-                        old_file.display(),
-                        new_file.display()
-                    ))?;
-                    println!("Moved {file_name} from {old_file:?} to {new_file:?}");
+                    installer.rename(&old_file, &new_file)?;
                 }
             }
         }
 
         // Chown the new directory if it was created
         if created_new_dir && let Some(parent_dir) = new_config_location.parent() {
-            fs::set_permissions(parent_dir, fs::Permissions::from_mode(0o700))?;
+            installer.set_mode(parent_dir, 0o700)?;
 
             let user_info = User::from_name(user)
                 .wrap_err("Failed to get user info")?
                 .ok_or_else(|| eyre::eyre!("User {user} not found"))?;
-            unix_fs::chown(
-                parent_dir,
-                Some(user_info.uid.into()),
-                Some(user_info.gid.into()),
-            )?;
-
-            println!("Chowned migrated config directory at {parent_dir:?} for {user}");
+            installer.chown(parent_dir, &user_info)?;
         }
     }
 
@@ -3,24 +3,19 @@
 //! Supports systemd, `OpenRC`, and launchd based on target OS.
 
 use core::net::IpAddr;
-use std::{
-    fs,
-    fs::File,
-    io::Write as _,
-    os::unix::fs::{self as nix_fs, PermissionsExt as _},
-    path::{Path, PathBuf},
-};
+use std::path::{self, Path, PathBuf};
 
 use clap::Parser;
 use eyre::WrapErr as _;
 use nix::unistd::User;
 
+mod installer;
 mod migration;
+pub mod uninstall;
 
-#[cfg(target_os = "linux")]
-use shuthost_common::{is_openrc, is_systemd};
+use installer::{DryRunInstaller, Installer, ServiceManager, SystemInstaller};
 
-use crate::cli::BINARY_NAME;
+use crate::cli::{BINARY_NAME, CONFIG_PATH_ENV, deprecated_config_path};
 
 #[cfg(target_os = "linux")]
 const SERVICE_FILE_TEMPLATE: &str = include_str!("shuthost_coordinator.service.tmpl.ini");
@@ -38,12 +33,21 @@ pub struct Args {
     user: String,
 
     /// Port on which the coordinator HTTP server will listen.
-    #[arg(long, short, default_value_t = 8080)]
+    #[arg(long, short, default_value_t = shuthost_common::DEFAULT_COORDINATOR_PORT)]
     port: u16,
 
     /// Bind address for the HTTP server (e.g., 127.0.0.1 or 0.0.0.0).
     #[arg(long, short, default_value = "127.0.0.1")]
     bind: String,
+
+    /// Location of the config file the service is started with.
+    /// Defaults to `~/.config/shuthost_coordinator/config.toml` in the home of `user`.
+    #[arg(long, short, env = CONFIG_PATH_ENV)]
+    config: Option<PathBuf>,
+
+    /// Print the actions that would be taken (including generated file contents) without performing them.
+    #[arg(long)]
+    dry_run: bool,
 }
 
 /// Installs the coordinator as a system service and creates its config file.
@@ -62,21 +66,31 @@ pub struct Args {
 pub(crate) fn setup(args: Args) -> eyre::Result<()> {
     let name = BINARY_NAME;
     let user = args.user;
+    let installer: &dyn Installer = if args.dry_run {
+        &DryRunInstaller
+    } else {
+        &SystemInstaller
+    };
 
     args.bind
         .parse::<IpAddr>()
         .wrap_err("Invalid bind address")?;
 
-    // sadly, due to the installation running under sudo, I can't use $XDG_CONFIG_HOME
-    #[cfg(target_os = "linux")]
-    let new_config_location = PathBuf::from(format!("/home/{user}/.config/{name}/config.toml"));
-    #[cfg(target_os = "macos")]
-    let new_config_location = PathBuf::from(format!("/Users/{user}/.config/{name}/config.toml"));
+    let configured_location = args
+        .config
+        .clone()
+        .or_else(|| deprecated_config_path().map(PathBuf::from));
+    let config_location = if let Some(configured_location) = configured_location {
+        // The service runs from a different working directory.
+        path::absolute(configured_location).wrap_err("Invalid config path")?
+    } else {
+        let new_config_location = config_location_for(&user, name);
 
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    migration::migrate_old_config(&user, &new_config_location)?;
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        migration::migrate_old_config(installer, &user, &new_config_location)?;
 
-    let config_location = new_config_location;
+        new_config_location
+    };
 
     let bind_known_vals = |arg: &str| {
         arg.to_owned()
@@ -86,93 +100,182 @@ pub(crate) fn setup(args: Args) -> eyre::Result<()> {
             .replace("{ config_location }", &config_location.to_string_lossy())
     };
 
-    #[cfg(target_os = "linux")]
-    if is_systemd() {
-        shuthost_common::systemd::install_self_as_service(
-            name,
-            &bind_known_vals(SERVICE_FILE_TEMPLATE),
-        )
-        .map_err(eyre::Report::msg)?;
-    } else if is_openrc() {
-        shuthost_common::openrc::install_self_as_service(
-            name,
-            &bind_known_vals(OPENRC_FILE_TEMPLATE),
-        )
-        .map_err(eyre::Report::msg)?;
-    } else {
-        eyre::bail!("Unsupported init system: expected systemd, OpenRC or sysvinit style.");
-    }
-
-    #[cfg(target_os = "macos")]
-    shuthost_common::macos::install_self_as_service(name, &bind_known_vals(SERVICE_FILE_TEMPLATE))
-        .map_err(eyre::Report::msg)?;
+    let service_manager = ServiceManager::detect()?;
+    let service_file_template = match service_manager {
+        #[cfg(target_os = "linux")]
+        ServiceManager::OpenRC => OPENRC_FILE_TEMPLATE,
+        #[cfg(target_os = "linux")]
+        ServiceManager::Systemd => SERVICE_FILE_TEMPLATE,
+        #[cfg(target_os = "macos")]
+        ServiceManager::Launchd => SERVICE_FILE_TEMPLATE,
+    };
+    installer.install_service(
+        service_manager,
+        name,
+        &bind_known_vals(service_file_template),
+    )?;
 
-    if Path::new(&config_location).exists() {
+    if config_location.exists() {
         println!("Config file already exists at {config_location:?}, not overwriting.");
     } else {
-        let created_dir = if let Some(parent_dir) = config_location.parent()
-            && !parent_dir.exists()
-        {
-            fs::create_dir_all(parent_dir).wrap_err("Failed to create config directory")?;
-            true
-        } else {
-            false
-        };
-
-        let mut config_file = File::create(&config_location).wrap_err(format!(
-            "Failed to create config file at {}",
-            config_location.display()
-        ))?;
-        let port = args.port;
-        let bind = args.bind;
-        let config_content = include_str!("../../../docs/examples/example_config.toml")
-            .replace("port = 8080", &format!("port = {port}"))
-            .replace("bind = \"127.0.0.1\"", &format!("bind = \"{bind}\""));
-        config_file
-            .write_all(config_content.as_bytes())
-            .wrap_err("Failed to write config file")?;
-
-        fs::set_permissions(&config_location, fs::Permissions::from_mode(0o600))?;
-
-        println!("Created config file at {config_location:?}");
         let user_info = User::from_name(&user)
             .wrap_err("Failed to get user info")?
             .ok_or_else(|| eyre::eyre!("User {user} not found"))?;
-
-        // Chown the config directory if it was created
-        if created_dir && let Some(parent_dir) = config_location.parent() {
-            fs::set_permissions(parent_dir, fs::Permissions::from_mode(0o700))?;
-            nix_fs::chown(
-                parent_dir,
-                Some(user_info.uid.into()),
-                Some(user_info.gid.into()),
-            )?;
-
-            println!("Chowned config directory at {parent_dir:?} for {user}");
-        }
-
-        nix_fs::chown(
+        install_config(
+            installer,
             &config_location,
-            Some(user_info.uid.into()),
-            Some(user_info.gid.into()),
+            &user_info,
+            args.port,
+            &args.bind,
         )?;
-
-        println!("Chowned config file at {config_location:?} for {user}");
     }
 
-    #[cfg(target_os = "macos")]
-    shuthost_common::macos::start_and_enable_self_as_service(name).map_err(eyre::Report::msg)?;
+    installer.start_and_enable_service(service_manager, name)?;
 
+    Ok(())
+}
+
+/// Location of the config file of the coordinator service `name` installed for `user`.
+fn config_location_for(user: &str, name: &str) -> PathBuf {
+    // sadly, due to the installation running under sudo, I can't use $XDG_CONFIG_HOME
     #[cfg(target_os = "linux")]
-    if is_systemd() {
-        shuthost_common::systemd::start_and_enable_self_as_service(name)
-            .map_err(eyre::Report::msg)?;
-    } else if is_openrc() {
-        shuthost_common::openrc::start_and_enable_self_as_service(name)
-            .map_err(eyre::Report::msg)?;
+    let home = format!("/home/{user}");
+    #[cfg(target_os = "macos")]
+    let home = format!("/Users/{user}");
+    PathBuf::from(format!("{home}/.config/{name}/config.toml"))
+}
+
+/// Writes the example config with `port` and `bind` filled in to `config_location`,
+/// creating its parent directory if needed, and hands both over to `user`.
+fn install_config(
+    installer: &dyn Installer,
+    config_location: &Path,
+    user: &User,
+    port: u16,
+    bind: &str,
+) -> eyre::Result<()> {
+    let created_dir = if let Some(parent_dir) = config_location.parent()
+        && !parent_dir.exists()
+    {
+        installer.create_dir_all(parent_dir)?;
+        true
     } else {
-        eyre::bail!("Unsupported init system: expected systemd, OpenRC or sysvinit style.");
+        false
+    };
+
+    let config_content = include_str!("../../../docs/examples/example_config.toml")
+        .replace("port = 8080", &format!("port = {port}"))
+        .replace("bind = \"127.0.0.1\"", &format!("bind = \"{bind}\""));
+    installer.write_file(config_location, &config_content)?;
+    installer.set_mode(config_location, 0o600)?;
+
+    // Chown the config directory if it was created
+    if created_dir && let Some(parent_dir) = config_location.parent() {
+        installer.set_mode(parent_dir, 0o700)?;
+        installer.chown(parent_dir, user)?;
     }
 
-    Ok(())
+    installer.chown(config_location, user)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use std::{env, process};
+
+    use nix::unistd::getuid;
+
+    use super::*;
+
+    /// Records the requested actions instead of performing them.
+    #[derive(Default)]
+    struct RecordingInstaller {
+        actions: RefCell<Vec<String>>,
+    }
+
+    impl RecordingInstaller {
+        fn push(&self, action: String) {
+            self.actions.borrow_mut().push(action);
+        }
+    }
+
+    impl Installer for RecordingInstaller {
+        fn install_service(
+            &self,
+            manager: ServiceManager,
+            name: &str,
+            _service_file: &str,
+        ) -> eyre::Result<()> {
+            self.push(format!("install_service {manager:?} {name}"));
+            Ok(())
+        }
+
+        fn start_and_enable_service(
+            &self,
+            manager: ServiceManager,
+            name: &str,
+        ) -> eyre::Result<()> {
+            self.push(format!("start_and_enable_service {manager:?} {name}"));
+            Ok(())
+        }
+
+        fn create_dir_all(&self, path: &Path) -> eyre::Result<()> {
+            self.push(format!("create_dir_all {}", path.display()));
+            Ok(())
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> eyre::Result<()> {
+            self.push(format!("rename {} {}", from.display(), to.display()));
+            Ok(())
+        }
+
+        fn write_file(&self, path: &Path, content: &str) -> eyre::Result<()> {
+            self.push(format!("write_file {}", path.display()));
+            self.push(content.to_owned());
+            Ok(())
+        }
+
+        fn set_mode(&self, path: &Path, mode: u32) -> eyre::Result<()> {
+            self.push(format!("set_mode {} {mode:o}", path.display()));
+            Ok(())
+        }
+
+        fn chown(&self, path: &Path, user: &User) -> eyre::Result<()> {
+            self.push(format!("chown {} {}", path.display(), user.name));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn install_config_only_records_actions() {
+        let installer = RecordingInstaller::default();
+        let dir = env::temp_dir().join(format!("shuthost_install_dry_run_{}", process::id()));
+        let config_location = dir.join("config.toml");
+        let user = User::from_uid(getuid())
+            .unwrap()
+            .expect("current user exists");
+
+        install_config(&installer, &config_location, &user, 9090, "0.0.0.0").unwrap();
+
+        assert!(!dir.exists(), "nothing is written to disk");
+        let actions = installer.actions.into_inner();
+        let dir_s = dir.display();
+        let config_s = config_location.display();
+        assert_eq!(actions[0], format!("create_dir_all {dir_s}"));
+        assert_eq!(actions[1], format!("write_file {config_s}"));
+        assert!(actions[2].contains("port = 9090"), "port is substituted");
+        assert!(
+            actions[2].contains("bind = \"0.0.0.0\""),
+            "bind is substituted"
+        );
+        assert_eq!(
+            actions[3..],
+            [
+                format!("set_mode {config_s} 600"),
+                format!("set_mode {dir_s} 700"),
+                format!("chown {dir_s} {}", user.name),
+                format!("chown {config_s} {}", user.name),
+            ]
+        );
+    }
 }
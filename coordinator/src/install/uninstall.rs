@@ -0,0 +1,64 @@
+//! Coordinator uninstaller: reverses what `install` set up.
+//!
+//! Stops and unregisters the service, removes the installed binary and, unless asked to keep
+//! it, the generated config file.
+
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use super::{config_location_for, installer::ServiceManager};
+use crate::cli::{BINARY_NAME, CONFIG_PATH_ENV, deprecated_config_path};
+
+/// Arguments for the `uninstall` subcommand of the coordinator.
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Username the config file was generated for.
+    #[arg(env = "SUDO_USER")]
+    user: String,
+
+    /// Location of the config file, if it was installed to a custom path.
+    #[arg(long, short, env = CONFIG_PATH_ENV)]
+    config: Option<PathBuf>,
+
+    /// Leave the config file in place.
+    #[arg(long)]
+    keep_config: bool,
+}
+
+/// Removes the coordinator service, its binary and (unless `--keep-config` is given) its config file.
+///
+/// # Arguments
+///
+/// * `args` - Uninstallation arguments including the user the config belongs to.
+///
+/// # Errors
+///
+/// Returns `Err` if no supported service manager is found or any removal step fails.
+pub(crate) fn run(args: &Args) -> eyre::Result<()> {
+    let name = BINARY_NAME;
+
+    match ServiceManager::detect()? {
+        #[cfg(target_os = "linux")]
+        ServiceManager::Systemd => shuthost_common::systemd::uninstall_self_as_service(name),
+        #[cfg(target_os = "linux")]
+        ServiceManager::OpenRC => shuthost_common::openrc::uninstall_self_as_service(name),
+        #[cfg(target_os = "macos")]
+        ServiceManager::Launchd => shuthost_common::macos::uninstall_self_as_service(name),
+    }
+    .map_err(eyre::Report::msg)?;
+
+    let config_location = args
+        .config
+        .clone()
+        .or_else(|| deprecated_config_path().map(PathBuf::from))
+        .unwrap_or_else(|| config_location_for(&args.user, name));
+    if args.keep_config {
+        println!("Keeping config file at {config_location:?}.");
+    } else {
+        shuthost_common::remove_file_if_exists(&config_location, "config file")
+            .map_err(eyre::Report::msg)?;
+    }
+
+    Ok(())
+}
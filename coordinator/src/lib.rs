@@ -13,16 +13,19 @@ extern crate alloc;
 extern crate core;
 
 pub mod app;
+pub mod audit_log;
 pub mod cli;
 pub mod config;
+pub mod cron;
 pub mod demo;
 pub mod http;
 #[cfg(unix)]
 pub mod install;
+pub mod metrics;
 pub mod websocket;
 pub mod wol;
 
-use std::{env, fs, process, sync::Once};
+use std::{fs, process, sync::Once};
 
 #[cfg(unix)]
 use nix::sys::stat;
@@ -33,7 +36,7 @@ use tracing::{Instrument as _, info, warn};
 use tracing_subscriber::{EnvFilter, fmt::time::ChronoLocal};
 
 use app::start;
-use cli::{Cli, Command, LogFormat};
+use cli::{Cli, Command, LogFormat, resolve_config_path};
 use demo::run_demo_service;
 pub use websocket::WsMessage;
 
@@ -60,26 +63,37 @@ pub async fn inner_main(invocation: Cli) -> Result<()> {
             install::setup(args)?;
             Ok(())
         }
+        #[cfg(unix)]
+        Command::Uninstall(args) => {
+            install::uninstall::run(&args)?;
+            Ok(())
+        }
+        Command::GenerateConfig(args) => config::generate::run(&args),
+        Command::ValidateConfig { config } => {
+            config::validate::run(&resolve_config_path(config)).await
+        }
+        Command::ExportConfig(args) => config::export::run(&args).await,
+        Command::Diagnose { config } => app::diagnose::run(&resolve_config_path(config)).await,
+        Command::BackupDb { config, output } => {
+            app::db_backup::backup(&resolve_config_path(config), &output).await
+        }
+        Command::RestoreDb { config, input } => {
+            app::db_backup::restore(&resolve_config_path(config), &input).await
+        }
         Command::ControlService(args) => {
             // Set umask to ensure database files have restrictive permissions
             #[cfg(unix)]
             stat::umask(stat::Mode::S_IRWXU.complement());
 
-            let config = &args.config;
+            let config = &resolve_config_path(args.config.clone());
             let config_path =
                 fs::canonicalize(config).wrap_err(format!("Config file not found at: {config}"))?;
 
             INIT_TRACING.call_once(move || {
-                let default_level = if env::var("SHUTHOST_INTEGRATION_TEST").is_ok() {
-                    "error"
-                } else {
-                    "info"
-                };
-
                 let builder = tracing_subscriber::fmt()
                     .with_env_filter(
                         EnvFilter::try_from_default_env()
-                            .unwrap_or_else(|_| EnvFilter::new(default_level)),
+                            .unwrap_or_else(|_| EnvFilter::new(args.log_level.to_string())),
                     )
                     .with_timer(ChronoLocal::rfc_3339());
 
@@ -122,8 +136,9 @@ pub async fn inner_main(invocation: Cli) -> Result<()> {
             port,
             bind,
             subpath,
+            demo,
         } => {
-            run_demo_service(port, &bind, &subpath).await;
+            run_demo_service(port, &bind, &subpath, demo).await;
             Ok(())
         }
     }
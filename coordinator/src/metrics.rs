@@ -0,0 +1,427 @@
+//! Prometheus metrics for host status, leases, polling and host control operations.
+//!
+//! Enabled through the optional `[server.metrics]` table. Counters and histograms are recorded
+//! as events happen; gauges (online state, lease counts) are computed from the current state
+//! on every scrape. Scrapers asking for `OpenMetrics` via `Accept` get that format instead,
+//! following the content negotiation of the Prometheus Go client.
+
+use core::{fmt::Write as _, time::Duration};
+use std::sync::{Mutex, PoisonError};
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, header::ACCEPT},
+    response::IntoResponse,
+};
+use axum_extra::{TypedHeader, headers::ContentType};
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, TextEncoder,
+    core::Collector,
+    proto::{Metric, MetricFamily, MetricType},
+};
+
+use crate::{
+    app::{AppState, HostState, LeaseSources},
+    http::error::ApiError,
+};
+
+/// Upper bounds (in seconds) of the poll duration histogram buckets.
+const POLL_DURATION_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Name of the label all metrics are partitioned by.
+const HOST_LABEL: &str = "host";
+
+/// Media type of the `OpenMetrics` text format.
+const OPENMETRICS_MEDIA_TYPE: &str = "application/openmetrics-text";
+
+/// Exposition format the metrics are rendered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// The classic Prometheus text format (version 0.0.4).
+    Prometheus,
+    /// The `OpenMetrics` text format (version 1.0.0).
+    OpenMetrics,
+}
+
+impl Format {
+    /// Picks `OpenMetrics` if `Accept` lists it with a non-zero quality, Prometheus otherwise.
+    pub(crate) fn negotiate(headers: &HeaderMap) -> Self {
+        let accepts_openmetrics = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|range| {
+                let mut params = range.split(';').map(str::trim);
+                params.next().is_some_and(|media_type| {
+                    media_type.eq_ignore_ascii_case(OPENMETRICS_MEDIA_TYPE)
+                }) && !params.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .is_some_and(|q| q <= 0.0)
+                })
+            });
+        if accepts_openmetrics {
+            Self::OpenMetrics
+        } else {
+            Self::Prometheus
+        }
+    }
+
+    const fn content_type(self) -> &'static str {
+        match self {
+            Self::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            Self::OpenMetrics => "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        }
+    }
+}
+
+/// Collected metrics, shared across request handlers and background tasks.
+#[derive(Debug)]
+pub(crate) struct Registry {
+    inner: prometheus::Registry,
+    host_online: IntGaugeVec,
+    lease_count: IntGaugeVec,
+    wake_total: IntCounterVec,
+    shutdown_total: IntCounterVec,
+    poll_duration: HistogramVec,
+    /// Held while rendering, as every scrape resets and refills the gauges.
+    scrape: Mutex<()>,
+}
+
+/// Current state of a host, as exported by the gauges.
+pub(crate) struct HostGauges<'host> {
+    pub name: &'host str,
+    pub online: bool,
+    pub lease_count: usize,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let inner = prometheus::Registry::new();
+        let host_online = IntGaugeVec::new(
+            Opts::new(
+                "shuthost_host_online",
+                "Whether the host is online (1) or not (0).",
+            ),
+            &[HOST_LABEL],
+        )
+        .expect("valid metric");
+        let lease_count = IntGaugeVec::new(
+            Opts::new(
+                "shuthost_lease_count",
+                "Number of active leases on the host.",
+            ),
+            &[HOST_LABEL],
+        )
+        .expect("valid metric");
+        let wake_total = IntCounterVec::new(
+            Opts::new(
+                "shuthost_wake_total",
+                "Number of wake operations started for the host.",
+            ),
+            &[HOST_LABEL],
+        )
+        .expect("valid metric");
+        let shutdown_total = IntCounterVec::new(
+            Opts::new(
+                "shuthost_shutdown_total",
+                "Number of shutdown operations started for the host.",
+            ),
+            &[HOST_LABEL],
+        )
+        .expect("valid metric");
+        let poll_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "shuthost_poll_duration_seconds",
+                "Duration of host status polls.",
+            )
+            .buckets(POLL_DURATION_BUCKETS.to_vec()),
+            &[HOST_LABEL],
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(host_online.clone()) as Box<dyn Collector>,
+            Box::new(lease_count.clone()),
+            Box::new(wake_total.clone()),
+            Box::new(shutdown_total.clone()),
+            Box::new(poll_duration.clone()),
+        ] {
+            inner.register(collector).expect("unique metric names");
+        }
+
+        Self {
+            inner,
+            host_online,
+            lease_count,
+            wake_total,
+            shutdown_total,
+            poll_duration,
+            scrape: Mutex::new(()),
+        }
+    }
+}
+
+impl Registry {
+    /// Counts a wake operation started for `host`.
+    pub(crate) fn record_wake(&self, host: &str) {
+        self.wake_total.with_label_values(&[host]).inc();
+    }
+
+    /// Counts a shutdown operation started for `host`.
+    pub(crate) fn record_shutdown(&self, host: &str) {
+        self.shutdown_total.with_label_values(&[host]).inc();
+    }
+
+    /// Records how long a status poll of `host` took.
+    pub(crate) fn observe_poll_duration(&self, host: &str, duration: Duration) {
+        self.poll_duration
+            .with_label_values(&[host])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Renders all metrics in the given text exposition format.
+    ///
+    /// Hosts are sorted by name so consecutive scrapes are stable.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the gathered metrics cannot be encoded.
+    pub(crate) fn render<'host>(
+        &self,
+        hosts: impl IntoIterator<Item = HostGauges<'host>>,
+        format: Format,
+    ) -> prometheus::Result<String> {
+        let _scrape = self.scrape.lock().unwrap_or_else(PoisonError::into_inner);
+        // Removed hosts must not linger in the gauges.
+        self.host_online.reset();
+        self.lease_count.reset();
+        for host in hosts {
+            self.host_online
+                .with_label_values(&[host.name])
+                .set(i64::from(host.online));
+            self.lease_count
+                .with_label_values(&[host.name])
+                .set(i64::try_from(host.lease_count).unwrap_or(i64::MAX));
+            // Exports the counters of hosts without operations yet as 0 instead of omitting them.
+            self.wake_total.with_label_values(&[host.name]);
+            self.shutdown_total.with_label_values(&[host.name]);
+        }
+
+        let families = self.inner.gather();
+        match format {
+            Format::Prometheus => TextEncoder::new().encode_to_string(&families),
+            Format::OpenMetrics => Ok(encode_openmetrics(&families)),
+        }
+    }
+}
+
+/// Encodes the gathered metrics in the `OpenMetrics` text format, which the `prometheus` crate
+/// has no encoder for.
+fn encode_openmetrics(families: &[MetricFamily]) -> String {
+    let mut out = String::new();
+    for family in families {
+        let name = family.name();
+        let kind = family.get_field_type();
+        // OpenMetrics names counter families without the `_total` suffix of their samples.
+        let family_name = match kind {
+            MetricType::COUNTER => name.strip_suffix("_total").unwrap_or(name),
+            _ => name,
+        };
+        let _ = writeln!(out, "# HELP {family_name} {}", escape(family.help()));
+        let _ = writeln!(
+            out,
+            "# TYPE {family_name} {}",
+            format!("{kind:?}").to_lowercase()
+        );
+        if name.ends_with("_seconds") {
+            let _ = writeln!(out, "# UNIT {family_name} seconds");
+        }
+
+        for metric in family.get_metric() {
+            let labels = labels(metric);
+            match kind {
+                MetricType::COUNTER => {
+                    let _ = writeln!(
+                        out,
+                        "{name}{{{labels}}} {}",
+                        metric.get_counter().get_value()
+                    );
+                }
+                MetricType::GAUGE => {
+                    let _ = writeln!(out, "{name}{{{labels}}} {}", metric.get_gauge().get_value());
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    for bucket in histogram.get_bucket() {
+                        // OpenMetrics wants canonical floats (`1.0`).
+                        let _ = writeln!(
+                            out,
+                            "{name}_bucket{{{labels},le=\"{:?}\"}} {}",
+                            bucket.upper_bound(),
+                            bucket.cumulative_count()
+                        );
+                    }
+                    let count = histogram.get_sample_count();
+                    let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {count}");
+                    let _ = writeln!(out, "{name}_sum{{{labels}}} {}", histogram.get_sample_sum());
+                    let _ = writeln!(out, "{name}_count{{{labels}}} {count}");
+                }
+                MetricType::SUMMARY | MetricType::UNTYPED => {}
+            }
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+/// Renders the labels of `metric` as `name="value"` pairs, without the enclosing braces.
+fn labels(metric: &Metric) -> String {
+    metric
+        .get_label()
+        .iter()
+        .map(|label| format!("{}=\"{}\"", label.name(), escape(label.value())))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Escapes a label value or help text as required by the exposition format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+/// Serves the metrics of all configured hosts.
+#[axum::debug_handler]
+pub(crate) async fn serve_metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let format = Format::negotiate(&headers);
+    let config = state.config_rx.borrow().clone();
+    let leases = state.leases.snapshot();
+    let status = state.host_actor.snapshot();
+    let body = state
+        .metrics
+        .render(
+            config.hosts.keys().map(|name| HostGauges {
+                name,
+                online: status.get(name) == Some(&HostState::Online),
+                lease_count: leases.get(name).map_or(0, LeaseSources::len),
+            }),
+            format,
+        )
+        .map_err(eyre::Report::from)?;
+    Ok((
+        TypedHeader(ContentType::from(
+            format
+                .content_type()
+                .parse::<mime::Mime>()
+                .expect("valid content type"),
+        )),
+        body,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_registry() -> Registry {
+        let registry = Registry::default();
+        registry.record_wake("nas");
+        registry.record_wake("nas");
+        registry.record_shutdown("nas");
+        registry.observe_poll_duration("nas", Duration::from_millis(20));
+        registry.observe_poll_duration("nas", Duration::from_secs(3));
+        registry
+    }
+
+    fn sample_hosts() -> [HostGauges<'static>; 2] {
+        [
+            HostGauges {
+                name: "nas",
+                online: true,
+                lease_count: 2,
+            },
+            HostGauges {
+                name: "backup",
+                online: false,
+                lease_count: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn renders_gauges_counters_and_histograms() {
+        let out = sample_registry()
+            .render(sample_hosts(), Format::Prometheus)
+            .unwrap();
+
+        assert!(out.contains("# TYPE shuthost_host_online gauge"));
+        assert!(out.contains("shuthost_host_online{host=\"nas\"} 1"));
+        assert!(out.contains("shuthost_host_online{host=\"backup\"} 0"));
+        assert!(out.contains("shuthost_lease_count{host=\"nas\"} 2"));
+        assert!(out.contains("# TYPE shuthost_wake_total counter"));
+        assert!(out.contains("shuthost_wake_total{host=\"nas\"} 2"));
+        assert!(out.contains("shuthost_shutdown_total{host=\"nas\"} 1"));
+        assert!(out.contains("shuthost_wake_total{host=\"backup\"} 0"));
+        assert!(out.contains("# TYPE shuthost_poll_duration_seconds histogram"));
+        assert!(out.contains("shuthost_poll_duration_seconds_bucket{host=\"nas\",le=\"0.01\"} 0"));
+        assert!(out.contains("shuthost_poll_duration_seconds_bucket{host=\"nas\",le=\"0.025\"} 1"));
+        assert!(out.contains("shuthost_poll_duration_seconds_bucket{host=\"nas\",le=\"5\"} 2"));
+        assert!(out.contains("shuthost_poll_duration_seconds_bucket{host=\"nas\",le=\"+Inf\"} 2"));
+        assert!(out.contains("shuthost_poll_duration_seconds_count{host=\"nas\"} 2"));
+        assert!(
+            out.find("host=\"backup\"") < out.find("host=\"nas\""),
+            "hosts are sorted"
+        );
+        assert!(!out.contains("# EOF"));
+    }
+
+    #[test]
+    fn renders_openmetrics() {
+        let out = sample_registry()
+            .render(sample_hosts(), Format::OpenMetrics)
+            .unwrap();
+
+        assert!(out.contains("shuthost_host_online{host=\"nas\"} 1"));
+        assert!(out.contains("# TYPE shuthost_wake counter"));
+        assert!(out.contains("# HELP shuthost_wake "));
+        assert!(out.contains("shuthost_wake_total{host=\"nas\"} 2"));
+        assert!(out.contains("# UNIT shuthost_poll_duration_seconds seconds"));
+        assert!(out.contains("shuthost_poll_duration_seconds_bucket{host=\"nas\",le=\"5.0\"} 2"));
+        assert!(out.contains("shuthost_poll_duration_seconds_bucket{host=\"nas\",le=\"0.025\"} 1"));
+        assert!(out.ends_with("\n# EOF\n"), "{out}");
+    }
+
+    #[test]
+    fn negotiates_format_from_accept() {
+        let negotiate = |accept: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT, accept.parse().unwrap());
+            Format::negotiate(&headers)
+        };
+        assert_eq!(Format::negotiate(&HeaderMap::new()), Format::Prometheus);
+        assert_eq!(negotiate("text/plain"), Format::Prometheus);
+        assert_eq!(
+            negotiate(
+                "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5,*/*;q=0.1"
+            ),
+            Format::OpenMetrics
+        );
+        assert_eq!(
+            negotiate("application/openmetrics-text; q=0, text/plain"),
+            Format::Prometheus
+        );
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+}
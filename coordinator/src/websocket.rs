@@ -1,27 +1,42 @@
 use alloc::sync::Arc;
-use core::error::Error;
+use core::{
+    error::Error,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 use std::collections::HashMap;
 
 use axum::{
+    body::Bytes,
     extract::{
         State,
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
-    http::HeaderMap,
-    response::IntoResponse,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse as _, Response},
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use tokio::sync::broadcast;
+use tokio::{
+    sync::broadcast,
+    time::{self, Instant},
+};
 use tracing::{Instrument as _, debug, error, info, warn};
 use tungstenite::{Error as TError, error::ProtocolError as TPError};
 
 use crate::app::{
-    AppState, ConfigRx, DbPool, HostState, HostStatus, HostStatusRx, LeaseMap, LeaseSources,
+    AppState, ConfigRx, DbPool, HostActorHandle, HostState, HostStatus, LeaseMap, LeaseSources,
     LeaseStore, OperationFailureMap,
     db::{self, ClientStats, HostStats},
 };
 use crate::config::{HookAction, HookConfig, Host};
+use crate::http::error::json_error;
+
+/// How often the server pings each web client.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a web client has to answer a ping before its connection is closed.
+const PONG_TIMEOUT: Duration = Duration::from_secs(15);
 
 /// Walk the error source chain and return true if any source is an error about the websocket being closed.
 fn is_websocket_closed(err: &axum::Error) -> bool {
@@ -43,6 +58,29 @@ fn is_websocket_closed(err: &axum::Error) -> bool {
     false
 }
 
+/// Counts an open WebSocket connection for as long as it is alive.
+struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl ConnectionGuard {
+    /// Registers a new connection, or returns `None` if `limit` connections are already open.
+    ///
+    /// A `limit` of `None` or `0` means unlimited.
+    fn acquire(connections: &Arc<AtomicUsize>, limit: Option<usize>) -> Option<Self> {
+        let previous = connections.fetch_add(1, Ordering::SeqCst);
+        let guard = Self(connections.clone());
+        match limit {
+            Some(limit) if limit > 0 && previous >= limit => None,
+            _ => Some(guard),
+        }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 /// Action metadata for a host hook that is safe to expose to the frontend.
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -90,6 +128,9 @@ pub struct FrontendHostConfig {
     pub enforce_state: bool,
     pub pre_startup: Option<FrontendHookConfig>,
     pub post_shutdown: Option<FrontendHookConfig>,
+    pub tags: Vec<String>,
+    /// The host's notes, rendered to sanitized HTML.
+    pub notes_html: Option<String>,
 }
 
 impl From<&Host> for FrontendHostConfig {
@@ -98,6 +139,8 @@ impl From<&Host> for FrontendHostConfig {
             enforce_state: host.enforce_state,
             pre_startup: host.pre_startup.as_ref().map(FrontendHookConfig::from),
             post_shutdown: host.post_shutdown.as_ref().map(FrontendHookConfig::from),
+            tags: host.tags.clone(),
+            notes_html: host.notes_html(),
         }
     }
 }
@@ -127,16 +170,40 @@ pub struct InitialPayload {
     #[serde(flatten)]
     pub dynamic_config: DynamicConfig,
     pub status_map: HostStatus,
+    /// The time each host last went online, see [`WsMessage::HostStatus`].
+    pub last_seen: HashMap<String, DateTime<Utc>>,
     pub lease_map: LeaseMap,
     pub db_data: DbDataState,
     pub operation_failures: OperationFailureMap,
+    /// The error of the last config reload, if it failed.
+    pub config_error: Option<String>,
+}
+
+/// Kind of failure reported through [`WsMessage::Error`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[expect(
+    clippy::enum_variant_names,
+    reason = "Names match the serialized kinds consumed by the web UI"
+)]
+pub enum ErrorKind {
+    /// Sending the `WoL` packet failed or the host did not come online in time.
+    WakeFailure,
+    /// The shutdown command failed or the host did not go offline in time.
+    ShutdownFailure,
+    /// The host is reachable but its agent rejected or broke off the status request.
+    PollFailure,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(tag = "type", content = "payload")]
 pub enum WsMessage {
-    /// Gets sent on host status changes
-    HostStatus(HostStatus),
+    /// Gets sent on host status changes, along with the time each host last went online.
+    #[serde(rename_all = "camelCase")]
+    HostStatus {
+        status: HostStatus,
+        last_seen: HashMap<String, DateTime<Utc>>,
+    },
     /// Gets sent when client stats are updated.
     ClientStats(HashMap<String, ClientStats>),
     /// Gets sent when host stats are updated.
@@ -150,6 +217,18 @@ pub enum WsMessage {
     LeaseUpdate { host: String, leases: LeaseSources },
     /// Gets sent when a host's last control operation failure state changes.
     OperationFailed(OperationFailureMap),
+    /// Gets sent when a host reports a different agent version than previously known.
+    AgentVersion { host: String, version: String },
+    /// Gets sent when reloading the config file fails, the last valid config stays in use.
+    ConfigError { message: String },
+    /// Gets sent when the config file loads again after a [`WsMessage::ConfigError`].
+    ConfigErrorResolved,
+    /// Gets sent when waking, shutting down or polling a host fails.
+    Error {
+        host: String,
+        kind: ErrorKind,
+        message: String,
+    },
 }
 
 /// Gets called for every new web client and spins up an event loop
@@ -165,9 +244,24 @@ pub(crate) async fn ws_handler(
         leases,
         db_pool,
         operation_failures,
+        ws_connections,
+        config_error,
         ..
     }): State<AppState>,
-) -> impl IntoResponse {
+) -> Response {
+    let max_connections = config_rx.borrow().server.max_connections;
+    let Some(connection) = ConnectionGuard::acquire(&ws_connections, max_connections) else {
+        warn!(
+            ?max_connections,
+            "Rejecting WebSocket connection: limit reached"
+        );
+        return json_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "too_many_connections",
+            "Too many WebSocket connections",
+        );
+    };
+
     // Log incoming headers so we can verify whether the Upgrade/Connection
     // and other WebSocket-related headers reach the backend (useful when
     // Traefik or another proxy is in front).
@@ -185,16 +279,22 @@ pub(crate) async fn ws_handler(
     debug!("Registering WebSocket upgrade handler");
 
     let op_failures_snapshot = operation_failures.borrow().clone();
+    let config_error_snapshot = config_error.read().await.clone();
 
     ws.on_upgrade(async move |mut socket| {
+        // Released when the connection ends, however it ends.
+        let _connection = connection;
         debug!("WebSocket upgrade completed; starting event loop");
+        // Subscribe before taking the startup snapshot, so no update between the two is lost.
+        let updates = ws_tx.subscribe();
         match send_startup_msg(
             &mut socket,
-            host_actor.subscribe_status(),
+            &host_actor,
             config_rx,
             current_leases,
             db_pool_clone.as_ref(),
             op_failures_snapshot,
+            config_error_snapshot,
         )
         .await
         {
@@ -204,8 +304,9 @@ pub(crate) async fn ws_handler(
                 return;
             }
         }
-        start_webui_ws_loop(socket, ws_tx.subscribe()).await;
+        start_webui_ws_loop(socket, updates).await;
     })
+    .into_response()
 }
 
 #[tracing::instrument(level = "debug", skip_all)]
@@ -220,11 +321,33 @@ async fn send_ws_message(socket: &mut WebSocket, msg: &WsMessage) -> Result<(),
 }
 
 /// We start one event loop per client
+///
+/// The client is pinged every [`PING_INTERVAL`], and the connection is closed if it doesn't answer
+/// within [`PONG_TIMEOUT`]. This cleans up connections of browsers that went to sleep, which would
+/// otherwise keep their broadcast receiver around forever.
 #[tracing::instrument(level = "debug", skip_all)]
 async fn start_webui_ws_loop(mut socket: WebSocket, mut rx: broadcast::Receiver<WsMessage>) {
+    let mut ping_interval = time::interval_at(Instant::now() + PING_INTERVAL, PING_INTERVAL);
+    let mut last_pong = Instant::now();
+    // Deadline for the answer to the last ping, if it's still outstanding.
+    let mut pong_deadline: Option<Instant> = None;
     // Handle broadcast messages
     loop {
         tokio::select! {
+            _ = ping_interval.tick() => {
+                if let Err(e) = socket.send(Message::Ping(Bytes::new())).await {
+                    debug!(%e, "Failed to send ping, closing connection");
+                    break;
+                }
+                pong_deadline.get_or_insert_with(|| Instant::now() + PONG_TIMEOUT);
+            }
+            () = time::sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() => {
+                info!(
+                    since_last_pong = ?last_pong.elapsed(),
+                    "WebSocket client didn't answer ping, closing connection"
+                );
+                break;
+            }
             // Receive messages from the broadcast channel
             msg = rx.recv() => {
                 match msg {
@@ -271,9 +394,13 @@ async fn start_webui_ws_loop(mut socket: WebSocket, mut rx: broadcast::Receiver<
                                         break;
                                     }
                                 }
-                                Message::Pong(_) | Message::Binary(_) => {
-                                    // Pong: client answered a server ping — nothing to do on server side
-                                    // Binary: we don't expect to receive any binary messages
+                                Message::Pong(_) => {
+                                    // Client answered a server ping
+                                    last_pong = Instant::now();
+                                    pong_deadline = None;
+                                }
+                                Message::Binary(_) => {
+                                    // We don't expect to receive any binary messages
                                 }
                                 Message::Close(_) => {
                                     debug!("WebSocket connection closed by client");
@@ -298,14 +425,15 @@ async fn start_webui_ws_loop(mut socket: WebSocket, mut rx: broadcast::Receiver<
 #[tracing::instrument(skip_all)]
 async fn send_startup_msg(
     socket: &mut WebSocket,
-    hoststatus_rx: HostStatusRx,
+    host_actor: &HostActorHandle,
     config_rx: ConfigRx,
     current_leases: Arc<LeaseStore>,
     db_pool: Option<&DbPool>,
     operation_failures: Arc<OperationFailureMap>,
+    config_error: Option<String>,
 ) -> Result<(), axum::Error> {
     // Read freshest values from the receivers just before sending.
-    let current_state = hoststatus_rx.borrow().clone();
+    let current_state = host_actor.snapshot();
     let config = config_rx.borrow().clone();
     let mut status_map = current_state.as_ref().clone();
     for host in config.hosts.keys() {
@@ -353,12 +481,107 @@ async fn send_startup_msg(
     let initial_msg = WsMessage::Initial(Box::new(InitialPayload {
         dynamic_config,
         status_map,
+        last_seen: host_actor.went_online().as_ref().clone(),
         lease_map: leases,
         db_data,
         operation_failures: operation_failures.as_ref().clone(),
+        config_error,
     }));
 
     send_ws_message(socket, &initial_msg)
         .in_current_span()
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use core::iter;
+
+    use super::*;
+
+    #[test]
+    fn connection_guard_enforces_limit() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let first = ConnectionGuard::acquire(&connections, Some(2)).unwrap();
+        let second = ConnectionGuard::acquire(&connections, Some(2)).unwrap();
+        assert!(ConnectionGuard::acquire(&connections, Some(2)).is_none());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        drop(first);
+        let third = ConnectionGuard::acquire(&connections, Some(2)).unwrap();
+        drop((second, third));
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+
+        let unlimited: Vec<_> =
+            iter::repeat_with(|| ConnectionGuard::acquire(&connections, Some(0)).unwrap())
+                .take(10)
+                .collect();
+        assert!(ConnectionGuard::acquire(&connections, None).is_some());
+        drop(unlimited);
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn error_message_serialization() {
+        let msg = WsMessage::Error {
+            host: "nas".to_string(),
+            kind: ErrorKind::WakeFailure,
+            message: "Failed to send WoL packet".to_string(),
+        };
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "Error",
+                "payload": {
+                    "host": "nas",
+                    "kind": "wake_failure",
+                    "message": "Failed to send WoL packet",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn agent_version_message_serialization() {
+        let msg = WsMessage::AgentVersion {
+            host: "nas".to_string(),
+            version: "1.2.3".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&msg).unwrap(),
+            serde_json::json!({
+                "type": "AgentVersion",
+                "payload": { "host": "nas", "version": "1.2.3" },
+            })
+        );
+    }
+
+    #[test]
+    fn error_message_roundtrip() {
+        for kind in [
+            ErrorKind::WakeFailure,
+            ErrorKind::ShutdownFailure,
+            ErrorKind::PollFailure,
+        ] {
+            let json = serde_json::to_string(&WsMessage::Error {
+                host: "nas".to_string(),
+                kind,
+                message: "boom".to_string(),
+            })
+            .unwrap();
+            let WsMessage::Error {
+                host,
+                kind: parsed_kind,
+                message,
+            } = serde_json::from_str(&json).unwrap()
+            else {
+                panic!("expected an Error message");
+            };
+            assert_eq!(
+                (host.as_str(), parsed_kind, message.as_str()),
+                ("nas", kind, "boom")
+            );
+        }
+    }
+}
@@ -4,25 +4,127 @@
     expect(dead_code, reason = "For some reason clippy sets coverage cfg?")
 )]
 
-use core::time::Duration;
+use core::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use std::{io, net::UdpSocket};
 
 use eyre::Context as _;
+use shuthost_common::{MAC_ADDRESS_LENGTH, parse_mac};
 use tokio::time::sleep;
+use tracing::warn;
 
-const MAC_ADDRESS_LENGTH: usize = 6;
+const MAC_REPETITIONS: usize = 16;
+const MAGIC_PACKET_LENGTH: usize = MAC_ADDRESS_LENGTH + MAC_REPETITIONS * MAC_ADDRESS_LENGTH;
+
+/// IPv6 link-local all-nodes multicast address, used in place of a broadcast for IPv6 hosts.
+const IPV6_ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// Returns the destination for magic packets waking a host with the given IP.
+///
+/// A configured `wol_broadcast` always takes precedence. Otherwise IPv4 hosts are woken via the
+/// limited broadcast address; IPv6 has no broadcast, so the link-local all-nodes multicast
+/// address is used instead.
+pub(crate) const fn wake_destination(host_ip: IpAddr, wol_broadcast: Option<IpAddr>) -> IpAddr {
+    if let Some(destination) = wol_broadcast {
+        return destination;
+    }
+    match host_ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::BROADCAST),
+        IpAddr::V6(_) => IpAddr::V6(IPV6_ALL_NODES),
+    }
+}
 
 #[cfg(not(coverage))]
+/// Sends a magic packet for `mac_address` to `destination` from each of `interfaces`.
+///
+/// A socket is bound to every listed interface address, so multi-homed coordinators reach hosts
+/// on all attached networks. With no interfaces the socket binds the unspecified address and the
+/// packet leaves via the default route (for IPv6, the default multicast interface).
+///
 /// # Errors
 ///
-/// Returns an error if the MAC address is invalid or if the UDP socket cannot be bound or sent.
+/// Returns an error if the MAC address is invalid or if sending failed on every interface.
+async fn send_magic_packet_on_interfaces(
+    mac_address: &str,
+    destination: IpAddr,
+    interfaces: &[IpAddr],
+) -> eyre::Result<()> {
+    let packet = build_magic_packet(mac_address)?;
+    send_on_interfaces(&packet, destination, interfaces, |interface| {
+        bind_socket(destination, interface)
+    })
+    .await
+}
+
+#[cfg(not(coverage))]
+/// Sends a magic packet for each of `mac_addresses`, e.g. to all NICs of a bonded interface.
+///
+/// Every address is validated before anything is sent. Sending continues for the remaining
+/// addresses if one of them fails.
+///
+/// # Errors
+///
+/// Returns an error if any MAC address is invalid, or the last error if sending failed for any
+/// of them.
 #[cfg_attr(
     test,
     expect(dead_code, reason = "This function is not used in tests.")
 )]
-pub(crate) async fn send_magic_packet(mac_address: &str, broadcast_ip: &str) -> eyre::Result<()> {
+pub(crate) async fn send_magic_packets(
+    mac_addresses: &[String],
+    destination: IpAddr,
+    interfaces: &[IpAddr],
+) -> eyre::Result<()> {
+    for mac_address in mac_addresses {
+        parse_mac(mac_address)?;
+    }
+    let mut last_error = None;
+    for mac_address in mac_addresses {
+        if let Err(e) = send_magic_packet_on_interfaces(mac_address, destination, interfaces).await
+        {
+            warn!("Failed to send magic packet for {mac_address}: {e:#}");
+            last_error = Some(e);
+        }
+    }
+    last_error.map_or(Ok(()), Err)
+}
+
+/// Socket a magic packet can be sent through, abstracted to allow testing without a network.
+trait PacketSocket {
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize>;
+}
+
+impl PacketSocket for UdpSocket {
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        Self::send_to(self, buf, addr)
+    }
+}
+
+#[cfg(not(coverage))]
+fn bind_socket(destination: IpAddr, interface: Option<IpAddr>) -> eyre::Result<UdpSocket> {
+    match (interface, destination) {
+        (Some(interface), _) => {
+            let socket = UdpSocket::bind((interface, 0))
+                .wrap_err(format!("Failed to bind WoL socket to {interface}"))?;
+            if interface.is_ipv4() {
+                socket
+                    .set_broadcast(true)
+                    .wrap_err("Failed to set broadcast on socket")?;
+            }
+            Ok(socket)
+        }
+        (None, IpAddr::V4(_)) => shuthost_common::create_broadcast_socket(0)
+            .map_err(|e| eyre::eyre!("Failed to create broadcast socket: {e}")),
+        (None, IpAddr::V6(_)) => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))
+            .wrap_err("Failed to create IPv6 multicast socket"),
+    }
+}
+
+fn build_magic_packet(mac_address: &str) -> eyre::Result<[u8; MAGIC_PACKET_LENGTH]> {
     let mac_bytes = parse_mac(mac_address)?;
-    const MAC_REPETITIONS: usize = 16;
-    let mut packet = [0xFFu8; MAC_ADDRESS_LENGTH + MAC_REPETITIONS * MAC_ADDRESS_LENGTH];
+    let mut packet = [0xFFu8; MAGIC_PACKET_LENGTH];
 
     for i in 0..MAC_REPETITIONS {
         #[expect(
@@ -32,18 +134,56 @@ pub(crate) async fn send_magic_packet(mac_address: &str, broadcast_ip: &str) ->
         packet[(i + 1) * MAC_ADDRESS_LENGTH..(i + 2) * MAC_ADDRESS_LENGTH]
             .copy_from_slice(&mac_bytes);
     }
+    Ok(packet)
+}
 
-    let socket = shuthost_common::create_broadcast_socket(0)
-        .map_err(|e| eyre::eyre!("Failed to create broadcast socket: {e}"))?;
+/// Sends `packet` through a socket bound to each of `interfaces` (or a single default socket
+/// when the list is empty). Succeeds if the packet went out on at least one interface.
+async fn send_on_interfaces<S: PacketSocket>(
+    packet: &[u8],
+    destination: IpAddr,
+    interfaces: &[IpAddr],
+    bind: impl Fn(Option<IpAddr>) -> eyre::Result<S>,
+) -> eyre::Result<()> {
+    if interfaces.is_empty() {
+        return send_burst(&bind(None)?, packet, destination).await;
+    }
 
+    let mut sent_on_any = false;
+    let mut last_error = None;
+    for &interface in interfaces {
+        let result = match bind(Some(interface)) {
+            Ok(socket) => send_burst(&socket, packet, destination).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => sent_on_any = true,
+            Err(e) => {
+                warn!("Failed to send magic packet via {interface}: {e:#}");
+                last_error = Some(e);
+            }
+        }
+    }
+
+    match last_error {
+        Some(e) if !sent_on_any => Err(e),
+        _ => Ok(()),
+    }
+}
+
+async fn send_burst(
+    socket: &impl PacketSocket,
+    packet: &[u8],
+    destination: IpAddr,
+) -> eyre::Result<()> {
     const BURST_COUNT: usize = 3;
     const BURST_DELAY: Duration = Duration::from_millis(100);
-    let destination = format!("{broadcast_ip}:9");
+    let destination = SocketAddr::new(destination, 9);
     let mut send_succeeded = false;
     let mut last_send_error = None;
 
     for attempt in 0..BURST_COUNT {
-        match socket.send_to(&packet, &destination) {
+        match socket.send_to(packet, destination) {
             Ok(_) => send_succeeded = true,
             Err(error) => last_send_error = Some(error),
         }
@@ -61,72 +201,238 @@ pub(crate) async fn send_magic_packet(mac_address: &str, broadcast_ip: &str) ->
     }
 }
 
-fn parse_mac(mac: &str) -> eyre::Result<[u8; MAC_ADDRESS_LENGTH]> {
-    let mut mac_bytes = [0u8; MAC_ADDRESS_LENGTH];
-    let mut parts = mac.split(':');
+/// Tests whether a broadcast from each of `interfaces` reaches a `test-wol` listener on
+/// `target_port`.
+///
+/// # Errors
+///
+/// Returns an error if the socket cannot be bound or configured.
+#[cfg(not(coverage))]
+pub(crate) fn test_wol_reachability(target_port: u16, interfaces: &[IpAddr]) -> eyre::Result<bool> {
+    probe_wol_test_listener(
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), target_port),
+        interfaces,
+    )
+}
 
-    for mac_byte in &mut mac_bytes {
-        let part = parts
-            .next()
-            .ok_or_else(|| eyre::eyre!("Invalid MAC address format: not enough parts"))?;
-        *mac_byte =
-            u8::from_str_radix(part, 16).map_err(|_| eyre::eyre!("Invalid MAC byte: {part}"))?;
-    }
+/// Outcome of [`test_host_wol_reachability`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct HostWolTest {
+    /// Whether a packet sent to the host's `WoL` destination was answered.
+    pub broadcast_reached: bool,
+    /// Whether a packet sent directly to the host's IP was answered.
+    pub unicast_reached: bool,
+}
 
-    // Ensure there are no extra parts
-    if parts.next().is_some() {
-        return Err(eyre::eyre!("Invalid MAC address format: too many parts"));
-    }
+/// Tests whether packets reach the `test-wol` listener of a host agent on `port`, both via the
+/// `WoL` destination the host is woken through and directly at its IP.
+///
+/// Like magic packets, the test packets are sent from each of `interfaces`.
+/// Blocks for up to a second per unanswered packet.
+///
+/// # Errors
+///
+/// Returns an error if a socket cannot be bound or a packet cannot be sent.
+#[cfg(not(coverage))]
+pub(crate) fn test_host_wol_reachability(
+    host_ip: IpAddr,
+    wol_broadcast: Option<IpAddr>,
+    port: u16,
+    interfaces: &[IpAddr],
+) -> eyre::Result<HostWolTest> {
+    let destination = wake_destination(host_ip, wol_broadcast);
+    Ok(HostWolTest {
+        broadcast_reached: probe_wol_test_listener(SocketAddr::new(destination, port), interfaces)?,
+        unicast_reached: probe_wol_test_listener(SocketAddr::new(host_ip, port), interfaces)?,
+    })
+}
 
-    Ok(mac_bytes)
+#[cfg(coverage)]
+pub(crate) fn test_host_wol_reachability(
+    _host_ip: IpAddr,
+    _wol_broadcast: Option<IpAddr>,
+    _port: u16,
+    _interfaces: &[IpAddr],
+) -> eyre::Result<HostWolTest> {
+    eyre::bail!("Unimplemented in coverage")
 }
 
-#[cfg(not(coverage))]
+/// Sends a test packet to `target` from each of `interfaces` (or the default route when the list
+/// is empty), and returns whether a `test-wol` listener answered any of them in time.
+///
 /// # Errors
 ///
-/// Returns an error if the socket cannot be bound or configured.
-pub(crate) fn test_wol_reachability(target_port: u16) -> eyre::Result<bool> {
-    let socket = shuthost_common::create_broadcast_socket(0)
-        .map_err(|e| eyre::eyre!("Failed to create broadcast socket: {e}"))?;
+/// Returns the last error if the packet couldn't be sent from any interface.
+#[cfg(not(coverage))]
+fn probe_wol_test_listener(target: SocketAddr, interfaces: &[IpAddr]) -> eyre::Result<bool> {
+    if interfaces.is_empty() {
+        return probe_from(target, None);
+    }
+
+    let mut probed_any = false;
+    let mut last_error = None;
+    for &interface in interfaces {
+        match probe_from(target, Some(interface)) {
+            Ok(true) => return Ok(true),
+            Ok(false) => probed_any = true,
+            Err(e) => {
+                warn!("Failed to send WoL test packet via {interface}: {e:#}");
+                last_error = Some(e);
+            }
+        }
+    }
+    match last_error {
+        Some(e) if !probed_any => Err(e),
+        _ => Ok(false),
+    }
+}
+
+/// Sends a test packet to `target` from `interface` and returns whether it was answered in time.
+#[cfg(not(coverage))]
+fn probe_from(target: SocketAddr, interface: Option<IpAddr>) -> eyre::Result<bool> {
+    let socket = bind_socket(target.ip(), interface)?;
     socket
         .set_read_timeout(Some(Duration::from_secs(1)))
         .wrap_err("Failed to set timeout")?;
 
     let test_message = b"SHUTHOST_WOL_TEST_BROADCAST";
     socket
-        .send_to(test_message, format!("255.255.255.255:{target_port}"))
-        .wrap_err(format!(
-            "Failed to send broadcast test to 255.255.255.255:{target_port}"
-        ))?;
+        .send_to(test_message, target)
+        .wrap_err(format!("Failed to send test packet to {target}"))?;
 
     let mut buf = [0u8; 32];
-    let broadcast_works = socket.recv(&mut buf).is_ok();
-
-    Ok(broadcast_works)
+    Ok(socket.recv(&mut buf).is_ok())
 }
 
 #[cfg(test)]
 mod tests {
+    use core::cell::RefCell;
+
     use super::*;
 
-    #[test]
-    fn parse_mac_valid() {
-        let mac_str = "01:23:45:67:89:ab";
-        let bytes = parse_mac(mac_str).expect("Should parse valid MAC");
-        assert_eq!(bytes, [0x01, 0x23, 0x45, 0x67, 0x89, 0xab]);
+    /// Records every packet instead of sending it.
+    struct RecordingSocket<'sent> {
+        interface: Option<IpAddr>,
+        fail: bool,
+        sent: &'sent RefCell<Vec<(Option<IpAddr>, SocketAddr, usize)>>,
+    }
+
+    impl PacketSocket for RecordingSocket<'_> {
+        fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+            if self.fail {
+                return Err(io::Error::other("network unreachable"));
+            }
+            self.sent
+                .borrow_mut()
+                .push((self.interface, addr, buf.len()));
+            Ok(buf.len())
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_on_each_interface() {
+        let sent = RefCell::new(Vec::new());
+        let interfaces = [IpAddr::from([192, 168, 1, 2]), IpAddr::from([10, 0, 0, 2])];
+        let packet = build_magic_packet("01:23:45:67:89:ab").unwrap();
+
+        send_on_interfaces(
+            &packet,
+            IpAddr::V4(Ipv4Addr::BROADCAST),
+            &interfaces,
+            |interface| {
+                Ok(RecordingSocket {
+                    interface,
+                    fail: false,
+                    sent: &sent,
+                })
+            },
+        )
+        .await
+        .unwrap();
+
+        let sent = sent.into_inner();
+        for interface in interfaces {
+            let on_interface: Vec<_> = sent.iter().filter(|s| s.0 == Some(interface)).collect();
+            assert!(!on_interface.is_empty(), "nothing sent via {interface}");
+            assert!(on_interface.iter().all(|&&(_, addr, len)| {
+                addr == SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), 9)
+                    && len == MAGIC_PACKET_LENGTH
+            }));
+        }
+        assert!(sent.iter().all(|s| s.0.is_some()), "no default socket used");
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_default_socket_without_interfaces() {
+        let sent = RefCell::new(Vec::new());
+        let packet = build_magic_packet("01:23:45:67:89:ab").unwrap();
+
+        send_on_interfaces(&packet, IpAddr::V4(Ipv4Addr::BROADCAST), &[], |interface| {
+            Ok(RecordingSocket {
+                interface,
+                fail: false,
+                sent: &sent,
+            })
+        })
+        .await
+        .unwrap();
+
+        let sent = sent.into_inner();
+        assert!(!sent.is_empty());
+        assert!(sent.iter().all(|s| s.0.is_none()));
+    }
+
+    #[tokio::test]
+    async fn fails_only_if_every_interface_fails() {
+        let sent = RefCell::new(Vec::new());
+        let working = IpAddr::from([10, 0, 0, 2]);
+        let interfaces = [IpAddr::from([192, 168, 1, 2]), working];
+        let packet = build_magic_packet("01:23:45:67:89:ab").unwrap();
+        let destination = IpAddr::V4(Ipv4Addr::BROADCAST);
+
+        let bind_failing_except = |ok: Option<IpAddr>| {
+            let sent = &sent;
+            move |interface| {
+                Ok(RecordingSocket {
+                    interface,
+                    fail: interface != ok,
+                    sent,
+                })
+            }
+        };
+        send_on_interfaces(
+            &packet,
+            destination,
+            &interfaces,
+            bind_failing_except(Some(working)),
+        )
+        .await
+        .unwrap();
+        assert!(
+            send_on_interfaces(&packet, destination, &interfaces, bind_failing_except(None))
+                .await
+                .is_err()
+        );
     }
 
     #[test]
-    fn parse_mac_invalid_format() {
-        let mac_str = "01:23:45:67:89";
-        let err = parse_mac(mac_str).unwrap_err();
-        assert!(err.to_string().contains("not enough parts"));
+    fn wake_destination_matches_address_family() {
+        assert_eq!(
+            wake_destination(IpAddr::from([192, 168, 1, 10]), None),
+            IpAddr::V4(Ipv4Addr::BROADCAST)
+        );
+        assert_eq!(
+            wake_destination(IpAddr::V6(Ipv6Addr::LOCALHOST), None),
+            "ff02::1".parse::<IpAddr>().unwrap()
+        );
     }
 
     #[test]
-    fn parse_mac_invalid_byte() {
-        let mac_str = "01:23:45:67:89:zz";
-        let err = parse_mac(mac_str).unwrap_err();
-        assert!(err.to_string().contains("Invalid MAC byte"));
+    fn wake_destination_prefers_configured_broadcast() {
+        let directed = IpAddr::from([192, 168, 1, 255]);
+        assert_eq!(
+            wake_destination(IpAddr::from([192, 168, 1, 10]), Some(directed)),
+            directed
+        );
     }
 }
@@ -1,3 +1,4 @@
+import { createSignal } from 'solid-js';
 import { createStore, produce } from 'solid-js/store';
 import { serverData } from './dataIslands';
 import { type Infer, is, validateDataAsync } from './utils/assertData';
@@ -18,6 +19,9 @@ export type Status = Infer<typeof statusOptionsChecker>;
 
 const statusMapChecker = is.recordOf(statusOptionsChecker);
 
+/** The time each host last went online, as an RFC 3339 timestamp. */
+const lastSeenChecker = is.recordOf(is.string);
+
 const clientLeaseChecker = is.object({
     type: 'Client',
     value: is.string,
@@ -25,9 +29,17 @@ const clientLeaseChecker = is.object({
 
 export type ClientLease = Infer<typeof clientLeaseChecker>;
 
+const scheduleLeaseChecker = is.object({
+    type: 'Schedule',
+    value: is.object({ name: is.string } as const),
+} as const);
+
+export type ScheduleLease = Infer<typeof scheduleLeaseChecker>;
+
 const leaseSourceChecker = is.oneOf(
     is.object({ type: 'WebInterface' } as const),
     clientLeaseChecker,
+    scheduleLeaseChecker,
 );
 
 export type LeaseSource = Infer<typeof leaseSourceChecker>;
@@ -47,6 +59,7 @@ export const hostStatChecker = is.object({
             'self-extracting-shell',
             'self-extracting-pwsh',
             'launchd',
+            'windows-service',
         ),
     ),
     operatingSystem: is.optional(is.oneOf('windows', 'linux', 'macos')),
@@ -90,6 +103,8 @@ const hostConfigChecker = is.object({
     enforceState: is.boolean,
     preStartup: is.optional(hostHookConfigChecker),
     postShutdown: is.optional(hostHookConfigChecker),
+    tags: is.arrayOf(is.string),
+    notesHtml: is.optional(is.string),
 } as const);
 
 export type HostConfig = Infer<typeof hostConfigChecker>;
@@ -129,9 +144,11 @@ const operationFailureChecker = is.object({
 
 const appStateChecker = is.object({
     statusMap: statusMapChecker,
+    lastSeen: lastSeenChecker,
     leaseMap: is.recordOf(is.arrayOf(leaseSourceChecker)),
     dbData: dbDataStateChecker,
     operationFailures: is.recordOf(operationFailureChecker),
+    configError: is.optional(is.string),
     ...dynamicConfigCheckerObj,
 } as const);
 
@@ -139,8 +156,22 @@ export type AppState = Infer<typeof appStateChecker>;
 
 export type OperationFailure = Infer<typeof operationFailureChecker>;
 
+const coordinatorErrorChecker = is.object({
+    host: is.string,
+    kind: is.oneOf('wake_failure', 'shutdown_failure', 'poll_failure'),
+    message: is.string,
+} as const);
+
+export type CoordinatorError = Infer<typeof coordinatorErrorChecker>;
+
 const wsMessageChecker = is.oneOf(
-    is.object({ type: 'HostStatus', payload: statusMapChecker } as const),
+    is.object({
+        type: 'HostStatus',
+        payload: is.object({
+            status: statusMapChecker,
+            lastSeen: lastSeenChecker,
+        }),
+    } as const),
     is.object({
         type: 'ClientStats',
         payload: is.recordOf(clientStatsChecker),
@@ -165,6 +196,16 @@ const wsMessageChecker = is.oneOf(
         type: 'OperationFailed',
         payload: is.recordOf(operationFailureChecker),
     } as const),
+    is.object({
+        type: 'AgentVersion',
+        payload: is.object({ host: is.string, version: is.string }),
+    } as const),
+    is.object({
+        type: 'ConfigError',
+        payload: is.object({ message: is.string }),
+    } as const),
+    is.object({ type: 'ConfigErrorResolved' } as const),
+    is.object({ type: 'Error', payload: coordinatorErrorChecker } as const),
 );
 
 export type WsMessage = Infer<typeof wsMessageChecker>;
@@ -179,15 +220,29 @@ const validateWsMessageAsync = (unknownMessage: unknown) =>
 const [state, setState] = createStore<AppState>({
     hosts: [],
     statusMap: {},
+    lastSeen: {},
     leaseMap: {},
     clients: [],
     dbData: { status: 'disabled' },
     operationFailures: {},
+    configError: null,
     hostConfigMap: {},
 });
 
 export { state };
 
+/** Maximum number of coordinator errors kept for display. */
+const MAX_COORDINATOR_ERRORS = 20;
+
+const [coordinatorErrors, setCoordinatorErrors] = createSignal<
+    CoordinatorError[]
+>([]);
+
+/** Most recent coordinator errors pushed over the WebSocket, newest last. */
+export { coordinatorErrors };
+
+export const dismissCoordinatorErrors = () => setCoordinatorErrors([]);
+
 export const applyMessage = (unknownMessage: unknown) => {
     validateWsMessageAsync(unknownMessage);
     applyTypedMessage(unknownMessage as WsMessage);
@@ -206,7 +261,8 @@ export const applyTypedMessage = (message: WsMessage) => {
             break;
         }
         case 'HostStatus':
-            setState('statusMap', message.payload);
+            setState('statusMap', message.payload.status);
+            setState('lastSeen', message.payload.lastSeen);
             break;
         case 'ConfigChanged':
             setState('hosts', message.payload.hosts);
@@ -246,6 +302,33 @@ export const applyTypedMessage = (message: WsMessage) => {
         case 'OperationFailed':
             setState('operationFailures', message.payload);
             break;
+        case 'AgentVersion':
+            setState(
+                produce((s: AppState) => {
+                    const stats =
+                        s.dbData.status === 'available'
+                            ? s.dbData.payload.hostStats[message.payload.host]
+                            : undefined;
+                    if (stats != null) {
+                        stats.agentVersion = message.payload.version;
+                    }
+                }),
+            );
+            break;
+        case 'ConfigError':
+            setState('configError', message.payload.message);
+            break;
+        case 'ConfigErrorResolved':
+            setState('configError', null);
+            break;
+        case 'Error':
+            console.error(
+                `Coordinator ${message.payload.kind} for ${message.payload.host}: ${message.payload.message}`,
+            );
+            setCoordinatorErrors((errors) =>
+                [...errors, message.payload].slice(-MAX_COORDINATOR_ERRORS),
+            );
+            break;
         default: {
             const _exhaustive: never = message;
             throw new Error(
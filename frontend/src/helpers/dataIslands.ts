@@ -10,6 +10,14 @@ const serverDataChecks = is.object({
     configPath: is.string,
     authWarning: is.boolean,
     demoSubpath: is.optional(is.string),
+    demoConfig: is.optional(
+        is.object({
+            numHosts: is.number,
+            wakeDelayMs: is.number,
+            shutdownDelayMs: is.number,
+            offlineProbability: is.number,
+        }),
+    ),
     authMode: authModeChecks,
     broadcastPort: is.number,
     dbEnabled: is.boolean,
@@ -37,6 +45,10 @@ const loadServerData = (): ServerData => {
  * Demo mode is encoded by presence of this field.
  * - `undefined` => normal mode
  * - `string` => demo mode (optional base subpath).
+ *
+ * `demoConfig`:
+ * Parameters of the simulated hosts, set from the `demo-service` flags.
+ * Absent in normal mode and in the Vite dev server, where defaults apply.
  */
 export const serverData = loadServerData();
 
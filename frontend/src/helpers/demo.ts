@@ -1,4 +1,10 @@
-import { applyTypedMessage, state } from './appStore';
+import {
+    applyTypedMessage,
+    type HostConfig,
+    type HostStats,
+    type Status,
+    state,
+} from './appStore';
 import { buildData, serverData } from './dataIslands';
 
 export const isDemoMode = serverData.demoSubpath != null;
@@ -20,6 +26,94 @@ const sanitizeDemoSubpath = (raw: string) => {
 /** Normalised demo subpath: `''` or `'/base'` (no trailing slash). */
 export const demoSubpath = sanitizeDemoSubpath(serverData.demoSubpath ?? '');
 
+/** Simulation parameters from the `demo-service` flags, with the same defaults for the dev server. */
+const demoConfig = {
+    numHosts: 3,
+    wakeDelayMs: 2000,
+    shutdownDelayMs: 1500,
+    offlineProbability: 1.0,
+    ...serverData.demoConfig,
+};
+
+/** The handcrafted hosts come first, any further ones are generic. */
+const demoHostNames = () =>
+    Array.from(
+        { length: demoConfig.numHosts },
+        (_, i) =>
+            ['archive', 'tarbean', 'junpui'][i] ?? `demo-host-${i + 1}`,
+    );
+
+const demoHostStats: Record<string, HostStats> = {
+    archive: {
+        agentVersion: '1.6.0',
+        lastOnline: new Date(Date.now() - 3_600_000).toISOString(),
+        isOnline: false,
+        initSystem: 'systemd',
+        operatingSystem: 'linux',
+    },
+    tarbean: {
+        agentVersion: buildData.version,
+        lastOnline: new Date(Date.now() - 7_200_000).toISOString(),
+        initSystem: 'self-extracting-shell',
+        operatingSystem: 'linux',
+        scriptPath: '/home/user/shuthost_host_agent_self_extracting',
+        isOnline: false,
+    },
+    junpui: {
+        agentVersion: '1.6.0',
+        initSystem: 'self-extracting-pwsh',
+        operatingSystem: 'windows',
+        scriptPath:
+            'C:\\Users\\user\\AppData\\Roaming\\shuthost\\shuthost_host_agent_self_extracting.ps1',
+        lastOnline: new Date(Date.now() - 1_800_000).toISOString(),
+        isOnline: false,
+    },
+};
+
+const demoHostConfigs: Record<string, HostConfig> = {
+    archive: {
+        enforceState: true,
+        tags: ['storage'],
+        notesHtml:
+            '<p>Backup NAS in the <strong>basement</strong>, rack 3 unit 7.</p>\n',
+        preStartup: {
+            action: {
+                type: 'http',
+                url: 'https://example.com/pre-startup',
+                method: 'POST',
+            },
+            delaySecs: 0,
+            timeoutSecs: 10,
+        },
+        postShutdown: {
+            action: {
+                type: 'exec',
+                program: '/home/user/disable-plug.sh',
+            },
+            delaySecs: 2,
+            timeoutSecs: 15,
+        },
+    },
+    tarbean: {
+        enforceState: false,
+        tags: ['compute', 'gpu'],
+        notesHtml:
+            '<p>GPU server, owner: alice. See the <a href="https://github.com/9SMTM6/shuthost" rel="noopener noreferrer">docs</a>.</p>\n',
+    },
+    junpui: {
+        enforceState: false,
+        tags: [],
+        postShutdown: {
+            action: {
+                type: 'exec',
+                program: '/home/user/disable-plug.sh',
+            },
+            delaySecs: 1,
+            timeoutSecs: 15,
+        },
+    },
+};
+
 const leaseTimeouts = new Map<string, ReturnType<typeof setTimeout>>();
 const statusTimeouts = new Map<string, ReturnType<typeof setTimeout>>();
 
@@ -31,98 +125,85 @@ export const initDemoMode = () => {
 
     console.info('Demo mode enabled: UI is using simulated data.');
 
+    const hosts = demoHostNames();
+    const pick = <T>(record: Record<string, T>) =>
+        Object.fromEntries(
+            Object.entries(record).filter(([host]) => hosts.includes(host)),
+        );
+    const genericHosts = hosts.filter((host) => !(host in demoHostStats));
+    const statusMap = Object.fromEntries(
+        hosts.map((host): [string, Status] => [
+            host,
+            Math.random() < demoConfig.offlineProbability
+                ? 'offline'
+                : 'online',
+        ]),
+    );
+    const startedAt = new Date().toISOString();
+
     // Simulate the Initial push from the backend
     setTimeout(() => {
         applyTypedMessage({
             type: 'Initial',
             payload: {
-                hosts: ['archive', 'tarbean', 'junpui'],
+                hosts,
                 clients: [],
-                statusMap: {
-                    tarbean: 'offline',
-                    archive: 'offline',
-                    junpui: 'offline',
-                },
-                leaseMap: { archive: [] },
+                statusMap,
+                lastSeen: Object.fromEntries(
+                    hosts
+                        .filter((host) => statusMap[host] === 'online')
+                        .map((host) => [host, startedAt]),
+                ),
+                leaseMap: pick({ archive: [] }),
                 operationFailures: {},
+                configError: null,
                 dbData: {
                     status: 'available',
                     payload: {
                         clientStats: {},
                         hostStats: {
-                            archive: {
-                                agentVersion: '1.6.0',
-                                lastOnline: new Date(
-                                    Date.now() - 3_600_000,
-                                ).toISOString(),
-                                isOnline: false,
-                                initSystem: 'systemd',
-                                operatingSystem: 'linux',
-                            },
-                            tarbean: {
-                                agentVersion: buildData.version,
-                                lastOnline: new Date(
-                                    Date.now() - 7_200_000,
-                                ).toISOString(),
-                                initSystem: 'self-extracting-shell',
-                                operatingSystem: 'linux',
-                                scriptPath:
-                                    '/home/user/shuthost_host_agent_self_extracting',
-                                isOnline: false,
-                            },
-                            junpui: {
-                                agentVersion: '1.6.0',
-                                initSystem: 'self-extracting-pwsh',
-                                operatingSystem: 'windows',
-                                scriptPath:
-                                    'C:\\Users\\user\\AppData\\Roaming\\shuthost\\shuthost_host_agent_self_extracting.ps1',
-                                lastOnline: new Date(
-                                    Date.now() - 1_800_000,
-                                ).toISOString(),
-                                isOnline: false,
-                            },
+                            ...pick(demoHostStats),
+                            ...Object.fromEntries(
+                                genericHosts.map((host): [string, HostStats] => [
+                                    host,
+                                    {
+                                        agentVersion: buildData.version,
+                                        initSystem: 'systemd',
+                                        operatingSystem: 'linux',
+                                        isOnline: false,
+                                    },
+                                ]),
+                            ),
                         },
                     },
                 },
                 hostConfigMap: {
-                    archive: {
-                        enforceState: true,
-                        preStartup: {
-                            action: {
-                                type: 'http',
-                                url: 'https://example.com/pre-startup',
-                                method: 'POST',
-                            },
-                            delaySecs: 0,
-                            timeoutSecs: 10,
-                        },
-                        postShutdown: {
-                            action: {
-                                type: 'exec',
-                                program: '/home/user/disable-plug.sh',
-                            },
-                            delaySecs: 2,
-                            timeoutSecs: 15,
-                        },
-                    },
-                    tarbean: {
-                        enforceState: false,
-                    },
-                    junpui: {
-                        enforceState: false,
-                        postShutdown: {
-                            action: {
-                                type: 'exec',
-                                program: '/home/user/disable-plug.sh',
-                            },
-                            delaySecs: 1,
-                            timeoutSecs: 15,
-                        },
-                    },
+                    ...pick(demoHostConfigs),
+                    ...Object.fromEntries(
+                        genericHosts.map((host): [string, HostConfig] => [
+                            host,
+                            { enforceState: false, tags: [] },
+                        ]),
+                    ),
                 },
             },
         });
     }, 500);
+
+    if (!hosts.includes('junpui')) return;
+
+    // Simulate a coordinator error push, for developing error notifications
+    setTimeout(() => {
+        applyTypedMessage({
+            type: 'Error',
+            payload: {
+                host: 'junpui',
+                kind: 'poll_failure',
+                message:
+                    'Agent rejected status request: Invalid HMAC signature',
+            },
+        });
+    }, 1500);
 };
 
 // ── Demo push subscription state ───────────────────────────────────────────
@@ -218,16 +299,24 @@ const demoRequests = {
                 setTimeout(() => {
                     applyTypedMessage({
                         type: 'HostStatus',
-                        payload: { [host]: 'waking' },
+                        payload: {
+                            status: { [host]: 'waking' },
+                            lastSeen: {},
+                        },
                     });
                     statusTimeouts.set(
                         host,
                         setTimeout(() => {
                             applyTypedMessage({
                                 type: 'HostStatus',
-                                payload: { [host]: 'online' },
+                                payload: {
+                                    status: { [host]: 'online' },
+                                    lastSeen: {
+                                        [host]: new Date().toISOString(),
+                                    },
+                                },
                             });
-                        }, 1500),
+                        }, demoConfig.wakeDelayMs),
                     );
                 }, 300),
             );
@@ -247,16 +336,22 @@ const demoRequests = {
                 setTimeout(() => {
                     applyTypedMessage({
                         type: 'HostStatus',
-                        payload: { [host]: 'shutting_down' },
+                        payload: {
+                            status: { [host]: 'shutting_down' },
+                            lastSeen: {},
+                        },
                     });
                     statusTimeouts.set(
                         host,
                         setTimeout(() => {
                             applyTypedMessage({
                                 type: 'HostStatus',
-                                payload: { [host]: 'offline' },
+                                payload: {
+                                    status: { [host]: 'offline' },
+                                    lastSeen: {},
+                                },
                             });
-                        }, 1500),
+                        }, demoConfig.shutdownDelayMs),
                     );
                 }, 300),
             );
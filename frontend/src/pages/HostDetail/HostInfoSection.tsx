@@ -148,6 +148,17 @@ export const HostInfoSection = ((props: {
                 Information
             </h3>
             <dl class="grid grid-cols-[auto_1fr] gap-x-6 gap-y-1 text-sm">
+                <Show when={props.hostConfig?.notesHtml}>
+                    {(notesHtml) => (
+                        <InfoRow
+                            label="Notes"
+                            ddClass="break-words [&_a]:underline [&_p]:m-0"
+                        >
+                            {/* Rendered from Markdown and sanitized by the coordinator */}
+                            <div innerHTML={notesHtml()} />
+                        </InfoRow>
+                    )}
+                </Show>
                 <InfoRow
                     label="Agent version"
                     hint={
@@ -178,6 +189,7 @@ export const HostInfoSection = ((props: {
                             'self-extracting-pwsh':
                                 'Self-extracting (PowerShell)',
                             launchd: 'launchd',
+                            'windows-service': 'Windows service',
                             unknown: 'Unknown',
                         }[props.hostStats?.initSystem ?? 'unknown']
                     }
@@ -3,6 +3,7 @@ import { For, Show } from 'solid-js';
 import {
     type ClientLease,
     type LeaseSource,
+    type ScheduleLease,
     state,
 } from '../../helpers/appStore';
 import { ApiFetchUnauthorizedError, apiFetch } from '../../helpers/utils';
@@ -15,6 +16,8 @@ export const HostLeasesSection = ((props: { hostname: string }) => {
         leases().some((l) => l.type === 'WebInterface');
     const clientLeases = () =>
         leases().filter((l): l is ClientLease => l.type === 'Client');
+    const scheduleLeases = () =>
+        leases().filter((l): l is ScheduleLease => l.type === 'Schedule');
 
     const updateLease = async (action: 'take' | 'release') => {
         try {
@@ -104,6 +107,19 @@ export const HostLeasesSection = ((props: { hostname: string }) => {
                                 </tr>
                             )}
                         </For>
+
+                        <For each={scheduleLeases()}>
+                            {(lease) => (
+                                <tr class="table-row">
+                                    <th class="table-cell" scope="row">
+                                        {lease.value.name}
+                                    </th>
+                                    <td class="table-cell text-[#616161] dark:text-[#9d9d9d] text-xs">
+                                        Schedule-held
+                                    </td>
+                                </tr>
+                            )}
+                        </For>
                     </tbody>
                 </table>
             </div>
@@ -19,13 +19,21 @@ import {
 } from '../sharedComponents/CopyButton';
 import { HostStatusBadge } from '../sharedComponents/HostStatusBadge';
 
-const formatLeaseSource = (lease: LeaseSource) =>
-    lease.type === 'Client' ? lease.value : '';
+const formatLeaseSource = (lease: LeaseSource) => {
+    switch (lease.type) {
+        case 'Client':
+            return lease.value;
+        case 'Schedule':
+            return `schedule ${lease.value.name}`;
+        case 'WebInterface':
+            return '';
+    }
+};
 
 const getFormattedLeases = (leases: LeaseSource[]) => {
-    const clientLeases = leases.filter((l) => l.type === 'Client');
-    return clientLeases.length > 0
-        ? clientLeases.map(formatLeaseSource).join(', ')
+    const externalLeases = leases.filter((l) => l.type !== 'WebInterface');
+    return externalLeases.length > 0
+        ? externalLeases.map(formatLeaseSource).join(', ')
         : 'None';
 };
 
@@ -8,6 +8,8 @@ import { initDemoMode, isDemoMode } from '../helpers/demo';
 import { connectWebSocket } from '../helpers/lifetimeManagement/websocket';
 import type { AnyComponent } from '../helpers/utils/solid';
 import { AuthWarningPanel } from './AuthWarningPanel';
+import { ConfigErrorBanner } from './ConfigErrorBanner';
+import { CoordinatorErrorNotifications } from './CoordinatorErrorNotifications';
 import { Footer } from './Footer';
 import { Header } from './Header';
 import { JsErrorBox } from './JsErrorBox';
@@ -35,6 +37,8 @@ export const AppLayout = ((props: ParentProps) => {
             >
                 <section class="py-4 sm:py-6">
                     <JsErrorBox />
+                    <ConfigErrorBanner />
+                    <CoordinatorErrorNotifications />
                     {/* Auth security warning */}
                     <Show when={serverData.authWarning}>
                         <AuthWarningPanel />
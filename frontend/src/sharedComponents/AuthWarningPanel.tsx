@@ -89,6 +89,18 @@ export const AuthWarningPanel = (() => {
                                 <code>/api/m2m/*</code> — Machine-to-machine API
                                 communication used by agents/clients
                             </li>
+                            <li>
+                                <code>/api/auth_exceptions_version</code> —
+                                Expected exceptions version for config tooling
+                            </li>
+                            <li>
+                                <code>/health</code> — Liveness probe for
+                                container orchestrators
+                            </li>
+                            <li>
+                                <code>/ready</code> — Readiness probe for
+                                container orchestrators
+                            </li>
                             <li>
                                 <code>/manifest.*.json</code> — PWA manifest
                                 required for webpage installability
@@ -120,6 +132,8 @@ export const AuthWarningPanel = (() => {
     resources:
         - '^/download/(.*)'
         - '^/api/m2m/(.*)$'
+        - '^/api/auth_exceptions_version$'
+        - '^/(health|ready)$'
         - '/manifest..*.json$'
         - '/favicon..*.svg$'`}
                         />
@@ -131,7 +145,7 @@ export const AuthWarningPanel = (() => {
                             label="Copy Nginx config"
                             id="nginx-config"
                             value={`# In your proxy host's advanced configuration
-location ~ ^/(download|api/m2m|manifest\\..*\\.json|favicon\\..*\\.svg)$ {
+location ~ ^/(download|api/m2m|api/auth_exceptions_version|health|ready|manifest\\..*\\.json|favicon\\..*\\.svg)$ {
     auth_basic off;
     proxy_pass http://your-shuthost-backend;
 }`}
@@ -144,7 +158,7 @@ location ~ ^/(download|api/m2m|manifest\\..*\\.json|favicon\\..*\\.svg)$ {
                             label="Copy Traefik config"
                             id="traefik-config"
                             value={`# Add to your service labels
-- "traefik.http.routers.shuthost-bypass.rule=Host(\`${domain}\`) && (PathPrefix(\`/download\`) || PathPrefix(\`/api/m2m\`) || PathRegexp(\`/manifest..*.json\`) || PathRegexp(\`/favicon..*.svg\`))"
+- "traefik.http.routers.shuthost-bypass.rule=Host(\`${domain}\`) && (PathPrefix(\`/download\`) || PathPrefix(\`/api/m2m\`) || Path(\`/api/auth_exceptions_version\`) || Path(\`/health\`) || Path(\`/ready\`) || PathRegexp(\`/manifest..*.json\`) || PathRegexp(\`/favicon..*.svg\`))"
 - "traefik.http.routers.shuthost-bypass.priority=100"
 # Remove auth middleware for bypass routes`}
                         />
@@ -0,0 +1,21 @@
+import { Show } from 'solid-js';
+import { state } from '../helpers/appStore';
+import type { AnyComponent } from '../helpers/utils/solid';
+
+/** Banner shown while the config file fails to load and the last valid config stays in use. */
+export const ConfigErrorBanner = (() => (
+    <Show when={state.configError}>
+        {(message) => (
+            <div
+                id="config-error"
+                class="alert alert-warning mb-4"
+                role="alert"
+            >
+                <strong class="alert-title">
+                    Config file has errors — running last valid config
+                </strong>
+                <p class="font-mono text-sm whitespace-pre-wrap">{message()}</p>
+            </div>
+        )}
+    </Show>
+)) satisfies AnyComponent;
@@ -0,0 +1,49 @@
+import { For, Show } from 'solid-js';
+import type { CoordinatorError } from '../helpers/appStore';
+import {
+    coordinatorErrors,
+    dismissCoordinatorErrors,
+} from '../helpers/appStore';
+import type { AnyComponent } from '../helpers/utils/solid';
+
+const kindLabels: Record<CoordinatorError['kind'], string> = {
+    wake_failure: 'Wake failed',
+    shutdown_failure: 'Shutdown failed',
+    poll_failure: 'Polling failed',
+};
+
+/** Errors the coordinator pushed over the WebSocket, shown until dismissed. */
+export const CoordinatorErrorNotifications = (() => (
+    <Show when={coordinatorErrors().length > 0}>
+        <div
+            id="coordinator-errors"
+            class="alert alert-error mb-4"
+            role="alert"
+        >
+            <div class="flex items-start justify-between gap-3">
+                <strong class="alert-title">Coordinator errors</strong>
+                <button
+                    type="button"
+                    class="btn btn-height btn-red dismiss-coordinator-errors"
+                    onClick={dismissCoordinatorErrors}
+                >
+                    Dismiss
+                </button>
+            </div>
+            <ul>
+                <For each={coordinatorErrors()}>
+                    {(error) => (
+                        <li>
+                            <span class="font-semibold">
+                                {kindLabels[error.kind]} ({error.host}):
+                            </span>{' '}
+                            <span class="font-mono text-sm whitespace-pre-wrap">
+                                {error.message}
+                            </span>
+                        </li>
+                    )}
+                </For>
+            </ul>
+        </div>
+    </Show>
+)) satisfies AnyComponent;
@@ -3,38 +3,177 @@
 //! This module provides functions for executing system commands,
 //! particularly shutdown commands received from the coordinator.
 
-use std::process;
+use core::time::Duration;
+use std::{env, process, thread, time::Instant};
 
 use shuthost_common::ResultMapErrExt as _;
 
 use crate::server::ServiceOptions;
 
-/// Executes the configured shutdown command via the appropriate shell for the platform.
-///
-/// # Arguments
-///
-/// * `config` - `ServiceOptions` holding the `shutdown_command` to execute.
-///
-/// # Errors
+/// How often a running shutdown command is checked for completion.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A running shutdown command.
 ///
-/// Returns `Err` if spawning or waiting on the process fails.
-pub(crate) fn execute_shutdown(config: &ServiceOptions) -> Result<(), String> {
-    println!("Executing command: {}", config.shutdown_command);
+/// If `shutdown_command_timeout_secs` is set, the command is killed if it hasn't finished by then,
+/// so a hanging script doesn't keep the agent from serving further requests.
+pub(crate) struct ShutdownCommand {
+    child: process::Child,
+    started: Instant,
+    timeout_secs: Option<u64>,
+}
+
+impl ShutdownCommand {
+    /// Spawns the configured shutdown command via the appropriate shell for the platform.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - `ServiceOptions` holding the `shutdown_command` to execute.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if spawning the process fails.
+    pub(crate) fn spawn(config: &ServiceOptions) -> Result<Self, String> {
+        println!("Executing command: {}", config.shutdown_command);
 
-    const IS_WINDOWS: bool = cfg!(target_os = "windows");
+        const IS_WINDOWS: bool = cfg!(target_os = "windows");
 
-    let status = process::Command::new(if IS_WINDOWS { "powershell.exe" } else { "sh" })
+        let child = process::Command::new(if IS_WINDOWS {
+            powershell_executable()
+        } else {
+            "sh"
+        })
         .arg(if IS_WINDOWS { "-Command" } else { "-c" })
         .arg(&config.shutdown_command)
-        .status()
+        .spawn()
         .map_err_to_string_simple()?;
 
-    if !status.success() {
-        return Err(format!(
-            "Shutdown command failed (exit code: {:?})",
-            status.code()
-        ));
+        Ok(Self {
+            child,
+            started: Instant::now(),
+            timeout_secs: config.shutdown_command_timeout_secs,
+        })
+    }
+
+    /// Waits for the command to exit, but at most `wait`.
+    ///
+    /// Returns `Ok(true)` if the command exited successfully and `Ok(false)` if it is still running.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if waiting on the process fails, if it exits unsuccessfully or if it ran
+    /// into the timeout, in which case it is killed.
+    pub(crate) fn wait_for(&mut self, wait: Duration) -> Result<bool, String> {
+        let deadline = self
+            .timeout_secs
+            .and_then(|secs| self.started.checked_add(Duration::from_secs(secs)));
+        // `None` if neither `wait` nor the timeout can be reached, i.e. waits until the exit.
+        let until = [Instant::now().checked_add(wait), deadline]
+            .into_iter()
+            .flatten()
+            .min();
+        loop {
+            if let Some(status) = self.child.try_wait().map_err_to_string_simple()? {
+                return if status.success() {
+                    Ok(true)
+                } else {
+                    Err(status.code().map_or_else(
+                        || "shutdown command was terminated by a signal".to_string(),
+                        |code| format!("shutdown command exited with code {code}"),
+                    ))
+                };
+            }
+            let now = Instant::now();
+            if let Some(timeout_secs) = self.timeout_secs
+                && deadline.is_some_and(|deadline| now >= deadline)
+            {
+                self.child.kill().map_err_to_string_simple()?;
+                self.child.wait().map_err_to_string_simple()?;
+                return Err(format!(
+                    "shutdown command timed out after {timeout_secs}s and was killed"
+                ));
+            }
+            match until {
+                Some(until) if now >= until => return Ok(false),
+                Some(until) => thread::sleep(POLL_INTERVAL.min(until - now)),
+                None => thread::sleep(POLL_INTERVAL),
+            }
+        }
+    }
+}
+
+/// Returns the `PowerShell` executable to invoke.
+///
+/// Windows always ships `powershell.exe`, while `PowerShell` 7+ installs as `pwsh` (the only
+/// variant available on Unix). The platform's usual name is preferred, the other one is used if only
+/// it can be found on the `PATH`. If neither is found, the preferred name is returned so that the
+/// spawn error names the expected executable.
+pub(crate) fn powershell_executable() -> &'static str {
+    let (preferred, fallback) = if cfg!(target_os = "windows") {
+        ("powershell.exe", "pwsh.exe")
+    } else {
+        ("pwsh", "powershell")
+    };
+    if !is_on_path(preferred) && is_on_path(fallback) {
+        fallback
+    } else {
+        preferred
+    }
+}
+
+fn is_on_path(executable: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(executable).is_file()))
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use clap::Parser as _;
+
+    use super::*;
+
+    fn options(shutdown_command: &str, timeout_secs: Option<u64>) -> ServiceOptions {
+        let mut options = ServiceOptions::parse_from(["shuthost_host_agent"]);
+        options.shutdown_command = shutdown_command.to_string();
+        options.shutdown_command_timeout_secs = timeout_secs;
+        options
+    }
+
+    fn run(shutdown_command: &str, timeout_secs: Option<u64>) -> Result<bool, String> {
+        ShutdownCommand::spawn(&options(shutdown_command, timeout_secs))?.wait_for(Duration::MAX)
     }
 
-    Ok(())
+    #[test]
+    fn shutdown_command_reports_exit_status() {
+        assert_eq!(run("true", Some(5)), Ok(true));
+        assert_eq!(
+            run("exit 3", Some(5)),
+            Err("shutdown command exited with code 3".to_string())
+        );
+    }
+
+    #[test]
+    fn shutdown_command_is_killed_after_timeout() {
+        let started = Instant::now();
+        let err = run("sleep 30", Some(1)).unwrap_err();
+        assert!(err.contains("timed out after 1s"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn wait_for_returns_while_still_running() {
+        let mut command = ShutdownCommand::spawn(&options("sleep 30", Some(5))).unwrap();
+        assert_eq!(command.wait_for(Duration::from_millis(200)), Ok(false));
+        drop(command.child.kill());
+    }
+
+    #[test]
+    fn shutdown_command_has_no_timeout_by_default() {
+        assert_eq!(
+            ServiceOptions::parse_from(["shuthost_host_agent"]).shutdown_command_timeout_secs,
+            None
+        );
+        assert_eq!(run("sleep 1", None), Ok(true));
+    }
 }
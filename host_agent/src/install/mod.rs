@@ -21,7 +21,11 @@ use shuthost_common::{ResultMapErrExt as _, create_signed_message};
 #[cfg(target_os = "linux")]
 use shuthost_common::{is_openrc, is_systemd};
 
-use crate::{registration, server::get_default_shutdown_command};
+use crate::{
+    commands,
+    registration::{self, OutputFormat},
+    server::get_default_shutdown_command,
+};
 
 /// The binary name, derived from the Cargo package name.
 pub(super) const BINARY_NAME: &str = env!("CARGO_PKG_NAME");
@@ -37,6 +41,14 @@ pub(crate) const OPENRC_SERVICE_FILE_TEMPLATE: &str =
 #[cfg(unix)]
 pub(crate) const SELF_EXTRACTING_SHELL_TEMPLATE: &str = include_str!("self_extracting.tmpl.sh");
 pub(crate) const SELF_EXTRACTING_PWSH_TEMPLATE: &str = include_str!("self_extracting.tmpl.ps1");
+/// Arguments the Windows service manager starts the agent with. The shared secret is passed in
+/// the service's environment instead, see [`WINDOWS_SERVICE_ENVIRONMENT_TEMPLATE`].
+#[cfg(any(target_os = "windows", test))]
+pub(crate) const WINDOWS_SERVICE_ARGUMENTS_TEMPLATE: &str = "service --port={ port } --broadcast-port={ broadcast_port } --shutdown-command=\"{ shutdown_command }\" --hostname={ hostname } --init-system=windows-service";
+/// Environment of the Windows service, as `(name, value template)` pairs.
+#[cfg(any(target_os = "windows", test))]
+pub(crate) const WINDOWS_SERVICE_ENVIRONMENT_TEMPLATE: [(&str, &str); 1] =
+    [("SHUTHOST_SHARED_SECRET", "{ secret }")];
 
 /// Generates a random secret string suitable for use as an HMAC key.
 ///
@@ -90,6 +102,10 @@ pub struct Args {
 
     #[arg(long, short = 'n', default_value_t = default_hostname())]
     pub hostname: String,
+
+    /// Format of the printed coordinator config snippet.
+    #[arg(long, value_enum, default_value_t)]
+    pub output_format: OutputFormat,
 }
 
 /// Arguments for the `update` subcommand of `host_agent`.
@@ -121,6 +137,9 @@ pub enum InitSystem {
     /// Launchd init system (macOS).
     #[cfg_attr(not(target_os = "macos"), clap(skip))]
     Launchd,
+    /// Windows service, started at boot by the Service Control Manager. Requires an elevated prompt.
+    #[cfg_attr(not(target_os = "windows"), clap(skip))]
+    WindowsService,
 }
 
 impl fmt::Display for InitSystem {
@@ -145,6 +164,7 @@ impl From<InitSystem> for shuthost_common::InitSystem {
             tIS::Launchd => cIS::Launchd,
             tIS::SelfExtractingShell => cIS::SelfExtractingShell,
             tIS::SelfExtractingPwsh => cIS::SelfExtractingPwsh,
+            tIS::WindowsService => cIS::WindowsService,
         }
     }
 }
@@ -159,6 +179,7 @@ impl From<shuthost_common::InitSystem> for InitSystem {
             cIS::Launchd => tIS::Launchd,
             cIS::SelfExtractingShell => tIS::SelfExtractingShell,
             cIS::SelfExtractingPwsh => tIS::SelfExtractingPwsh,
+            cIS::WindowsService => tIS::WindowsService,
         }
     }
 }
@@ -168,10 +189,6 @@ impl From<shuthost_common::InitSystem> for InitSystem {
 /// Selects and invokes the appropriate init system installer or generates a script.
 pub(crate) fn install_host_agent(arguments: &Args) -> Result<(), String> {
     let name = BINARY_NAME;
-    #[cfg_attr(
-        target_os = "windows",
-        expect(unused_variables, reason = "windows doesn't need that, the others do")
-    )]
     let bind_known_vals = |arg: &str| {
         bind_template_replacements(
             arg,
@@ -212,23 +229,24 @@ pub(crate) fn install_host_agent(arguments: &Args) -> Result<(), String> {
             #[cfg(not(target_os = "macos"))]
             unreachable!("Launchd is not supported on this platform");
         }
+        InitSystem::WindowsService => {
+            #[cfg(target_os = "windows")]
+            install_windows_service(name, arguments.port, bind_known_vals)?;
+            #[cfg(not(target_os = "windows"))]
+            unreachable!("Windows services are not supported on this platform");
+        }
     }
 
-    let interface = &get_default_interface();
-    if interface.is_none() {
-        eprintln!(
-            "Failed to determine the default network interface. Continuing on assuming docker or similar environment."
-        );
-    }
-    registration::print_registration_config(&registration::ServiceConfig {
-        secret: arguments.shared_secret.clone(),
-        port: arguments.port,
-        broadcast_port: arguments.broadcast_port,
-        hostname: arguments.hostname.clone(),
-        shutdown_command: arguments.shutdown_command.clone(),
-    });
-
-    Ok(())
+    registration::print_registration_config(
+        &registration::ServiceConfig {
+            secret: arguments.shared_secret.clone(),
+            port: arguments.port,
+            broadcast_port: arguments.broadcast_port,
+            hostname: arguments.hostname.clone(),
+            shutdown_command: arguments.shutdown_command.clone(),
+        },
+        arguments.output_format,
+    )
 }
 
 /// Updates an existing installation in place using the current installed config.
@@ -288,6 +306,12 @@ pub(crate) fn update_host_agent(args: &UpdateArgs) -> Result<(), String> {
             #[cfg(not(target_os = "macos"))]
             unreachable!("Launchd updates are not supported on this platform");
         }
+        InitSystem::WindowsService => {
+            #[cfg(target_os = "windows")]
+            update_windows_service(name)?;
+            #[cfg(not(target_os = "windows"))]
+            unreachable!("Windows service updates are not supported on this platform");
+        }
     }
 
     Ok(())
@@ -346,32 +370,14 @@ fn install_self_extracting_pwsh(
         &bind_known_vals(SELF_EXTRACTING_PWSH_TEMPLATE),
         &target_script_path,
     )?;
-    let powershell_cmd = if cfg!(target_os = "windows") {
-        "powershell.exe"
-    } else {
-        "pwsh"
-    };
+    let powershell_cmd = commands::powershell_executable();
 
     #[cfg(target_os = "windows")]
-    {
-        if let Ok(appdata) = std::env::var("APPDATA") {
-            let exe_path = std::path::Path::new(&appdata)
-                .join("shuthost")
-                .join("host_agent.exe");
-            let exe_path_str = exe_path.to_string_lossy();
-            let ps_command = format!(
-                "$ruleName = \"ShutHost Host Agent\"; $existingRule = Get-NetFirewallRule -DisplayName $ruleName -ErrorAction SilentlyContinue; if (-not $existingRule) {{ New-NetFirewallRule -DisplayName $ruleName -Direction Inbound -Protocol TCP -LocalPort {} -Program \"{}\" -Action Allow -Profile Any }}",
-                arguments.port,
-                exe_path_str.replace('\\', "\\\\").replace('"', "\\\"")
-            );
-            if let Err(e) = Command::new(powershell_cmd)
-                .arg("-Command")
-                .arg(&ps_command)
-                .output()
-            {
-                eprintln!("Failed to add Windows Firewall rule: {e}");
-            }
-        }
+    if let Ok(appdata) = std::env::var("APPDATA") {
+        let exe_path = std::path::Path::new(&appdata)
+            .join("shuthost")
+            .join("host_agent.exe");
+        add_windows_firewall_rule(arguments.port, &exe_path);
     }
 
     // Start the PowerShell script in the background
@@ -392,6 +398,52 @@ fn install_self_extracting_pwsh(
     Ok(())
 }
 
+/// Display name of the firewall rule allowing inbound connections to the agent.
+#[cfg(target_os = "windows")]
+const WINDOWS_FIREWALL_RULE_NAME: &str = "ShutHost Host Agent";
+
+/// Allows inbound connections to the agent's port for the executable at `exe_path`, unless a
+/// rule already exists. Failures are only reported, the agent may still be reachable.
+#[cfg(target_os = "windows")]
+fn add_windows_firewall_rule(port: u16, exe_path: &Path) {
+    let ps_command = format!(
+        "$ruleName = \"{WINDOWS_FIREWALL_RULE_NAME}\"; $existingRule = Get-NetFirewallRule -DisplayName $ruleName -ErrorAction SilentlyContinue; if (-not $existingRule) {{ New-NetFirewallRule -DisplayName $ruleName -Direction Inbound -Protocol TCP -LocalPort {} -Program \"{}\" -Action Allow -Profile Any }}",
+        port,
+        exe_path
+            .to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    );
+    if let Err(e) = Command::new(commands::powershell_executable())
+        .arg("-Command")
+        .arg(&ps_command)
+        .output()
+    {
+        eprintln!("Failed to add Windows Firewall rule: {e}");
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn install_windows_service(
+    name: &str,
+    port: u16,
+    bind_known_vals: impl Fn(&str) -> String,
+) -> Result<(), String> {
+    let environment =
+        WINDOWS_SERVICE_ENVIRONMENT_TEMPLATE.map(|(key, value)| (key, bind_known_vals(value)));
+    shuthost_common::windows::install_self_as_service(
+        name,
+        env!("CARGO_PKG_DESCRIPTION"),
+        &bind_known_vals(WINDOWS_SERVICE_ARGUMENTS_TEMPLATE),
+        &environment
+            .each_ref()
+            .map(|&(key, ref value)| (key, value.as_str())),
+    )?;
+    add_windows_firewall_rule(port, &shuthost_common::windows::get_binary_path(name));
+    shuthost_common::windows::start_and_enable_self_as_service(name)?;
+    Ok(())
+}
+
 #[cfg(target_os = "macos")]
 fn install_launchd(name: &str, bind_known_vals: impl Fn(&str) -> String) -> Result<(), String> {
     shuthost_common::macos::install_self_as_service(
@@ -483,6 +535,28 @@ fn update_launchd(name: &str) -> Result<(), String> {
     Ok(())
 }
 
+#[cfg(target_os = "windows")]
+fn update_windows_service(name: &str) -> Result<(), String> {
+    let config = registration::parse_config(&registration::Args {
+        init_system: InitSystem::WindowsService,
+        script_path: None,
+    })?;
+
+    let bind_known_vals = |arg: &str| {
+        bind_template_replacements(
+            arg,
+            env!("CARGO_PKG_DESCRIPTION"),
+            config.port,
+            config.broadcast_port,
+            &config.shutdown_command,
+            &config.secret,
+            &config.hostname,
+        )
+    };
+
+    install_windows_service(name, config.port, bind_known_vals)
+}
+
 #[cfg(unix)]
 fn update_self_extracting_shell(name: &str, script_path: Option<&str>) -> Result<(), String> {
     let path = script_path.map_or_else(|| format!("./{name}_self_extracting"), ToString::to_string);
@@ -585,11 +659,7 @@ fn update_self_extracting_pwsh(name: &str, script_path: Option<&str>) -> Result<
     shutdown_self_extracting_service(&config)?;
     wait_for_port_to_free(config.port)?;
 
-    let powershell_cmd = if cfg!(target_os = "windows") {
-        "powershell.exe"
-    } else {
-        "pwsh"
-    };
+    let powershell_cmd = commands::powershell_executable();
 
     if let Err(e) = Command::new(powershell_cmd)
         .arg("-ExecutionPolicy")
@@ -629,9 +699,14 @@ pub(crate) fn get_inferred_init_system() -> InitSystem {
     {
         InitSystem::Launchd
     }
+    // Registering a service requires an elevated prompt, the script works without.
     #[cfg(target_os = "windows")]
     {
-        InitSystem::SelfExtractingPwsh
+        if shuthost_common::is_superuser() {
+            InitSystem::WindowsService
+        } else {
+            InitSystem::SelfExtractingPwsh
+        }
     }
 }
 
@@ -683,46 +758,99 @@ pub(crate) fn get_default_interface() -> Option<String> {
     }
 }
 
-/// Retrieves the MAC address for the named network interface.
-pub(crate) fn get_mac(interface: &str) -> Option<String> {
+/// Retrieves the MAC addresses for the named network interface.
+///
+/// Besides the interface's own address this includes the permanent addresses of bonded member
+/// interfaces, since Wake-on-LAN has to reach whichever NIC is active while the host sleeps.
+/// The addresses are normalized to lowercase, colon-separated octets (Windows reports them with
+/// dashes), addresses that can't be parsed are skipped.
+/// Returns an empty list if no address could be determined.
+pub(crate) fn get_macs(interface: &str) -> Vec<String> {
+    normalize_macs(get_interface_macs(interface))
+}
+
+/// Normalizes `macs` with [`shuthost_common::normalize_mac`], dropping invalid and duplicate ones.
+fn normalize_macs(macs: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for mac in macs {
+        match shuthost_common::normalize_mac(&mac) {
+            Ok(mac) if !normalized.contains(&mac) => normalized.push(mac),
+            Ok(_) => {}
+            Err(e) => eprintln!("Ignoring {e}"),
+        }
+    }
+    normalized
+}
+
+/// Retrieves the MAC addresses for the named network interface as reported by the OS.
+fn get_interface_macs(interface: &str) -> Vec<String> {
     #[cfg(target_os = "linux")]
     {
-        let output = Command::new("ip")
-            .args(["link", "show", interface])
-            .output()
-            .ok()?;
-        let text = String::from_utf8_lossy(&output.stdout);
-        for line in text.lines() {
-            if line.contains("ether") {
-                return line.split_whitespace().nth(1).map(ToString::to_string);
+        let mut macs = Vec::new();
+        for args in [
+            ["link", "show", "dev", interface],
+            ["link", "show", "master", interface],
+        ] {
+            let Ok(output) = Command::new("ip").args(args).output() else {
+                continue;
+            };
+            for mac in parse_ip_link_macs(&String::from_utf8_lossy(&output.stdout)) {
+                if !macs.contains(&mac) {
+                    macs.push(mac);
+                }
             }
         }
-        None
+        macs
     }
 
     #[cfg(target_os = "macos")]
     {
-        let output = Command::new("ifconfig").arg(interface).output().ok()?;
+        let Ok(output) = Command::new("ifconfig").arg(interface).output() else {
+            return Vec::new();
+        };
         let text = String::from_utf8_lossy(&output.stdout);
-        for line in text.lines() {
-            if line.trim_start().starts_with("ether ") {
-                return line.split_whitespace().nth(1).map(|s| s.to_string());
-            }
-        }
-        None
+        text.lines()
+            .filter_map(|line| line.trim_start().strip_prefix("ether "))
+            .filter_map(|rest| rest.split_whitespace().next())
+            .map(|s| s.to_string())
+            .collect()
     }
 
     #[cfg(target_os = "windows")]
     {
-        let output = Command::new("powershell")
+        let Ok(output) = Command::new("powershell")
             .args(["-Command", &format!("Get-NetAdapter | Where-Object {{ $_.Name -eq '{}' }} | Select-Object -ExpandProperty MacAddress", interface)])
             .output()
-            .ok()?;
-        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !text.is_empty() { Some(text) } else { None }
+        else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
     }
 }
 
+/// Extracts the `link/ether` and `permaddr` addresses from `ip link show` output.
+#[cfg(any(target_os = "linux", test))]
+fn parse_ip_link_macs(text: &str) -> Vec<String> {
+    let mut macs: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        while let Some(word) = words.next() {
+            if (word == "link/ether" || word == "permaddr")
+                && let Some(mac) = words.next()
+                && !macs.iter().any(|known| known == mac)
+            {
+                macs.push(mac.to_string());
+            }
+        }
+    }
+    macs
+}
+
 /// Retrieves the IP address for the named network interface.
 pub(crate) fn get_ip(interface: &str) -> Option<String> {
     #[cfg(target_os = "linux")]
@@ -790,21 +918,25 @@ pub(crate) fn default_hostname() -> String {
 }
 
 /// Tests Wake-on-LAN packet reachability by listening and echoing back packets.
-pub(crate) fn test_wol_reachability(port: u16) -> Result<(), String> {
+///
+/// Waits up to `first_packet_timeout` for the first packet, and a second for the next.
+pub(crate) fn test_wol_reachability(
+    port: u16,
+    first_packet_timeout: Duration,
+) -> Result<(), String> {
     let socket = shuthost_common::create_broadcast_socket(port)?;
 
-    // Don't block forever in environments where one of the test packets
-    // (direct vs broadcast) may be dropped. Use a small read timeout
-    // and treat at least one received packet as success.
-    socket
-        .set_read_timeout(Some(Duration::from_secs(1)))
-        .map_err(|e| format!("Failed to set socket timeout: {e}"))?;
-
     println!("Listening for WOL test packets on port {port}...");
 
     let mut buf = [0u8; 32];
     let mut received = 0u8;
-    for _ in 0..2 {
+    for timeout in [first_packet_timeout, Duration::from_secs(1)] {
+        // Don't block forever in environments where one of the test packets
+        // (direct vs broadcast) may be dropped. Use a small read timeout after
+        // the first packet and treat at least one received packet as success.
+        socket
+            .set_read_timeout(Some(timeout))
+            .map_err(|e| format!("Failed to set socket timeout: {e}"))?;
         match socket.recv_from(&mut buf) {
             Ok((_n, addr)) => {
                 // Echo back to confirm receipt
@@ -853,4 +985,33 @@ mod tests {
             Err("--script-path must be an absolute path".to_string())
         );
     }
+
+    #[test]
+    fn normalize_macs_unifies_format() {
+        let macs = [
+            "AA-BB-CC-DD-EE-01",
+            "aa:bb:cc:dd:ee:01",
+            "AA:BB:CC:DD:EE:02",
+            "not-a-mac",
+        ];
+        assert_eq!(
+            normalize_macs(macs.map(ToString::to_string).to_vec()),
+            ["aa:bb:cc:dd:ee:01", "aa:bb:cc:dd:ee:02"]
+        );
+    }
+
+    #[test]
+    fn parse_ip_link_macs_includes_bond_members() {
+        let output = "\
+3: eth0: <BROADCAST,MULTICAST,SLAVE,UP,LOWER_UP> mtu 1500 qdisc fq_codel master bond0 state UP mode DEFAULT group default qlen 1000
+    link/ether aa:bb:cc:dd:ee:01 brd ff:ff:ff:ff:ff:ff
+4: eth1: <BROADCAST,MULTICAST,SLAVE,UP,LOWER_UP> mtu 1500 qdisc fq_codel master bond0 state UP mode DEFAULT group default qlen 1000
+    link/ether aa:bb:cc:dd:ee:01 brd ff:ff:ff:ff:ff:ff permaddr aa:bb:cc:dd:ee:02
+    altname enp0s2
+";
+        assert_eq!(
+            parse_ip_link_macs(output),
+            ["aa:bb:cc:dd:ee:01", "aa:bb:cc:dd:ee:02"]
+        );
+    }
 }
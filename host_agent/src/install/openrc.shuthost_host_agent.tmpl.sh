@@ -5,12 +5,14 @@ supervisor=supervise-daemon
 name="{ name }"
 description="{ description }"
 command="/usr/local/sbin/{ name }"
-command_args="service --port={ port } --broadcast-port={ broadcast_port } --shutdown-command=\"{ shutdown_command }\" --hostname={ hostname } --init-system openrc"
+command_args="service --port={ port } --broadcast-port={ broadcast_port } --shutdown-command=\"{ shutdown_command }\" --hostname={ hostname } --init-system openrc --pid-file=/run/{ name }.pid"
 command_user="root"
-pidfile="/run/${RC_SVCNAME}.pid"
+# The agent writes its own PID file (see --pid-file), this one belongs to supervise-daemon.
+pidfile="/run/supervise-${RC_SVCNAME}.pid"
 
 export SHUTHOST_SHARED_SECRET="{ secret }"
 
 depend() {
     need net
+    use logger
 }
@@ -7,12 +7,18 @@ extern crate core;
 
 mod commands;
 mod install;
+#[cfg(feature = "mdns")]
+mod mdns;
 pub mod registration;
 pub mod script_generator;
 pub mod server;
+pub mod status;
 pub mod validation;
+#[cfg(target_os = "windows")]
+mod windows_service;
 
-use std::env;
+use core::time::Duration;
+use std::{env, process};
 
 use clap::{Parser, Subcommand};
 
@@ -59,11 +65,21 @@ pub enum Command {
         /// UDP port to listen on for WOL test packets.
         #[arg(long, short, default_value_t = shuthost_common::DEFAULT_AGENT_TCP_PORT + 1)]
         port: u16,
+
+        /// Seconds to wait for the first test packet. The default leaves time to start the test
+        /// from the coordinator's web UI.
+        #[arg(long, default_value_t = 30)]
+        wait_secs: u64,
     },
 
     /// Print the registration configuration for the installed agent.
     Registration(registration::Args),
 
+    /// Check whether an agent responds to signed status requests and print its version.
+    ///
+    /// Exits with a non-zero code if the agent is unreachable or rejects the request.
+    Status(status::Args),
+
     /// Generate a `shuthost_direct_control` script for this `host_agent`.
     #[clap(visible_alias = "gdc")]
     GenerateDirectControl(script_generator::Args),
@@ -79,19 +95,43 @@ pub fn inner_main(invocation: Cli) {
             Ok(()) => println!("Agent updated successfully!"),
             Err(e) => eprintln!("Error updating host_agent: {e}"),
         },
+        #[cfg(target_os = "windows")]
+        Command::Service(args) if args.init_system == install::InitSystem::WindowsService => {
+            windows_service::run(args);
+        }
         Command::Service(args) => {
             server::start_host_agent(args);
         }
-        Command::TestWol { port } => match install::test_wol_reachability(port) {
-            Ok(()) => (),
-            Err(e) => eprintln!("Error during WoL test: {e}"),
-        },
+        Command::TestWol { port, wait_secs } => {
+            match install::test_wol_reachability(port, Duration::from_secs(wait_secs)) {
+                Ok(()) => (),
+                Err(e) => eprintln!("Error during WoL test: {e}"),
+            }
+        }
         Command::Registration(args) => match registration::parse_config(&args) {
             Ok(config) => {
-                registration::print_registration_config(&config);
+                if let Err(e) = registration::print_registration_config(
+                    &config,
+                    registration::OutputFormat::Toml,
+                ) {
+                    eprintln!("Error: {e}");
+                }
             }
             Err(e) => eprintln!("Error parsing config: {e}"),
         },
+        Command::Status(args) => match status::query(&args) {
+            Ok(response) => {
+                let version = status::parse_agent_version(&response).unwrap_or("unknown");
+                println!(
+                    "Agent at {}:{} is reachable (version: {version})",
+                    args.ip, args.port
+                );
+            }
+            Err(e) => {
+                eprintln!("Error: {e}");
+                process::exit(1);
+            }
+        },
         Command::GenerateDirectControl(args) => {
             match script_generator::write_control_script(&args) {
                 Ok(()) => (),
@@ -0,0 +1,26 @@
+//! mDNS announcement of the agent, so coordinators can discover the port of hosts configured with
+//! `port = 0`.
+
+use mdns_sd::{Error, ServiceDaemon, ServiceInfo};
+use shuthost_common::MDNS_SERVICE_TYPE;
+
+use crate::VERSION;
+
+/// Announces the agent listening on `port` under [`MDNS_SERVICE_TYPE`], with `hostname` as the
+/// instance name and the addresses of all interfaces.
+///
+/// The returned daemon answers queries from its own thread as long as the process runs.
+pub(crate) fn announce(hostname: &str, port: u16) -> Result<ServiceDaemon, Error> {
+    let daemon = ServiceDaemon::new()?;
+    let service = ServiceInfo::new(
+        MDNS_SERVICE_TYPE,
+        hostname,
+        &format!("{hostname}.local."),
+        "",
+        port,
+        &[("agent_version", VERSION)][..],
+    )?
+    .enable_addr_auto();
+    daemon.register(service)?;
+    Ok(daemon)
+}
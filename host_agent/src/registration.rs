@@ -1,11 +1,12 @@
 use std::{fs, path::Path};
 
 use clap::Parser;
+use miniserde::json;
 
 use crate::install::{
-    BINARY_NAME, InitSystem, get_default_interface, get_inferred_init_system, get_ip, get_mac,
+    BINARY_NAME, InitSystem, get_default_interface, get_inferred_init_system, get_ip, get_macs,
 };
-use shuthost_common::{ResultMapErrExt as _, UnwrapToStringExt as _};
+use shuthost_common::{ResultMapErrExt as _, UNRECOGNIZED_ADDRESS, UnwrapToStringExt as _};
 
 /// Helper function to find and extract flag values from service file lines.
 ///
@@ -131,6 +132,14 @@ pub(crate) fn parse_config(args: &Args) -> Result<ServiceConfig, String> {
             #[cfg(not(target_os = "macos"))]
             unreachable!("Launchd is not supported on this platform");
         }
+        InitSystem::WindowsService => {
+            #[cfg(target_os = "windows")]
+            return parse_windows_service_content(&shuthost_common::windows::query_service_config(
+                BINARY_NAME,
+            )?);
+            #[cfg(not(target_os = "windows"))]
+            unreachable!("Windows services are not supported on this platform");
+        }
     })
 }
 
@@ -172,48 +181,155 @@ pub(crate) fn detect_installation_init_system() -> Result<InitSystem, String> {
         }
     }
 
+    #[cfg(target_os = "windows")]
+    if shuthost_common::windows::service_exists(BINARY_NAME) {
+        return Ok(InitSystem::WindowsService);
+    }
+
     Err("No existing host_agent installation detected for update.".to_string())
 }
 
-pub(crate) fn print_registration_config(
-    &ServiceConfig {
-        ref hostname,
-        port,
-        ref secret,
-        broadcast_port,
-        ..
-    }: &ServiceConfig,
-) {
-    let interface = &get_default_interface();
-    if interface.is_none() {
-        eprintln!(
-            "Failed to determine the default network interface. Continuing on assuming docker or similar environment."
-        );
-    }
-    let ip = interface
-        .as_ref()
-        .and_then(|it| get_ip(it))
-        .unwrap_or("unrecognized".to_string());
-    let mac = interface
-        .as_ref()
-        .and_then(|it| get_mac(it))
-        .unwrap_or("unrecognized".to_string());
-    let default_broadcast_port = shuthost_common::DEFAULT_COORDINATOR_BROADCAST_PORT;
-    println!(
-        r#"Ensure the coordinator sets `broadcast_port` to {broadcast_port} to receive broadcasts from this host (coordinator defaults to {default_broadcast_port}).
+/// Format of the coordinator config snippet printed after installation.
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A `[hosts."name"]` table to paste into the coordinator config.
+    #[default]
+    Toml,
+    /// A single-line JSON object, for automation.
+    Json,
+}
+
+/// Everything the coordinator needs to know to register this host.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct RegistrationInfo {
+    pub name: String,
+    pub ip: String,
+    pub macs: Vec<String>,
+    pub port: u16,
+    pub shared_secret: String,
+    pub broadcast_port: u16,
+}
 
-Place the following in the coordinator's [hosts] section:
+impl RegistrationInfo {
+    /// Collects the registration info of `config`, detecting IP and MAC addresses of the default
+    /// network interface.
+    fn detect(config: &ServiceConfig) -> Self {
+        let interface = &get_default_interface();
+        if interface.is_none() {
+            eprintln!(
+                "Failed to determine the default network interface. Continuing on assuming docker or similar environment."
+            );
+        }
+        Self {
+            name: config.hostname.clone(),
+            ip: interface
+                .as_ref()
+                .and_then(|it| get_ip(it))
+                .unwrap_or(UNRECOGNIZED_ADDRESS.to_string()),
+            macs: interface
+                .as_ref()
+                .map(|it| get_macs(it))
+                .unwrap_or_default(),
+            port: config.port,
+            shared_secret: config.secret.clone(),
+            broadcast_port: config.broadcast_port,
+        }
+    }
 
-[hosts."{hostname}"]
+    /// Renders the `[hosts."name"]` table for the coordinator config.
+    pub(crate) fn to_toml(&self) -> String {
+        let name = toml_escape(&self.name);
+        let ip = toml_escape(&self.ip);
+        let port = self.port;
+        let shared_secret = toml_escape(&self.shared_secret);
+        let mac = match *self.macs.as_slice() {
+            [] => format!("\"{UNRECOGNIZED_ADDRESS}\""),
+            [ref mac] => format!("\"{}\"", toml_escape(mac)),
+            ref macs => format!(
+                "[{}]",
+                macs.iter()
+                    .map(|mac| format!("\"{}\"", toml_escape(mac)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        format!(
+            r#"[hosts."{name}"]
 ip = "{ip}"
-mac = "{mac}"
+mac = {mac}
 port = {port}
-shared_secret = "{secret}"
+shared_secret = "{shared_secret}"
 enforce_state = false
 # wake_timeout_secs = 120
 # shutdown_timeout_secs = 20
 "#
+        )
+    }
+
+    /// Renders the registration as a JSON object.
+    ///
+    /// Like in the TOML snippet, `mac` is a string for a single address and an array otherwise.
+    pub(crate) fn to_json(&self) -> String {
+        let string = |value: &str| json::Value::String(value.to_string());
+        let mac = match *self.macs.as_slice() {
+            [] => string(UNRECOGNIZED_ADDRESS),
+            [ref mac] => string(mac),
+            ref macs => json::Value::Array(macs.iter().map(|mac| string(mac)).collect()),
+        };
+        let mut object = json::Object::new();
+        object.insert("name".to_string(), string(&self.name));
+        object.insert("ip".to_string(), string(&self.ip));
+        object.insert("mac".to_string(), mac);
+        object.insert(
+            "port".to_string(),
+            json::Value::Number(json::Number::U64(self.port.into())),
+        );
+        object.insert("shared_secret".to_string(), string(&self.shared_secret));
+        json::to_string(&json::Value::Object(object))
+    }
+}
+
+/// Prints the registration info for the coordinator config.
+///
+/// # Errors
+///
+/// Returns an error if the TOML snippet would not be a valid `[hosts]` entry.
+/// Escapes `value` for use inside a basic (double-quoted) TOML string.
+fn toml_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Prints the registration info for the coordinator config.
+///
+/// # Errors
+///
+/// Returns an error if the TOML snippet would not be a valid `[hosts]` entry.
+pub(crate) fn print_registration_config(
+    config: &ServiceConfig,
+    format: OutputFormat,
+) -> Result<(), String> {
+    let info = RegistrationInfo::detect(config);
+    let broadcast_port = info.broadcast_port;
+    let default_broadcast_port = shuthost_common::DEFAULT_COORDINATOR_BROADCAST_PORT;
+    let broadcast_note = format!(
+        "Ensure the coordinator sets `broadcast_port` to {broadcast_port} to receive broadcasts from this host (coordinator defaults to {default_broadcast_port})."
     );
+    match format {
+        OutputFormat::Toml => {
+            let snippet = info.to_toml();
+            shuthost_common::validate_config_snippet(&snippet)
+                .map_err(|e| format!("Generated an invalid coordinator config snippet: {e}"))?;
+            println!(
+                "{broadcast_note}\n\nPlace the following in the coordinator's [hosts] section:\n\n{snippet}"
+            );
+        }
+        OutputFormat::Json => {
+            // Keep stdout machine readable.
+            eprintln!("{broadcast_note}");
+            println!("{}", info.to_json());
+        }
+    }
+    Ok(())
 }
 
 #[cfg(any(target_os = "linux", test))]
@@ -476,6 +592,52 @@ fn parse_launchd_config() -> Result<ServiceConfig, String> {
     )
 }
 
+/// Parses the output of [`shuthost_common::windows::query_service_config`], i.e. the service's
+/// command line from `sc.exe qc` and its environment from `reg.exe query`.
+#[cfg(any(target_os = "windows", test))]
+fn parse_windows_service_content(content: &str) -> Result<ServiceConfig, String> {
+    let mut secret = None;
+    let mut port = None;
+    let mut broadcast_port = None;
+    let mut hostname = None;
+    let mut shutdown_command = None;
+
+    for line in content.lines() {
+        // The strings of the `REG_MULTI_SZ` environment are separated by a literal `\0`.
+        if let Some(value) = line
+            .split("SHUTHOST_SHARED_SECRET=")
+            .nth(1)
+            .and_then(|rest| rest.split("\\0").next())
+        {
+            secret = Some(value.trim().to_string());
+        }
+        if let Some(value) = find_flag_value(line, "port", " ") {
+            port = value.parse().ok();
+        }
+        if let Some(value) = find_flag_value(line, "broadcast-port", " ") {
+            broadcast_port = value.parse().ok();
+        }
+        if let Some(value) = find_flag_value(line, "hostname", " ") {
+            hostname = Some(value);
+        }
+        if let Some(value) = find_flag_value(line, "shutdown-command", " ") {
+            shutdown_command = Some(value);
+        }
+    }
+
+    match (secret, port, hostname, shutdown_command) {
+        (Some(s), Some(p), Some(h), Some(cmd)) => Ok(ServiceConfig {
+            secret: s,
+            port: p,
+            broadcast_port: broadcast_port
+                .unwrap_or(shuthost_common::DEFAULT_COORDINATOR_BROADCAST_PORT),
+            hostname: h,
+            shutdown_command: cmd,
+        }),
+        _ => Err("Failed to parse secret, port, and hostname from the Windows service".to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -508,6 +670,74 @@ mod tests {
         assert!(content.contains(&port.to_string()));
     }
 
+    fn registration_info(macs: &[&str]) -> RegistrationInfo {
+        RegistrationInfo {
+            name: "nas".to_string(),
+            ip: "192.168.1.10".to_string(),
+            macs: macs.iter().map(ToString::to_string).collect(),
+            port: 5757,
+            shared_secret: "s3cr\"et".to_string(),
+            broadcast_port: 5757,
+        }
+    }
+
+    #[test]
+    fn registration_info_renders_toml() {
+        let toml = registration_info(&["aa:bb:cc:dd:ee:ff"]).to_toml();
+        assert!(toml.starts_with("[hosts.\"nas\"]\nip = \"192.168.1.10\"\n"));
+        assert!(toml.contains("mac = \"aa:bb:cc:dd:ee:ff\"\n"));
+        assert!(toml.contains("port = 5757\n"));
+
+        let multiple_macs =
+            registration_info(&["aa:bb:cc:dd:ee:ff", "11:22:33:44:55:66"]).to_toml();
+        assert!(multiple_macs.contains("mac = [\"aa:bb:cc:dd:ee:ff\", \"11:22:33:44:55:66\"]\n"));
+    }
+
+    #[test]
+    fn registration_info_toml_is_valid_config_snippet() {
+        for macs in [
+            &[][..],
+            &["aa:bb:cc:dd:ee:ff"],
+            &["aa:bb:cc:dd:ee:ff", "11:22:33:44:55:66"],
+        ] {
+            let info = RegistrationInfo {
+                shared_secret: "s3cret".to_string(),
+                ..registration_info(macs)
+            };
+            assert_eq!(
+                shuthost_common::validate_config_snippet(&info.to_toml()),
+                Ok(())
+            );
+        }
+        // Quotes and backslashes get escaped.
+        let info = RegistrationInfo {
+            name: r#"my "nas"\"#.to_string(),
+            ..registration_info(&["aa:bb:cc:dd:ee:ff"])
+        };
+        let toml = info.to_toml();
+        assert_eq!(shuthost_common::validate_config_snippet(&toml), Ok(()));
+        assert!(toml.starts_with(r#"[hosts."my \"nas\"\\"]"#));
+        assert!(toml.contains(r#"shared_secret = "s3cr\"et""#));
+    }
+
+    #[test]
+    fn registration_info_renders_json() {
+        assert_eq!(
+            registration_info(&["aa:bb:cc:dd:ee:ff"]).to_json(),
+            r#"{"ip":"192.168.1.10","mac":"aa:bb:cc:dd:ee:ff","name":"nas","port":5757,"shared_secret":"s3cr\"et"}"#
+        );
+        assert!(
+            registration_info(&["aa:bb:cc:dd:ee:ff", "11:22:33:44:55:66"])
+                .to_json()
+                .contains(r#""mac":["aa:bb:cc:dd:ee:ff","11:22:33:44:55:66"]"#)
+        );
+        assert!(
+            registration_info(&[])
+                .to_json()
+                .contains(r#""mac":"unrecognized""#)
+        );
+    }
+
     #[test]
     fn parse_systemd_content_works() {
         test_parse_content(
@@ -544,4 +774,23 @@ mod tests {
             parse_self_extracting_pwsh_content,
         );
     }
+
+    #[test]
+    fn parse_windows_service_content_works() {
+        let [(env_name, env_value)] = install::WINDOWS_SERVICE_ENVIRONMENT_TEMPLATE;
+        // Abbreviated output of `sc.exe qc` and `reg.exe query`.
+        let template = format!(
+            "SERVICE_NAME: shuthost_host_agent
+        TYPE               : 10  WIN32_OWN_PROCESS
+        START_TYPE         : 2   AUTO_START
+        BINARY_PATH_NAME   : \"C:\\Program Files\\shuthost\\shuthost_host_agent.exe\" {}
+        DISPLAY_NAME       : shuthost_host_agent
+
+HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\shuthost_host_agent
+    Environment    REG_MULTI_SZ    {env_name}={env_value}\\0OTHER=value
+",
+            install::WINDOWS_SERVICE_ARGUMENTS_TEMPLATE
+        );
+        test_parse_content(&template, parse_windows_service_content);
+    }
 }
@@ -3,7 +3,7 @@ use std::{fs, path::PathBuf};
 
 use crate::{
     install::{
-        InitSystem, get_default_interface, get_hostname, get_inferred_init_system, get_ip, get_mac,
+        InitSystem, get_default_interface, get_hostname, get_inferred_init_system, get_ip, get_macs,
     },
     registration::{self, parse_config},
 };
@@ -114,7 +114,10 @@ pub(crate) fn generate_control_script(
 
     let (ip, mac) = if let Some(interface) = get_default_interface() {
         let ip = get_ip(&interface).unwrap_or_to_string("127.0.0.1");
-        let mac = get_mac(&interface).unwrap_or_to_string("00:00:00:00:00:00");
+        let mac = get_macs(&interface)
+            .into_iter()
+            .next()
+            .unwrap_or_to_string("00:00:00:00:00:00");
         (ip, mac)
     } else {
         eprintln!(
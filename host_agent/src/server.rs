@@ -1,14 +1,22 @@
 //! Server module: listens for TCP connections to process commands and optionally perform shutdown.
 
+#[cfg(unix)]
+use alloc::sync::Arc;
+use core::{net::Ipv6Addr, time::Duration};
+#[cfg(unix)]
+use std::sync::{Mutex, PoisonError};
 use std::{
-    env,
+    fs, io,
     io::{Read as _, Write as _},
     net::{TcpListener, TcpStream},
-    process,
+    path::{Path, PathBuf},
+    process, thread,
 };
 
 use clap::Parser;
 use miniserde::json;
+#[cfg(unix)]
+use nix::sys::signal::{SigSet, Signal};
 use secrecy::SecretString;
 use shuthost_common::{
     CoordinatorMessage, UnwrapToStringExt as _, create_signed_message,
@@ -17,15 +25,18 @@ use shuthost_common::{
 
 use crate::{
     VERSION,
-    commands::execute_shutdown,
+    commands::ShutdownCommand,
     install::{
-        InitSystem, default_hostname, get_default_interface, get_inferred_init_system, get_ip,
-        get_mac,
+        InitSystem, default_hostname, generate_secret, get_default_interface,
+        get_inferred_init_system, get_ip, get_macs,
     },
     registration,
     validation::validate_request,
 };
 
+#[cfg(feature = "mdns")]
+use crate::mdns;
+
 /// Configuration options for running the `host_agent` service.
 #[derive(Debug, Parser, Clone)]
 pub struct ServiceOptions {
@@ -45,8 +56,9 @@ pub struct ServiceOptions {
     pub shutdown_command: String,
 
     /// Shared secret for validating incoming HMAC-signed requests.
-    /// Usually set from environment variables, after parsing.
-    #[arg(skip)]
+    /// Prefer the environment variable, command line arguments are visible to other users.
+    /// A random secret is used when neither is set.
+    #[arg(long, env = SHARED_SECRET_ENV, hide_env_values = true)]
     pub shared_secret: Option<SecretString>,
 
     /// Hostname of this machine.
@@ -60,19 +72,39 @@ pub struct ServiceOptions {
     /// Path to the self-extracting script, only used and allowed for self-extracting installs. Must be absolute.
     #[arg(long)]
     pub script_path: Option<String>,
+
+    /// Maximum clock skew in seconds accepted on signed coordinator requests.
+    /// Increase on hosts without reliable time synchronisation.
+    #[arg(long, default_value_t = shuthost_common::ALLOWED_WINDOW)]
+    pub hmac_tolerance_secs: u64,
+
+    /// Seconds to wait for the shutdown command to finish before it is killed.
+    /// Without it, the command may run for as long as it takes.
+    #[arg(long)]
+    pub shutdown_command_timeout_secs: Option<u64>,
+
+    /// Write the process ID to this file once the agent listens, for init systems that track
+    /// services by PID file. The file is removed again when the service stops, on Unix also
+    /// when it gets stopped by SIGTERM or SIGINT.
+    #[arg(long)]
+    pub pid_file: Option<PathBuf>,
+
+    /// Announce the agent via mDNS, for coordinators configured with `port = 0` for this host.
+    #[cfg(feature = "mdns")]
+    #[arg(long)]
+    pub mdns_announce: bool,
 }
 
+/// Environment variable the service files pass the shared secret in.
+const SHARED_SECRET_ENV: &str = "SHUTHOST_SHARED_SECRET";
+
 /// Starts the TCP listener and handles incoming client connections in sequence.
-///
-/// # Panics
-///
-/// Panics if the `SHUTHOST_SHARED_SECRET` environment variable is not set (and the value wasn't smuggled into `ServiceArgs`).
 pub(crate) fn start_host_agent(mut config: ServiceOptions) {
     config.shared_secret.get_or_insert_with(|| {
-        SecretString::from(
-            env::var("SHUTHOST_SHARED_SECRET")
-                .expect("SHUTHOST_SHARED_SECRET environment variable must be set or injected"),
-        )
+        eprintln!(
+            "Warning: neither --shared-secret nor {SHARED_SECRET_ENV} is set, using a random secret. No coordinator will be able to control this host."
+        );
+        SecretString::from(generate_secret())
     });
     registration::validate_script_path_args(&registration::Args {
         init_system: config.init_system,
@@ -83,32 +115,43 @@ pub(crate) fn start_host_agent(mut config: ServiceOptions) {
         process::exit(1);
     });
 
-    let port = config.port;
-    let addr = format!("0.0.0.0:{port}");
-    let listener =
-        TcpListener::bind(&addr).unwrap_or_else(|_| panic!("Failed to bind port {addr}"));
-    println!("Listening on {addr}");
+    let listener = bind_listener(config.port);
+    // Kept alive until the agent stops, dropping it removes the PID file.
+    let _pid_file = config.pid_file.as_deref().map(|path| {
+        let pid_file = PidFile::create(path).unwrap_or_else(|err| {
+            eprintln!("Error: failed to write PID file {}: {err}", path.display());
+            process::exit(1);
+        });
+        #[cfg(unix)]
+        let pid_file = PidFileGuard::remove_on_termination(pid_file);
+        pid_file
+    });
 
     broadcast_startup(&config);
+    // Kept alive until the agent stops, dropping it would stop answering queries.
+    #[cfg(feature = "mdns")]
+    let _mdns_daemon = config.mdns_announce.then(|| {
+        let port = listener
+            .local_addr()
+            .map_or(config.port, |addr| addr.port());
+        match mdns::announce(&config.hostname, port) {
+            Ok(daemon) => {
+                println!("Announcing {} via mDNS", config.hostname);
+                Some(daemon)
+            }
+            Err(e) => {
+                eprintln!("Failed to announce via mDNS: {e}");
+                None
+            }
+        }
+    });
 
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
-                let action = handle_client(stream, &config);
-                use CoordinatorMessage as M;
-                match action {
-                    Some(M::Shutdown) => {
-                        print!(
-                            "Shutdown requested. Executing shutdown command {}... ",
-                            config.shutdown_command
-                        );
-                        execute_shutdown(&config).expect("failed to execute shutdown command");
-                    }
-                    Some(M::Abort) => {
-                        println!("Abort requested. Stopping host_agent service.");
-                        break;
-                    }
-                    _ => {}
+                if let Some(CoordinatorMessage::Abort) = handle_client(stream, &config) {
+                    println!("Abort requested. Stopping host_agent service.");
+                    break;
                 }
             }
             Err(e) => {
@@ -118,6 +161,108 @@ pub(crate) fn start_host_agent(mut config: ServiceOptions) {
     }
 }
 
+/// Binds the TCP listener for coordinator requests.
+///
+/// On Unix the agent listens on the IPv6 wildcard address, which (with the default
+/// `bindv6only=0` on Linux and macOS) accepts both IPv4 and IPv6 connections.
+/// Windows sockets are IPv6-only by default, so there, and whenever IPv6 is unavailable,
+/// the agent listens on IPv4 only.
+fn bind_listener(port: u16) -> TcpListener {
+    if cfg!(unix)
+        && let Ok(listener) = TcpListener::bind((Ipv6Addr::UNSPECIFIED, port))
+    {
+        println!("Listening on [::]:{port}");
+        return listener;
+    }
+    let addr = format!("0.0.0.0:{port}");
+    let listener =
+        TcpListener::bind(&addr).unwrap_or_else(|_| panic!("Failed to bind port {addr}"));
+    println!("Listening on {addr}");
+    listener
+}
+
+/// PID file of the running service, removed again on drop.
+struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Writes the current process ID to `path`.
+    ///
+    /// The ID is written to a temporary file next to `path` first and then renamed into place,
+    /// so readers never see a partially written file.
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, process::id().to_string())?;
+        if let Err(e) = fs::rename(&tmp_path, path) {
+            drop(fs::remove_file(&tmp_path));
+            return Err(e);
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            eprintln!("Failed to remove PID file {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Keeps the [`PidFile`] until dropped, like the file itself, but also removes it before the
+/// process exits on SIGTERM or SIGINT, which would otherwise skip all destructors.
+#[cfg(unix)]
+struct PidFileGuard(Arc<Mutex<Option<PidFile>>>);
+
+#[cfg(unix)]
+impl PidFileGuard {
+    /// Blocks SIGTERM and SIGINT and handles them on a thread of their own.
+    ///
+    /// Must be called before any other thread is spawned, as only threads spawned afterwards
+    /// inherit the blocked signals. Otherwise the signals may be delivered to a thread that
+    /// still has the default handling, which ends the process right away.
+    fn remove_on_termination(pid_file: PidFile) -> Self {
+        let pid_file = Arc::new(Mutex::new(Some(pid_file)));
+        let mut signals = SigSet::empty();
+        signals.add(Signal::SIGTERM);
+        signals.add(Signal::SIGINT);
+        if let Err(e) = signals.thread_block() {
+            eprintln!("Failed to block termination signals, the PID file may be left behind: {e}");
+            return Self(pid_file);
+        }
+
+        let for_signals = Arc::clone(&pid_file);
+        thread::spawn(move || match signals.wait() {
+            Ok(signal) => {
+                println!("Received {signal}. Stopping host_agent service.");
+                drop(take(&for_signals));
+                process::exit(0);
+            }
+            Err(e) => eprintln!("Failed to wait for termination signals: {e}"),
+        });
+        Self(pid_file)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for PidFileGuard {
+    fn drop(&mut self) {
+        drop(take(&self.0));
+    }
+}
+
+#[cfg(unix)]
+fn take(pid_file: &Mutex<Option<PidFile>>) -> Option<PidFile> {
+    pid_file
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+}
+
 fn get_os() -> OsType {
     if cfg!(target_os = "linux") {
         OsType::Linux
@@ -130,10 +275,18 @@ fn get_os() -> OsType {
     }
 }
 
+/// Number of times the startup broadcast is attempted before giving up.
+const STARTUP_BROADCAST_ATTEMPTS: u32 = 3;
+/// Delay between failed startup broadcast attempts.
+const STARTUP_BROADCAST_RETRY_DELAY: Duration = Duration::from_millis(500);
+
 fn broadcast_startup(config: &ServiceOptions) {
     let interface = get_default_interface().unwrap_or_else(|| "unknown".to_string());
     let ip_address = get_ip(&interface).unwrap_or_else(|| "unknown".to_string());
-    let mac_address = get_mac(&interface).unwrap_or_else(|| "unknown".to_string());
+    let mac_address = get_macs(&interface)
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| "unknown".to_string());
     let agent_version = VERSION.to_string();
     let timestamp = shuthost_common::unix_time_seconds();
     let broadcast = BroadcastMessage::AgentStartup(StartupBroadcast {
@@ -157,19 +310,42 @@ fn broadcast_startup(config: &ServiceOptions) {
             .as_ref()
             .expect("Shared secret should be set by now"),
     );
-    match shuthost_common::create_broadcast_socket(0) {
-        Ok(socket) => {
-            let broadcast_addr = format!("255.255.255.255:{}", config.broadcast_port);
-            if let Err(e) = socket.send_to(signed_message.as_bytes(), &broadcast_addr) {
-                eprintln!("Failed to send startup broadcast: {e}");
-            } else {
+    let broadcast_addr = format!("255.255.255.255:{}", config.broadcast_port);
+    // The network may not be ready yet when the agent starts during boot, so retry a few times.
+    for attempt in 1..=STARTUP_BROADCAST_ATTEMPTS {
+        let result = shuthost_common::create_broadcast_socket(0)
+            .map_err(|e| format!("Failed to create broadcast socket: {e}"))
+            .and_then(|socket| {
+                socket
+                    .send_to(signed_message.as_bytes(), &broadcast_addr)
+                    .map_err(|e| format!("Failed to send startup broadcast: {e}"))
+            });
+        match result {
+            Ok(_) => {
                 println!("Sent startup broadcast to {broadcast_addr}");
+                return;
+            }
+            Err(e) => {
+                eprintln!("{e} (attempt {attempt}/{STARTUP_BROADCAST_ATTEMPTS})");
+                if attempt < STARTUP_BROADCAST_ATTEMPTS {
+                    thread::sleep(STARTUP_BROADCAST_RETRY_DELAY);
+                }
             }
         }
-        Err(e) => eprintln!("Failed to create broadcast socket: {e}"),
     }
 }
 
+/// How long a shutdown request waits for the shutdown command before replying.
+///
+/// Commands that fail within this window are reported to the coordinator as an error.
+const SHUTDOWN_CONFIRMATION_WINDOW: Duration = Duration::from_secs(2);
+
+/// How long a connection may take to send its request before it is dropped.
+///
+/// Connections are handled one at a time, so a peer that connects but never sends anything
+/// would otherwise block the agent indefinitely.
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Handles a client connection: reads data, invokes handler, writes response, and triggers shutdown if needed.
 /// Returns the action to take after handling the request.
 fn handle_client(mut stream: TcpStream, config: &ServiceOptions) -> Option<CoordinatorMessage> {
@@ -178,6 +354,9 @@ fn handle_client(mut stream: TcpStream, config: &ServiceOptions) -> Option<Coord
         .peer_addr()
         .map(|a| a.to_string())
         .unwrap_or_to_string("unknown");
+    if let Err(e) = stream.set_read_timeout(Some(REQUEST_READ_TIMEOUT)) {
+        eprintln!("Failed to set read timeout ({peer_addr}): {e}");
+    }
     match stream.read(&mut buffer) {
         Ok(size) => {
             let Some(data) = buffer.get(..size) else {
@@ -185,6 +364,7 @@ fn handle_client(mut stream: TcpStream, config: &ServiceOptions) -> Option<Coord
             };
             use CoordinatorMessage as M;
             let result = validate_request(data, config);
+            let mut pending_shutdown = None;
             let (response_bytes, action) = match result {
                 Ok(M::Status) => {
                     let mut fields = vec![
@@ -200,23 +380,50 @@ fn handle_client(mut stream: TcpStream, config: &ServiceOptions) -> Option<Coord
                         None,
                     )
                 }
-                Ok(M::Shutdown) => (
-                    format!(
-                        "Now executing command: {}. Hopefully goodbye.",
+                Ok(M::Shutdown) => {
+                    println!(
+                        "Shutdown requested. Executing shutdown command {}... ",
                         config.shutdown_command
-                    )
-                    .into_bytes(),
-                    Some(M::Shutdown),
-                ),
+                    );
+                    // Failures usually show right away (e.g. command not found), so they can still
+                    // be reported. Successful commands may take the host down before they exit.
+                    let started = ShutdownCommand::spawn(config).and_then(|mut command| {
+                        let finished = command.wait_for(SHUTDOWN_CONFIRMATION_WINDOW)?;
+                        Ok((!finished).then_some(command))
+                    });
+                    match started {
+                        Ok(running) => {
+                            pending_shutdown = running;
+                            (
+                                format!(
+                                    "Now executing command: {}. Hopefully goodbye.",
+                                    config.shutdown_command
+                                )
+                                .into_bytes(),
+                                Some(M::Shutdown),
+                            )
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to execute shutdown command: {e}");
+                            (format!("ERROR: {e}").into_bytes(), None)
+                        }
+                    }
+                }
                 Ok(M::Abort) => (b"OK: aborting service".to_vec(), Some(M::Abort)),
                 Err(msg) => {
                     eprintln!("Validation error from {peer_addr}: {msg}");
-                    (msg.as_bytes().to_vec(), None)
+                    (format!("ERROR: {msg}").into_bytes(), None)
                 }
             };
             if let Err(e) = stream.write_all(&response_bytes) {
                 eprintln!("Failed to write response to stream ({peer_addr}): {e}");
             }
+            drop(stream);
+            if let Some(mut command) = pending_shutdown
+                && let Err(e) = command.wait_for(Duration::MAX)
+            {
+                eprintln!("Failed to execute shutdown command: {e}");
+            }
             action
         }
         Err(e) => {
@@ -238,16 +445,17 @@ pub(crate) fn get_default_shutdown_command() -> String {
     #[cfg(target_os = "macos")]
     return "shutdown -h now".to_string();
     #[cfg(target_os = "windows")]
-    return "shutdown /s /t 0".to_string();
+    return "Stop-Computer -Force".to_string();
 }
 
 #[cfg(test)]
 mod tests {
+    use std::env;
     use std::io::{Read as _, Write as _};
     use std::net::{TcpListener, TcpStream};
     use std::thread;
 
-    use secrecy::SecretString;
+    use secrecy::{ExposeSecret, SecretString};
     use shuthost_common::create_signed_message;
 
     use super::*;
@@ -261,6 +469,11 @@ mod tests {
             hostname: "test_hostname".to_string(),
             init_system: InitSystem::SelfExtractingShell,
             script_path: None,
+            hmac_tolerance_secs: shuthost_common::ALLOWED_WINDOW,
+            shutdown_command_timeout_secs: None,
+            pid_file: None,
+            #[cfg(feature = "mdns")]
+            mdns_announce: false,
         }
     }
 
@@ -275,6 +488,15 @@ mod tests {
         // shutdown_command and hostname have reasonable defaults but we don't assert them here.
     }
 
+    #[test]
+    fn service_options_accept_shared_secret_argument() {
+        let opts = ServiceOptions::parse_from(["shuthost_host_agent", "--shared-secret", "s3cret"]);
+        assert_eq!(
+            opts.shared_secret.as_ref().map(ExposeSecret::expose_secret),
+            Some("s3cret")
+        );
+    }
+
     #[test]
     fn service_options_custom_ports() {
         let opts = ServiceOptions::parse_from([
@@ -288,6 +510,18 @@ mod tests {
         assert_eq!(opts.broadcast_port, 4321);
     }
 
+    #[test]
+    fn pid_file_contains_process_id_and_is_removed_on_drop() {
+        let path = env::temp_dir().join(format!("shuthost_pid_file_{}.pid", process::id()));
+        let pid_file = PidFile::create(&path).expect("write PID file");
+        assert_eq!(
+            fs::read_to_string(&path).expect("read PID file"),
+            process::id().to_string()
+        );
+        drop(pid_file);
+        assert!(!path.exists(), "PID file should be removed on drop");
+    }
+
     #[test]
     fn status_response_includes_extended_info() {
         let secret = SecretString::from("secret");
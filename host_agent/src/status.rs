@@ -0,0 +1,101 @@
+//! Queries a running `host_agent` with an HMAC-signed status request.
+//!
+//! Useful for health-check scripts and for debugging deployments without crafting requests by hand.
+
+use core::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+use std::{
+    env,
+    io::{Read as _, Write as _},
+    net::TcpStream,
+};
+
+use clap::Parser;
+use secrecy::SecretString;
+use shuthost_common::{CoordinatorMessage, ResultMapErrExt as _, create_signed_message};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// IP address of the agent to query.
+    #[arg(long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    pub ip: IpAddr,
+
+    /// TCP port the agent listens on.
+    #[arg(long, short, default_value_t = shuthost_common::DEFAULT_AGENT_TCP_PORT)]
+    pub port: u16,
+
+    /// Shared secret of the agent.
+    /// Falls back to the `SHUTHOST_SHARED_SECRET` environment variable when omitted.
+    #[arg(long, short)]
+    pub secret: Option<String>,
+}
+
+/// Sends a status request to the agent described by `args` and returns its response.
+///
+/// # Errors
+///
+/// Returns an error if no secret is available, the agent cannot be reached, or it rejects the request.
+pub fn query(args: &Args) -> Result<String, String> {
+    let secret = match args.secret {
+        Some(ref secret) => SecretString::from(secret.as_str()),
+        None => SecretString::from(env::var("SHUTHOST_SHARED_SECRET").map_err(|_| {
+            "No secret given; pass --secret or set SHUTHOST_SHARED_SECRET".to_string()
+        })?),
+    };
+    let addr = SocketAddr::new(args.ip, args.port);
+
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)
+        .map_err_to_string(&format!("Failed to connect to {addr}"))?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .map_err_to_string_simple()?;
+    let signed_message = create_signed_message(&CoordinatorMessage::Status.to_string(), &secret);
+    stream
+        .write_all(signed_message.as_bytes())
+        .map_err_to_string(&format!("Failed to send status request to {addr}"))?;
+
+    let mut buf = [0u8; 1024];
+    let n = stream
+        .read(&mut buf)
+        .map_err_to_string(&format!("Failed to read response from {addr}"))?;
+    let response = String::from_utf8_lossy(buf.get(..n).unwrap_or_default())
+        .trim()
+        .to_string();
+
+    if response.starts_with("OK: status") {
+        Ok(response)
+    } else if response.is_empty() {
+        Err(format!(
+            "Agent at {addr} closed the connection without a response"
+        ))
+    } else {
+        Err(format!("Agent at {addr} rejected the request: {response}"))
+    }
+}
+
+/// Extracts the `agent_version` field from a status response, if the agent reported one.
+pub(crate) fn parse_agent_version(response: &str) -> Option<&str> {
+    response
+        .split(';')
+        .find_map(|field| field.trim().strip_prefix("agent_version="))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_agent_version_extracts_field() {
+        let response = "OK: status;agent_version=1.2.3; init_system=systemd; os=linux";
+        assert_eq!(parse_agent_version(response), Some("1.2.3"));
+    }
+
+    #[test]
+    fn parse_agent_version_missing_for_old_agents() {
+        assert_eq!(parse_agent_version("OK: status"), None);
+    }
+}
@@ -6,7 +6,7 @@
 use core::str::{self, FromStr as _};
 
 use crate::server::ServiceOptions;
-use shuthost_common::{CoordinatorMessage, validate_hmac_message};
+use shuthost_common::{CoordinatorMessage, validate_hmac_message_with_tolerance};
 
 /// Parses incoming bytes, validates HMAC-signed commands, and returns the action to take or an error.
 ///
@@ -22,7 +22,7 @@ use shuthost_common::{CoordinatorMessage, validate_hmac_message};
 ///
 /// # Errors
 ///
-/// For validation or parsing errors.
+/// For validation or parsing errors, with the reason to report back to the peer.
 ///
 /// # Panics
 ///
@@ -49,25 +49,33 @@ use shuthost_common::{CoordinatorMessage, validate_hmac_message};
 pub fn validate_request(
     data: &[u8],
     config: &ServiceOptions,
-) -> Result<CoordinatorMessage, &'static str> {
+) -> Result<CoordinatorMessage, String> {
     let Ok(data_str) = str::from_utf8(data) else {
-        return Err("Invalid UTF-8");
+        return Err("Invalid UTF-8".to_string());
     };
 
-    match validate_hmac_message(
+    match validate_hmac_message_with_tolerance(
         data_str,
         config.shared_secret.as_ref().expect("Should be set by now"),
+        config.hmac_tolerance_secs,
     ) {
         shuthost_common::HmacValidationResult::Valid(command) => {
             use CoordinatorMessage as M;
             let Ok(msg): Result<M, _> = CoordinatorMessage::from_str(&command) else {
-                return Err("Invalid command");
+                return Err(format!("unknown command '{command}'"));
             };
             Ok(msg)
         }
-        shuthost_common::HmacValidationResult::InvalidTimestamp => Err("Timestamp out of range"),
-        shuthost_common::HmacValidationResult::InvalidHmac => Err("Invalid HMAC signature"),
-        shuthost_common::HmacValidationResult::MalformedMessage => Err("Invalid request format"),
+        shuthost_common::HmacValidationResult::InvalidTimestamp => {
+            Err("Timestamp out of range".to_string())
+        }
+        shuthost_common::HmacValidationResult::InvalidHmac => {
+            Err("Invalid HMAC signature".to_string())
+        }
+        shuthost_common::HmacValidationResult::MalformedMessage => {
+            Err("Invalid request format".to_string())
+        }
+        shuthost_common::HmacValidationResult::Replayed => Err("Replayed request".to_string()),
     }
 }
 
@@ -87,6 +95,11 @@ mod tests {
             hostname: "test_hostname".to_string(),
             init_system: InitSystem::SelfExtractingShell,
             script_path: None,
+            hmac_tolerance_secs: shuthost_common::ALLOWED_WINDOW,
+            shutdown_command_timeout_secs: None,
+            pid_file: None,
+            #[cfg(feature = "mdns")]
+            mdns_announce: false,
         }
     }
 
@@ -95,7 +108,7 @@ mod tests {
         let args = make_args(SecretString::from("s"));
         let data = [0xff, 0xfe, 0xfd];
         let result = validate_request(&data, &args);
-        assert_eq!(result, Err("Invalid UTF-8"));
+        assert_eq!(result, Err("Invalid UTF-8".to_string()));
     }
 
     #[test]
@@ -126,13 +139,45 @@ mod tests {
         assert_eq!(result, Ok(CoordinatorMessage::Abort));
     }
 
+    #[test]
+    fn handle_unknown_command() {
+        let secret = SecretString::from("sec");
+        let args = make_args(secret.clone());
+        let signed = shuthost_common::create_signed_message("reboot", &secret);
+        let result = validate_request(signed.as_bytes(), &args);
+        assert_eq!(result, Err("unknown command 'reboot'".to_string()));
+    }
+
     #[test]
     fn handle_invalid_timestamp() {
         let secret = SecretString::from("s");
         let args = make_args(secret);
         let data = "0|cmd|signature".to_string();
         let result = validate_request(data.as_bytes(), &args);
-        assert_eq!(result, Err("Timestamp out of range"));
+        assert_eq!(result, Err("Timestamp out of range".to_string()));
+    }
+
+    #[test]
+    fn hmac_tolerance_accepts_skewed_clock() {
+        let secret = SecretString::from("s");
+        let mut args = make_args(secret.clone());
+        let message = format!(
+            "{}|status",
+            shuthost_common::unix_time_seconds() - 2 * shuthost_common::ALLOWED_WINDOW
+        );
+        let signed = format!(
+            "{message}|{}",
+            shuthost_common::sign_hmac(&message, &secret)
+        );
+        assert_eq!(
+            validate_request(signed.as_bytes(), &args),
+            Err("Timestamp out of range".to_string())
+        );
+        args.hmac_tolerance_secs = 3 * shuthost_common::ALLOWED_WINDOW;
+        assert_eq!(
+            validate_request(signed.as_bytes(), &args),
+            Ok(CoordinatorMessage::Status)
+        );
     }
 
     #[test]
@@ -141,7 +186,7 @@ mod tests {
         let args = make_args(secret.clone());
         let signed = shuthost_common::create_signed_message("cmd", &secret) + "x";
         let result = validate_request(signed.as_bytes(), &args);
-        assert_eq!(result, Err("Invalid HMAC signature"));
+        assert_eq!(result, Err("Invalid HMAC signature".to_string()));
     }
 
     #[test]
@@ -150,6 +195,6 @@ mod tests {
         let args = make_args(secret);
         let data = "no separators";
         let result = validate_request(data.as_bytes(), &args);
-        assert_eq!(result, Err("Invalid request format"));
+        assert_eq!(result, Err("Invalid request format".to_string()));
     }
 }
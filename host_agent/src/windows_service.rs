@@ -0,0 +1,113 @@
+//! Runs the agent under the Windows Service Control Manager.
+//!
+//! A process started by the SCM has to connect to it via the service dispatcher and report its
+//! status, otherwise the SCM considers the start failed and kills it after a timeout.
+
+use core::time::Duration;
+use std::{
+    ffi::OsString,
+    process,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+};
+
+use crate::{
+    install::BINARY_NAME,
+    server::{self, ServiceOptions},
+};
+
+/// Options to start the agent with, handed over to [`service_main`], which the SCM calls on a
+/// thread of its own without a way to pass them.
+static OPTIONS: Mutex<Option<ServiceOptions>> = Mutex::new(None);
+
+/// Handle to report the service status with, set once [`service_main`] registered the control
+/// handler.
+static STATUS_HANDLE: OnceLock<ServiceStatusHandle> = OnceLock::new();
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Runs the agent as the service started by the SCM.
+///
+/// Falls back to running the agent in the foreground if the process wasn't started by the SCM,
+/// e.g. when invoked from a console for debugging.
+pub(crate) fn run(options: ServiceOptions) {
+    *OPTIONS.lock().unwrap_or_else(PoisonError::into_inner) = Some(options);
+
+    // Only returns once the service stopped.
+    if let Err(e) = service_dispatcher::start(BINARY_NAME, ffi_service_main) {
+        eprintln!(
+            "Failed to connect to the service control manager ({e}), running in the foreground"
+        );
+        if let Some(service_options) = take_options() {
+            server::start_host_agent(service_options);
+        }
+    }
+}
+
+/// Entry point the SCM calls once the dispatcher is connected.
+fn service_main(_arguments: Vec<OsString>) {
+    let handle = match service_control_handler::register(BINARY_NAME, control_handler) {
+        Ok(handle) => handle,
+        Err(e) => {
+            eprintln!("Failed to register the service control handler: {e}");
+            return;
+        }
+    };
+    let _ = STATUS_HANDLE.set(handle);
+
+    set_status(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+    );
+    if let Some(service_options) = take_options() {
+        server::start_host_agent(service_options);
+    }
+    set_status(ServiceState::Stopped, ServiceControlAccept::empty());
+}
+
+/// Handles control requests of the SCM. The agent holds no state that needs flushing, so stopping
+/// just exits the process after reporting it.
+fn control_handler(control: ServiceControl) -> ServiceControlHandlerResult {
+    match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            set_status(ServiceState::Stopped, ServiceControlAccept::empty());
+            process::exit(0);
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    }
+}
+
+/// Reports the service state to the SCM.
+fn set_status(state: ServiceState, controls_accepted: ServiceControlAccept) {
+    let Some(handle) = STATUS_HANDLE.get() else {
+        return;
+    };
+    if let Err(e) = handle.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted,
+        exit_code: ServiceExitCode::NO_ERROR,
+        checkpoint: 0,
+        wait_hint: Duration::ZERO,
+        process_id: None,
+    }) {
+        eprintln!("Failed to report the service status: {e}");
+    }
+}
+
+fn take_options() -> Option<ServiceOptions> {
+    OPTIONS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+}
@@ -8,14 +8,17 @@ set -eu
 print_help() {
         cat <<EOF
 Usage: $0 <take|release|status> <host> [remote_url] [--async]
+       $0 wait <host> <online|offline> <timeout_secs> [remote_url]
 
 Requires: curl, openssl, date, hexdump
 
 Arguments:
-    <take|release|status>   Action to perform (required)
-    <host>                  Target host (required)
-    [remote_url]            Coordinator base URL (optional)
-    [--async]               Perform action asynchronously (optional, can be anywhere; ignored for status)
+    <take|release|status|wait>  Action to perform (required)
+    <host>                      Target host (required)
+    <online|offline>            State to wait for (required for wait)
+    <timeout_secs>              Maximum time to wait, in seconds (required for wait)
+    [remote_url]                Coordinator base URL (optional)
+    [--async]                   Perform action asynchronously (optional, can be anywhere; ignored for status and wait)
 
 Options:
     -h, --help       Show this help message and exit
@@ -25,6 +28,7 @@ Examples:
     $0 release myhost https://coordinator.example.com --async
     $0 --async take myhost
     $0 status myhost
+    $0 take myhost --async && $0 wait myhost online 120 && ssh myhost run_job.sh
 EOF
 }
 
@@ -66,10 +70,40 @@ fi
 
 ACTION="$1"
 TARGET_HOST="$2"
+
+if [ "$ACTION" = "wait" ]; then
+    if [ $# -lt 4 ]; then
+        echo "Error: wait requires the state and a timeout." >&2
+        print_help
+        exit 1
+    fi
+    WAIT_STATE="$3"
+    WAIT_TIMEOUT="$4"
+    case "$WAIT_STATE" in
+        online|offline) ;;
+        *)
+            echo "Error: state must be 'online' or 'offline', got '$WAIT_STATE'." >&2
+            exit 1
+            ;;
+    esac
+    case "$WAIT_TIMEOUT" in
+        ''|*[!0-9]*)
+            echo "Error: timeout must be a number of seconds, got '$WAIT_TIMEOUT'." >&2
+            exit 1
+            ;;
+    esac
+    # Drop the wait arguments, so the remote URL is the third argument as for the other actions
+    shift 2
+fi
+
 REMOTE_URL="${3:-"{embedded_remote_url}"}"
 
 # Build coordinator URL depending on action
-if [ "$ACTION" = "status" ]; then
+if [ "$ACTION" = "wait" ]; then
+    if [ "$ASYNC_MODE" = true ]; then
+        echo "Warning: --async is ignored for the wait action" >&2
+    fi
+elif [ "$ACTION" = "status" ]; then
     if [ "$ASYNC_MODE" = true ]; then
         echo "Warning: --async is ignored for the status action" >&2
     fi
@@ -88,15 +122,54 @@ SECRET="{shared_secret}"
 
 ################## Boring setup complete ------------- Interesting stuff is starting here
 
-# Get current timestamp (UTC)
-TIMESTAMP=$(date -u +%s)
+# Prints the X-Request header for the given action: "<timestamp>|<action>|<signature>"
+sign_request() {
+    # Get current timestamp (UTC)
+    TIMESTAMP=$(date -u +%s)
+
+    # Build the message and signature
+    MESSAGE="${TIMESTAMP}|$1"
+    SIGNATURE=$(printf "%s" "$MESSAGE" | openssl dgst -sha256 -hmac "$SECRET" -binary | hexdump -ve '/1 "%02x"') || return 1
+
+    printf "%s|%s" "$MESSAGE" "$SIGNATURE"
+}
+
+# Polls the state of all hosts every 2 seconds until a host reaches the given state.
+# Usage: wait_for_host_state HOSTNAME online|offline TIMEOUT_SECS
+# Returns non-zero if the timeout expires, the host is unknown, or a request fails.
+wait_for_host_state() {
+    DEADLINE=$(( $(date -u +%s) + $3 ))
+    while :; do
+        X_REQUEST=$(sign_request status) || return 1
+        HOSTS_STATUS=$(curl -sS --fail-with-body "${REMOTE_URL}/api/m2m/hosts_status?format=legacy" \
+          -H "X-Client-ID: $CLIENT_ID" \
+          -H "X-Request: $X_REQUEST") || return 1
+        case "$HOSTS_STATUS" in
+            *"\"$1\":\"$2\""*)
+                return 0
+                ;;
+            *"\"$1\":"*)
+                ;;
+            *)
+                echo "Error: unknown host '$1'." >&2
+                return 1
+                ;;
+        esac
+        if [ "$(date -u +%s)" -ge "$DEADLINE" ]; then
+            echo "Error: $1 did not become $2 within $3 seconds." >&2
+            return 1
+        fi
+        sleep 2
+    done
+}
 
-# Build the message and signature
-MESSAGE="${TIMESTAMP}|${ACTION}"
-SIGNATURE=$(printf "%s" "$MESSAGE" | openssl dgst -sha256 -hmac "$SECRET" -binary | hexdump -ve '/1 "%02x"')
+if [ "$ACTION" = "wait" ]; then
+    wait_for_host_state "$TARGET_HOST" "$WAIT_STATE" "$WAIT_TIMEOUT"
+    echo "$TARGET_HOST is $WAIT_STATE"
+    exit 0
+fi
 
-# Combine into final X-Request header
-X_REQUEST="${TIMESTAMP}|${ACTION}|${SIGNATURE}"
+X_REQUEST=$(sign_request "$ACTION")
 
 # set -xv
 # Make the request
@@ -18,6 +18,7 @@ use std::{
     io::Write as _,
     net::{TcpListener as StdTcpListener, TcpStream as StdTcpStream},
     path::Path,
+    sync::{Mutex as StdMutex, PoisonError},
     thread,
     time::Instant,
 };
@@ -26,7 +27,7 @@ use axum::{Router, body::Bytes, http::HeaderMap, http::StatusCode, routing::post
 use clap::Parser as _;
 use secrecy::SecretString;
 use shuthost_common::CoordinatorMessage;
-use shuthost_coordinator::cli::Cli as CoordinatorCli;
+use shuthost_coordinator::cli::{CONFIG_PATH_ENV, Cli as CoordinatorCli};
 use shuthost_host_agent::Cli as AgentCli;
 use tokio::{
     io::{AsyncReadExt as _, AsyncWriteExt as _},
@@ -132,26 +133,38 @@ pub(crate) fn spawn_coordinator_with_config(port: u16, config_toml: &str) -> Kil
     spawn_coordinator_with_config_file(&tmp, port)
 }
 
+/// Serializes setting the config path env var and parsing the CLI across concurrent tests.
+static CONFIG_PATH_ENV_LOCK: StdMutex<()> = StdMutex::new(());
+
 /// Spawn the coordinator service from a given config file path.
+///
+/// The path is passed via [`CONFIG_PATH_ENV`] rather than `--config`, which also covers the
+/// env var fallback of the CLI.
 pub(crate) fn spawn_coordinator_with_config_file(
     config_path: &Path,
     broadcast_port: u16,
 ) -> KillOnDrop {
-    let cli = CoordinatorCli::parse_from([
-        "shuthost_coordinator",
-        "control-service",
-        "--log-format",
-        "pretty",
-        "--config",
-        config_path.to_str().unwrap(),
-        "--broadcast-port",
-        &broadcast_port.to_string(),
-    ]);
-    let handle = tokio::spawn(async move {
-        // SAFETY: This is only used in integration tests and no user-facing code. It just tells the coordinator to log less verbose output.
+    let cli = {
+        let _guard = CONFIG_PATH_ENV_LOCK
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // SAFETY: Only integration tests set this variable, and all of them hold the lock
+        // while setting and reading it.
         unsafe {
-            env::set_var("SHUTHOST_INTEGRATION_TEST", "1");
+            env::set_var(CONFIG_PATH_ENV, config_path);
         }
+        CoordinatorCli::parse_from([
+            "shuthost_coordinator",
+            "control-service",
+            "--log-format",
+            "pretty",
+            "--log-level",
+            "error",
+            "--broadcast-port",
+            &broadcast_port.to_string(),
+        ])
+    };
+    let handle = tokio::spawn(async move {
         shuthost_coordinator::inner_main(cli)
             .await
             .expect("inner_main failed");
@@ -251,7 +264,8 @@ pub(crate) async fn wait_for_agent_ready(
 }
 
 /// Wait until the coordinator reports the specified host in the expected state.
-/// Polls the /`api/hosts_status` endpoint until the host reaches the desired state or timeout.
+/// Polls the legacy format of the /`api/hosts_status` endpoint until the host reaches the desired
+/// state or timeout.
 pub(crate) async fn wait_for_host_state(
     coord_port: u16,
     host_name: &str,
@@ -259,7 +273,7 @@ pub(crate) async fn wait_for_host_state(
     max_attempts: usize,
 ) -> bool {
     let client = reqwest::Client::new();
-    let status_url = format!("http://127.0.0.1:{coord_port}/api/hosts_status");
+    let status_url = format!("http://127.0.0.1:{coord_port}/api/hosts_status?format=legacy");
 
     for _ in 0..max_attempts {
         let resp = client.get(&status_url).send().await;
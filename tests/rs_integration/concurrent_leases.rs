@@ -0,0 +1,162 @@
+//! Integration tests for concurrent M2M lease operations on the same host.
+
+use core::{fmt::Write as _, time::Duration};
+use std::{env, fs, path::Path};
+
+use reqwest::Client;
+use secrecy::SecretString;
+use shuthost_common::create_signed_message;
+use shuthost_coordinator::app::HostState;
+use tokio::time;
+
+use crate::common::{
+    get_free_port, runtime_test_config, spawn_coordinator_with_config, spawn_host_agent,
+    wait_for_agent_ready, wait_for_host_state, wait_for_listening,
+};
+
+const CLIENTS: [(&str, &str); 2] = [("client-a", "secret-a"), ("client-b", "secret-b")];
+
+/// Runs the lease `action` for all [`CLIENTS`] at the same time, without waiting for the host.
+async fn lease_concurrently(coord_port: u16, host: &str, action: &'static str) {
+    let requests = CLIENTS.map(|(client_id, client_secret)| {
+        let url = format!("http://127.0.0.1:{coord_port}/api/m2m/lease/{host}/{action}?async=true");
+        let signed = create_signed_message(action, &SecretString::from(client_secret));
+        tokio::spawn(async move {
+            let resp = Client::new()
+                .post(&url)
+                .header("X-Client-ID", client_id)
+                .header("X-Request", signed)
+                .send()
+                .await
+                .expect("failed to send lease request");
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            assert!(
+                status.is_success(),
+                "{client_id} failed to {action} lease with status {status}: {body}"
+            );
+        })
+    });
+    for request in requests {
+        request.await.expect("lease request task panicked");
+    }
+}
+
+/// Returns the IDs of the clients holding a lease on `host`, sorted.
+async fn client_leases(coord_port: u16, host: &str) -> Vec<String> {
+    let leases: Vec<serde_json::Value> = Client::new()
+        .get(format!("http://127.0.0.1:{coord_port}/api/leases/{host}"))
+        .send()
+        .await
+        .expect("failed to list host leases")
+        .json()
+        .await
+        .expect("host leases should be JSON");
+    let mut clients: Vec<String> = leases
+        .iter()
+        .filter(|lease| lease["type"] == "Client")
+        .filter_map(|lease| lease["value"].as_str().map(ToOwned::to_owned))
+        .collect();
+    clients.sort();
+    clients
+}
+
+async fn wait_for_file(path: &Path, timeout: Duration) -> bool {
+    let start = time::Instant::now();
+    while start.elapsed() < timeout {
+        if path.exists() {
+            return true;
+        }
+        time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn m2m_concurrent_leases_from_multiple_clients() {
+    let coord_port = get_free_port();
+    let agent_port = get_free_port();
+    let agent_id = "testhost";
+    let agent_secret = "testsecret";
+
+    let clients_config =
+        CLIENTS
+            .iter()
+            .fold(String::new(), |mut config, &(client_id, client_secret)| {
+                writeln!(
+                    config,
+                    r#"
+        [clients."{client_id}"]
+        shared_secret = "{client_secret}""#
+                )
+                .unwrap();
+                config
+            });
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts."{agent_id}"]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "{agent_secret}"
+        shutdown_timeout_secs = 3
+    "#
+        ) + &clients_config
+            + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    // The agent records shutdown requests instead of shutting anything down.
+    let shutdown_file = env::temp_dir().join(format!("concurrent_leases_{agent_port}.tmp"));
+    drop(fs::remove_file(&shutdown_file));
+    let _agent_guard = {
+        let agent = spawn_host_agent(
+            agent_secret,
+            agent_port,
+            agent_port,
+            &format!("echo STOP > {}", shutdown_file.display()),
+        );
+        wait_for_agent_ready(agent_port, &SecretString::from(agent_secret), 5).await;
+        agent
+    };
+    assert!(
+        wait_for_host_state(coord_port, agent_id, HostState::Online, 10).await,
+        "Host should be online before taking leases"
+    );
+
+    lease_concurrently(coord_port, agent_id, "take").await;
+    assert_eq!(
+        client_leases(coord_port, agent_id).await,
+        ["client-a", "client-b"]
+    );
+
+    // Give the coordinator a few poll cycles to act on the leases; it must keep the host up.
+    time::sleep(Duration::from_secs(2)).await;
+    assert!(
+        wait_for_host_state(coord_port, agent_id, HostState::Online, 1).await,
+        "Host should stay online while leases are held"
+    );
+    assert!(
+        !shutdown_file.exists(),
+        "Host must not be shut down while leases are held"
+    );
+
+    lease_concurrently(coord_port, agent_id, "release").await;
+    assert!(
+        client_leases(coord_port, agent_id).await.is_empty(),
+        "All leases should be released"
+    );
+
+    let shut_down = wait_for_file(&shutdown_file, Duration::from_secs(5)).await;
+    drop(fs::remove_file(&shutdown_file));
+    assert!(
+        shut_down,
+        "Host should be shut down after all leases are released"
+    );
+}
@@ -12,6 +12,7 @@ use crate::common::{
     TEST_ENFORCE_THRESHOLD_SECS, get_free_port, runtime_test_config, spawn_coordinator_with_config,
     spawn_host_agent, wait_for_agent_ready, wait_for_host_state, wait_for_listening,
 };
+use reqwest::Client;
 use secrecy::SecretString;
 use shuthost_coordinator::app::HostState;
 use tokio::time;
@@ -85,3 +86,81 @@ async fn no_enforce_state_does_not_shutdown_manual_online_host() {
         "shutdown file must NOT be created when enforce_state=false"
     );
 }
+
+#[tokio::test]
+async fn enforce_state_shuts_down_promptly_on_web_lease_release() {
+    let coord_port = get_free_port();
+    let agent_port = get_free_port();
+    let secret = "secret123";
+
+    let config = format!(
+        r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.foo]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "{secret}"
+        enforce_state = true
+
+        [clients]
+        "#
+    ) + &runtime_test_config();
+
+    let _coord = spawn_coordinator_with_config(coord_port, &config);
+    wait_for_listening(coord_port, 5).await;
+
+    let shutdown_file = env::temp_dir().join(format!("enforce_state_web_{agent_port}.tmp"));
+    drop(fs::remove_file(&shutdown_file));
+
+    // Take the lease synchronously before the agent is up, so the enforcer never sees it unleased.
+    let take_url = format!("http://127.0.0.1:{coord_port}/api/lease/foo/take?async=false");
+    let take_req = tokio::spawn(async move {
+        let resp = Client::new()
+            .post(&take_url)
+            .send()
+            .await
+            .expect("failed to take lease");
+        assert!(resp.status().is_success(), "sync take should succeed");
+        resp.text().await.unwrap()
+    });
+
+    let _agent = spawn_host_agent(
+        secret,
+        agent_port,
+        shuthost_common::DEFAULT_COORDINATOR_BROADCAST_PORT,
+        &format!("echo STOP > {}", shutdown_file.display()),
+    );
+    wait_for_agent_ready(agent_port, &SecretString::from(secret), 5).await;
+
+    let body = take_req.await.unwrap();
+    assert_eq!(body, "Lease taken, host is now online");
+    assert!(!shutdown_file.exists(), "leased host must not be shut down");
+
+    let resp = Client::new()
+        .post(format!(
+            "http://127.0.0.1:{coord_port}/api/lease/foo/release"
+        ))
+        .send()
+        .await
+        .expect("failed to release lease");
+    assert!(resp.status().is_success());
+    assert_eq!(resp.text().await.unwrap(), "Lease released (async)");
+
+    // The release must trigger a shutdown right away, well before the enforcer's threshold.
+    let shut_down = time::timeout(Duration::from_secs(TEST_ENFORCE_THRESHOLD_SECS), async {
+        while !shutdown_file.exists() {
+            time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .is_ok();
+    drop(fs::remove_file(&shutdown_file));
+    assert!(
+        shut_down,
+        "releasing the last web lease should shut the host down promptly"
+    );
+}
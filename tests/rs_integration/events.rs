@@ -0,0 +1,149 @@
+//! Integration tests for the Server-Sent Events stream.
+
+use core::time::Duration;
+
+use reqwest::{Client, Response, StatusCode, header};
+use shuthost_coordinator::{WsMessage, app::HostState};
+use tokio::time;
+
+use crate::{
+    common::{
+        get_free_port, runtime_test_config, spawn_coordinator_with_config,
+        spawn_host_agent_default, wait_for_listening,
+    },
+    login_error_redirects::spawn_coordinator_with_token,
+};
+
+/// Reads events from an SSE response, buffering partially received ones.
+struct EventReader {
+    resp: Response,
+    buffer: String,
+}
+
+impl EventReader {
+    const fn new(resp: Response) -> Self {
+        Self {
+            resp,
+            buffer: String::new(),
+        }
+    }
+
+    /// Returns the name and data of the next event, skipping keep-alive comments.
+    async fn next(&mut self) -> (String, String) {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let raw: String = self.buffer.drain(..end + 2).collect();
+                let mut name = String::new();
+                let mut data = String::new();
+                for line in raw.lines() {
+                    if let Some(value) = line.strip_prefix("event:") {
+                        value.trim_start().clone_into(&mut name);
+                    } else if let Some(value) = line.strip_prefix("data:") {
+                        data.push_str(value.trim_start());
+                    }
+                }
+                if !name.is_empty() {
+                    return (name, data);
+                }
+                continue;
+            }
+            let chunk = self
+                .resp
+                .chunk()
+                .await
+                .expect("failed to read SSE stream")
+                .expect("SSE stream ended unexpectedly");
+            self.buffer.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    }
+
+    /// Waits for a `host_status` event in which `host` has the given state.
+    async fn wait_for_host_state(&mut self, host: &str, state: HostState) {
+        loop {
+            let (name, data) = self.next().await;
+            if name != "host_status" {
+                continue;
+            }
+            let msg: WsMessage =
+                serde_json::from_str(&data).expect("host_status data should be a WsMessage");
+            match msg {
+                WsMessage::HostStatus { status, .. } if status.get(host) == Some(&state) => return,
+                WsMessage::HostStatus { .. } => {}
+                _ => panic!("host_status event carried a different message: {data}"),
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn sse_host_status_changes() {
+    let coord_port = get_free_port();
+    let agent_port = get_free_port();
+    let shared_secret = "testsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.testhost]
+        ip = "127.0.0.1"
+        mac = "00:11:22:33:44:55"
+        port = {agent_port}
+        shared_secret = "{shared_secret}"
+
+        [clients]
+    "#
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let resp = Client::new()
+        .get(format!("http://127.0.0.1:{coord_port}/api/events"))
+        .send()
+        .await
+        .expect("failed to open SSE stream");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok()),
+        Some("text/event-stream")
+    );
+    let mut events = EventReader::new(resp);
+
+    // The stream starts with the current status.
+    time::timeout(
+        Duration::from_secs(5),
+        events.wait_for_host_state("testhost", HostState::Offline),
+    )
+    .await
+    .expect("Timeout waiting for the initial host_status event");
+
+    let _agent = spawn_host_agent_default(shared_secret, agent_port);
+
+    time::timeout(
+        Duration::from_secs(10),
+        events.wait_for_host_state("testhost", HostState::Online),
+    )
+    .await
+    .expect("Timeout waiting for host_status event of the host coming online");
+}
+
+#[tokio::test]
+async fn sse_requires_auth() {
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_token(port, "testtoken-events");
+    wait_for_listening(port, 5).await;
+
+    let resp = Client::new()
+        .get(format!("http://127.0.0.1:{port}/api/events"))
+        .header(header::ACCEPT, "text/event-stream")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
@@ -5,7 +5,8 @@ use std::{env, fs as fs_sync, process};
 
 use crate::common::{
     get_free_port, host_agent_bin_path, runtime_test_config, spawn_coordinator_with_config,
-    spawn_host_agent, wait_for_agent_ready, wait_for_host_state, wait_for_listening,
+    spawn_host_agent, spawn_host_agent_default, wait_for_agent_ready, wait_for_host_state,
+    wait_for_listening,
 };
 use secrecy::SecretString;
 use shuthost_coordinator::app::HostState;
@@ -22,6 +23,47 @@ fn host_agent_binary_runs() {
     assert!(status.success());
 }
 
+#[tokio::test]
+async fn status_subcommand_reports_agent() {
+    let agent_port = get_free_port();
+    let shared_secret = "testsecret";
+
+    let _agent = spawn_host_agent_default(shared_secret, agent_port);
+    wait_for_agent_ready(agent_port, &SecretString::from(shared_secret), 5).await;
+
+    let output = process::Command::new(host_agent_bin_path())
+        .args([
+            "status",
+            "--port",
+            &agent_port.to_string(),
+            "--secret",
+            shared_secret,
+        ])
+        .output()
+        .expect("failed to run host_agent status");
+    assert!(output.status.success(), "status should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("is reachable"),
+        "unexpected output: {stdout}"
+    );
+
+    let output = process::Command::new(host_agent_bin_path())
+        .args([
+            "status",
+            "--port",
+            &agent_port.to_string(),
+            "--secret",
+            "wrongsecret",
+        ])
+        .output()
+        .expect("failed to run host_agent status");
+    assert!(
+        !output.status.success(),
+        "status should fail with a wrong secret"
+    );
+}
+
 #[tokio::test]
 async fn shutdown_command_execution() {
     let shutdown_file = env::temp_dir().join("shuthost_shutdown_test");
@@ -88,6 +130,76 @@ async fn shutdown_command_execution() {
     drop(fs::remove_file(shutdown_file).await); // Clean up after test
 }
 
+#[tokio::test]
+#[cfg_attr(coverage, ignore = "Coverage builds send no WoL test packets")]
+async fn host_wol_test_reaches_test_wol_listener() {
+    let coord_port = get_free_port();
+    let wol_test_port = get_free_port();
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.testhost]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        wol_broadcast = "127.0.0.1"
+        port = {agent_port}
+        shared_secret = "testsecret"
+
+        [clients]
+    "#,
+            agent_port = get_free_port()
+        ),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let mut listener = process::Command::new(host_agent_bin_path())
+        .args(["test-wol", "--port", &wol_test_port.to_string()])
+        .stdout(process::Stdio::null())
+        .spawn()
+        .expect("failed to spawn host_agent test-wol");
+    // Give the listener time to bind its socket.
+    time::sleep(Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let result: serde_json::Value = client
+        .post(format!(
+            "http://127.0.0.1:{coord_port}/api/hosts/testhost/test_wol?port={wol_test_port}"
+        ))
+        .send()
+        .await
+        .expect("failed to run WoL test")
+        .json()
+        .await
+        .expect("WoL test result should be JSON");
+    let status = listener.wait().expect("failed to wait for test-wol");
+
+    assert_eq!(
+        result,
+        serde_json::json!({
+            "reachable": true,
+            "broadcast_reached": true,
+            "unicast_reached": true,
+            "error": null,
+        })
+    );
+    assert!(status.success());
+
+    let resp = client
+        .post(format!(
+            "http://127.0.0.1:{coord_port}/api/hosts/unknown/test_wol"
+        ))
+        .send()
+        .await
+        .expect("failed to run WoL test");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
 #[cfg(unix)]
 const SELF_EXTRACTING_SCRIPT: &str = "self-extracting-shell";
 #[cfg(windows)]
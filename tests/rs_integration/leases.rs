@@ -9,8 +9,8 @@ use shuthost_coordinator::app::HostState;
 use tokio::time;
 
 use crate::common::{
-    get_free_port, runtime_test_config, spawn_coordinator_with_config, spawn_host_agent_default,
-    wait_for_agent_ready, wait_for_host_state, wait_for_listening,
+    get_free_port, runtime_test_config, spawn_coordinator_with_config, spawn_host_agent,
+    spawn_host_agent_default, wait_for_agent_ready, wait_for_host_state, wait_for_listening,
 };
 
 #[tokio::test]
@@ -107,9 +107,10 @@ async fn m2m_lease_take_and_release() {
         }
     });
 
-    // without sleeping here it seems to fail
-    // maybe the scheduler doesn't finish the request before the agent was killed.
-    time::sleep(Duration::from_millis(100)).await;
+    // Give the coordinator time to deliver the shutdown command before the agent is killed.
+    // If the agent is already gone, the command fails to connect and the release reports the
+    // failed shutdown instead of waiting for the host to go offline.
+    time::sleep(Duration::from_millis(500)).await;
 
     // Simulate shutdown by killing the agent
     drop(agent_guard);
@@ -294,6 +295,295 @@ async fn api_reset_client_leases() {
     reset_task.await.unwrap();
 }
 
+#[tokio::test]
+async fn reset_all_leases_clears_every_host() {
+    let coord_port = get_free_port();
+    let client_id = "test-client-reset-all";
+    let client_secret = "clientsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.host1]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [hosts.host2]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [hosts.idle]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [clients."{client_id}"]
+        shared_secret = "{client_secret}"
+    "#,
+            port = get_free_port()
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let client = Client::new();
+    let resp = client
+        .post(format!(
+            "http://127.0.0.1:{coord_port}/api/lease/host1/take"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let resp = client
+        .post(format!(
+            "http://127.0.0.1:{coord_port}/api/m2m/lease/host2/take?async=true"
+        ))
+        .header("X-Client-ID", client_id)
+        .header(
+            "X-Request",
+            create_signed_message("take", &SecretString::from(client_secret)),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let resp = client
+        .post(format!("http://127.0.0.1:{coord_port}/api/reset_leases"))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["cleared_hosts"], serde_json::json!(["host1", "host2"]));
+
+    let leases: serde_json::Value = client
+        .get(format!("http://127.0.0.1:{coord_port}/api/leases"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    for host in ["host1", "host2"] {
+        assert!(
+            leases
+                .get(host)
+                .is_none_or(|l| l.as_array().is_some_and(Vec::is_empty)),
+            "{host} should have no leases left: {leases}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn release_client_leases_keeps_other_holders() {
+    let coord_port = get_free_port();
+    let client_id = "test-client-release";
+    let client_secret = "clientsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.host1]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [hosts.host2]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [clients."{client_id}"]
+        shared_secret = "{client_secret}"
+    "#,
+            port = get_free_port()
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let client = Client::new();
+    for host in ["host1", "host2"] {
+        let resp = client
+            .post(format!(
+                "http://127.0.0.1:{coord_port}/api/m2m/lease/{host}/take?async=true"
+            ))
+            .header("X-Client-ID", client_id)
+            .header(
+                "X-Request",
+                create_signed_message("take", &SecretString::from(client_secret)),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+    let resp = client
+        .post(format!(
+            "http://127.0.0.1:{coord_port}/api/lease/host2/take"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let resp = client
+        .delete(format!(
+            "http://127.0.0.1:{coord_port}/api/clients/{client_id}/leases"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body["released_hosts"],
+        serde_json::json!(["host1", "host2"])
+    );
+
+    let leases: serde_json::Value = client
+        .get(format!("http://127.0.0.1:{coord_port}/api/leases"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        leases["host2"],
+        serde_json::json!([{ "type": "WebInterface" }]),
+        "{leases}"
+    );
+    assert!(
+        leases
+            .get("host1")
+            .is_none_or(|l| l.as_array().is_some_and(Vec::is_empty)),
+        "{leases}"
+    );
+}
+
+#[tokio::test]
+async fn list_client_leases_per_client() {
+    let coord_port = get_free_port();
+    // client-a leases both hosts, client-b only host2.
+    let holdings: [(&str, &str, &[&str]); 2] = [
+        ("client-a", "secret-a", &["host1", "host2"]),
+        ("client-b", "secret-b", &["host2"]),
+    ];
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.host1]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [hosts.host2]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [clients.client-a]
+        shared_secret = "secret-a"
+
+        [clients.client-b]
+        shared_secret = "secret-b"
+    "#,
+            port = get_free_port()
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let client = Client::new();
+    let client_leases = async |client_id: &str| {
+        let resp = client
+            .get(format!(
+                "http://127.0.0.1:{coord_port}/api/clients/{client_id}/leases"
+            ))
+            .send()
+            .await
+            .unwrap();
+        let status = resp.status();
+        (status, resp.json::<serde_json::Value>().await.unwrap())
+    };
+
+    for (client_id, client_secret, hosts) in holdings {
+        for host in hosts {
+            let resp = client
+                .post(format!(
+                    "http://127.0.0.1:{coord_port}/api/m2m/lease/{host}/take?async=true"
+                ))
+                .header("X-Client-ID", client_id)
+                .header(
+                    "X-Request",
+                    create_signed_message("take", &SecretString::from(client_secret)),
+                )
+                .send()
+                .await
+                .unwrap();
+            assert!(resp.status().is_success());
+        }
+    }
+
+    assert_eq!(
+        client_leases("client-a").await,
+        (
+            reqwest::StatusCode::OK,
+            serde_json::json!(["host1", "host2"])
+        )
+    );
+    assert_eq!(
+        client_leases("client-b").await,
+        (reqwest::StatusCode::OK, serde_json::json!(["host2"]))
+    );
+
+    let resp = client
+        .delete(format!(
+            "http://127.0.0.1:{coord_port}/api/clients/client-b/leases"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    assert_eq!(
+        client_leases("client-b").await,
+        (reqwest::StatusCode::OK, serde_json::json!([]))
+    );
+    assert_eq!(
+        client_leases("client-a").await.1,
+        serde_json::json!(["host1", "host2"])
+    );
+
+    let (status, body) = client_leases("unknown-client").await;
+    assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "client_not_found");
+}
+
 #[tokio::test]
 async fn m2m_lease_sync_take_timeout_when_host_offline() {
     let coord_port = get_free_port();
@@ -399,16 +689,35 @@ async fn m2m_lease_sync_release_timeout_when_host_online() {
         "Host should be online before triggering shutdown"
     );
 
-    // Try to release a lease synchronously when host is online but no lease exists
-    let release_url = format!("http://127.0.0.1:{coord_port}/api/m2m/lease/{agent_id}/release");
+    let lease_url =
+        |action: &str| format!("http://127.0.0.1:{coord_port}/api/m2m/lease/{agent_id}/{action}");
+    let signed = |action: &str| create_signed_message(action, &SecretString::from(client_secret));
 
-    let signed_message = create_signed_message("release", &SecretString::from(client_secret));
+    // Releasing a lease that is not held changes nothing and does not wait for a shutdown.
+    let resp = Client::new()
+        .post(lease_url("release"))
+        .header("X-Client-ID", client_id)
+        .header("X-Request", signed("release"))
+        .send()
+        .await
+        .expect("Failed to get resp");
+    assert!(resp.status().is_success());
+    assert_eq!(resp.text().await.unwrap(), "Lease not held");
 
-    // Start the release request
     let resp = Client::new()
-        .post(&release_url)
+        .post(lease_url("take") + "?async=true")
         .header("X-Client-ID", client_id)
-        .header("X-Request", signed_message)
+        .header("X-Request", signed("take"))
+        .send()
+        .await
+        .expect("Failed to take lease");
+    assert!(resp.status().is_success());
+
+    // Release the lease synchronously; the agent ignores the shutdown, so this times out.
+    let resp = Client::new()
+        .post(lease_url("release"))
+        .header("X-Client-ID", client_id)
+        .header("X-Request", signed("release"))
         .send()
         .await
         .expect("Failed to get resp");
@@ -419,6 +728,397 @@ async fn m2m_lease_sync_release_timeout_when_host_online() {
             .text()
             .await
             .unwrap_or_else(|_| String::from("(no body)"));
-        panic!("Releasing nonexistent lease succeeded unexpectedly with status {status}: {body}");
+        panic!(
+            "Release on a host that stays online succeeded unexpectedly with status {status}: {body}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn m2m_lease_sync_release_fails_when_shutdown_command_fails() {
+    let coord_port = get_free_port();
+
+    let client_id = "test-client-sync-release-failure";
+    let client_secret = "clientsecret";
+
+    let agent_port = get_free_port();
+    let agent_id = "testhost";
+    let agent_secret = "testsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts."{agent_id}"]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "{agent_secret}"
+        shutdown_timeout_secs = 30
+
+        [clients."{client_id}"]
+        shared_secret = "{client_secret}"
+    "#
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let _agent_guard = {
+        let agent = spawn_host_agent(agent_secret, agent_port, agent_port, "exit 1");
+        wait_for_agent_ready(agent_port, &SecretString::from(agent_secret), 5).await;
+        agent
+    };
+
+    assert!(
+        wait_for_host_state(coord_port, "testhost", HostState::Online, 10).await,
+        "Host should be online before triggering shutdown"
+    );
+
+    let lease_url =
+        |action: &str| format!("http://127.0.0.1:{coord_port}/api/m2m/lease/{agent_id}/{action}");
+    let signed = |action: &str| create_signed_message(action, &SecretString::from(client_secret));
+
+    let resp = Client::new()
+        .post(lease_url("take") + "?async=true")
+        .header("X-Client-ID", client_id)
+        .header("X-Request", signed("take"))
+        .send()
+        .await
+        .expect("Failed to take lease");
+    assert!(resp.status().is_success());
+
+    // The failure is reported well before the 30s shutdown timeout.
+    let resp = time::timeout(
+        Duration::from_secs(15),
+        Client::new()
+            .post(lease_url("release"))
+            .header("X-Client-ID", client_id)
+            .header("X-Request", signed("release"))
+            .send(),
+    )
+    .await
+    .expect("Release should fail before the shutdown timeout")
+    .expect("Failed to get resp");
+
+    assert_eq!(resp.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "operation_failed", "{body}");
+}
+
+#[tokio::test]
+async fn api_list_leases() {
+    let coord_port = get_free_port();
+    let agent_port = get_free_port();
+    let agent_id = "testhost";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts."{agent_id}"]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "testsecret"
+
+        [clients]
+    "#
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let client = Client::new();
+
+    let resp = client
+        .post(format!(
+            "http://127.0.0.1:{coord_port}/api/lease/{agent_id}/take"
+        ))
+        .send()
+        .await
+        .expect("failed to take lease");
+    assert!(resp.status().is_success());
+
+    let all_leases: serde_json::Value = client
+        .get(format!("http://127.0.0.1:{coord_port}/api/leases"))
+        .send()
+        .await
+        .expect("failed to list leases")
+        .json()
+        .await
+        .expect("leases should be JSON");
+    assert_eq!(
+        all_leases,
+        serde_json::json!({ agent_id: [{ "type": "WebInterface" }] })
+    );
+
+    let host_leases: serde_json::Value = client
+        .get(format!(
+            "http://127.0.0.1:{coord_port}/api/leases/{agent_id}"
+        ))
+        .send()
+        .await
+        .expect("failed to list host leases")
+        .json()
+        .await
+        .expect("host leases should be JSON");
+    assert_eq!(host_leases, serde_json::json!([{ "type": "WebInterface" }]));
+
+    let resp = client
+        .get(format!(
+            "http://127.0.0.1:{coord_port}/api/leases/unknownhost"
+        ))
+        .send()
+        .await
+        .expect("failed to query unknown host");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn m2m_lease_take_rejected_over_max_leases() {
+    let coord_port = get_free_port();
+
+    let client_id = "limited-client";
+    let client_secret = "clientsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts."first"]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {first_port}
+        shared_secret = "s1"
+
+        [hosts."second"]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {second_port}
+        shared_secret = "s2"
+
+        [clients."{client_id}"]
+        shared_secret = "{client_secret}"
+        max_leases = 1
+    "#,
+            first_port = get_free_port(),
+            second_port = get_free_port(),
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let take = async |host: &str| {
+        Client::new()
+            .post(format!(
+                "http://127.0.0.1:{coord_port}/api/m2m/lease/{host}/take?async=true"
+            ))
+            .header("X-Client-ID", client_id)
+            .header(
+                "X-Request",
+                create_signed_message("take", &SecretString::from(client_secret)),
+            )
+            .send()
+            .await
+            .expect("failed to take lease")
+    };
+
+    let resp = take("first").await;
+    assert!(resp.status().is_success(), "first lease within limit");
+
+    let resp = take("first").await;
+    assert!(
+        resp.status().is_success(),
+        "re-taking a held lease does not count twice"
+    );
+
+    let resp = take("second").await;
+    assert_eq!(resp.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key("x-request-id"));
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "lease_limit_exceeded");
+
+    let leases: serde_json::Value = Client::new()
+        .get(format!("http://127.0.0.1:{coord_port}/api/leases/second"))
+        .send()
+        .await
+        .expect("failed to get leases")
+        .json()
+        .await
+        .expect("invalid leases json");
+    assert_eq!(
+        leases,
+        serde_json::json!([]),
+        "no lease recorded on rejection"
+    );
+}
+
+#[tokio::test]
+async fn m2m_lease_with_ttl_expires() {
+    let coord_port = get_free_port();
+
+    let client_id = "ttl-client";
+    let client_secret = "clientsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts."ttlhost"]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "s1"
+
+        [clients."{client_id}"]
+        shared_secret = "{client_secret}"
+    "#,
+            agent_port = get_free_port(),
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let resp = Client::new()
+        .post(format!(
+            "http://127.0.0.1:{coord_port}/api/m2m/lease/ttlhost/take?async=true&ttl=1"
+        ))
+        .header("X-Client-ID", client_id)
+        .header(
+            "X-Request",
+            create_signed_message("take", &SecretString::from(client_secret)),
+        )
+        .send()
+        .await
+        .expect("failed to take lease");
+    assert!(resp.status().is_success());
+
+    let get_leases = async || -> serde_json::Value {
+        Client::new()
+            .get(format!("http://127.0.0.1:{coord_port}/api/leases/ttlhost"))
+            .send()
+            .await
+            .expect("failed to get leases")
+            .json()
+            .await
+            .expect("invalid leases json")
+    };
+    assert_eq!(
+        get_leases().await,
+        serde_json::json!([{ "type": "Client", "value": client_id }])
+    );
+
+    let deadline = time::Instant::now() + Duration::from_secs(10);
+    while get_leases().await != serde_json::json!([]) {
+        assert!(time::Instant::now() < deadline, "lease did not expire");
+        time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+#[tokio::test]
+async fn m2m_batch_lease_updates_hosts_together() {
+    let coord_port = get_free_port();
+    let client_id = "batch-client";
+    let client_secret = "clientsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.host1]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [hosts.host2]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [clients."{client_id}"]
+        shared_secret = "{client_secret}"
+    "#,
+            port = get_free_port()
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let client = Client::new();
+    let batch = async |signed_action: &str, action: &str| {
+        client
+            .post(format!("http://127.0.0.1:{coord_port}/api/m2m/batch_lease"))
+            .header("X-Client-ID", client_id)
+            .header(
+                "X-Request",
+                create_signed_message(signed_action, &SecretString::from(client_secret)),
+            )
+            .json(&serde_json::json!({
+                "action": action,
+                "hosts": ["host2", "missing", "host1"],
+            }))
+            .send()
+            .await
+            .unwrap()
+    };
+    let leases = async || -> serde_json::Value {
+        client
+            .get(format!("http://127.0.0.1:{coord_port}/api/leases"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    };
+
+    let resp = batch("take", "take").await;
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "invalid_action", "{body}");
+
+    let resp = batch("batch_take", "take").await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["succeeded"], serde_json::json!(["host1", "host2"]));
+    assert_eq!(
+        body["failed"],
+        serde_json::json!({ "missing": "Host not found: missing" })
+    );
+    let held = serde_json::json!([{ "type": "Client", "value": client_id }]);
+    let current = leases().await;
+    assert_eq!(current["host1"], held, "{current}");
+    assert_eq!(current["host2"], held, "{current}");
+
+    let resp = batch("batch_release", "release").await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["succeeded"], serde_json::json!(["host1", "host2"]));
+    let current = leases().await;
+    for host in ["host1", "host2"] {
+        assert!(
+            current
+                .get(host)
+                .is_none_or(|l| l.as_array().is_some_and(Vec::is_empty)),
+            "{current}"
+        );
     }
 }
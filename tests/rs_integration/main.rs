@@ -13,25 +13,29 @@ extern crate alloc;
 extern crate core;
 
 mod common;
+mod concurrent_leases;
 mod enforce_state;
+mod events;
 mod hooks;
 mod host_agent;
 mod leases;
 mod login_error_redirects;
 mod notifications;
+mod oidc;
 mod token_login;
 mod websocket;
 
 use core::time::Duration;
-use std::{env, fs};
+use std::{collections::HashMap, env, fs};
 
 use secrecy::SecretString;
+use shuthost_common::create_signed_message;
 
-use reqwest::{Client, StatusCode};
+use reqwest::{Client, StatusCode, tls::TlsInfo};
 
 use common::{
-    get_free_port, spawn_coordinator_with_config, spawn_host_agent_default, wait_for_agent_ready,
-    wait_for_host_state, wait_for_listening,
+    get_free_port, runtime_test_config, spawn_coordinator_with_config, spawn_host_agent_default,
+    wait_for_agent_ready, wait_for_host_state, wait_for_listening,
 };
 use shuthost_coordinator::app::HostState;
 use tokio::time;
@@ -93,6 +97,996 @@ async fn coordinator_and_agent_online_status() {
     );
 }
 
+#[tokio::test]
+async fn coordinator_and_agent_online_status_ipv6() {
+    let coord_port = get_free_port();
+    let agent_port = get_free_port();
+    let shared_secret = "testsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.testhost]
+        ip = "::1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "{shared_secret}"
+
+        [clients]
+    "#
+        ),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let _agent = spawn_host_agent_default(shared_secret, agent_port);
+
+    wait_for_agent_ready(agent_port, &SecretString::from(shared_secret), 5).await;
+
+    assert!(
+        wait_for_host_state(coord_port, "testhost", HostState::Online, 10).await,
+        "Host should be online when polled over IPv6"
+    );
+}
+
+#[tokio::test]
+async fn api_host_details() {
+    let coord_port = get_free_port();
+    let agent_port = get_free_port();
+    let shared_secret = "testsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.testhost]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "{shared_secret}"
+        enforce_state = false
+
+        [clients]
+    "#
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:{coord_port}/api/hosts/testhost");
+
+    let details: serde_json::Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(details["hostname"], "testhost");
+    assert_eq!(details["ip"], "127.0.0.1");
+    assert_eq!(details["mac"], serde_json::json!(["disableWOL"]));
+    assert_eq!(details["port"], agent_port);
+    assert_eq!(details["online"], false);
+    assert_eq!(details["enforce_state"], false);
+    assert_eq!(details["leases"], serde_json::json!([]));
+    assert!(details["last_seen"].is_null());
+    assert!(details["agent_version"].is_null());
+
+    let _agent = spawn_host_agent_default(shared_secret, agent_port);
+    wait_for_agent_ready(agent_port, &SecretString::from(shared_secret), 5).await;
+    assert!(
+        wait_for_host_state(coord_port, "testhost", HostState::Online, 10).await,
+        "Host should come online"
+    );
+
+    // The host may have been marked online by its startup broadcast before the next poll.
+    let details = time::timeout(Duration::from_secs(5), async {
+        loop {
+            let details: serde_json::Value =
+                client.get(&url).send().await.unwrap().json().await.unwrap();
+            if details["last_seen"].is_string() {
+                break details;
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("last_seen should be set once the host was polled online");
+    assert_eq!(details["online"], true);
+    assert!(
+        details["agent_version"].is_string(),
+        "agent version is reported in the status reply"
+    );
+
+    let resp = client
+        .get(format!(
+            "http://127.0.0.1:{coord_port}/api/hosts/unknownhost"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn api_manual_wake_and_shutdown_bypass_leases() {
+    let coord_port = get_free_port();
+    let agent_port = get_free_port();
+    let shared_secret = "testsecret";
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.testhost]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "{shared_secret}"
+        enforce_state = false
+
+        [clients]
+    "#
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+    let _agent = spawn_host_agent_default(shared_secret, agent_port);
+    wait_for_agent_ready(agent_port, &SecretString::from(shared_secret), 5).await;
+
+    let client = Client::new();
+    let url = |host: &str, action: &str| {
+        format!("http://127.0.0.1:{coord_port}/api/hosts/{host}/{action}")
+    };
+
+    let resp = client
+        .post(url("unknownhost", "wake"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // WoL is disabled for the host, so there is nothing to send.
+    let resp = client.post(url("testhost", "wake")).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "operation_failed");
+
+    let resp = client
+        .post(url("testhost", "shutdown"))
+        .json(&serde_json::json!({ "confirm": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = client
+        .post(url("testhost", "shutdown"))
+        .json(&serde_json::json!({ "confirm": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.text().await.unwrap(), "Shutdown command sent");
+
+    let leases: serde_json::Value = client
+        .get(format!("http://127.0.0.1:{coord_port}/api/leases/testhost"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(leases, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn api_rotate_host_secret_applies_without_restart() {
+    let coord_port = get_free_port();
+    let agent_port = get_free_port();
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.testhost]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "oldsecret"
+        enforce_state = false
+
+        [clients]
+    "#
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    // The agent was already restarted with the new secret, so the coordinator can't reach it.
+    let _agent = spawn_host_agent_default("newsecret", agent_port);
+    wait_for_agent_ready(agent_port, &SecretString::from("newsecret"), 5).await;
+    assert!(
+        !wait_for_host_state(coord_port, "testhost", HostState::Online, 5).await,
+        "Host should not be reachable with the old secret"
+    );
+
+    let client = Client::new();
+    let url = |host: &str| format!("http://127.0.0.1:{coord_port}/api/hosts/{host}/secret");
+
+    let resp = client
+        .put(url("unknownhost"))
+        .json(&serde_json::json!({ "new_secret": "newsecret" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = client
+        .put(url("testhost"))
+        .json(&serde_json::json!({ "new_secret": "newsecret" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["hostname"], "testhost");
+
+    assert!(
+        wait_for_host_state(coord_port, "testhost", HostState::Online, 10).await,
+        "Host should come online once the coordinator uses the new secret"
+    );
+}
+
+#[tokio::test]
+async fn hosts_status_filters_by_tag() {
+    let coord_port = get_free_port();
+    let agent_port = get_free_port();
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.gpu-box]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "testsecret"
+        tags = ["compute", "gpu"]
+
+        [hosts.cpu-box]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "testsecret"
+        tags = ["compute"]
+
+        [hosts.nas]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "testsecret"
+
+        [clients]
+    "#
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+    // All hosts point at the same agent, so all of them come online.
+    let _agent = spawn_host_agent_default("testsecret", agent_port);
+
+    let client = Client::new();
+    let hosts_for = async |query: &str| {
+        let status: HashMap<String, serde_json::Value> = client
+            .get(format!(
+                "http://127.0.0.1:{coord_port}/api/hosts_status{query}"
+            ))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let mut hosts: Vec<String> = status.into_keys().collect();
+        hosts.sort();
+        hosts
+    };
+
+    // Hosts only show up in the status once they were seen online.
+    time::timeout(Duration::from_secs(10), async {
+        while hosts_for("").await.len() < 3 {
+            time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("all hosts should come online");
+
+    assert_eq!(hosts_for("").await, ["cpu-box", "gpu-box", "nas"]);
+    assert_eq!(hosts_for("?tag=compute").await, ["cpu-box", "gpu-box"]);
+    assert_eq!(hosts_for("?tag=compute&tag=gpu").await, ["gpu-box"]);
+    assert!(hosts_for("?tag=storage").await.is_empty());
+
+    let detailed: serde_json::Value = client
+        .get(format!(
+            "http://127.0.0.1:{coord_port}/api/hosts_status?tag=gpu"
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(detailed["gpu-box"]["state"], "online");
+    assert_eq!(detailed["gpu-box"]["online"], true);
+    assert!(detailed["gpu-box"]["last_seen"].is_string());
+
+    let legacy: serde_json::Value = client
+        .get(format!(
+            "http://127.0.0.1:{coord_port}/api/hosts_status?tag=gpu&format=legacy"
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(legacy, serde_json::json!({ "gpu-box": "online" }));
+
+    let resp = client
+        .get(format!(
+            "http://127.0.0.1:{coord_port}/api/hosts_status?format=xml"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn api_hosts_lists_hosts_without_secrets() {
+    let coord_port = get_free_port();
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.nas]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = 9
+        shared_secret = "nassecret"
+        tags = ["storage"]
+        notes = "In the basement"
+
+        [hosts.backup]
+        ip = "127.0.0.2"
+        mac = "disableWOL"
+        port = 9
+        shared_secret = "backupsecret"
+        enforce_state = true
+
+        [clients]
+    "#
+        ),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let resp = Client::new()
+        .get(format!("http://127.0.0.1:{coord_port}/api/hosts"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = resp.text().await.unwrap();
+    assert!(!body.contains("secret"), "secrets leaked: {body}");
+
+    let hosts: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(hosts[0]["name"], "backup");
+    assert_eq!(hosts[0]["ip"], "127.0.0.2");
+    assert_eq!(hosts[0]["enforce_state"], true);
+    assert_eq!(hosts[0]["online"], false);
+    assert_eq!(hosts[1]["name"], "nas");
+    assert_eq!(hosts[1]["port"], 9);
+    assert_eq!(hosts[1]["tags"], serde_json::json!(["storage"]));
+    assert_eq!(hosts[1]["notes"], "In the basement");
+}
+
+#[tokio::test]
+async fn m2m_hosts_status_requires_hmac() {
+    let coord_port = get_free_port();
+    let client_id = "test-client";
+    let client_secret = SecretString::from("clientsecret");
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+
+        [hosts.testhost]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [clients."{client_id}"]
+        shared_secret = "clientsecret"
+    "#,
+            port = get_free_port()
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:{coord_port}/api/m2m/hosts_status");
+    let web_status: serde_json::Value = client
+        .get(format!("http://127.0.0.1:{coord_port}/api/hosts_status"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let resp = client
+        .get(&url)
+        .header("X-Client-ID", client_id)
+        .header("X-Request", create_signed_message("status", &client_secret))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let status: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(status, web_status);
+
+    let resp = client
+        .get(&url)
+        .header("X-Client-ID", client_id)
+        .header("X-Request", create_signed_message("take", &client_secret))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = client
+        .get(&url)
+        .header("X-Client-ID", client_id)
+        .header(
+            "X-Request",
+            create_signed_message("status", &SecretString::from("wrong")),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client.get(&url).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn m2m_replayed_request_is_rejected() {
+    let coord_port = get_free_port();
+    let client_id = "test-client";
+    let client_secret = SecretString::from("clientsecret");
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+        m2m_replay_protection = true
+
+        [hosts.testhost]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {port}
+        shared_secret = "testsecret"
+
+        [clients."{client_id}"]
+        shared_secret = "clientsecret"
+    "#,
+            port = get_free_port()
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:{coord_port}/api/m2m/hosts_status");
+    let signed = create_signed_message("status", &client_secret);
+    let send = || {
+        client
+            .get(&url)
+            .header("X-Client-ID", client_id)
+            .header("X-Request", &signed)
+            .send()
+    };
+
+    assert_eq!(send().await.unwrap().status(), StatusCode::OK);
+
+    let resp = send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "replayed_request");
+}
+
+#[tokio::test]
+async fn m2m_unsigned_requests_do_not_use_client_rate_limit() {
+    let coord_port = get_free_port();
+    let client_id = "test-client";
+    let client_secret = SecretString::from("clientsecret");
+
+    let _coordinator_child = spawn_coordinator_with_config(
+        coord_port,
+        &(format!(
+            r#"
+        [server]
+        port = {coord_port}
+        bind = "127.0.0.1"
+        m2m_rate_limit_rps = 1
+        m2m_rate_limit_burst = 2
+
+        [hosts]
+
+        [clients."{client_id}"]
+        shared_secret = "clientsecret"
+    "#
+        ) + &runtime_test_config()),
+    );
+    wait_for_listening(coord_port, 5).await;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:{coord_port}/api/m2m/hosts_status");
+
+    // Requests with the client's ID but no valid signature are limited per IP, not per client.
+    let mut rejected = 0;
+    let limited = loop {
+        let resp = client
+            .get(&url)
+            .header("X-Client-ID", client_id)
+            .header(
+                "X-Request",
+                create_signed_message("status", &SecretString::from("wrong")),
+            )
+            .send()
+            .await
+            .unwrap();
+        if resp.status() != StatusCode::UNAUTHORIZED {
+            break resp;
+        }
+        rejected += 1;
+        assert!(rejected < 120, "unsigned requests were not rate limited");
+    };
+    assert!(rejected >= 60, "limited after {rejected} requests");
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // The client still has its full burst.
+    for _ in 0..2 {
+        let resp = client
+            .get(&url)
+            .header("X-Client-ID", client_id)
+            .header("X-Request", create_signed_message("status", &client_secret))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn m2m_nonce_is_public_and_rate_limited() {
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_config(
+        port,
+        &format!(
+            r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+        hmac_tolerance_secs = 45
+
+        [server.auth.token]
+        token = "testtoken123"
+
+        [hosts]
+
+        [clients]
+    "#
+        ),
+    );
+    wait_for_listening(port, 5).await;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:{port}/api/m2m/nonce");
+    let resp = client.get(&url).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let timestamp = body["timestamp"].as_u64().unwrap();
+    assert!(timestamp.abs_diff(shuthost_common::unix_time_seconds()) <= 1);
+    assert_eq!(body["tolerance_secs"], 45);
+
+    // 60 requests per minute are allowed, tokens refilling during the loop may add a few.
+    let mut accepted = 1;
+    let limited = loop {
+        let resp = client.get(&url).send().await.unwrap();
+        if resp.status() != StatusCode::OK {
+            break resp;
+        }
+        accepted += 1;
+        assert!(accepted < 120, "nonce requests were not rate limited");
+    };
+    assert!(accepted >= 60, "limited after {accepted} requests");
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(limited.headers().contains_key("retry-after"));
+}
+
+#[tokio::test]
+async fn auth_exceptions_version_is_public() {
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_config(
+        port,
+        &format!(
+            r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+
+        [server.auth.token]
+        token = "testtoken123"
+
+        [server.tls]
+
+        [hosts]
+
+        [clients]
+    "#
+        ),
+    );
+    wait_for_listening(port, 20).await;
+
+    let client = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let resp = client
+        .get(format!(
+            "https://127.0.0.1:{port}/api/auth_exceptions_version"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["expected_version"].is_u64(), "{body}");
+}
+
+#[tokio::test]
+async fn health_and_ready_are_public() {
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_config(
+        port,
+        &format!(
+            r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+
+        [server.auth.token]
+        token = "testtoken123"
+
+        [server.tls]
+
+        [db]
+        path = ":memory:"
+
+        [hosts]
+
+        [clients]
+    "#
+        ),
+    );
+    wait_for_listening(port, 20).await;
+
+    let client = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    for path in ["health", "ready"] {
+        let resp = client
+            .get(format!("https://127.0.0.1:{port}/{path}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK, "/{path}");
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["status"], "ok", "{body}");
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"), "{body}");
+    }
+}
+
+/// Returns the DER encoded certificate the coordinator at `port` presents on a new connection.
+async fn presented_certificate(port: u16) -> Vec<u8> {
+    let client = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .tls_info(true)
+        .build()
+        .unwrap();
+    let resp = client
+        .get(format!("https://127.0.0.1:{port}/health"))
+        .send()
+        .await
+        .unwrap();
+    resp.extensions()
+        .get::<TlsInfo>()
+        .and_then(TlsInfo::peer_certificate)
+        .expect("TLS connection presents a certificate")
+        .to_vec()
+}
+
+#[tokio::test]
+async fn tls_certificate_is_reloaded_on_change() {
+    let tls_config = |port: u16| {
+        format!(
+            r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+
+        [server.tls]
+        cert_path = "tls_reload_{port}_cert.pem"
+        key_path = "tls_reload_{port}_key.pem"
+
+        [hosts]
+
+        [clients]
+    "#
+        )
+    };
+    let tls_file =
+        |port: u16, kind: &str| env::temp_dir().join(format!("tls_reload_{port}_{kind}.pem"));
+
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_config(port, &tls_config(port));
+    wait_for_listening(port, 20).await;
+    let initial = presented_certificate(port).await;
+
+    // A second coordinator persists another self-signed certificate to take over.
+    let other_port = get_free_port();
+    let other_child = spawn_coordinator_with_config(other_port, &tls_config(other_port));
+    wait_for_listening(other_port, 20).await;
+    let renewed = presented_certificate(other_port).await;
+    drop(other_child);
+    assert_ne!(initial, renewed);
+
+    for kind in ["key", "cert"] {
+        fs::copy(tls_file(other_port, kind), tls_file(port, kind)).unwrap();
+    }
+
+    let reloaded = time::timeout(Duration::from_secs(10), async {
+        loop {
+            if presented_certificate(port).await == renewed {
+                break;
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+    for p in [port, other_port] {
+        for kind in ["key", "cert"] {
+            drop(fs::remove_file(tls_file(p, kind)));
+        }
+    }
+    assert!(
+        reloaded.is_ok(),
+        "Coordinator should present the renewed certificate"
+    );
+}
+
+#[tokio::test]
+async fn tls_pkcs12_bundle_is_served() {
+    let rcgen::CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+    let chain = p12_keystore::PrivateKeyChain::new(
+        "shuthost",
+        p12_keystore::PrivateKey::from_der(&signing_key.serialize_der()).unwrap(),
+        [p12_keystore::Certificate::from_der(cert.der()).unwrap()],
+    );
+    let mut keystore = p12_keystore::KeyStore::new();
+    keystore.add_entry(
+        "shuthost",
+        p12_keystore::KeyStoreEntry::PrivateKeyChain(chain),
+    );
+
+    let port = get_free_port();
+    let bundle = env::temp_dir().join(format!("tls_p12_{port}.p12"));
+    fs::write(&bundle, keystore.writer("bundle-pass").write().unwrap()).unwrap();
+    let _child = spawn_coordinator_with_config(
+        port,
+        &format!(
+            r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+
+        [server.tls]
+        format = "pkcs12"
+        cert_path = "tls_p12_{port}.p12"
+        pkcs12_password = "bundle-pass"
+
+        [hosts]
+
+        [clients]
+    "#
+        ),
+    );
+    wait_for_listening(port, 20).await;
+    let presented = presented_certificate(port).await;
+    drop(fs::remove_file(&bundle));
+    assert_eq!(presented, cert.der().to_vec());
+}
+
+#[tokio::test]
+async fn client_script_download_fills_in_placeholders() {
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_config(
+        port,
+        &format!(
+            r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+
+        [server.auth.token]
+        token = "testtoken123"
+
+        [server.tls]
+
+        [hosts]
+
+        [clients]
+    "#
+        ),
+    );
+    wait_for_listening(port, 20).await;
+
+    let client = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    for script in ["client.sh", "client.ps1"] {
+        let resp = client
+            .get(format!(
+                "https://127.0.0.1:{port}/download/{script}?coordinator_url=https%3A%2F%2Fshuthost.example.com&client_id=ci_job"
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK, "{script}");
+        assert_eq!(
+            resp.headers()["content-disposition"],
+            format!("attachment; filename=\"shuthost_{script}\"")
+        );
+        let body = resp.text().await.unwrap();
+        assert!(body.contains("https://shuthost.example.com"), "{script}");
+        assert!(body.contains("\"ci_job\""), "{script}");
+        assert!(!body.contains("{embedded_remote_url}"), "{script}");
+        assert!(body.contains("{shared_secret}"), "{script}");
+    }
+
+    let resp = client
+        .get(format!(
+            "https://127.0.0.1:{port}/download/client.sh?coordinator_url=https%3A%2F%2Fexample.com%2F%24(reboot)"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn metrics_endpoint_is_public_and_lists_hosts() {
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_config(
+        port,
+        &format!(
+            r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+
+        [server.auth.token]
+        token = "testtoken123"
+
+        [server.tls]
+
+        [server.metrics]
+        path = "/custom-metrics"
+
+        [hosts.testhost]
+        ip = "127.0.0.1"
+        mac = "disableWOL"
+        port = {agent_port}
+        shared_secret = "testsecret"
+
+        [clients]
+    "#,
+            agent_port = get_free_port()
+        ),
+    );
+    wait_for_listening(port, 20).await;
+
+    let client = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+
+    let protected = client
+        .get(format!("https://127.0.0.1:{port}/api/hosts_status"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(protected.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .get(format!("https://127.0.0.1:{port}/custom-metrics"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let content_type = resp.headers()["content-type"].to_str().unwrap().to_owned();
+    assert!(content_type.starts_with("text/plain"), "{content_type}");
+    let body = resp.text().await.unwrap();
+    assert!(
+        body.contains("shuthost_host_online{host=\"testhost\"} 0"),
+        "{body}"
+    );
+    assert!(
+        body.contains("shuthost_lease_count{host=\"testhost\"} 0"),
+        "{body}"
+    );
+    assert!(body.contains("# TYPE shuthost_poll_duration_seconds histogram"));
+
+    let openmetrics = client
+        .get(format!("https://127.0.0.1:{port}/custom-metrics"))
+        .header(
+            "Accept",
+            "application/openmetrics-text;version=1.0.0,text/plain;q=0.5",
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(openmetrics.status(), StatusCode::OK);
+    let content_type = openmetrics.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(
+        content_type.starts_with("application/openmetrics-text"),
+        "{content_type}"
+    );
+    let body = openmetrics.text().await.unwrap();
+    assert!(body.contains("# TYPE shuthost_wake counter"), "{body}");
+    assert!(body.ends_with("# EOF\n"), "{body}");
+}
+
 #[tokio::test]
 async fn lease_persistence_across_restarts() {
     let coord_port = get_free_port();
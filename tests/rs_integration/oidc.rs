@@ -0,0 +1,142 @@
+//! Integration tests for the OIDC login flow against a mock provider.
+
+use axum::{Json, Router, routing::get};
+use reqwest::{Client, StatusCode, Url, header, redirect};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::common::{KillOnDrop, get_free_port, spawn_coordinator_with_config, wait_for_listening};
+
+/// Serves just enough of an OIDC provider for the coordinator to start a login, i.e. the
+/// discovery document and an empty key set. There is no token endpoint.
+///
+/// Returns the issuer URL.
+async fn start_mock_oidc_provider() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("failed to bind mock OIDC provider");
+    let issuer = format!(
+        "http://127.0.0.1:{}",
+        listener.local_addr().expect("no local addr").port()
+    );
+
+    let metadata = json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/authorize"),
+        "token_endpoint": format!("{issuer}/token"),
+        "jwks_uri": format!("{issuer}/jwks"),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+    });
+    let app = Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(move || {
+                let metadata = metadata.clone();
+                async move { Json(metadata) }
+            }),
+        )
+        .route("/jwks", get(|| async { Json(json!({ "keys": [] })) }));
+
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    issuer
+}
+
+fn spawn_coordinator_with_oidc(port: u16, issuer: &str) -> KillOnDrop {
+    let config = format!(
+        r#"
+    [server]
+    port = {port}
+    bind = "127.0.0.1"
+
+    [server.auth.oidc]
+    issuer = "{issuer}"
+    client_secret = "test-secret"
+
+    [hosts]
+
+    [clients]
+        "#
+    );
+    spawn_coordinator_with_config(port, &config)
+}
+
+#[tokio::test]
+async fn oidc_callback_with_mismatched_state_redirects_with_oidc_error() {
+    let issuer = start_mock_oidc_provider().await;
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_oidc(port, &issuer);
+    wait_for_listening(port, 10).await;
+
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    // Start a login to get the signed state cookie; indicate a secure connection via
+    // x-forwarded-proto, as the coordinator refuses to set the OIDC cookies otherwise.
+    let login = client
+        .get(format!("http://127.0.0.1:{port}/oidc/login"))
+        .header("x-forwarded-proto", "https")
+        .send()
+        .await
+        .unwrap();
+    assert!(login.status().is_redirection());
+    let auth_url = Url::parse(
+        login
+            .headers()
+            .get(header::LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(
+        auth_url
+            .as_str()
+            .starts_with(&format!("{issuer}/authorize")),
+        "login did not redirect to the provider: {auth_url}"
+    );
+    let correct_state = auth_url
+        .query_pairs()
+        .find_map(|(key, value)| (key == "state").then(|| value.into_owned()))
+        .expect("authorization URL should contain the state");
+    // The cookies are `Secure`, so they are passed on by hand rather than via a cookie store.
+    let cookies = login
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|cookie| cookie.to_str().ok()?.split(';').next())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let callback = async |state: &str| {
+        client
+            .get(format!(
+                "http://127.0.0.1:{port}/oidc/callback?code=x&state={state}"
+            ))
+            .header(header::COOKIE, &cookies)
+            .send()
+            .await
+            .unwrap()
+    };
+
+    let resp = callback("wrong_state").await;
+    assert!(resp.status().is_redirection());
+    let loc = resp
+        .headers()
+        .get(header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert_eq!(loc, "/login?error=oidc");
+
+    // With the matching state the callback gets past the check and fails at the token exchange,
+    // as the mock provider has no token endpoint.
+    let resp = callback(&correct_state).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+}
@@ -1,6 +1,9 @@
-use reqwest::{Client, header, redirect};
+use reqwest::{Client, StatusCode, Url, header, redirect};
 
-use crate::common::{get_free_port, spawn_coordinator_with_config, wait_for_listening};
+use crate::{
+    common::{get_free_port, spawn_coordinator_with_config, wait_for_listening},
+    login_error_redirects::spawn_coordinator_with_token,
+};
 
 #[tokio::test]
 async fn token_login_flow() {
@@ -68,3 +71,149 @@ async fn token_login_flow() {
         "protected endpoint not accessible"
     );
 }
+
+#[tokio::test]
+async fn rotate_token_requires_session_and_generated_token() {
+    let port = get_free_port();
+    let token = "testtoken-rotate";
+    let config = format!(
+        r#"
+    [server]
+    port = {port}
+    bind = "127.0.0.1"
+
+    [server.auth.token]
+    token = "{token}"
+
+    [server.tls]
+
+    [hosts]
+
+    [clients]
+        "#
+    );
+    let _child = spawn_coordinator_with_config(port, &config);
+    wait_for_listening(port, 20).await;
+
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let rotate_url = format!("https://127.0.0.1:{port}/api/auth/rotate-token");
+
+    let resp = client.post(&rotate_url).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .post(format!("https://127.0.0.1:{port}/login"))
+        .form(&[("token", token)])
+        .send()
+        .await
+        .unwrap();
+    let cookies: Vec<String> = resp
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok().map(ToString::to_string))
+        .collect();
+
+    // A token from the config file would win again on the next start, so it can't be rotated.
+    let resp = client
+        .post(&rotate_url)
+        .header(header::COOKIE, cookies.join("; "))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "token_configured");
+}
+
+#[tokio::test]
+async fn token_login_redirects_to_return_to_query() {
+    let port = get_free_port();
+    let token = "testtoken-return-to";
+    let _child = spawn_coordinator_with_token(port, token);
+    wait_for_listening(port, 10).await;
+
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let login = async |return_to: &str| {
+        let url = Url::parse_with_params(
+            &format!("http://127.0.0.1:{port}/login"),
+            [("return_to", return_to)],
+        )
+        .unwrap();
+        let page = client.get(url).send().await.unwrap();
+        assert!(page.status().is_success());
+        // The cookies are `Secure`, so they are passed on by hand rather than via a cookie store.
+        let cookies = page
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok()?.split(';').next())
+            .collect::<Vec<_>>()
+            .join("; ");
+        let resp = client
+            .post(format!("http://127.0.0.1:{port}/login"))
+            .header("x-forwarded-proto", "https")
+            .header(header::COOKIE, cookies)
+            .form(&[("token", token)])
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_redirection());
+        resp.headers()
+            .get(header::LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned()
+    };
+
+    assert_eq!(login("/some/page?x=1").await, "/some/page?x=1");
+    // Anything that could lead to another site is ignored.
+    assert_eq!(login("https://evil.example/").await, "/");
+    assert_eq!(login("//evil.example/").await, "/");
+    assert_eq!(login("/\t/evil.example/").await, "/");
+}
+
+#[tokio::test]
+async fn unauthenticated_requests_redirect_browsers_and_challenge_api_clients() {
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_token(port, "testtoken-challenge");
+    wait_for_listening(port, 20).await;
+
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap();
+    let url = format!("http://127.0.0.1:{port}/api/hosts_status");
+
+    let resp = client
+        .get(&url)
+        .header(header::ACCEPT, "application/json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        resp.headers()[header::WWW_AUTHENTICATE],
+        r#"Bearer realm="shuthost""#
+    );
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "unauthorized");
+
+    let resp = client
+        .get(&url)
+        .header(header::ACCEPT, "text/html,application/xhtml+xml,*/*;q=0.8")
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_redirection());
+    assert_eq!(resp.headers()[header::LOCATION], "/login");
+}
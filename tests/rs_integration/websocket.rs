@@ -10,7 +10,10 @@ use shuthost_coordinator::{
     websocket::{DynamicConfig, FrontendHookAction},
 };
 use tokio::{fs, time};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{Error as WsError, Message},
+};
 
 use crate::common::{
     get_free_port, runtime_test_config, spawn_coordinator_with_config,
@@ -162,6 +165,323 @@ async fn websocket_config_updates() {
     assert!(config_changed_received);
 }
 
+#[tokio::test]
+async fn websocket_config_updates_on_atomic_save() {
+    let port = get_free_port();
+    let config_path = env::temp_dir().join(format!("ws_atomic_config_{port}.toml"));
+    let server_section = format!(
+        r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+    "#
+    );
+    fs::write(
+        &config_path,
+        format!("{server_section}\n[hosts]\n[clients]\n"),
+    )
+    .await
+    .expect("failed to write config");
+
+    let _child = spawn_coordinator_with_config_file(&config_path, port);
+    wait_for_listening(port, 5).await;
+
+    let (ws_stream, _) = connect_async(format!("ws://127.0.0.1:{port}/ws"))
+        .await
+        .expect("failed to connect websocket");
+    let (_write, mut read) = ws_stream.split();
+    let initial_msg = read.next().await.unwrap().unwrap();
+    assert!(matches!(
+        serde_json::from_str(&initial_msg.to_string()).unwrap(),
+        WsMessage::Initial(_)
+    ));
+
+    // Save the way most editors do: write a temp file next to the config, then rename it over.
+    let temp_path = config_path.with_extension("toml.tmp");
+    fs::write(
+        &temp_path,
+        format!(
+            r#"{server_section}
+        [hosts.renamedhost]
+        ip = "192.168.1.2"
+        mac = "00:11:22:33:44:66"
+        port = 8080
+        shared_secret = "secret"
+
+        [clients]
+    "#
+        ),
+    )
+    .await
+    .expect("failed to write temp config");
+    fs::rename(&temp_path, &config_path)
+        .await
+        .expect("failed to rename config");
+
+    let hosts = time::timeout(Duration::from_secs(10), async {
+        while let Some(msg) = read.next().await {
+            if let Message::Text(text) = msg.unwrap()
+                && let WsMessage::ConfigChanged(DynamicConfig { hosts, .. }) =
+                    serde_json::from_str(&text).unwrap()
+            {
+                return hosts;
+            }
+        }
+        panic!("websocket closed before ConfigChanged");
+    })
+    .await
+    .expect("Timeout waiting for ConfigChanged message");
+
+    assert_eq!(hosts, vec!["renamedhost".to_string()]);
+    drop(fs::remove_file(&config_path).await);
+}
+
+#[tokio::test]
+async fn websocket_config_reload_adds_host() {
+    let port = get_free_port();
+    let config_path = env::temp_dir().join(format!("ws_added_host_config_{port}.toml"));
+    let host_section = |host: &str| {
+        format!(
+            r#"
+        [hosts.{host}]
+        ip = "192.168.1.3"
+        mac = "00:11:22:33:44:77"
+        port = 8080
+        shared_secret = "secret"
+    "#
+        )
+    };
+    let base_config = format!(
+        r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+
+        [clients]
+    {}"#,
+        host_section("existinghost")
+    );
+    fs::write(&config_path, &base_config)
+        .await
+        .expect("failed to write config");
+
+    let _child = spawn_coordinator_with_config_file(&config_path, port);
+    wait_for_listening(port, 5).await;
+
+    let (ws_stream, _) = connect_async(format!("ws://127.0.0.1:{port}/ws"))
+        .await
+        .expect("failed to connect websocket");
+    let (_write, mut read) = ws_stream.split();
+    let initial_msg = read.next().await.unwrap().unwrap();
+    match serde_json::from_str(&initial_msg.to_string()).unwrap() {
+        WsMessage::Initial(initial) => {
+            assert_eq!(
+                initial.dynamic_config.hosts,
+                vec!["existinghost".to_string()]
+            );
+        }
+        _ => panic!("Expected Initial message"),
+    }
+
+    fs::write(
+        &config_path,
+        base_config.clone() + &host_section("addedhost"),
+    )
+    .await
+    .expect("failed to update config");
+
+    // Exercises the file watcher, the config reload and the websocket broadcast together.
+    let hosts = time::timeout(Duration::from_secs(5), async {
+        while let Some(msg) = read.next().await {
+            if let Message::Text(text) = msg.unwrap()
+                && let WsMessage::ConfigChanged(DynamicConfig { hosts, .. }) =
+                    serde_json::from_str(&text).unwrap()
+                && hosts.iter().any(|h| h == "addedhost")
+            {
+                return hosts;
+            }
+        }
+        panic!("websocket closed before ConfigChanged");
+    })
+    .await
+    .expect("Timeout waiting for ConfigChanged message with the added host");
+
+    assert_eq!(hosts.len(), 2);
+    assert!(hosts.iter().any(|h| h == "existinghost"));
+    drop(fs::remove_file(&config_path).await);
+}
+
+#[tokio::test]
+async fn websocket_config_reload_error_keeps_watching() {
+    let port = get_free_port();
+    let config_path = env::temp_dir().join(format!("ws_reload_error_config_{port}.toml"));
+    let base_config = format!(
+        r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+
+        [hosts]
+
+        [clients]
+    "#
+    );
+    fs::write(&config_path, &base_config)
+        .await
+        .expect("failed to write config");
+
+    let _child = spawn_coordinator_with_config_file(&config_path, port);
+    wait_for_listening(port, 5).await;
+
+    let ws_url = format!("ws://127.0.0.1:{port}/ws");
+    let (ws_stream, _) = connect_async(&ws_url)
+        .await
+        .expect("failed to connect websocket");
+    let (_write, mut read) = ws_stream.split();
+    read.next().await.unwrap().unwrap();
+
+    fs::write(&config_path, "[server\nport = ")
+        .await
+        .expect("failed to break config");
+    let message = time::timeout(Duration::from_secs(5), async {
+        while let Some(msg) = read.next().await {
+            if let Message::Text(text) = msg.unwrap()
+                && let WsMessage::ConfigError { message } = serde_json::from_str(&text).unwrap()
+            {
+                return message;
+            }
+        }
+        panic!("websocket closed before ConfigError");
+    })
+    .await
+    .expect("Timeout waiting for ConfigError message");
+    assert!(!message.is_empty());
+
+    // Clients connecting while the file is broken learn about it right away.
+    let (late_stream, _) = connect_async(&ws_url)
+        .await
+        .expect("failed to connect websocket");
+    let (_late_write, mut late_read) = late_stream.split();
+    let initial_msg = late_read.next().await.unwrap().unwrap();
+    match serde_json::from_str(&initial_msg.to_string()).unwrap() {
+        WsMessage::Initial(initial) => assert_eq!(initial.config_error, Some(message)),
+        _ => panic!("Expected Initial message"),
+    }
+
+    fs::write(
+        &config_path,
+        base_config.replace(
+            "[hosts]",
+            r#"[hosts.addedhost]
+        ip = "192.168.1.3"
+        mac = "00:11:22:33:44:77"
+        port = 8080
+        shared_secret = "secret""#,
+        ),
+    )
+    .await
+    .expect("failed to fix config");
+    let (mut resolved, mut added) = (false, false);
+    time::timeout(Duration::from_secs(5), async {
+        while let Some(msg) = read.next().await {
+            if let Message::Text(text) = msg.unwrap() {
+                match serde_json::from_str(&text).unwrap() {
+                    WsMessage::ConfigErrorResolved => resolved = true,
+                    WsMessage::ConfigChanged(DynamicConfig { hosts, .. }) => {
+                        added = hosts.iter().any(|h| h == "addedhost");
+                    }
+                    _ => {}
+                }
+            }
+            if resolved && added {
+                return;
+            }
+        }
+        panic!("websocket closed before the config was reloaded");
+    })
+    .await
+    .expect("Timeout waiting for the fixed config to be reloaded");
+    drop(fs::remove_file(&config_path).await);
+}
+
+#[tokio::test]
+async fn websocket_included_file_reload_adds_host() {
+    let port = get_free_port();
+    let config_path = env::temp_dir().join(format!("ws_include_config_{port}.toml"));
+    let included_name = format!("ws_include_hosts_{port}.toml");
+    let included_path = env::temp_dir().join(&included_name);
+    let host_section = |host: &str| {
+        format!(
+            r#"
+        [hosts.{host}]
+        ip = "192.168.1.3"
+        mac = "00:11:22:33:44:77"
+        port = 8080
+        shared_secret = "secret"
+    "#
+        )
+    };
+    let config = format!(
+        r#"
+        include = ["{included_name}"]
+
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+    "#
+    );
+    fs::write(&config_path, &config)
+        .await
+        .expect("failed to write config");
+    fs::write(&included_path, host_section("existinghost"))
+        .await
+        .expect("failed to write included config");
+
+    let _child = spawn_coordinator_with_config_file(&config_path, port);
+    wait_for_listening(port, 5).await;
+
+    let (ws_stream, _) = connect_async(format!("ws://127.0.0.1:{port}/ws"))
+        .await
+        .expect("failed to connect websocket");
+    let (_write, mut read) = ws_stream.split();
+    let initial_msg = read.next().await.unwrap().unwrap();
+    match serde_json::from_str(&initial_msg.to_string()).unwrap() {
+        WsMessage::Initial(initial) => {
+            assert_eq!(
+                initial.dynamic_config.hosts,
+                vec!["existinghost".to_string()]
+            );
+        }
+        _ => panic!("Expected Initial message"),
+    }
+
+    fs::write(
+        &included_path,
+        host_section("existinghost") + &host_section("addedhost"),
+    )
+    .await
+    .expect("failed to update included config");
+
+    let hosts = time::timeout(Duration::from_secs(5), async {
+        while let Some(msg) = read.next().await {
+            if let Message::Text(text) = msg.unwrap()
+                && let WsMessage::ConfigChanged(DynamicConfig { hosts, .. }) =
+                    serde_json::from_str(&text).unwrap()
+                && hosts.iter().any(|h| h == "addedhost")
+            {
+                return hosts;
+            }
+        }
+        panic!("websocket closed before ConfigChanged");
+    })
+    .await
+    .expect("Timeout waiting for ConfigChanged message with the included host");
+
+    assert_eq!(hosts.len(), 2);
+    drop(fs::remove_file(&config_path).await);
+    drop(fs::remove_file(&included_path).await);
+}
+
 #[tokio::test]
 async fn websocket_host_status_changes() {
     let coord_port = get_free_port();
@@ -219,9 +539,13 @@ async fn websocket_host_status_changes() {
             let msg = msg.unwrap();
             if let Message::Text(text) = msg {
                 let ws_msg: WsMessage = serde_json::from_str(&text).unwrap();
-                if let WsMessage::HostStatus(status) = ws_msg
+                if let WsMessage::HostStatus { status, last_seen } = ws_msg
                     && status.get("testhost") == Some(&HostState::Online)
                 {
+                    assert!(
+                        last_seen.contains_key("testhost"),
+                        "The status of the host going online should tell when it did"
+                    );
                     online_received = true;
                     break;
                 }
@@ -244,7 +568,7 @@ async fn websocket_host_status_changes() {
             let msg = msg.unwrap();
             if let Message::Text(text) = msg {
                 let ws_msg: WsMessage = serde_json::from_str(&text).unwrap();
-                if let WsMessage::HostStatus(status) = ws_msg
+                if let WsMessage::HostStatus { status, .. } = ws_msg
                     && status.get("testhost") == Some(&HostState::Offline)
                 {
                     offline_received = true;
@@ -259,3 +583,49 @@ async fn websocket_host_status_changes() {
 
     assert!(offline_received, "Host should have gone offline");
 }
+
+#[tokio::test]
+async fn websocket_max_connections_rejects_excess() {
+    let port = get_free_port();
+    let _child = spawn_coordinator_with_config(
+        port,
+        &format!(
+            r#"
+        [server]
+        port = {port}
+        bind = "127.0.0.1"
+        max_connections = 1
+
+        [hosts]
+
+        [clients]
+    "#
+        ),
+    );
+    wait_for_listening(port, 5).await;
+
+    let url = format!("ws://127.0.0.1:{port}/ws");
+    let (first, _) = connect_async(&url)
+        .await
+        .expect("failed to connect websocket");
+
+    match connect_async(&url).await {
+        Err(WsError::Http(response)) => {
+            assert_eq!(response.status(), 503);
+        }
+        other => panic!("expected 503 for the second connection, got {other:?}"),
+    }
+
+    drop(first);
+    // The slot is released once the server notices the closed connection.
+    let reconnected = time::timeout(Duration::from_secs(5), async {
+        loop {
+            if connect_async(&url).await.is_ok() {
+                break;
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+    assert!(reconnected.is_ok(), "connection slot was not released");
+}
@@ -0,0 +1,244 @@
+//! Development tasks for the workspace, run with `cargo xtask <task>`.
+//!
+//! Tasks:
+//!
+//! * `coverage [--fail-under <percent>]`: runs the Rust tests under `cargo-llvm-cov`, writes an
+//!   LCOV report to `target/coverage/lcov.info` and prints the files with uncovered lines.
+//!
+//! `cargo-llvm-cov` builds with `--cfg coverage`. That cfg is not a cargo feature but is declared
+//! in the workspace `check-cfg` list, and gates code that can't run under coverage
+//! instrumentation, such as sending magic packets.
+
+extern crate alloc;
+extern crate core;
+
+use alloc::collections::BTreeMap;
+use core::cmp::Reverse;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use eyre::{WrapErr as _, bail, eyre};
+
+/// Where the LCOV report is written, relative to the workspace root.
+const LCOV_PATH: &str = "target/coverage/lcov.info";
+
+/// Sources excluded from the report, matching the `coverage` recipe in the Justfile.
+const IGNORE_FILENAME_REGEX: &str = ".*cargo/registry/src/.*|tests/rs_integration/.*";
+
+/// Features enabled in addition to the defaults. `--all-features` would also enable embedding
+/// the cross-compiled agents, which need to be built first.
+const FEATURES: &str = "shuthost_coordinator/mdns,shuthost_host_agent/mdns";
+
+/// Number of files listed in the summary of uncovered lines.
+const SUMMARY_FILES: usize = 15;
+
+const USAGE: &str = "usage: cargo xtask coverage [--fail-under <percent>]";
+
+fn main() -> eyre::Result<()> {
+    let mut args = env::args().skip(1);
+    match args.next().as_deref() {
+        Some("coverage") => {
+            let fail_under = match (args.next().as_deref(), args.next()) {
+                (None, _) => None,
+                (Some("--fail-under"), Some(percent)) => Some(
+                    percent
+                        .parse::<f64>()
+                        .wrap_err(format!("Invalid percentage '{percent}'"))?,
+                ),
+                _ => bail!(USAGE),
+            };
+            coverage(fail_under)
+        }
+        _ => bail!(USAGE),
+    }
+}
+
+fn coverage(fail_under: Option<f64>) -> eyre::Result<()> {
+    let root = workspace_root();
+    let lcov_path = root.join(LCOV_PATH);
+    if let Some(dir) = lcov_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .current_dir(&root)
+        .args(["llvm-cov", "--workspace", "--features", FEATURES, "--lcov"])
+        .arg("--output-path")
+        .arg(&lcov_path)
+        .args(["--ignore-filename-regex", IGNORE_FILENAME_REGEX])
+        .status()
+        .wrap_err("Failed to run cargo llvm-cov, install it with `cargo install cargo-llvm-cov`")?;
+    if !status.success() {
+        bail!("cargo llvm-cov failed with {status}");
+    }
+
+    let lcov = fs::read_to_string(&lcov_path)
+        .wrap_err(format!("Failed to read {}", lcov_path.display()))?;
+    let files = parse_lcov(&lcov)?;
+    print!("{}", render_summary(&files, &root));
+    println!("LCOV report written to {}", lcov_path.display());
+
+    let percent = covered_percent(&files);
+    if let Some(threshold) = fail_under
+        && percent < threshold
+    {
+        bail!("Line coverage of {percent:.1}% is below the minimum of {threshold:.1}%");
+    }
+    Ok(())
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+}
+
+/// Line numbers of a source file, split by whether any test executed them.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct FileCoverage {
+    covered: usize,
+    uncovered: Vec<u32>,
+}
+
+/// Collects the executed and unexecuted lines of each source file in an LCOV report.
+fn parse_lcov(lcov: &str) -> eyre::Result<BTreeMap<String, FileCoverage>> {
+    let mut files = BTreeMap::<String, FileCoverage>::new();
+    let mut current = None;
+    for line in lcov.lines() {
+        if let Some(path) = line.strip_prefix("SF:") {
+            current = Some(path.to_string());
+        } else if let Some(data) = line.strip_prefix("DA:") {
+            let file = current
+                .clone()
+                .ok_or_else(|| eyre!("Line data before a source file: {line}"))?;
+            let mut fields = data.split(',');
+            let (Some(number), Some(count)) = (fields.next(), fields.next()) else {
+                bail!("Malformed line data: {line}");
+            };
+            let number: u32 = number.parse().wrap_err(format!("Malformed line: {line}"))?;
+            let count: u64 = count.parse().wrap_err(format!("Malformed line: {line}"))?;
+            let entry = files.entry(file).or_default();
+            if count == 0 {
+                entry.uncovered.push(number);
+            } else {
+                entry.covered += 1;
+            }
+        } else if line == "end_of_record" {
+            current = None;
+        }
+    }
+    Ok(files)
+}
+
+#[expect(
+    clippy::cast_precision_loss,
+    reason = "line counts are far below the precision limit"
+)]
+fn covered_percent(files: &BTreeMap<String, FileCoverage>) -> f64 {
+    let covered: usize = files.values().map(|file| file.covered).sum();
+    let total: usize = files
+        .values()
+        .map(|file| file.covered + file.uncovered.len())
+        .sum();
+    if total == 0 {
+        return 100.0;
+    }
+    100.0 * covered as f64 / total as f64
+}
+
+/// Lists the files with the most uncovered lines, followed by the total line coverage.
+fn render_summary(files: &BTreeMap<String, FileCoverage>, root: &Path) -> String {
+    let mut uncovered: Vec<_> = files
+        .iter()
+        .filter(|&(_, file)| !file.uncovered.is_empty())
+        .collect();
+    uncovered.sort_by_key(|&(path, file)| (Reverse(file.uncovered.len()), path));
+
+    let mut lines = vec!["Uncovered lines:".to_string()];
+    lines.extend(uncovered.iter().take(SUMMARY_FILES).map(|&(path, file)| {
+        let display = Path::new(path)
+            .strip_prefix(root)
+            .unwrap_or(Path::new(path));
+        format!(
+            "  {:>5}  {}: {}",
+            file.uncovered.len(),
+            display.display(),
+            line_ranges(&file.uncovered)
+        )
+    }));
+    if uncovered.len() > SUMMARY_FILES {
+        lines.push(format!(
+            "  ... and {} more file(s)",
+            uncovered.len() - SUMMARY_FILES
+        ));
+    }
+    lines.push(format!("Line coverage: {:.1}%", covered_percent(files)));
+    lines.join("\n") + "\n"
+}
+
+/// Formats sorted line numbers as ranges, e.g. `3-5, 9`.
+fn line_ranges(lines: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &line in lines {
+        match ranges.last_mut() {
+            Some(&mut (_, ref mut end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LCOV: &str = "\
+SF:/repo/coordinator/src/wol.rs
+DA:1,3
+DA:2,0
+DA:3,0
+DA:5,0
+end_of_record
+SF:/repo/common/src/lib.rs
+DA:1,1
+end_of_record
+";
+
+    #[test]
+    fn summarizes_uncovered_lines() {
+        let files = parse_lcov(LCOV).unwrap();
+        assert_eq!(
+            files.get("/repo/coordinator/src/wol.rs"),
+            Some(&FileCoverage {
+                covered: 1,
+                uncovered: vec![2, 3, 5],
+            })
+        );
+        assert_eq!(
+            render_summary(&files, Path::new("/repo")),
+            "Uncovered lines:\n      3  coordinator/src/wol.rs: 2-3, 5\nLine coverage: 40.0%\n"
+        );
+    }
+
+    #[test]
+    fn rejects_line_data_outside_a_file() {
+        let error = parse_lcov("DA:1,1\n").unwrap_err();
+        assert!(
+            error.to_string().contains("before a source file"),
+            "{error}"
+        );
+    }
+}